@@ -13,9 +13,9 @@ fn main() {
 
     let mut led = [0.; 4];
 
-    for i in 0..4 {
+    for (i, l) in led.iter().enumerate() {
         monome.ring_all(i, 0);
-        monome.ring_set(i, led[i] as u32, 15);
+        monome.ring_set(i, *l as u32, 15);
     }
 
     loop {
@@ -24,9 +24,8 @@ fn main() {
 
             match e {
                 Some(MonomeEvent::EncoderDelta { n, delta }) => {
-                    let n = n as usize;
                     monome.ring_set(n, led[n] as u32, 0);
-                    led[n] = led[n] + (delta as f32 / 4.);
+                    led[n] += delta as f32 / 4.;
                     if led[n] < 0. {
                         led[n] += 64.;
                     }
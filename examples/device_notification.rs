@@ -16,10 +16,10 @@ fn main() {
         DeviceChangeEvent::Removed(id) => {
             println!("Device {} removed", id);
         }
-    });
+    })
+    .expect("Could not listen to device changes");
     let mut answer = String::new();
     io::stdin()
         .read_line(&mut answer)
-        .ok()
         .expect("Failed to read line");
 }
@@ -10,10 +10,8 @@ fn main() {
     match enumeration {
         Ok(devices) => {
             println!("Found {} devices.", devices.len());
-            let mut idx = 0;
-            for device in &devices {
+            for (idx, device) in devices.iter().enumerate() {
                 println!("Device {}: {}. Setting it up:", idx, device);
-                idx+=1;
                 match Monome::from_device(device, "prefix") {
                     Ok(m) => {
                         println!("Monome {} setup OK:\n{}", device, m);
@@ -33,8 +33,8 @@ fn main() {
         // both methods are equivalent
         monome.set_all_intensity(&grid);
 
-        monome.map(0, 0, &v);
-        monome.map(8, 0, &v);
+        monome.map(0, 0, &v).unwrap();
+        monome.map(8, 0, &v).unwrap();
 
         sp += dir;
         if sp == 15 {
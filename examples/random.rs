@@ -15,7 +15,7 @@ fn main() {
         Ok(devices) => {
             for d in devices.iter() {
                 if d.device_type() == MonomeDeviceType::Grid {
-                    let mut monome = Monome::from_device(&d, "/prefix2").unwrap();
+                    let mut monome = Monome::from_device(d, "/prefix2").unwrap();
                     println!("{:?}", monome);
 
                     let mut v = [0; 64];
@@ -24,12 +24,12 @@ fn main() {
                     loop {
                         for i in 0..64 {
                             v[i] = (random::<u8>() % 16) as u8;
-                            v2[i] = if random::<u8>() % 2 == 0 { false } else { true };
+                            v2[i] = random::<bool>();
                         }
                         // random intensity from 0 to 15
-                        monome.map(0, 0, &v);
+                        monome.map(0, 0, &v).unwrap();
                         // On/Off
-                        monome.map(8, 0, &v2);
+                        monome.map(8, 0, &v2).unwrap();
 
                         let refresh = time::Duration::from_millis(33);
                         thread::sleep(refresh);
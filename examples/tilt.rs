@@ -19,7 +19,7 @@ fn main() {
 
     let mut grid: Vec<bool> = vec![false; 128];
 
-    fn moveall(grid: &mut Vec<bool>, dx: i32, dy: i32) {
+    fn moveall(grid: &mut [bool], dx: i32, dy: i32) {
         let mut grid2: Vec<bool> = vec![false; 128];
         for x in 0..16 {
             for y in 0..8 {
@@ -28,9 +28,7 @@ fn main() {
             }
         }
 
-        for x in 0..128 {
-            grid[x] = grid2[x];
-        }
+        grid.copy_from_slice(&grid2);
     }
 
     let mut i = 0;
@@ -40,13 +38,12 @@ fn main() {
     loop {
         loop {
             match monome.poll() {
-                Some(MonomeEvent::GridKey { x, y, direction }) => match direction {
-                    KeyDirection::Down => {
+                Some(MonomeEvent::GridKey { x, y, direction }) => {
+                    if let KeyDirection::Down = direction {
                         let idx = toidx(x, y, 16);
                         grid[idx] = !grid[idx];
                     }
-                    _ => {}
-                },
+                }
                 Some(MonomeEvent::Tilt { n: _n, x, y, z: _z }) => {
                     if i % 10 == 0 {
                         moveall(
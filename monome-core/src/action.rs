@@ -0,0 +1,156 @@
+//! Mapping of keys and chords to named actions, to separate the layout of a grid from the logic
+//! of an application.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::event::{KeyDirection, MonomeEvent};
+
+/// A named action, triggered by a single key, or by a chord of keys held together.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ActionBinding {
+    /// The name of the action.
+    pub action: String,
+    /// The keys that trigger the action, as `(x, y)`: one key, or all the keys of a chord.
+    pub keys: Vec<(i32, i32)>,
+}
+
+/// Maps keys and chords of a grid to named actions, so that an application handles actions such
+/// as `"play"` or `"next-pattern"` instead of coordinates, and can be re-mapped without changing
+/// its logic. With the `serde` feature, the bindings can be loaded from a configuration file.
+///
+/// An action is triggered when the last key of its binding is pressed while the others are
+/// held. When multiple bindings are complete, the one with the most keys wins, so that a chord
+/// can contain a key that is also bound on its own.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{ActionMap, KeyDirection, MonomeEvent};
+///
+/// let mut map = ActionMap::new();
+/// map.bind_key("play", 0, 7);
+/// map.bind_chord("clear", &[(0, 7), (15, 7)]);
+///
+/// let press = |x, y| MonomeEvent::GridKey { x, y, direction: KeyDirection::Down };
+/// assert_eq!(map.process(&press(0, 7)), Some("play"));
+/// assert_eq!(map.process(&press(15, 7)), Some("clear"));
+/// assert_eq!(map.process(&press(3, 3)), None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ActionMap {
+    /// The bindings, in the order in which they have been added.
+    bindings: Vec<ActionBinding>,
+    /// The keys currently held.
+    held: Vec<(i32, i32)>,
+}
+
+impl ActionMap {
+    /// Create a map without any binding.
+    pub fn new() -> ActionMap {
+        ActionMap::default()
+    }
+
+    /// Create a map from a list of bindings, for example loaded from a configuration file.
+    pub fn from_bindings(bindings: Vec<ActionBinding>) -> ActionMap {
+        ActionMap {
+            bindings,
+            held: Vec::new(),
+        }
+    }
+
+    /// Bind the key at `x`, `y` to `action`.
+    pub fn bind_key(&mut self, action: &str, x: i32, y: i32) {
+        self.bind_chord(action, &[(x, y)]);
+    }
+
+    /// Bind the chord made of `keys`, as `(x, y)`, to `action`. A chord without keys is never
+    /// triggered.
+    pub fn bind_chord(&mut self, action: &str, keys: &[(i32, i32)]) {
+        self.bindings.push(ActionBinding {
+            action: action.to_string(),
+            keys: keys.to_vec(),
+        });
+    }
+
+    /// Remove all the bindings of `action`.
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.retain(|binding| binding.action != action);
+    }
+
+    /// Get the bindings, for example to save them.
+    pub fn bindings(&self) -> &[ActionBinding] {
+        &self.bindings
+    }
+
+    /// Process an event received from a grid. Returns the action triggered by this event, if
+    /// any.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<&str> {
+        let (x, y, direction) = match *event {
+            MonomeEvent::GridKey { x, y, direction } => (x, y, direction),
+            _ => return None,
+        };
+        self.held.retain(|&key| key != (x, y));
+        if direction == KeyDirection::Up {
+            return None;
+        }
+        self.held.push((x, y));
+        let held = &self.held;
+        self.bindings
+            .iter()
+            .filter(|binding| {
+                binding.keys.contains(&(x, y)) && binding.keys.iter().all(|key| held.contains(key))
+            })
+            // The first of the longest bindings.
+            .fold(None, |best: Option<&ActionBinding>, binding| match best {
+                Some(best) if best.keys.len() >= binding.keys.len() => Some(best),
+                _ => Some(binding),
+            })
+            .map(|binding| binding.action.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: i32, y: i32, direction: KeyDirection) -> MonomeEvent {
+        MonomeEvent::GridKey { x, y, direction }
+    }
+
+    #[test]
+    fn actions() {
+        let mut map = ActionMap::new();
+        map.bind_key("a", 0, 0);
+        map.bind_key("b", 1, 0);
+        map.bind_chord("ab", &[(0, 0), (1, 0)]);
+        map.bind_chord("abc", &[(0, 0), (1, 0), (2, 0)]);
+        map.bind_chord("never", &[]);
+
+        assert_eq!(map.process(&key(1, 0, KeyDirection::Down)), Some("b"));
+        assert_eq!(map.process(&key(0, 0, KeyDirection::Down)), Some("ab"));
+        assert_eq!(map.process(&key(2, 0, KeyDirection::Down)), Some("abc"));
+        assert_eq!(map.process(&key(2, 0, KeyDirection::Up)), None);
+        assert_eq!(map.process(&key(0, 0, KeyDirection::Up)), None);
+        assert_eq!(map.process(&key(0, 0, KeyDirection::Down)), Some("ab"));
+        assert_eq!(
+            map.process(&MonomeEvent::EncoderDelta { n: 0, delta: 1 }),
+            None
+        );
+
+        map.unbind("ab");
+        assert_eq!(map.bindings().len(), 4);
+        assert_eq!(map.process(&key(1, 0, KeyDirection::Up)), None);
+        assert_eq!(map.process(&key(1, 0, KeyDirection::Down)), Some("b"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn load_bindings() {
+        let bindings: Vec<ActionBinding> =
+            serde_json::from_str(r#"[{ "action": "play", "keys": [[0, 7]] }]"#).unwrap();
+        let mut map = ActionMap::from_bindings(bindings);
+        assert_eq!(map.process(&key(0, 7, KeyDirection::Down)), Some("play"));
+    }
+}
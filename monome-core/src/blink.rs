@@ -0,0 +1,181 @@
+//! Blinking of grid cells, to indicate armed tracks or pending actions.
+
+use std::time::{Duration, Instant};
+
+use crate::frame::GridFrame;
+
+/// How a cell blinks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Blink {
+    /// The duration of a full on and off cycle.
+    period: Duration,
+    /// The fraction of the period during which the cell is on, between 0.0 and 1.0.
+    duty: f32,
+    /// The intensity of the cell when it is on.
+    intensity: u8,
+    /// When the cell started blinking, at the start of an on phase.
+    start: Instant,
+}
+
+impl Blink {
+    /// Returns true if the cell is in its on phase at `now`.
+    fn is_on_at(&self, now: Instant) -> bool {
+        let period = self.period.as_nanos();
+        if period == 0 {
+            return true;
+        }
+        let elapsed = now.saturating_duration_since(self.start).as_nanos() % period;
+        (elapsed as f64) < period as f64 * f64::from(self.duty)
+    }
+}
+
+/// Makes cells of a grid blink, each at its own rate and duty cycle, without the application
+/// having to manage timers. The blinking cells are drawn over a frame built by the application,
+/// just before it is sent to the grid: they are lit at their intensity during the on phase of
+/// their cycle, and off during the off phase. The other cells are left as they are.
+///
+/// # Example
+///
+/// Blink the cell of an armed track twice per second, and a pending action faster:
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{BlinkScheduler, GridFrame};
+///
+/// let mut blinks = BlinkScheduler::new();
+/// let start = Instant::now();
+/// blinks.start_at(0, 3, Duration::from_millis(500), 0.5, 15, start);
+/// blinks.start_at(15, 0, Duration::from_millis(200), 0.25, 8, start);
+///
+/// let mut frame = GridFrame::new(16, 8);
+/// blinks.draw_at(&mut frame, start + Duration::from_millis(100));
+/// assert_eq!(frame.get(0, 3), Some(15));
+/// assert_eq!(frame.get(15, 0), Some(0));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BlinkScheduler {
+    /// The blinking cells, with how they blink.
+    cells: Vec<((usize, usize), Blink)>,
+}
+
+impl BlinkScheduler {
+    /// Create a scheduler without any blinking cell.
+    pub fn new() -> BlinkScheduler {
+        BlinkScheduler { cells: Vec::new() }
+    }
+
+    /// Start blinking the cell at `x`, `y` now. See `start_at`.
+    pub fn start(&mut self, x: usize, y: usize, period: Duration, duty: f32, intensity: u8) {
+        self.start_at(x, y, period, duty, intensity, Instant::now())
+    }
+
+    /// Start blinking the cell at `x`, `y` at `now`, replacing how it blinked if it was already
+    /// blinking.
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - the duration of a full on and off cycle.
+    /// * `duty` - the fraction of the period during which the cell is on, between 0.0 and 1.0.
+    /// * `intensity` - the intensity of the cell when it is on, between 0 and 15.
+    /// * `now` - the start of the first on phase.
+    pub fn start_at(
+        &mut self,
+        x: usize,
+        y: usize,
+        period: Duration,
+        duty: f32,
+        intensity: u8,
+        now: Instant,
+    ) {
+        let blink = Blink {
+            period,
+            duty: duty.clamp(0., 1.),
+            intensity: intensity.min(15),
+            start: now,
+        };
+        match self.cells.iter_mut().find(|(cell, _)| *cell == (x, y)) {
+            Some(existing) => existing.1 = blink,
+            None => self.cells.push(((x, y), blink)),
+        }
+    }
+
+    /// Stop blinking the cell at `x`, `y`.
+    pub fn stop(&mut self, x: usize, y: usize) {
+        self.cells.retain(|(cell, _)| *cell != (x, y));
+    }
+
+    /// Stop blinking all the cells.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Returns true if the cell at `x`, `y` is blinking.
+    pub fn is_blinking(&self, x: usize, y: usize) -> bool {
+        self.cells.iter().any(|(cell, _)| *cell == (x, y))
+    }
+
+    /// Returns whether the cell at `x`, `y` is in its on phase at `now`, or `None` if it is not
+    /// blinking.
+    pub fn is_on_at(&self, x: usize, y: usize, now: Instant) -> Option<bool> {
+        self.cells
+            .iter()
+            .find(|(cell, _)| *cell == (x, y))
+            .map(|(_, blink)| blink.is_on_at(now))
+    }
+
+    /// Draw the blinking cells, as they are now, on `frame`.
+    pub fn draw(&self, frame: &mut GridFrame) {
+        self.draw_at(frame, Instant::now())
+    }
+
+    /// Draw the blinking cells, as they are at `now`, on `frame`.
+    pub fn draw_at(&self, frame: &mut GridFrame, now: Instant) {
+        for &((x, y), ref blink) in &self.cells {
+            let intensity = if blink.is_on_at(now) {
+                blink.intensity
+            } else {
+                0
+            };
+            frame.set(x, y, intensity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blink() {
+        let mut blinks = BlinkScheduler::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        blinks.start_at(1, 1, Duration::from_millis(100), 0.3, 10, start);
+        assert_eq!(blinks.is_on_at(1, 1, at(0)), Some(true));
+        assert_eq!(blinks.is_on_at(1, 1, at(29)), Some(true));
+        assert_eq!(blinks.is_on_at(1, 1, at(31)), Some(false));
+        assert_eq!(blinks.is_on_at(1, 1, at(135)), Some(false));
+        assert_eq!(blinks.is_on_at(1, 1, at(210)), Some(true));
+        assert_eq!(blinks.is_on_at(0, 0, at(0)), None);
+
+        // Restarting changes the rate and the phase.
+        blinks.start_at(1, 1, Duration::from_millis(40), 0.5, 20, at(10));
+        assert_eq!(blinks.is_on_at(1, 1, at(40)), Some(false));
+        assert_eq!(blinks.is_on_at(1, 1, at(50)), Some(true));
+
+        let mut frame = GridFrame::new(4, 4);
+        frame.fill(5);
+        blinks.start_at(2, 2, Duration::from_secs(0), 0., 7, start);
+        blinks.draw_at(&mut frame, at(50));
+        assert_eq!(frame.get(1, 1), Some(15));
+        assert_eq!(frame.get(2, 2), Some(7));
+        assert_eq!(frame.get(0, 0), Some(5));
+        blinks.draw_at(&mut frame, at(35));
+        assert_eq!(frame.get(1, 1), Some(0));
+
+        blinks.stop(1, 1);
+        assert!(!blinks.is_blinking(1, 1));
+        blinks.clear();
+        assert!(!blinks.is_blinking(2, 2));
+    }
+}
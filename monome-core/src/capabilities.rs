@@ -0,0 +1,139 @@
+//! What a device can do, deduced from what serialosc reports about it.
+
+use crate::event::MonomeDeviceType;
+
+/// What a device can do, so that an application can adapt its user interface to the device it
+/// is used with. This is deduced from the type, the model and the size of the device, as
+/// reported by serialosc.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{Capabilities, MonomeDeviceType};
+///
+/// let grid = Capabilities::new(MonomeDeviceType::Grid, Some("monome 128"), (16, 8));
+/// assert_eq!(grid.intensity_levels(), 16);
+/// assert!(grid.tilt);
+///
+/// let arc = Capabilities::new(MonomeDeviceType::Arc, Some("monome arc 2"), (0, 0));
+/// assert_eq!(arc.encoders, 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    /// The type of the device.
+    pub device_type: MonomeDeviceType,
+    /// The number of columns of leds of a grid, 0 for an arc.
+    pub width: usize,
+    /// The number of rows of leds of a grid, 0 for an arc.
+    pub height: usize,
+    /// The number of encoders of an arc, 0 for a grid.
+    pub encoders: usize,
+    /// True if the leds can be set to 16 levels of intensity, false if they can only be on or
+    /// off. Only the models known not to support it are considered monochrome.
+    pub varibright: bool,
+    /// True if the device has tilt sensors.
+    pub tilt: bool,
+    /// True if the device can be rotated with `/sys/rotation`.
+    pub rotation: bool,
+}
+
+impl Capabilities {
+    /// Deduce the capabilities of a device from its type, its model, e.g. "monome 128", if it
+    /// is known, and its size.
+    pub fn new(
+        device_type: MonomeDeviceType,
+        model: Option<&str>,
+        size: (i32, i32),
+    ) -> Capabilities {
+        let (width, height) = (size.0.max(0) as usize, size.1.max(0) as usize);
+        let model = model.unwrap_or("");
+        let is_grid = device_type == MonomeDeviceType::Grid;
+        let encoders = if device_type == MonomeDeviceType::Arc {
+            // "monome arc 2" or "monome arc 4".
+            model
+                .split_whitespace()
+                .last()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(4)
+        } else {
+            0
+        };
+        // The 40h and the kits built from it only have leds that are on or off.
+        let monochrome = model.contains("40h") || model.contains("kit");
+        Capabilities {
+            device_type,
+            width,
+            height,
+            encoders,
+            varibright: !monochrome,
+            tilt: is_grid,
+            rotation: is_grid,
+        }
+    }
+
+    /// Refine these capabilities with the serial number of the device, e.g. "m128-0042": the
+    /// serial numbers of the 40h and of the series grids show that their leds can only be on or
+    /// off, even when their model is not known.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use monome_core::{Capabilities, MonomeDeviceType};
+    ///
+    /// let grid = Capabilities::new(MonomeDeviceType::Grid, None, (16, 8));
+    /// assert!(grid.clone().with_serial("m1000123").varibright);
+    /// assert!(!grid.with_serial("m128-0042").varibright);
+    /// ```
+    pub fn with_serial(mut self, serial: &str) -> Capabilities {
+        let series = ["m40h", "m64-", "m128-", "m256-"];
+        if series.iter().any(|prefix| serial.starts_with(prefix)) {
+            self.varibright = false;
+        }
+        self
+    }
+
+    /// Get the number of levels of intensity of the leds: 16 for varibright devices, 2
+    /// otherwise.
+    pub fn intensity_levels(&self) -> u8 {
+        if self.varibright {
+            16
+        } else {
+            2
+        }
+    }
+
+    /// Returns true if the device is a grid that can be updated entirely with 8x8 quads.
+    pub fn quad_updates(&self) -> bool {
+        self.device_type == MonomeDeviceType::Grid
+            && self.width > 0
+            && self.height > 0
+            && self.width.is_multiple_of(8)
+            && self.height.is_multiple_of(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities() {
+        let grid = Capabilities::new(MonomeDeviceType::Grid, None, (8, 8));
+        assert!(grid.varibright);
+        assert!(grid.rotation);
+        assert!(grid.quad_updates());
+        assert_eq!(grid.encoders, 0);
+
+        let grid = Capabilities::new(MonomeDeviceType::Grid, Some("monome 40h"), (8, 8));
+        assert_eq!(grid.intensity_levels(), 2);
+
+        let grid = Capabilities::new(MonomeDeviceType::Grid, Some("monome zero"), (16, 6));
+        assert!(!grid.quad_updates());
+
+        let arc = Capabilities::new(MonomeDeviceType::Arc, None, (0, 0));
+        assert_eq!(arc.encoders, 4);
+        assert!(!arc.tilt);
+        assert!(!arc.quad_updates());
+        assert_eq!((arc.width, arc.height), (0, 0));
+    }
+}
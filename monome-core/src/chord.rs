@@ -0,0 +1,186 @@
+//! Grouping of the grid keys pressed nearly at the same time into chords.
+
+use std::time::{Duration, Instant};
+
+use crate::event::{KeyDirection, MonomeEvent};
+
+/// The keys pressed together, reported by a `ChordDetector`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyGesture {
+    /// A key pressed alone, as `(x, y)`: no other key has been pressed within the window.
+    Press(i32, i32),
+    /// Keys pressed within the window of the first one, as `(x, y)`, in the order in which they
+    /// have been pressed.
+    Chord(Vec<(i32, i32)>),
+}
+
+/// Groups the grid keys pressed within a short window into chords, e.g. for shortcuts made of
+/// several keys, or to select several cells at once. A group starts with a key press, and
+/// gathers the keys pressed until the window has elapsed, or until one of its keys is released,
+/// so that a quick tap is reported without waiting for the end of the window.
+///
+/// A group is reported by `process` when an event ends it, and by `poll` when its window has
+/// elapsed: `poll` has to be called regularly.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{ChordDetector, KeyDirection, KeyGesture, MonomeEvent};
+///
+/// let mut chords = ChordDetector::new(Duration::from_millis(50));
+/// let start = Instant::now();
+/// let press = |x, y| MonomeEvent::GridKey { x, y, direction: KeyDirection::Down };
+/// assert_eq!(chords.process_at(&press(0, 7), start), None);
+/// assert_eq!(chords.process_at(&press(15, 7), start + Duration::from_millis(20)), None);
+///
+/// assert_eq!(
+///     chords.poll_at(start + Duration::from_millis(50)),
+///     Some(KeyGesture::Chord(vec![(0, 7), (15, 7)]))
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChordDetector {
+    /// How long after its first key a group gathers keys.
+    window: Duration,
+    /// The keys of the group in progress, if any, with the time of its first key.
+    pending: Option<(Vec<(i32, i32)>, Instant)>,
+}
+
+impl ChordDetector {
+    /// Create a detector that groups the keys pressed within `window` of the first one.
+    pub fn new(window: Duration) -> ChordDetector {
+        ChordDetector {
+            window,
+            pending: None,
+        }
+    }
+
+    /// Get how long after its first key a group gathers keys.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Set how long after its first key a group gathers keys.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Process an event received from a device, timestamped now. See `process_at`.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<KeyGesture> {
+        self.process_at(event, Instant::now())
+    }
+
+    /// Process an event received from a device at a specific time. Returns the group ended by
+    /// this event, if any: a key pressed after the window of the group in progress starts a new
+    /// group, and the release of a key of the group ends it. Events other than grid keys are
+    /// ignored.
+    pub fn process_at(&mut self, event: &MonomeEvent, now: Instant) -> Option<KeyGesture> {
+        let (key, direction) = match *event {
+            MonomeEvent::GridKey { x, y, direction } => ((x, y), direction),
+            _ => return None,
+        };
+        match direction {
+            KeyDirection::Down => {
+                let ended = self.poll_at(now);
+                match self.pending {
+                    Some((ref mut keys, _)) => {
+                        if !keys.contains(&key) {
+                            keys.push(key);
+                        }
+                    }
+                    None => self.pending = Some((vec![key], now)),
+                }
+                ended
+            }
+            KeyDirection::Up => match self.pending {
+                Some((ref keys, _)) if keys.contains(&key) => self.end(),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the group in progress if its window has elapsed now. See `poll_at`.
+    pub fn poll(&mut self) -> Option<KeyGesture> {
+        self.poll_at(Instant::now())
+    }
+
+    /// Returns the group in progress if its window has elapsed at `now`.
+    pub fn poll_at(&mut self, now: Instant) -> Option<KeyGesture> {
+        match self.pending {
+            Some((_, start)) if now.saturating_duration_since(start) >= self.window => self.end(),
+            _ => None,
+        }
+    }
+
+    /// Returns true if a group is in progress.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// End the group in progress, if any, and return it.
+    fn end(&mut self) -> Option<KeyGesture> {
+        let (keys, _) = self.pending.take()?;
+        Some(match keys[..] {
+            [(x, y)] => KeyGesture::Press(x, y),
+            _ => KeyGesture::Chord(keys),
+        })
+    }
+}
+
+impl Default for ChordDetector {
+    fn default() -> ChordDetector {
+        ChordDetector::new(Duration::from_millis(50))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: i32, y: i32, direction: KeyDirection) -> MonomeEvent {
+        MonomeEvent::GridKey { x, y, direction }
+    }
+
+    #[test]
+    fn chords() {
+        let mut detector = ChordDetector::new(Duration::from_millis(40));
+        let start = Instant::now();
+        let ms = |m| start + Duration::from_millis(m);
+
+        assert_eq!(
+            detector.process_at(&key(1, 1, KeyDirection::Down), ms(0)),
+            None
+        );
+        assert_eq!(
+            detector.process_at(&key(2, 1, KeyDirection::Down), ms(10)),
+            None
+        );
+        assert_eq!(
+            detector.process_at(&key(2, 1, KeyDirection::Down), ms(15)),
+            None
+        );
+        assert_eq!(detector.poll_at(ms(39)), None);
+        // A key pressed after the window starts a new group.
+        assert_eq!(
+            detector.process_at(&key(3, 1, KeyDirection::Down), ms(45)),
+            Some(KeyGesture::Chord(vec![(1, 1), (2, 1)]))
+        );
+        assert!(detector.is_pending());
+        // A quick tap is reported when it is released.
+        assert_eq!(
+            detector.process_at(&key(1, 1, KeyDirection::Up), ms(50)),
+            None
+        );
+        assert_eq!(
+            detector.process_at(&key(3, 1, KeyDirection::Up), ms(55)),
+            Some(KeyGesture::Press(3, 1))
+        );
+        assert!(!detector.is_pending());
+        assert_eq!(detector.poll_at(ms(200)), None);
+        assert_eq!(
+            detector.process_at(&MonomeEvent::EncoderDelta { n: 0, delta: 1 }, ms(200)),
+            None
+        );
+    }
+}
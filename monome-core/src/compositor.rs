@@ -0,0 +1,127 @@
+//! A stack of frames, to temporarily cover the content of a grid.
+
+use log::*;
+
+use crate::frame::GridFrame;
+
+/// Composes the frame to draw on a grid from a base frame, drawn by the application, and a stack
+/// of overlays. The overlay at the top of the stack covers everything below it, until it is
+/// popped, at which point what was below is visible again, unchanged. This is useful for
+/// transient user interface elements such as confirmation prompts or menus.
+///
+/// The base frame can still be modified while it is covered, and is drawn as it is when the last
+/// overlay is popped.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{Compositor, GridFrame};
+///
+/// let mut compositor = Compositor::new(16, 8);
+/// compositor.base_mut().set(0, 0, 15);
+///
+/// let mut prompt = GridFrame::new(16, 8);
+/// prompt.fill(4);
+/// compositor.push_overlay(prompt);
+/// assert_eq!(compositor.frame().get(0, 0), Some(4));
+///
+/// compositor.pop_overlay();
+/// assert_eq!(compositor.frame().get(0, 0), Some(15));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compositor {
+    /// The content drawn when there is no overlay.
+    base: GridFrame,
+    /// The overlays, the last one being at the top.
+    overlays: Vec<GridFrame>,
+}
+
+impl Compositor {
+    /// Create a compositor for a grid of `width` by `height` leds, with all the leds of the base
+    /// frame off, and no overlay.
+    pub fn new(width: usize, height: usize) -> Compositor {
+        Compositor {
+            base: GridFrame::new(width, height),
+            overlays: Vec::new(),
+        }
+    }
+
+    /// Get the base frame.
+    pub fn base(&self) -> &GridFrame {
+        &self.base
+    }
+
+    /// Get the base frame, for modification.
+    pub fn base_mut(&mut self) -> &mut GridFrame {
+        &mut self.base
+    }
+
+    /// Push an overlay on top of the stack, covering the base frame and the other overlays.
+    /// Overlays that are not the size of the base frame are ignored.
+    pub fn push_overlay(&mut self, overlay: GridFrame) {
+        if overlay.width() != self.base.width() || overlay.height() != self.base.height() {
+            error!(
+                "Overlay size {}x{} does not match the size {}x{}.",
+                overlay.width(),
+                overlay.height(),
+                self.base.width(),
+                self.base.height()
+            );
+            return;
+        }
+        self.overlays.push(overlay);
+    }
+
+    /// Remove the overlay at the top of the stack, and return it. Returns `None` if there is no
+    /// overlay.
+    pub fn pop_overlay(&mut self) -> Option<GridFrame> {
+        self.overlays.pop()
+    }
+
+    /// Get the overlay at the top of the stack, for modification.
+    pub fn top_overlay_mut(&mut self) -> Option<&mut GridFrame> {
+        self.overlays.last_mut()
+    }
+
+    /// Get the number of overlays in the stack.
+    pub fn overlay_count(&self) -> usize {
+        self.overlays.len()
+    }
+
+    /// Get the frame to draw: the overlay at the top of the stack, or the base frame if there is
+    /// no overlay.
+    pub fn frame(&self) -> &GridFrame {
+        self.overlays.last().unwrap_or(&self.base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays() {
+        let mut compositor = Compositor::new(8, 8);
+        compositor.base_mut().set(1, 1, 15);
+        let base = compositor.base().clone();
+
+        compositor.push_overlay(GridFrame::new(8, 8));
+        compositor.push_overlay(GridFrame::new(4, 4));
+        assert_eq!(compositor.overlay_count(), 1);
+
+        let mut menu = GridFrame::new(8, 8);
+        menu.set(2, 2, 8);
+        compositor.push_overlay(menu.clone());
+        compositor.base_mut().set(3, 3, 7);
+        assert_eq!(compositor.frame(), &menu);
+
+        assert_eq!(compositor.pop_overlay(), Some(menu));
+        assert_eq!(compositor.frame(), &GridFrame::new(8, 8));
+        compositor.pop_overlay();
+
+        let mut expected = base;
+        expected.set(3, 3, 7);
+        assert_eq!(compositor.frame(), &expected);
+        assert_eq!(compositor.pop_overlay(), None);
+    }
+}
@@ -0,0 +1,318 @@
+//! Small control messages exchanged between applications that share a device.
+
+use std::time::{Duration, Instant};
+
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::osc::build_osc_message;
+
+/// The address prefix of the control messages, distinct from the addresses of the devices.
+const CONTROL_PREFIX: &str = "/monome-rs/control";
+
+/// A control message exchanged between applications that share a device, for example to ask
+/// another application to hand the device over. Each message is a single OSC message, at an
+/// address starting with `/monome-rs/control`, so that it can travel over any OSC link between
+/// the applications.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlMessage {
+    /// Asks the other application to answer with a `Pong` of the same id.
+    Ping {
+        /// The id of the ping.
+        id: i32,
+    },
+    /// The answer to a `Ping`.
+    Pong {
+        /// The id of the ping answered.
+        id: i32,
+    },
+    /// Asks the other application for something, e.g. "focus" or "handoff".
+    Request {
+        /// The id of the request, repeated in its response.
+        id: i32,
+        /// What is requested.
+        name: String,
+    },
+    /// The answer to a `Request`.
+    Response {
+        /// The id of the request answered.
+        id: i32,
+        /// Whether the request has been accepted.
+        accepted: bool,
+    },
+}
+
+impl ControlMessage {
+    /// Build the OSC packet of this message.
+    pub fn to_packet(&self) -> OscPacket {
+        let address = |name: &str| format!("{}/{}", CONTROL_PREFIX, name);
+        match *self {
+            ControlMessage::Ping { id } => {
+                build_osc_message(&address("ping"), vec![OscType::Int(id)])
+            }
+            ControlMessage::Pong { id } => {
+                build_osc_message(&address("pong"), vec![OscType::Int(id)])
+            }
+            ControlMessage::Request { id, ref name } => build_osc_message(
+                &address("request"),
+                vec![OscType::Int(id), OscType::String(name.clone())],
+            ),
+            ControlMessage::Response { id, accepted } => build_osc_message(
+                &address("response"),
+                vec![OscType::Int(id), OscType::Int(i32::from(accepted))],
+            ),
+        }
+    }
+
+    /// Parse a control message, or returns `None` if `message` is not one.
+    pub fn from_message(message: &OscMessage) -> Option<ControlMessage> {
+        let name = message.addr.strip_prefix(CONTROL_PREFIX)?;
+        let args = message.args.as_deref().unwrap_or(&[]);
+        match (name, args) {
+            ("/ping", [OscType::Int(id)]) => Some(ControlMessage::Ping { id: *id }),
+            ("/pong", [OscType::Int(id)]) => Some(ControlMessage::Pong { id: *id }),
+            ("/request", [OscType::Int(id), OscType::String(name)]) => {
+                Some(ControlMessage::Request {
+                    id: *id,
+                    name: name.clone(),
+                })
+            }
+            ("/response", [OscType::Int(id), OscType::Int(accepted)]) => {
+                Some(ControlMessage::Response {
+                    id: *id,
+                    accepted: *accepted != 0,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What a `ControlChannel` received.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlEvent {
+    /// The other application pinged this one: `reply` is the pong to send back.
+    Pinged {
+        /// The pong to send back.
+        reply: OscPacket,
+    },
+    /// The other application answered a ping, after `round_trip`.
+    Pong {
+        /// The id of the ping.
+        id: i32,
+        /// The time between the ping and its answer.
+        round_trip: Duration,
+    },
+    /// The other application asks for something. Answer it with `ControlChannel::respond`.
+    Request {
+        /// The id of the request.
+        id: i32,
+        /// What is requested.
+        name: String,
+    },
+    /// The other application answered a request sent with `ControlChannel::request`.
+    Response {
+        /// The id of the request.
+        id: i32,
+        /// What was requested.
+        name: String,
+        /// Whether the request has been accepted.
+        accepted: bool,
+    },
+}
+
+/// Sends pings and requests to another application sharing a device, and matches the answers
+/// with them. The application sends the packets returned over its link with the other
+/// application, and passes the messages it receives to `process`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use monome_core::{ControlChannel, ControlEvent};
+/// use rosc::OscPacket;
+///
+/// let mut sequencer = ControlChannel::new();
+/// let mut mixer = ControlChannel::new();
+/// let now = Instant::now();
+/// let message = |packet| match packet {
+///     OscPacket::Message(message) => message,
+///     _ => unreachable!(),
+/// };
+///
+/// let (id, packet) = sequencer.request_at("focus", now);
+/// let request = mixer.process_at(&message(packet), now);
+/// assert_eq!(request, Some(ControlEvent::Request { id, name: "focus".to_string() }));
+/// let response = mixer.respond(id, true);
+/// match sequencer.process_at(&message(response), now) {
+///     Some(ControlEvent::Response { name, accepted, .. }) => {
+///         assert_eq!(name, "focus");
+///         assert!(accepted);
+///     }
+///     _ => panic!(),
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ControlChannel {
+    /// The id of the next ping or request.
+    next_id: i32,
+    /// The pings waiting for an answer, with the time at which they have been sent.
+    pings: Vec<(i32, Instant)>,
+    /// The requests waiting for an answer, with their name and the time at which they have been
+    /// sent.
+    requests: Vec<(i32, String, Instant)>,
+}
+
+impl ControlChannel {
+    /// Create a channel with nothing waiting for an answer.
+    pub fn new() -> ControlChannel {
+        ControlChannel::default()
+    }
+
+    /// Returns a new id.
+    fn next_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+
+    /// Build a ping sent now. Returns its id, and the packet to send.
+    pub fn ping(&mut self) -> (i32, OscPacket) {
+        self.ping_at(Instant::now())
+    }
+
+    /// Build a ping sent at `now`. Returns its id, and the packet to send.
+    pub fn ping_at(&mut self, now: Instant) -> (i32, OscPacket) {
+        let id = self.next_id();
+        self.pings.push((id, now));
+        (id, ControlMessage::Ping { id }.to_packet())
+    }
+
+    /// Build a request for `name`, e.g. "focus" or "handoff", sent now. Returns its id, and the
+    /// packet to send.
+    pub fn request(&mut self, name: &str) -> (i32, OscPacket) {
+        self.request_at(name, Instant::now())
+    }
+
+    /// Build a request for `name` sent at `now`. Returns its id, and the packet to send.
+    pub fn request_at(&mut self, name: &str, now: Instant) -> (i32, OscPacket) {
+        let id = self.next_id();
+        self.requests.push((id, name.to_string(), now));
+        let request = ControlMessage::Request {
+            id,
+            name: name.to_string(),
+        };
+        (id, request.to_packet())
+    }
+
+    /// Build the response to the request `id` received from the other application.
+    pub fn respond(&self, id: i32, accepted: bool) -> OscPacket {
+        ControlMessage::Response { id, accepted }.to_packet()
+    }
+
+    /// Process a message received now. See `process_at`.
+    pub fn process(&mut self, message: &OscMessage) -> Option<ControlEvent> {
+        self.process_at(message, Instant::now())
+    }
+
+    /// Process a message received at `now`. Returns what it means, or `None` if it is not a
+    /// control message, or if it answers a ping or a request that is not waiting anymore.
+    pub fn process_at(&mut self, message: &OscMessage, now: Instant) -> Option<ControlEvent> {
+        match ControlMessage::from_message(message)? {
+            ControlMessage::Ping { id } => Some(ControlEvent::Pinged {
+                reply: ControlMessage::Pong { id }.to_packet(),
+            }),
+            ControlMessage::Pong { id } => {
+                let index = self.pings.iter().position(|&(ping, _)| ping == id)?;
+                let (_, sent_at) = self.pings.remove(index);
+                Some(ControlEvent::Pong {
+                    id,
+                    round_trip: now.saturating_duration_since(sent_at),
+                })
+            }
+            ControlMessage::Request { id, name } => Some(ControlEvent::Request { id, name }),
+            ControlMessage::Response { id, accepted } => {
+                let index = self
+                    .requests
+                    .iter()
+                    .position(|(request, _, _)| *request == id)?;
+                let (_, name, _) = self.requests.remove(index);
+                Some(ControlEvent::Response { id, name, accepted })
+            }
+        }
+    }
+
+    /// Forget the pings and requests sent more than `timeout` before `now` without an answer,
+    /// and returns their ids, for example to consider that the other application is gone.
+    pub fn expire_at(&mut self, timeout: Duration, now: Instant) -> Vec<i32> {
+        let expired = |sent_at: Instant| now.saturating_duration_since(sent_at) >= timeout;
+        let mut ids: Vec<i32> = self
+            .pings
+            .iter()
+            .filter(|&&(_, sent_at)| expired(sent_at))
+            .map(|&(id, _)| id)
+            .collect();
+        ids.extend(
+            self.requests
+                .iter()
+                .filter(|(_, _, sent_at)| expired(*sent_at))
+                .map(|(id, _, _)| *id),
+        );
+        self.pings.retain(|&(_, sent_at)| !expired(sent_at));
+        self.requests.retain(|(_, _, sent_at)| !expired(*sent_at));
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(packet: OscPacket) -> OscMessage {
+        match packet {
+            OscPacket::Message(message) => message,
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn ping_pong() {
+        let start = Instant::now();
+        let mut a = ControlChannel::new();
+        let mut b = ControlChannel::new();
+        let (id, ping) = a.ping_at(start);
+        let reply = match b.process_at(&message(ping), start) {
+            Some(ControlEvent::Pinged { reply }) => reply,
+            event => panic!("unexpected event {:?}", event),
+        };
+        let later = start + Duration::from_millis(3);
+        assert_eq!(
+            a.process_at(&message(reply.clone()), later),
+            Some(ControlEvent::Pong {
+                id,
+                round_trip: Duration::from_millis(3)
+            })
+        );
+        // Answered already.
+        assert_eq!(a.process_at(&message(reply), later), None);
+
+        let (first, _) = a.ping_at(start);
+        let (second, _) = a.request_at("handoff", later);
+        assert_eq!(
+            a.expire_at(Duration::from_millis(2), later + Duration::from_millis(1)),
+            vec![first]
+        );
+        assert_eq!(
+            a.expire_at(Duration::from_millis(2), later + Duration::from_millis(2)),
+            vec![second]
+        );
+        assert_eq!(
+            a.process_at(&message(b.respond(second, false)), later),
+            None
+        );
+
+        let other = message(build_osc_message("/plop/grid/key", vec![]));
+        assert_eq!(a.process_at(&other, later), None);
+        let malformed = message(build_osc_message("/monome-rs/control/ping", vec![]));
+        assert_eq!(ControlMessage::from_message(&malformed), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! Mapping of led intensities to the levels sent to a device, to correct their brightness.
+
+/// A mapping of each of the 16 intensities of a led to the level actually sent to the device.
+/// The brightness of varibright leds is not perceived as linear in their level: a gamma curve
+/// makes the intensities 0 to 15 look like an even ramp.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::BrightnessCurve;
+///
+/// let curve = BrightnessCurve::gamma(2.2);
+/// assert_eq!(curve.map(0), 0);
+/// assert_eq!(curve.map(1), 1);
+/// assert_eq!(curve.map(8), 4);
+/// assert_eq!(curve.map(15), 15);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BrightnessCurve {
+    /// The level sent for each intensity.
+    table: [u8; 16],
+}
+
+impl BrightnessCurve {
+    /// The curve that leaves the intensities unchanged.
+    pub fn linear() -> BrightnessCurve {
+        let mut table = [0; 16];
+        for (intensity, level) in table.iter_mut().enumerate() {
+            *level = intensity as u8;
+        }
+        BrightnessCurve { table }
+    }
+
+    /// A gamma curve: an intensity `i` is sent as `15 * (i / 15) ^ gamma`, rounded. Intensities
+    /// above 0 are sent as 1 at least, so that leds that are on stay visible. A gamma above 1
+    /// darkens the middle intensities, a gamma below 1 brightens them.
+    pub fn gamma(gamma: f32) -> BrightnessCurve {
+        let mut table = [0; 16];
+        for (intensity, level) in table.iter_mut().enumerate().skip(1) {
+            let corrected = 15. * (intensity as f32 / 15.).powf(gamma.max(0.));
+            *level = (corrected.round() as u8).clamp(1, 15);
+        }
+        BrightnessCurve { table }
+    }
+
+    /// A curve defined by the level to send for each intensity. Levels above 15 are sent as 15.
+    pub fn from_table(table: [u8; 16]) -> BrightnessCurve {
+        let mut curve = BrightnessCurve { table };
+        for level in curve.table.iter_mut() {
+            *level = (*level).min(15);
+        }
+        curve
+    }
+
+    /// Get the level sent for each intensity.
+    pub fn table(&self) -> [u8; 16] {
+        self.table
+    }
+
+    /// Get the level to send for `intensity`. Intensities above 15 are mapped as 15.
+    pub fn map(&self, intensity: u8) -> u8 {
+        self.table[usize::from(intensity.min(15))]
+    }
+
+    /// Replace each intensity of `leds` by the level to send for it.
+    pub fn apply(&self, leds: &mut [u8]) {
+        for led in leds.iter_mut() {
+            *led = self.map(*led);
+        }
+    }
+}
+
+impl Default for BrightnessCurve {
+    fn default() -> BrightnessCurve {
+        BrightnessCurve::linear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves() {
+        let linear = BrightnessCurve::linear();
+        assert_eq!(linear.map(7), 7);
+        assert_eq!(linear.map(20), 15);
+
+        let curve = BrightnessCurve::gamma(2.2);
+        let table = curve.table();
+        assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(BrightnessCurve::gamma(1.), linear);
+
+        let curve = BrightnessCurve::from_table([3; 16]);
+        let mut leds = [0, 15, 30];
+        curve.apply(&mut leds);
+        assert_eq!(leds, [3, 3, 3]);
+        assert_eq!(BrightnessCurve::from_table([20; 16]).map(0), 15);
+    }
+}
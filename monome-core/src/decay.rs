@@ -0,0 +1,139 @@
+//! A layer of leds that fade out over time, for motion trails and activity meters.
+
+use std::time::Duration;
+
+use crate::frame::GridFrame;
+
+/// A grid of led intensities that fade toward zero on each tick. Writing to a led sets it to an
+/// intensity, from which it decays exponentially, so that moving a lit led leaves a trail behind
+/// it.
+///
+/// # Example
+///
+/// Leave a trail behind the last pressed keys:
+///
+/// ```
+/// use std::time::Duration;
+/// use monome_core::DecayBuffer;
+///
+/// let mut trail = DecayBuffer::new(16, 8, Duration::from_millis(200));
+/// trail.set(3, 4, 15);
+/// // On each frame, at about 30 frames per second:
+/// trail.tick(Duration::from_millis(33));
+/// let frame = trail.frame();
+/// assert!(frame.get(3, 4).unwrap() < 15);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecayBuffer {
+    /// The width of the buffer, in leds.
+    width: usize,
+    /// The height of the buffer, in leds.
+    height: usize,
+    /// The intensities of the leds, packed in row order, kept as floats so that slow decays
+    /// are not lost to rounding.
+    levels: Vec<f32>,
+    /// The time it takes for an intensity to decay to about 37% of its value.
+    time_constant: Duration,
+}
+
+impl DecayBuffer {
+    /// Create a buffer of `width` by `height` leds, all off.
+    ///
+    /// # Arguments
+    ///
+    /// * `time_constant` - the time it takes for a led to decay to about a third of its
+    ///   intensity. A zero time constant turns leds off on the next tick.
+    pub fn new(width: usize, height: usize, time_constant: Duration) -> DecayBuffer {
+        DecayBuffer {
+            width,
+            height,
+            levels: vec![0.; width * height],
+            time_constant,
+        }
+    }
+
+    /// Set the time constant of the decay, for the next ticks.
+    pub fn set_time_constant(&mut self, time_constant: Duration) {
+        self.time_constant = time_constant;
+    }
+
+    /// Get the time constant of the decay.
+    pub fn time_constant(&self) -> Duration {
+        self.time_constant
+    }
+
+    /// Set the intensity of the led at `x`, `y`, between 0 and 15, from which it starts
+    /// decaying. This does nothing if it is outside of the buffer.
+    pub fn set(&mut self, x: usize, y: usize, intensity: u8) {
+        if x < self.width && y < self.height {
+            self.levels[y * self.width + x] = f32::from(intensity.min(15));
+        }
+    }
+
+    /// Get the current intensity of the led at `x`, `y`, or `None` if it is outside of the
+    /// buffer.
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x < self.width && y < self.height {
+            Some(self.levels[y * self.width + x].round() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Turn all the leds off immediately.
+    pub fn clear(&mut self) {
+        for level in self.levels.iter_mut() {
+            *level = 0.;
+        }
+    }
+
+    /// Make all the leds decay by the amount corresponding to `elapsed`, the time since the
+    /// previous tick.
+    pub fn tick(&mut self, elapsed: Duration) {
+        let tau = self.time_constant.as_secs_f32();
+        let factor = if tau > 0. {
+            (-elapsed.as_secs_f32() / tau).exp()
+        } else {
+            0.
+        };
+        for level in self.levels.iter_mut() {
+            *level *= factor;
+            // Below half an intensity step, the led would be off once rounded.
+            if *level < 0.5 {
+                *level = 0.;
+            }
+        }
+    }
+
+    /// Get the current intensities as a frame, that can be drawn on a grid.
+    pub fn frame(&self) -> GridFrame {
+        let mut frame = GridFrame::new(self.width, self.height);
+        for (led, level) in frame.leds_mut().iter_mut().zip(self.levels.iter()) {
+            *led = level.round() as u8;
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decay() {
+        let mut buffer = DecayBuffer::new(8, 8, Duration::from_millis(100));
+        buffer.set(1, 2, 15);
+        assert_eq!(buffer.get(1, 2), Some(15));
+        buffer.tick(Duration::from_millis(100));
+        // 15 / e
+        assert_eq!(buffer.get(1, 2), Some(6));
+        buffer.tick(Duration::from_millis(1000));
+        assert_eq!(buffer.get(1, 2), Some(0));
+        assert_eq!(buffer.get(8, 0), None);
+
+        buffer.set(0, 0, 10);
+        buffer.set_time_constant(Duration::from_secs(0));
+        buffer.tick(Duration::from_millis(1));
+        assert_eq!(buffer.frame(), GridFrame::new(8, 8));
+    }
+}
@@ -0,0 +1,119 @@
+//! Suppression of the duplicate key events sent by some devices.
+
+use std::time::{Duration, Instant};
+
+use crate::event::{KeyDirection, MonomeEvent};
+
+/// A key, grid or encoder, identified by its position or its index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Grid(i32, i32),
+    Encoder(usize),
+}
+
+/// Suppresses key events that repeat the previous event of the same key, in the same direction,
+/// within a short window. Some combinations of serialosc and firmware occasionally deliver such
+/// duplicates. Events other than key presses and releases are never suppressed.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{EventDeduplicator, KeyDirection, MonomeEvent};
+///
+/// let mut dedup = EventDeduplicator::new(Duration::from_millis(20));
+/// let press = MonomeEvent::GridKey { x: 1, y: 1, direction: KeyDirection::Down };
+/// let now = Instant::now();
+/// assert!(dedup.accept_at(&press, now));
+/// assert!(!dedup.accept_at(&press, now + Duration::from_millis(5)));
+/// assert_eq!(dedup.suppressed(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventDeduplicator {
+    /// The window in which a repeated event is a duplicate.
+    window: Duration,
+    /// For each key that sent an event, the direction of its last event, and when it was
+    /// received.
+    last: Vec<(Key, KeyDirection, Instant)>,
+    /// The number of events suppressed so far.
+    suppressed: u64,
+}
+
+impl EventDeduplicator {
+    /// Create a deduplicator for which an event repeated within `window` is a duplicate.
+    pub fn new(window: Duration) -> EventDeduplicator {
+        EventDeduplicator {
+            window,
+            last: Vec::new(),
+            suppressed: 0,
+        }
+    }
+
+    /// Get the window in which a repeated event is a duplicate.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Get the number of events suppressed so far.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Returns true if `event`, received now, is not a duplicate.
+    pub fn accept(&mut self, event: &MonomeEvent) -> bool {
+        self.accept_at(event, Instant::now())
+    }
+
+    /// Returns true if `event`, received at `now`, is not a duplicate.
+    pub fn accept_at(&mut self, event: &MonomeEvent, now: Instant) -> bool {
+        let (key, direction) = match *event {
+            MonomeEvent::GridKey { x, y, direction } => (Key::Grid(x, y), direction),
+            MonomeEvent::EncoderKey { n, direction } => (Key::Encoder(n), direction),
+            _ => return true,
+        };
+        match self.last.iter_mut().find(|(k, _, _)| *k == key) {
+            Some(last) => {
+                let duplicate = last.1 == direction && now.duration_since(last.2) < self.window;
+                *last = (key, direction, now);
+                if duplicate {
+                    self.suppressed += 1;
+                    return false;
+                }
+            }
+            None => self.last.push((key, direction, now)),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicates() {
+        let mut dedup = EventDeduplicator::new(Duration::from_millis(10));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let key = |x, direction| MonomeEvent::GridKey { x, y: 0, direction };
+
+        assert!(dedup.accept_at(&key(0, KeyDirection::Down), at(0)));
+        assert!(dedup.accept_at(&key(1, KeyDirection::Down), at(1)));
+        assert!(!dedup.accept_at(&key(0, KeyDirection::Down), at(2)));
+        assert!(dedup.accept_at(&key(0, KeyDirection::Up), at(3)));
+        assert!(dedup.accept_at(&key(0, KeyDirection::Down), at(4)));
+        // Outside of the window.
+        assert!(dedup.accept_at(&key(0, KeyDirection::Down), at(20)));
+        assert!(dedup.accept_at(
+            &MonomeEvent::EncoderKey {
+                n: 0,
+                direction: KeyDirection::Down
+            },
+            at(20)
+        ));
+        let delta = MonomeEvent::EncoderDelta { n: 0, delta: 1 };
+        assert!(dedup.accept_at(&delta, at(21)));
+        assert!(dedup.accept_at(&delta, at(21)));
+        assert_eq!(dedup.suppressed(), 1);
+    }
+}
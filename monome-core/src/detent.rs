@@ -0,0 +1,357 @@
+//! Detents on the encoders of an arc: encoders that move in steps, and visual feedback on a ring
+//! when a value crosses a detent.
+
+use std::time::{Duration, Instant};
+
+use crate::event::MonomeEvent;
+use crate::ring::{RingLayout, RING_LEDS};
+
+/// An encoder that moves in steps, e.g. to select a preset: the `EncoderDelta` events of the
+/// encoder are accumulated, and each time they add up to a number of units of delta, the
+/// position moves by a step. Past the first and the last step, the position either stops, or
+/// wraps around to the other end.
+///
+/// The steps are evenly spaced on a full turn of the ring, laid out with a `RingLayout`, and
+/// `ring()` draws a tick mark for each of them, and the current position.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{DetentedEncoder, MonomeEvent, RingLayout};
+///
+/// let mut presets = DetentedEncoder::new(0, 8, RingLayout::default());
+/// presets.set_deltas_per_step(10);
+/// assert_eq!(presets.process(&MonomeEvent::EncoderDelta { n: 0, delta: 6 }), None);
+/// assert_eq!(presets.process(&MonomeEvent::EncoderDelta { n: 0, delta: 6 }), Some(1));
+///
+/// let ring = presets.ring();
+/// assert_eq!(ring[0], 4);
+/// assert_eq!(ring[8], 15);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetentedEncoder {
+    /// The encoder whose deltas move the position.
+    n: usize,
+    /// The number of steps.
+    steps: usize,
+    /// The current step.
+    position: usize,
+    /// The units of delta that make a step.
+    deltas_per_step: i32,
+    /// The units of delta accumulated since the last step.
+    accumulated: i32,
+    /// Whether the position wraps around past the first and the last step.
+    wrap: bool,
+    /// How the steps are laid out on the ring.
+    layout: RingLayout,
+    /// The intensity of the tick marks.
+    tick_intensity: u8,
+    /// The intensity of the current position.
+    position_intensity: u8,
+}
+
+impl DetentedEncoder {
+    /// Create an encoder moved by the deltas of encoder `n`, with `steps` steps laid out on the
+    /// ring with `layout`, at the first step. A step takes 32 units of delta, and the position
+    /// stops at the first and the last steps.
+    pub fn new(n: usize, steps: usize, layout: RingLayout) -> DetentedEncoder {
+        DetentedEncoder {
+            n,
+            steps: steps.max(1),
+            position: 0,
+            deltas_per_step: 32,
+            accumulated: 0,
+            wrap: false,
+            layout,
+            tick_intensity: 4,
+            position_intensity: 15,
+        }
+    }
+
+    /// Get the encoder whose deltas move the position.
+    pub fn encoder(&self) -> usize {
+        self.n
+    }
+
+    /// Get the number of steps.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Set the number of steps, 1 at least. The position is kept if it is still a step, and
+    /// moved to the last step otherwise.
+    pub fn set_steps(&mut self, steps: usize) {
+        self.steps = steps.max(1);
+        self.position = self.position.min(self.steps - 1);
+    }
+
+    /// Get the current step, from 0.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Move to a step, or to the last step if it doesn't exist. The deltas accumulated towards
+    /// the next step are forgotten.
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position.min(self.steps - 1);
+        self.accumulated = 0;
+    }
+
+    /// Set the units of delta that make a step, 1 at least.
+    pub fn set_deltas_per_step(&mut self, deltas: i32) {
+        self.deltas_per_step = deltas.max(1);
+    }
+
+    /// Set whether the position wraps around past the first and the last step, or stops there.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Set the intensity of the tick marks, and of the current position.
+    pub fn set_intensities(&mut self, tick: u8, position: u8) {
+        self.tick_intensity = tick.min(15);
+        self.position_intensity = position.min(15);
+    }
+
+    /// Accumulate `delta` units of delta, and move by the steps they make. Returns the new
+    /// position if it has changed. Turning past the first or the last step, when the position
+    /// doesn't wrap around, doesn't accumulate anything, so that turning back moves right away.
+    pub fn turn(&mut self, delta: i32) -> Option<usize> {
+        let previous = self.position;
+        self.accumulated += delta;
+        let mut steps = self.accumulated / self.deltas_per_step;
+        self.accumulated %= self.deltas_per_step;
+        while steps != 0 {
+            let step = steps.signum();
+            steps -= step;
+            if step > 0 && self.position + 1 < self.steps {
+                self.position += 1;
+            } else if step < 0 && self.position > 0 {
+                self.position -= 1;
+            } else if self.wrap {
+                self.position = if step > 0 { 0 } else { self.steps - 1 };
+            } else {
+                self.accumulated = 0;
+                break;
+            }
+        }
+        if self.position != previous {
+            Some(self.position)
+        } else {
+            None
+        }
+    }
+
+    /// Process an event received from a device. Returns the new position if the event has
+    /// changed it. Events other than the deltas of the encoder of this position are ignored.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<usize> {
+        match *event {
+            MonomeEvent::EncoderDelta { n, delta } if n == self.n => self.turn(delta),
+            _ => None,
+        }
+    }
+
+    /// Get the led of a step on the ring.
+    pub fn step_led(&self, step: usize) -> usize {
+        self.layout.value_to_led(step as f32 / self.steps as f32)
+    }
+
+    /// Get the ring that shows the steps: a tick mark for each step, and the current position,
+    /// brighter.
+    pub fn ring(&self) -> [u8; RING_LEDS] {
+        let mut ring = [0; RING_LEDS];
+        for step in 0..self.steps {
+            ring[self.step_led(step)] = self.tick_intensity;
+        }
+        ring[self.step_led(self.position)] = self.position_intensity;
+        ring
+    }
+}
+
+/// Flashes the led of a detent on an arc ring when the value of an encoder crosses it, fading
+/// out over a short time, so that turning the encoder past a detent feels like a click.
+///
+/// The value is between 0.0 and 1.0, as laid out by a `RingLayout`, and the detents are evenly
+/// spaced on it: with 4 detents, they are at 0.0, 0.25, 0.5, 0.75 and 1.0. The flashes are drawn
+/// on top of the ring drawn by the application.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use monome_core::{DetentFeedback, RingLayout};
+///
+/// let mut feedback = DetentFeedback::new(4, RingLayout::default());
+/// let now = Instant::now();
+/// // The value goes from 0.2 to 0.3, past the detent at 0.25.
+/// assert_eq!(feedback.update_at(0.2, 0.3, now), 1);
+///
+/// let mut ring = [0; 64];
+/// feedback.draw_at(&mut ring, now);
+/// assert_eq!(ring[16], 15);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DetentFeedback {
+    /// The number of detents on a full turn.
+    detents: usize,
+    /// How the value is laid out on the ring.
+    layout: RingLayout,
+    /// The intensity of a flash when it starts.
+    intensity: u8,
+    /// How long a flash takes to fade out.
+    duration: Duration,
+    /// The leds flashing, with the time at which they started.
+    flashes: Vec<(usize, Instant)>,
+}
+
+impl DetentFeedback {
+    /// Create the feedback for `detents` detents evenly spaced on a full turn, laid out on the
+    /// ring with `layout`. Flashes start at full intensity and fade out in 150ms.
+    pub fn new(detents: usize, layout: RingLayout) -> DetentFeedback {
+        DetentFeedback {
+            detents,
+            layout,
+            intensity: 15,
+            duration: Duration::from_millis(150),
+            flashes: Vec::new(),
+        }
+    }
+
+    /// Set the intensity at which a flash starts, and how long it takes to fade out.
+    pub fn set_flash(&mut self, intensity: u8, duration: Duration) {
+        self.intensity = intensity.min(15);
+        self.duration = duration;
+    }
+
+    /// Get the number of detents on a full turn.
+    pub fn detents(&self) -> usize {
+        self.detents
+    }
+
+    /// Set the number of detents on a full turn. The flashes in progress are kept.
+    pub fn set_detents(&mut self, detents: usize) {
+        self.detents = detents;
+    }
+
+    /// Flash the detents crossed now by a value going from `previous` to `value`. Returns the
+    /// number of detents crossed.
+    pub fn update(&mut self, previous: f32, value: f32) -> usize {
+        self.update_at(previous, value, Instant::now())
+    }
+
+    /// Flash the detents crossed at `now` by a value going from `previous` to `value`. A detent
+    /// is crossed when the value reaches it or goes past it, but not when the value leaves it.
+    /// Returns the number of detents crossed.
+    pub fn update_at(&mut self, previous: f32, value: f32, now: Instant) -> usize {
+        let mut crossed = 0;
+        for detent in 0..=self.detents {
+            let position = detent as f32 / self.detents as f32;
+            if (previous < position && position <= value)
+                || (value <= position && position < previous)
+            {
+                let led = self.layout.value_to_led(position);
+                self.flashes.retain(|&(flashing, _)| flashing != led);
+                self.flashes.push((led, now));
+                crossed += 1;
+            }
+        }
+        crossed
+    }
+
+    /// Returns true while a flash is in progress at `now`, so that the ring needs to be drawn
+    /// again.
+    pub fn is_flashing_at(&self, now: Instant) -> bool {
+        self.flashes
+            .iter()
+            .any(|&(_, start)| now.saturating_duration_since(start) < self.duration)
+    }
+
+    /// Draw the flashes in progress now on `ring`. See `draw_at`.
+    pub fn draw(&mut self, ring: &mut [u8; RING_LEDS]) {
+        self.draw_at(ring, Instant::now())
+    }
+
+    /// Draw the flashes in progress at `now` on `ring`: the leds that are flashing are at least
+    /// as bright as their flash. The flashes that are over are forgotten.
+    pub fn draw_at(&mut self, ring: &mut [u8; RING_LEDS], now: Instant) {
+        let duration = self.duration;
+        self.flashes
+            .retain(|&(_, start)| now.saturating_duration_since(start) < duration);
+        for &(led, start) in &self.flashes {
+            let elapsed = now.saturating_duration_since(start).as_secs_f32();
+            let remaining = 1. - elapsed / duration.as_secs_f32();
+            let intensity = (f32::from(self.intensity) * remaining).round() as u8;
+            ring[led] = ring[led].max(intensity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring::RingDirection;
+
+    #[test]
+    fn detent_feedback() {
+        let now = Instant::now();
+        let mut feedback = DetentFeedback::new(8, RingLayout::new(32, RingDirection::Clockwise));
+        feedback.set_flash(10, Duration::from_millis(100));
+        // Reaching a detent flashes it, leaving it doesn't.
+        assert_eq!(feedback.update_at(0.1, 0.125, now), 1);
+        assert_eq!(feedback.update_at(0.125, 0.2, now), 0);
+        // Going down, across two detents.
+        assert_eq!(feedback.update_at(0.6, 0.3, now), 2);
+        assert_eq!(feedback.update_at(0.1, 1., now), 8);
+        assert_eq!(feedback.update_at(0.5, 0.5, now), 0);
+
+        let mut ring = [0; RING_LEDS];
+        ring[40] = 12;
+        feedback.draw_at(&mut ring, now + Duration::from_millis(50));
+        // 0.125 is at led 40, already brighter than the flash.
+        assert_eq!(ring[40], 12);
+        assert_eq!(ring[48], 5);
+        assert_eq!(ring[41], 0);
+        assert!(feedback.is_flashing_at(now + Duration::from_millis(50)));
+        assert!(!feedback.is_flashing_at(now + Duration::from_millis(100)));
+
+        let mut ring = [0; RING_LEDS];
+        feedback.draw_at(&mut ring, now + Duration::from_millis(100));
+        assert!(ring.iter().all(|&led| led == 0));
+    }
+
+    #[test]
+    fn detented_encoder() {
+        let mut encoder = DetentedEncoder::new(1, 4, RingLayout::default());
+        encoder.set_deltas_per_step(10);
+        assert_eq!(encoder.turn(-25), None);
+        // The deltas past the first step are not accumulated.
+        assert_eq!(encoder.turn(10), Some(1));
+        assert_eq!(encoder.turn(9), None);
+        assert_eq!(encoder.turn(1), Some(2));
+        assert_eq!(encoder.turn(100), Some(3));
+        assert_eq!(
+            encoder.process(&MonomeEvent::EncoderDelta { n: 0, delta: -10 }),
+            None
+        );
+
+        encoder.set_wrap(true);
+        assert_eq!(encoder.turn(10), Some(0));
+        assert_eq!(encoder.turn(-20), Some(2));
+        assert_eq!(encoder.turn(40), None);
+
+        encoder.set_intensities(3, 12);
+        let ring = encoder.ring();
+        let lit: Vec<(usize, u8)> = ring
+            .iter()
+            .enumerate()
+            .filter(|&(_, &led)| led > 0)
+            .map(|(led, &intensity)| (led, intensity))
+            .collect();
+        assert_eq!(lit, vec![(0, 3), (16, 3), (32, 12), (48, 3)]);
+
+        encoder.set_steps(2);
+        assert_eq!(encoder.position(), 1);
+        encoder.set_position(7);
+        assert_eq!(encoder.position(), 1);
+    }
+}
@@ -0,0 +1,548 @@
+//! Higher-level helpers for the encoders of an arc.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::event::{KeyDirection, MonomeEvent};
+
+/// Default number of encoders tracked by the helpers in this module.
+const ENCODER_COUNT: usize = 4;
+
+/// A gesture recognized from the deltas sent by an encoder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncoderGesture {
+    /// The encoder has been turned quickly in one direction.
+    Flick {
+        /// Which encoder performed the gesture.
+        n: usize,
+        /// The accumulated delta of the flick. The sign is the direction.
+        magnitude: i32,
+    },
+    /// The encoder has been turned slowly in one direction for a while.
+    Creep {
+        /// Which encoder performed the gesture.
+        n: usize,
+        /// The accumulated delta of the creep. The sign is the direction.
+        magnitude: i32,
+    },
+    /// The encoder has been turned back and forth quickly.
+    Scrub {
+        /// Which encoder performed the gesture.
+        n: usize,
+        /// The accumulated absolute movement during the scrub.
+        magnitude: i32,
+    },
+}
+
+/// Detects flicks, creeps and scrubs from `MonomeEvent::EncoderDelta` events.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{EncoderGesture, EncoderGestureDetector, MonomeEvent};
+/// let mut gestures = EncoderGestureDetector::new();
+/// // Events received from an arc, over any transport.
+/// let events = vec![MonomeEvent::EncoderDelta { n: 0, delta: 40 }];
+///
+/// for event in events {
+///     if let Some(EncoderGesture::Flick { n, magnitude }) = gestures.process(&event) {
+///         println!("Flicked encoder {} by {}", n, magnitude);
+///     }
+/// }
+/// ```
+pub struct EncoderGestureDetector {
+    /// Recent deltas for each encoder, with the time at which they have been received.
+    history: Vec<VecDeque<(Instant, i32)>>,
+    /// The window in which deltas are accumulated to detect a flick.
+    flick_window: Duration,
+    /// The accumulated delta above which a movement is considered a flick.
+    flick_threshold: i32,
+    /// The duration of slow movement after which a creep is detected.
+    creep_duration: Duration,
+    /// The maximum pause between two deltas of the same creep.
+    creep_max_gap: Duration,
+    /// The window in which direction changes are counted to detect a scrub.
+    scrub_window: Duration,
+    /// The number of direction changes after which a movement is considered a scrub.
+    scrub_reversals: usize,
+}
+
+impl Default for EncoderGestureDetector {
+    fn default() -> EncoderGestureDetector {
+        EncoderGestureDetector::new()
+    }
+}
+
+impl EncoderGestureDetector {
+    /// Create a gesture detector with default thresholds.
+    pub fn new() -> EncoderGestureDetector {
+        EncoderGestureDetector {
+            history: vec![VecDeque::new(); ENCODER_COUNT],
+            flick_window: Duration::from_millis(100),
+            flick_threshold: 24,
+            creep_duration: Duration::from_millis(1000),
+            creep_max_gap: Duration::from_millis(300),
+            scrub_window: Duration::from_millis(500),
+            scrub_reversals: 3,
+        }
+    }
+
+    /// Set the window and accumulated delta above which a movement is a flick.
+    pub fn set_flick_threshold(&mut self, window: Duration, threshold: i32) {
+        self.flick_window = window;
+        self.flick_threshold = threshold;
+    }
+
+    /// Set how long a slow movement has to last to be a creep, and the maximum pause between two
+    /// deltas of the same creep.
+    pub fn set_creep_duration(&mut self, duration: Duration, max_gap: Duration) {
+        self.creep_duration = duration;
+        self.creep_max_gap = max_gap;
+    }
+
+    /// Set the window and the number of direction changes after which a movement is a scrub.
+    pub fn set_scrub_threshold(&mut self, window: Duration, reversals: usize) {
+        self.scrub_window = window;
+        self.scrub_reversals = reversals;
+    }
+
+    /// Process an event received from a device, timestamped now. Returns a gesture if one has
+    /// been completed by this event. Events other than encoder deltas are ignored.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<EncoderGesture> {
+        self.process_at(event, Instant::now())
+    }
+
+    /// Process an event received from a device at a specific time. Returns a gesture if one has
+    /// been completed by this event. Events other than encoder deltas are ignored.
+    pub fn process_at(&mut self, event: &MonomeEvent, now: Instant) -> Option<EncoderGesture> {
+        let (n, delta) = match *event {
+            MonomeEvent::EncoderDelta { n, delta } => (n, delta),
+            _ => return None,
+        };
+        if n >= self.history.len() {
+            self.history.resize(n + 1, VecDeque::new());
+        }
+
+        let max_age = self.creep_duration.max(self.scrub_window);
+        let creep_max_gap = self.creep_max_gap;
+        let history = &mut self.history[n];
+
+        // A pause longer than the creep gap ends any movement in progress.
+        if let Some(&(last, _)) = history.back() {
+            if now.duration_since(last) > creep_max_gap {
+                history.clear();
+            }
+        }
+        history.push_back((now, delta));
+        while let Some(&(t, _)) = history.front() {
+            if now.duration_since(t) > max_age {
+                history.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let within = |window: Duration| {
+            history
+                .iter()
+                .filter(move |(t, _)| now.duration_since(*t) <= window)
+                .map(|(_, d)| *d)
+        };
+
+        let mut reversals = 0;
+        let mut previous = 0;
+        for d in within(self.scrub_window) {
+            if d.signum() != 0 && previous != 0 && d.signum() != previous {
+                reversals += 1;
+            }
+            if d.signum() != 0 {
+                previous = d.signum();
+            }
+        }
+        if reversals >= self.scrub_reversals {
+            let magnitude = within(self.scrub_window).map(i32::abs).sum();
+            history.clear();
+            return Some(EncoderGesture::Scrub { n, magnitude });
+        }
+
+        let flick: i32 = within(self.flick_window).sum();
+        if flick.abs() >= self.flick_threshold {
+            history.clear();
+            return Some(EncoderGesture::Flick {
+                n,
+                magnitude: flick,
+            });
+        }
+
+        let start = history.front().map(|&(t, _)| t).unwrap_or(now);
+        let same_direction = history.iter().all(|(_, d)| d.signum() == delta.signum());
+        if same_direction && now.duration_since(start) >= self.creep_duration {
+            let magnitude = history.iter().map(|(_, d)| *d).sum();
+            history.clear();
+            return Some(EncoderGesture::Creep { n, magnitude });
+        }
+
+        None
+    }
+}
+
+/// A composite event combining the key and the rotation of an encoder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushTurnEvent {
+    /// The encoder has been turned while not pressed.
+    Turn {
+        /// Which encoder has been turned.
+        n: usize,
+        /// The delta of this movement.
+        delta: i32,
+    },
+    /// The encoder has been turned while pressed.
+    PushTurn {
+        /// Which encoder has been turned.
+        n: usize,
+        /// The delta of this movement.
+        delta: i32,
+    },
+    /// The encoder has been pressed and released without being turned.
+    Click {
+        /// Which encoder has been clicked.
+        n: usize,
+    },
+}
+
+/// Tracks the pressed state of encoders with a key (only available on some older devices), and
+/// combines `EncoderKey` and `EncoderDelta` events into `PushTurnEvent`s.
+///
+/// # Example
+///
+/// Use push and turn for fine control of a value:
+///
+/// ```
+/// use monome_core::{MonomeEvent, PushTurnEvent, PushTurnTracker};
+/// let mut tracker = PushTurnTracker::new();
+/// let mut value = 0.;
+/// // Events received from an arc, over any transport.
+/// let events = vec![MonomeEvent::EncoderDelta { n: 0, delta: 3 }];
+///
+/// for event in events {
+///     match tracker.process(&event) {
+///         Some(PushTurnEvent::Turn { delta, .. }) => value += delta as f32,
+///         Some(PushTurnEvent::PushTurn { delta, .. }) => value += delta as f32 / 10.,
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct PushTurnTracker {
+    /// For each encoder, whether it is pressed, and whether it has been turned while pressed.
+    state: Vec<(bool, bool)>,
+}
+
+impl Default for PushTurnTracker {
+    fn default() -> PushTurnTracker {
+        PushTurnTracker::new()
+    }
+}
+
+impl PushTurnTracker {
+    /// Create a tracker for which all encoders are released.
+    pub fn new() -> PushTurnTracker {
+        PushTurnTracker {
+            state: vec![(false, false); ENCODER_COUNT],
+        }
+    }
+
+    /// Returns true if encoder `n` is currently pressed.
+    pub fn is_pressed(&self, n: usize) -> bool {
+        self.state.get(n).map(|s| s.0).unwrap_or(false)
+    }
+
+    /// Process an event received from a device. Returns a composite event if there is one.
+    /// Events other than encoder keys and deltas are ignored.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<PushTurnEvent> {
+        match *event {
+            MonomeEvent::EncoderKey { n, ref direction } => {
+                let state = self.state_mut(n);
+                match direction {
+                    KeyDirection::Down => {
+                        *state = (true, false);
+                        None
+                    }
+                    KeyDirection::Up => {
+                        let (pressed, turned) = *state;
+                        *state = (false, false);
+                        if pressed && !turned {
+                            Some(PushTurnEvent::Click { n })
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
+            MonomeEvent::EncoderDelta { n, delta } => {
+                let state = self.state_mut(n);
+                if state.0 {
+                    state.1 = true;
+                    Some(PushTurnEvent::PushTurn { n, delta })
+                } else {
+                    Some(PushTurnEvent::Turn { n, delta })
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn state_mut(&mut self, n: usize) -> &mut (bool, bool) {
+        if n >= self.state.len() {
+            self.state.resize(n + 1, (false, false));
+        }
+        &mut self.state[n]
+    }
+}
+
+/// A value edited with an encoder of an arc: the `EncoderDelta` events of the encoder move it by
+/// a step for each unit of delta, between a minimum and a maximum. Past them, the value is
+/// either clamped, or wraps around to the other end, e.g. for an angle or a position in a loop.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{MonomeEvent, VirtualEncoder};
+/// let mut volume = VirtualEncoder::new(0, 0., 1.);
+/// volume.set_step(0.01);
+/// // Events received from an arc, over any transport.
+/// let events = vec![
+///     MonomeEvent::EncoderDelta { n: 0, delta: 25 },
+///     MonomeEvent::EncoderDelta { n: 1, delta: 3 },
+///     MonomeEvent::EncoderDelta { n: 0, delta: 100 },
+/// ];
+///
+/// for event in events {
+///     if let Some(value) = volume.process(&event) {
+///         println!("Volume changed to {}", value);
+///     }
+/// }
+/// assert_eq!(volume.value(), 1.);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualEncoder {
+    /// The encoder whose deltas move the value.
+    n: usize,
+    /// The current value.
+    value: f32,
+    /// The lowest value.
+    min: f32,
+    /// The highest value.
+    max: f32,
+    /// The change of value for a delta of 1.
+    step: f32,
+    /// Whether the value wraps around past its minimum and maximum, instead of being clamped.
+    wrap: bool,
+}
+
+impl VirtualEncoder {
+    /// Create a value between `min` and `max`, starting at `min`, moved by the deltas of encoder
+    /// `n` by 1 for each unit of delta, and clamped.
+    pub fn new(n: usize, min: f32, max: f32) -> VirtualEncoder {
+        VirtualEncoder {
+            n,
+            value: min,
+            min,
+            max,
+            step: 1.,
+            wrap: false,
+        }
+    }
+
+    /// Get the encoder whose deltas move the value.
+    pub fn encoder(&self) -> usize {
+        self.n
+    }
+
+    /// Get the current value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Set the value. It is clamped or wrapped around between the minimum and the maximum.
+    pub fn set_value(&mut self, value: f32) {
+        self.value = self.limit(value);
+    }
+
+    /// Get the lowest and the highest values.
+    pub fn range(&self) -> (f32, f32) {
+        (self.min, self.max)
+    }
+
+    /// Set the lowest and the highest values. The current value is clamped or wrapped around
+    /// between them.
+    pub fn set_range(&mut self, min: f32, max: f32) {
+        self.min = min;
+        self.max = max;
+        self.value = self.limit(self.value);
+    }
+
+    /// Get the change of value for a delta of 1.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Set the change of value for a delta of 1. A negative step makes the value decrease when
+    /// the encoder is turned clockwise.
+    pub fn set_step(&mut self, step: f32) {
+        self.step = step;
+    }
+
+    /// Returns true if the value wraps around past its minimum and maximum.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Set whether the value wraps around past its minimum and maximum, or is clamped. When
+    /// wrapping, the minimum and the maximum are the same position, and the value is always
+    /// below the maximum.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+        self.value = self.limit(self.value);
+    }
+
+    /// Get the position of the value between the minimum, at 0.0, and the maximum, at 1.0, e.g.
+    /// to lay it out on a ring with a `RingLayout`.
+    pub fn normalized(&self) -> f32 {
+        if self.max > self.min {
+            (self.value - self.min) / (self.max - self.min)
+        } else {
+            0.
+        }
+    }
+
+    /// Move the value by `delta` steps. Returns the new value if it has changed.
+    pub fn turn(&mut self, delta: i32) -> Option<f32> {
+        let value = self.limit(self.value + delta as f32 * self.step);
+        if value == self.value {
+            return None;
+        }
+        self.value = value;
+        Some(value)
+    }
+
+    /// Process an event received from a device. Returns the new value if the event has changed
+    /// it. Events other than the deltas of the encoder of this value are ignored.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<f32> {
+        match *event {
+            MonomeEvent::EncoderDelta { n, delta } if n == self.n => self.turn(delta),
+            _ => None,
+        }
+    }
+
+    /// Returns `value` clamped or wrapped around between the minimum and the maximum.
+    fn limit(&self, value: f32) -> f32 {
+        if self.max <= self.min {
+            self.min
+        } else if self.wrap {
+            self.min + (value - self.min).rem_euclid(self.max - self.min)
+        } else {
+            value.clamp(self.min, self.max)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(n: usize, delta: i32) -> MonomeEvent {
+        MonomeEvent::EncoderDelta { n, delta }
+    }
+
+    #[test]
+    fn gestures() {
+        let mut detector = EncoderGestureDetector::new();
+        let start = Instant::now();
+        let ms = |m| start + Duration::from_millis(m);
+
+        // Fast movement in a single direction
+        assert_eq!(detector.process_at(&delta(0, 10), ms(0)), None);
+        assert_eq!(detector.process_at(&delta(0, 10), ms(20)), None);
+        assert_eq!(
+            detector.process_at(&delta(0, 10), ms(40)),
+            Some(EncoderGesture::Flick {
+                n: 0,
+                magnitude: 30
+            })
+        );
+
+        // Back and forth
+        assert_eq!(detector.process_at(&delta(1, 2), ms(1000)), None);
+        assert_eq!(detector.process_at(&delta(1, -2), ms(1100)), None);
+        assert_eq!(detector.process_at(&delta(1, 2), ms(1200)), None);
+        assert_eq!(
+            detector.process_at(&delta(1, -2), ms(1300)),
+            Some(EncoderGesture::Scrub { n: 1, magnitude: 8 })
+        );
+
+        // Slow movement in a single direction
+        let mut gesture = None;
+        for i in 0..=10 {
+            gesture = detector.process_at(&delta(2, -1), ms(2000 + i * 100));
+            if gesture.is_some() {
+                break;
+            }
+        }
+        assert_eq!(
+            gesture,
+            Some(EncoderGesture::Creep {
+                n: 2,
+                magnitude: -11
+            })
+        );
+    }
+
+    #[test]
+    fn push_turn() {
+        let mut tracker = PushTurnTracker::new();
+        let key = |direction| MonomeEvent::EncoderKey { n: 1, direction };
+
+        assert_eq!(
+            tracker.process(&delta(1, 3)),
+            Some(PushTurnEvent::Turn { n: 1, delta: 3 })
+        );
+        assert_eq!(tracker.process(&key(KeyDirection::Down)), None);
+        assert!(tracker.is_pressed(1));
+        assert_eq!(
+            tracker.process(&delta(1, -1)),
+            Some(PushTurnEvent::PushTurn { n: 1, delta: -1 })
+        );
+        assert_eq!(tracker.process(&key(KeyDirection::Up)), None);
+        assert!(!tracker.is_pressed(1));
+        assert_eq!(tracker.process(&key(KeyDirection::Down)), None);
+        assert_eq!(
+            tracker.process(&key(KeyDirection::Up)),
+            Some(PushTurnEvent::Click { n: 1 })
+        );
+    }
+
+    #[test]
+    fn virtual_encoder() {
+        let mut encoder = VirtualEncoder::new(2, -1., 1.);
+        encoder.set_step(0.25);
+        assert_eq!(encoder.value(), -1.);
+        assert_eq!(encoder.process(&delta(2, -1)), None);
+        assert_eq!(encoder.process(&delta(1, 4)), None);
+        assert_eq!(encoder.process(&delta(2, 6)), Some(0.5));
+        assert_eq!(encoder.process(&delta(2, 100)), Some(1.));
+        assert_eq!(encoder.normalized(), 1.);
+        assert_eq!(encoder.turn(1), None);
+
+        encoder.set_wrap(true);
+        // The maximum is the same position as the minimum.
+        assert_eq!(encoder.value(), -1.);
+        assert_eq!(encoder.turn(-2), Some(0.5));
+        assert_eq!(encoder.turn(3), Some(-0.75));
+        assert_eq!(encoder.turn(8), None);
+
+        encoder.set_range(0., 0.5);
+        assert_eq!(encoder.value(), 0.25);
+        encoder.set_value(3.);
+        assert_eq!(encoder.value(), 0.);
+    }
+}
@@ -0,0 +1,257 @@
+//! The events received from devices, and their parsing from OSC packets.
+
+use std::fmt;
+
+use log::*;
+use rosc::{OscPacket, OscType};
+
+/// Whether a key press is going up or down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyDirection {
+    /// The key has been released.
+    Up,
+    /// The key has been pressed.
+    Down,
+}
+
+/// An event received from a monome device. This can be either a key press or release, a tilt
+/// event, an encoder rotation event, or an encoder press or release. Events describing the state
+/// of the device are also emitted when the device has been set up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonomeEvent {
+    /// A key press or release
+    GridKey {
+        /// The horizontal offset at which the key has been pressed.
+        x: i32,
+        /// The vertical offset at which the key has been pressed.
+        y: i32,
+        /// Whether the key has been pressed (`Down`), or released (`Up`).
+        direction: KeyDirection,
+    },
+    /// A update about the tilt of this device.
+    Tilt {
+        /// Which sensor sent this tilt update.
+        n: i32,
+        /// The pitch of this device.
+        x: i32,
+        /// The roll of this device.
+        y: i32,
+        /// The yaw of this device.
+        z: i32,
+    },
+    /// An encoder delta information
+    EncoderDelta {
+        /// Which encoder is sending the event.
+        n: usize,
+        /// The delta of this movement on this encoder.
+        delta: i32,
+    },
+    /// A key press on an encoder (only available on some older devices).
+    EncoderKey {
+        /// Which encoder is sending the event.
+        n: usize,
+        /// Whether the encoder key has been pressed (`Down`), or released (`Up`).
+        direction: KeyDirection,
+    },
+    /// The size of the device, emitted after setup.
+    Size {
+        /// The number of columns of the device.
+        width: i32,
+        /// The number of rows of the device.
+        height: i32,
+    },
+    /// The rotation of the device, emitted after setup.
+    Rotation {
+        /// The rotation of the device: 0, 90, 180 or 270.
+        rotation: i32,
+    },
+    /// The prefix of the device, emitted after setup.
+    Prefix {
+        /// The prefix used for this device and this application.
+        prefix: String,
+    },
+    /// An action triggered by a key or a chord, emitted instead of the key press that triggered
+    /// it when an `ActionMap` is set on the device.
+    Action {
+        /// The name of the action.
+        name: String,
+    },
+    /// The device has been used again after being idle, emitted before the event that woke it
+    /// up, or instead of it if it has been swallowed, when idle dimming is enabled on the device.
+    Woke,
+}
+
+/// A type of device, either Grid (of various size), Arc (with 2 or 4 encoders), or unknown.
+#[derive(PartialEq, Clone)]
+pub enum MonomeDeviceType {
+    /// The type for a monome grid.
+    Grid,
+    /// The type for a monome arc.
+    Arc,
+    /// Unknown device, please file an issue.
+    Unknown,
+}
+
+impl From<&str> for MonomeDeviceType {
+    fn from(string: &str) -> MonomeDeviceType {
+        if string.contains("arc") {
+            MonomeDeviceType::Arc
+        } else {
+            MonomeDeviceType::Grid
+        }
+    }
+}
+
+impl fmt::Display for MonomeDeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            if *self == MonomeDeviceType::Grid {
+                "grid"
+            } else {
+                "arc"
+            }
+        )
+    }
+}
+
+impl fmt::Debug for MonomeDeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Parses a packet received from a device into an event, if it is an event for `prefix`. Packets
+/// that are not events, such as `/sys` and `/serialosc` messages, are logged and return `None`,
+/// and so do bundles: each of their packets must be parsed, as the transport of a device does,
+/// timestamping the events with the timetag of the bundle.
+pub fn parse(prefix: &str, packet: OscPacket) -> Option<MonomeEvent> {
+    debug!("⇦ {:?}", packet);
+
+    match packet {
+        OscPacket::Message(message) => {
+            if message.addr.starts_with("/serialosc") {
+                match (message.addr.as_str(), message.args.as_deref()) {
+                    ("/serialosc/device", _) => info!("/serialosc/device"),
+                    ("/serialosc/add", Some([OscType::String(ref device_name), ..])) => {
+                        info!("device added: {}", device_name);
+                    }
+                    ("/serialosc/remove", Some([OscType::String(ref device_name), ..])) => {
+                        info!("device removed: {}", device_name);
+                    }
+                    _ => warn!("unexpected message for prefix {}", message.addr),
+                }
+            } else if message.addr.starts_with("/sys") {
+                // This should only be received during the setup phase
+                debug!("/sys received: {:?}", message);
+            } else if message.addr.starts_with(prefix) {
+                if let Some(args) = &message.args {
+                    if message.addr.starts_with(&format!("{}/grid/key", prefix)) {
+                        if let [OscType::Int(x), OscType::Int(y), OscType::Int(v)] = args.as_slice()
+                        {
+                            info!("Key: {}:{} {}", *x, *y, *v);
+                            let direction = if *v == 1 {
+                                KeyDirection::Down
+                            } else {
+                                KeyDirection::Up
+                            };
+                            return Some(MonomeEvent::GridKey {
+                                x: *x,
+                                y: *y,
+                                direction,
+                            });
+                        }
+                        error!("Invalid /grid/key message received {:?}.", message);
+                    } else if message.addr.starts_with(&format!("{}/tilt", prefix)) {
+                        if let [OscType::Int(n), OscType::Int(x), OscType::Int(y), OscType::Int(z)] =
+                            args.as_slice()
+                        {
+                            info!("Tilt {} {},{},{}", *n, *x, *y, *z);
+                            return Some(MonomeEvent::Tilt {
+                                n: *n,
+                                x: *x,
+                                y: *y,
+                                z: *z,
+                            });
+                        }
+                        error!("Invalid /tilt message received {:?}.", message);
+                    } else if message.addr.starts_with(&format!("{}/enc/delta", prefix)) {
+                        if let [OscType::Int(n @ 0..), OscType::Int(delta)] = args.as_slice() {
+                            info!("Encoder delta {} {}", *n, *delta);
+                            return Some(MonomeEvent::EncoderDelta {
+                                n: *n as usize,
+                                delta: *delta,
+                            });
+                        }
+                        error!("Invalid /end/delta message received {:?}.", message);
+                    } else if message.addr.starts_with(&format!("{}/enc/key", prefix)) {
+                        if let [OscType::Int(n @ 0..), OscType::Int(direction)] = args.as_slice() {
+                            info!("Encoder key {} {}", *n, *direction);
+                            return Some(MonomeEvent::EncoderKey {
+                                n: *n as usize,
+                                direction: if *direction == 1 {
+                                    KeyDirection::Down
+                                } else {
+                                    KeyDirection::Up
+                                },
+                            });
+                        }
+                        error!("Invalid /end/key message received {:?}.", message);
+                    } else {
+                        error!("not handled: {:?}", message.addr);
+                    }
+                }
+            }
+            None
+        }
+        OscPacket::Bundle(bundle) => {
+            warn!("Unexpected bundle received, ignored: {:?}", bundle);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_osc_message;
+
+    #[test]
+    fn parse_events() {
+        let key = build_osc_message(
+            "/plop/grid/key",
+            vec![OscType::Int(3), OscType::Int(4), OscType::Int(1)],
+        );
+        assert_eq!(
+            parse("/plop", key.clone()),
+            Some(MonomeEvent::GridKey {
+                x: 3,
+                y: 4,
+                direction: KeyDirection::Down
+            })
+        );
+        assert_eq!(parse("/other", key), None);
+        let delta = build_osc_message("/plop/enc/delta", vec![OscType::Int(1), OscType::Int(-2)]);
+        assert_eq!(
+            parse("/plop", delta),
+            Some(MonomeEvent::EncoderDelta { n: 1, delta: -2 })
+        );
+        let sys = build_osc_message("/sys/rotation", vec![OscType::Int(90)]);
+        assert_eq!(parse("/plop", sys), None);
+
+        // Malformed packets are skipped.
+        let empty = build_osc_message("/serialosc/add", vec![]);
+        assert_eq!(parse("/plop", empty), None);
+        let short = build_osc_message("/plop/grid/key", vec![OscType::Int(3)]);
+        assert_eq!(parse("/plop", short), None);
+        let negative =
+            build_osc_message("/plop/enc/delta", vec![OscType::Int(-1), OscType::Int(1)]);
+        assert_eq!(parse("/plop", negative), None);
+        let bundle = OscPacket::Bundle(rosc::OscBundle {
+            timetag: OscType::Time(0, 1),
+            content: vec![build_osc_message("/plop/enc/delta", vec![])],
+        });
+        assert_eq!(parse("/plop", bundle), None);
+    }
+}
@@ -0,0 +1,116 @@
+//! Filtering of the events received from a device, before they are parsed.
+
+use rosc::{OscMessage, OscType};
+
+/// Describes which events an application is interested in. Events that are not accepted by the
+/// filter are dropped as soon as they are received, and never take space in the event queue.
+///
+/// # Example
+///
+/// Only receive key presses on the leftmost 8x8 quad of a grid:
+///
+/// ```
+/// use monome_core::EventFilter;
+/// let mut filter = EventFilter::none();
+/// filter.grid_keys = true;
+/// filter.grid_region = Some((0, 0, 8, 8));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventFilter {
+    /// Whether grid key presses and releases are received.
+    pub grid_keys: bool,
+    /// If set, only the grid keys in this region are received, as `(x, y, width, height)`.
+    pub grid_region: Option<(i32, i32, i32, i32)>,
+    /// Whether tilt events are received.
+    pub tilt: bool,
+    /// Whether encoder deltas are received.
+    pub encoder_deltas: bool,
+    /// Whether encoder key presses and releases are received.
+    pub encoder_keys: bool,
+}
+
+impl Default for EventFilter {
+    fn default() -> EventFilter {
+        EventFilter::all()
+    }
+}
+
+impl EventFilter {
+    /// A filter that accepts all the events.
+    pub fn all() -> EventFilter {
+        EventFilter {
+            grid_keys: true,
+            grid_region: None,
+            tilt: true,
+            encoder_deltas: true,
+            encoder_keys: true,
+        }
+    }
+
+    /// A filter that rejects all the events.
+    pub fn none() -> EventFilter {
+        EventFilter {
+            grid_keys: false,
+            grid_region: None,
+            tilt: false,
+            encoder_deltas: false,
+            encoder_keys: false,
+        }
+    }
+
+    /// Returns true if `message`, received from a device, should be queued. Messages that are not
+    /// events, such as `/sys` messages, are always accepted.
+    pub fn accepts(&self, message: &OscMessage) -> bool {
+        let addr = &message.addr;
+        if addr.starts_with("/sys") || addr.starts_with("/serialosc") {
+            true
+        } else if addr.ends_with("/grid/key") {
+            if !self.grid_keys {
+                return false;
+            }
+            match (self.grid_region, &message.args) {
+                (Some((rx, ry, rw, rh)), Some(args)) => match args.as_slice() {
+                    [OscType::Int(x), OscType::Int(y), ..] => {
+                        *x >= rx && *x < rx + rw && *y >= ry && *y < ry + rh
+                    }
+                    _ => true,
+                },
+                _ => true,
+            }
+        } else if addr.ends_with("/tilt") {
+            self.tilt
+        } else if addr.ends_with("/enc/delta") {
+            self.encoder_deltas
+        } else if addr.ends_with("/enc/key") {
+            self.encoder_keys
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(addr: &str, args: Vec<i32>) -> OscMessage {
+        OscMessage {
+            addr: addr.to_string(),
+            args: Some(args.into_iter().map(OscType::Int).collect()),
+        }
+    }
+
+    #[test]
+    fn filter() {
+        let mut filter = EventFilter::none();
+        filter.grid_keys = true;
+        filter.grid_region = Some((8, 0, 8, 8));
+
+        assert!(filter.accepts(&message("/prefix/grid/key", vec![9, 2, 1])));
+        assert!(!filter.accepts(&message("/prefix/grid/key", vec![2, 2, 1])));
+        assert!(!filter.accepts(&message("/prefix/tilt", vec![0, 1, 2, 3])));
+        assert!(!filter.accepts(&message("/prefix/enc/delta", vec![0, 1])));
+        assert!(filter.accepts(&message("/sys/rotation", vec![90])));
+        assert!(EventFilter::all().accepts(&message("/prefix/tilt", vec![0, 1, 2, 3])));
+    }
+}
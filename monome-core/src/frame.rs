@@ -0,0 +1,192 @@
+//! Frames of led intensities, that can be drawn independently from a device.
+
+/// A grid of led intensities, between 0 and 15, packed in row order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridFrame {
+    /// The width of the frame, in leds.
+    width: usize,
+    /// The height of the frame, in leds.
+    height: usize,
+    /// The intensities of the leds, packed in row order.
+    leds: Vec<u8>,
+}
+
+impl GridFrame {
+    /// Create a frame of `width` by `height` leds, all off.
+    pub fn new(width: usize, height: usize) -> GridFrame {
+        GridFrame {
+            width,
+            height,
+            leds: vec![0; width * height],
+        }
+    }
+
+    /// Get the width of this frame.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of this frame.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the intensity of the led at `x`, `y`, or `None` if it is outside of the frame.
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x < self.width && y < self.height {
+            Some(self.leds[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Set the intensity of the led at `x`, `y`. This does nothing if it is outside of the frame.
+    pub fn set(&mut self, x: usize, y: usize, intensity: u8) {
+        if x < self.width && y < self.height {
+            self.leds[y * self.width + x] = intensity;
+        }
+    }
+
+    /// Set all the leds of this frame to `intensity`.
+    pub fn fill(&mut self, intensity: u8) {
+        for led in self.leds.iter_mut() {
+            *led = intensity;
+        }
+    }
+
+    /// Shift the content of this frame `n` leds to the left. Leds that move out of the frame
+    /// reappear on the right side if `wrap` is true, and the leds uncovered are turned off
+    /// otherwise.
+    pub fn shift_left(&mut self, n: usize, wrap: bool) {
+        self.shift_region(self.bounds(), -(n as isize), 0, wrap);
+    }
+
+    /// Shift the content of this frame `n` leds to the right. Leds that move out of the frame
+    /// reappear on the left side if `wrap` is true, and the leds uncovered are turned off
+    /// otherwise.
+    pub fn shift_right(&mut self, n: usize, wrap: bool) {
+        self.shift_region(self.bounds(), n as isize, 0, wrap);
+    }
+
+    /// Shift the content of this frame `n` leds up. Leds that move out of the frame reappear at
+    /// the bottom if `wrap` is true, and the leds uncovered are turned off otherwise.
+    pub fn shift_up(&mut self, n: usize, wrap: bool) {
+        self.shift_region(self.bounds(), 0, -(n as isize), wrap);
+    }
+
+    /// Shift the content of this frame `n` leds down. Leds that move out of the frame reappear
+    /// at the top if `wrap` is true, and the leds uncovered are turned off otherwise.
+    pub fn shift_down(&mut self, n: usize, wrap: bool) {
+        self.shift_region(self.bounds(), 0, n as isize, wrap);
+    }
+
+    /// Shift the content of a region of this frame, leaving the rest of the frame untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `region` - the region to shift, as `(x, y, width, height)`. It is clipped to the frame.
+    /// * `dx` - the horizontal distance to shift by, positive to the right.
+    /// * `dy` - the vertical distance to shift by, positive downwards.
+    /// * `wrap` - if true, leds that move out of the region reappear on the other side,
+    ///   otherwise the leds uncovered are turned off.
+    ///
+    /// # Example
+    ///
+    /// Scroll the top row of a 16x8 frame one step to the left, like a marquee:
+    ///
+    /// ```
+    /// use monome_core::GridFrame;
+    /// let mut frame = GridFrame::new(16, 8);
+    /// frame.set(0, 0, 15);
+    /// frame.shift_region((0, 0, 16, 1), -1, 0, true);
+    /// assert_eq!(frame.get(15, 0), Some(15));
+    /// ```
+    pub fn shift_region(
+        &mut self,
+        region: (usize, usize, usize, usize),
+        dx: isize,
+        dy: isize,
+        wrap: bool,
+    ) {
+        let (x, y) = (region.0.min(self.width), region.1.min(self.height));
+        let width = region.2.min(self.width - x);
+        let height = region.3.min(self.height - y);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let mut shifted = vec![0; width * height];
+        for j in 0..height {
+            for i in 0..width {
+                let source_x = i as isize - dx;
+                let source_y = j as isize - dy;
+                let (source_x, source_y) = if wrap {
+                    (
+                        source_x.rem_euclid(width as isize),
+                        source_y.rem_euclid(height as isize),
+                    )
+                } else if source_x < 0
+                    || source_y < 0
+                    || source_x >= width as isize
+                    || source_y >= height as isize
+                {
+                    continue;
+                } else {
+                    (source_x, source_y)
+                };
+                shifted[j * width + i] =
+                    self.leds[(y + source_y as usize) * self.width + x + source_x as usize];
+            }
+        }
+        for j in 0..height {
+            let start = (y + j) * self.width + x;
+            self.leds[start..start + width].copy_from_slice(&shifted[j * width..(j + 1) * width]);
+        }
+    }
+
+    /// The region covering the whole frame.
+    fn bounds(&self) -> (usize, usize, usize, usize) {
+        (0, 0, self.width, self.height)
+    }
+
+    /// Get the intensities of this frame, packed in row order.
+    pub fn leds(&self) -> &[u8] {
+        &self.leds
+    }
+
+    /// Get the intensities of this frame, packed in row order, for modification.
+    pub fn leds_mut(&mut self) -> &mut [u8] {
+        &mut self.leds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift() {
+        let mut frame = GridFrame::new(4, 2);
+        frame.set(0, 0, 1);
+        frame.set(3, 1, 2);
+
+        frame.shift_right(1, false);
+        assert_eq!(frame.leds(), &[0, 1, 0, 0, 0, 0, 0, 0]);
+
+        frame.set(3, 1, 2);
+        frame.shift_right(1, true);
+        assert_eq!(frame.leds(), &[0, 0, 1, 0, 2, 0, 0, 0]);
+
+        frame.shift_up(1, true);
+        assert_eq!(frame.leds(), &[2, 0, 0, 0, 0, 0, 1, 0]);
+
+        frame.shift_down(3, false);
+        assert_eq!(frame.leds(), &[0; 8]);
+
+        frame.set(1, 0, 3);
+        frame.set(1, 1, 4);
+        frame.shift_region((1, 0, 2, 2), 0, 1, true);
+        assert_eq!(frame.leds(), &[0, 4, 0, 0, 0, 3, 0, 0]);
+        frame.shift_left(1, false);
+        assert_eq!(frame.leds(), &[4, 0, 0, 0, 3, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Logging of the frames drawn on a grid to a compact binary file, and reading them back.
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::frame::GridFrame;
+
+/// The bytes at the start of a frame log, followed by the version of the format.
+const MAGIC: &[u8; 4] = b"MFL1";
+
+/// Writes frames drawn on a grid, with the time at which they have been drawn, to a compact
+/// binary log, for example to find out what an installation displayed overnight. The log can be
+/// read back with a `FrameLogReader`.
+///
+/// The log starts with the 4 bytes `MFL1`. Each frame is then written as the number of
+/// microseconds since the log was created, as a little-endian `u64`, its width and its height as
+/// little-endian `u16`s, and its intensities, two per byte, the first one in the high nibble, in
+/// row order.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{FrameLogReader, FrameLogWriter, GridFrame};
+///
+/// let mut writer = FrameLogWriter::new(Vec::new()).unwrap();
+/// let mut frame = GridFrame::new(16, 8);
+/// frame.set(3, 2, 15);
+/// writer.write_frame(&frame).unwrap();
+///
+/// let log = writer.into_inner();
+/// let frames: Vec<_> = FrameLogReader::new(&log[..]).unwrap().collect();
+/// let (_elapsed, read) = frames[0].as_ref().unwrap();
+/// assert_eq!(read.get(3, 2), Some(15));
+/// ```
+#[derive(Debug)]
+pub struct FrameLogWriter<W: Write> {
+    /// Where the log is written.
+    writer: W,
+    /// When the log has been created.
+    start: Instant,
+}
+
+impl<W: Write> FrameLogWriter<W> {
+    /// Create a log written to `writer`, starting now.
+    pub fn new(writer: W) -> io::Result<FrameLogWriter<W>> {
+        FrameLogWriter::new_at(writer, Instant::now())
+    }
+
+    /// Create a log written to `writer`, starting at `start`.
+    pub fn new_at(mut writer: W, start: Instant) -> io::Result<FrameLogWriter<W>> {
+        writer.write_all(MAGIC)?;
+        Ok(FrameLogWriter { writer, start })
+    }
+
+    /// Append a frame drawn now.
+    pub fn write_frame(&mut self, frame: &GridFrame) -> io::Result<()> {
+        self.write_frame_at(frame, Instant::now())
+    }
+
+    /// Append a frame drawn at `at`. Frames larger than 65535 leds in either direction can't be
+    /// logged.
+    pub fn write_frame_at(&mut self, frame: &GridFrame, at: Instant) -> io::Result<()> {
+        if frame.width() > usize::from(u16::MAX) || frame.height() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame too large to be logged",
+            ));
+        }
+        let elapsed = at.saturating_duration_since(self.start).as_micros() as u64;
+        let mut record = Vec::with_capacity(12 + frame.leds().len().div_ceil(2));
+        record.extend_from_slice(&elapsed.to_le_bytes());
+        record.extend_from_slice(&(frame.width() as u16).to_le_bytes());
+        record.extend_from_slice(&(frame.height() as u16).to_le_bytes());
+        for pair in frame.leds().chunks(2) {
+            let high = pair[0].min(15);
+            let low = pair.get(1).map_or(0, |&led| led.min(15));
+            record.push(high << 4 | low);
+        }
+        self.writer.write_all(&record)
+    }
+
+    /// Flush the frames written to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Get the underlying writer back.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the frames written by a `FrameLogWriter`, with the time elapsed between the creation of
+/// the log and the moment they were drawn, for example to replay them on a grid. Reading stops
+/// at the end of the log, or at the first error.
+///
+/// # Example
+///
+/// Replay a log on a grid, at the speed at which it was recorded:
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::thread;
+/// use std::time::Instant;
+/// use monome_core::FrameLogReader;
+///
+/// # fn draw(_frame: &monome_core::GridFrame) {}
+/// let reader = FrameLogReader::new(File::open("frames.log").unwrap()).unwrap();
+/// let start = Instant::now();
+/// for frame in reader {
+///     let (elapsed, frame) = frame.unwrap();
+///     if let Some(wait) = elapsed.checked_sub(start.elapsed()) {
+///         thread::sleep(wait);
+///     }
+///     draw(&frame);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FrameLogReader<R: Read> {
+    /// Where the log is read from.
+    reader: R,
+    /// Set after the end of the log or an error.
+    done: bool,
+}
+
+impl<R: Read> FrameLogReader<R> {
+    /// Read a log from `reader`. This fails if it doesn't start like a frame log.
+    pub fn new(mut reader: R) -> io::Result<FrameLogReader<R>> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a frame log",
+            ));
+        }
+        Ok(FrameLogReader {
+            reader,
+            done: false,
+        })
+    }
+
+    /// Read the next frame, or `None` at the end of the log.
+    pub fn read_frame(&mut self) -> io::Result<Option<(Duration, GridFrame)>> {
+        let mut header = [0u8; 12];
+        // Distinguish the end of the log from a truncated frame.
+        let read = self.reader.read(&mut header)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.reader.read_exact(&mut header[read..])?;
+        let mut elapsed = [0u8; 8];
+        elapsed.copy_from_slice(&header[..8]);
+        let elapsed = Duration::from_micros(u64::from_le_bytes(elapsed));
+        let width = usize::from(u16::from_le_bytes([header[8], header[9]]));
+        let height = usize::from(u16::from_le_bytes([header[10], header[11]]));
+
+        let mut frame = GridFrame::new(width, height);
+        let mut packed = vec![0u8; frame.leds().len().div_ceil(2)];
+        self.reader.read_exact(&mut packed)?;
+        for (i, led) in frame.leds_mut().iter_mut().enumerate() {
+            let byte = packed[i / 2];
+            *led = if i % 2 == 0 { byte >> 4 } else { byte & 0xf };
+        }
+        Ok(Some((elapsed, frame)))
+    }
+}
+
+impl<R: Read> Iterator for FrameLogReader<R> {
+    type Item = io::Result<(Duration, GridFrame)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_frame() {
+            Ok(Some(frame)) => Some(Ok(frame)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_log() {
+        let start = Instant::now();
+        let mut writer = FrameLogWriter::new_at(Vec::new(), start).unwrap();
+        let mut first = GridFrame::new(3, 3);
+        first.set(0, 0, 1);
+        first.set(2, 2, 15);
+        let mut second = GridFrame::new(8, 8);
+        second.fill(7);
+        writer.write_frame_at(&first, start).unwrap();
+        writer
+            .write_frame_at(&second, start + Duration::from_millis(1500))
+            .unwrap();
+        let log = writer.into_inner();
+
+        let frames: Vec<_> = FrameLogReader::new(&log[..])
+            .unwrap()
+            .map(|frame| frame.unwrap())
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].0, Duration::from_secs(0));
+        assert_eq!(frames[0].1.leds(), first.leds());
+        assert_eq!(frames[1].0, Duration::from_millis(1500));
+        assert_eq!(frames[1].1.leds(), second.leds());
+
+        // A truncated log ends with an error.
+        let mut reader = FrameLogReader::new(&log[..log.len() - 1]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+
+        assert!(FrameLogReader::new(&b"nope"[..]).is_err());
+    }
+}
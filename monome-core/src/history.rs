@@ -0,0 +1,126 @@
+//! A bounded history of the events received, to find out what happened during a stall.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::event::MonomeEvent;
+
+/// Keeps the last events received, with the time at which they have been received, dropping the
+/// oldest ones when it is full. An application that could not handle events for a while, for
+/// example because it was blocked on a file dialog, can find out what happened in the meantime,
+/// even if the events themselves have been dropped.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{EventHistory, KeyDirection, MonomeEvent};
+///
+/// let mut history = EventHistory::new(2);
+/// let start = Instant::now();
+/// let key = |x| MonomeEvent::GridKey { x, y: 0, direction: KeyDirection::Down };
+/// history.push_at(key(0), start);
+/// history.push_at(key(1), start + Duration::from_millis(10));
+/// history.push_at(key(2), start + Duration::from_millis(20));
+///
+/// let recent = history.since(start + Duration::from_millis(5));
+/// assert_eq!(recent.len(), 2);
+/// assert_eq!(recent[0].1, key(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventHistory {
+    /// The maximum number of events kept.
+    capacity: usize,
+    /// The events kept, oldest first, with the time at which they have been received.
+    events: VecDeque<(Instant, MonomeEvent)>,
+}
+
+impl EventHistory {
+    /// Create a history that keeps at most the last `capacity` events.
+    pub fn new(capacity: usize) -> EventHistory {
+        EventHistory {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Get the maximum number of events kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of events kept.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if no event is kept.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Record an event received now.
+    pub fn push(&mut self, event: MonomeEvent) {
+        self.push_at(event, Instant::now())
+    }
+
+    /// Record an event received at `at`, dropping the oldest event if the history is full.
+    pub fn push_at(&mut self, event: MonomeEvent, at: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back((at, event));
+    }
+
+    /// Get all the events kept, oldest first, with the time at which they have been received.
+    pub fn events(&self) -> Vec<(Instant, MonomeEvent)> {
+        self.events.iter().cloned().collect()
+    }
+
+    /// Get the events received at or after `start`, oldest first.
+    pub fn since(&self, start: Instant) -> Vec<(Instant, MonomeEvent)> {
+        self.events
+            .iter()
+            .filter(|(at, _)| *at >= start)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget all the events kept.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn history() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let delta = |delta| MonomeEvent::EncoderDelta { n: 0, delta };
+
+        let mut history = EventHistory::new(3);
+        assert!(history.is_empty());
+        for i in 0..5 {
+            history.push_at(delta(i), at(i as u64));
+        }
+        assert_eq!(history.len(), 3);
+        let events: Vec<MonomeEvent> = history.events().into_iter().map(|(_, e)| e).collect();
+        assert_eq!(events, vec![delta(2), delta(3), delta(4)]);
+        assert_eq!(history.since(at(4)), vec![(at(4), delta(4))]);
+        assert!(history.since(at(5)).is_empty());
+        history.clear();
+        assert!(history.is_empty());
+
+        let mut history = EventHistory::new(0);
+        history.push_at(delta(1), at(0));
+        assert!(history.is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Detection of long presses on grid keys, and feedback while a key is being held.
+
+use std::time::{Duration, Instant};
+
+use crate::event::{KeyDirection, MonomeEvent};
+use crate::frame::GridFrame;
+
+/// Detects grid keys that are held down for longer than a set duration, and reports the
+/// progress of the keys being held, so that hold-to-confirm actions can give feedback.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{KeyDirection, LongPressDetector, MonomeEvent};
+///
+/// let mut detector = LongPressDetector::new(Duration::from_secs(1));
+/// let start = Instant::now();
+/// detector.process_at(&MonomeEvent::GridKey { x: 0, y: 7, direction: KeyDirection::Down }, start);
+///
+/// assert_eq!(detector.progress_at(0, 7, start + Duration::from_millis(500)), Some(0.5));
+/// assert_eq!(detector.poll_at(start + Duration::from_secs(1)), vec![(0, 7)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LongPressDetector {
+    /// How long a key has to be held for a long press.
+    duration: Duration,
+    /// The keys held down, with the time at which they have been pressed, and whether the long
+    /// press has already been reported.
+    held: Vec<((i32, i32), Instant, bool)>,
+}
+
+impl LongPressDetector {
+    /// Create a detector for which a key held for `duration` is a long press.
+    pub fn new(duration: Duration) -> LongPressDetector {
+        LongPressDetector {
+            duration,
+            held: Vec::new(),
+        }
+    }
+
+    /// Set how long a key has to be held for a long press.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    /// Get how long a key has to be held for a long press.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Process an event received from a device, timestamped now. Events other than grid keys are
+    /// ignored.
+    pub fn process(&mut self, event: &MonomeEvent) {
+        self.process_at(event, Instant::now())
+    }
+
+    /// Process an event received from a device at a specific time. Events other than grid keys
+    /// are ignored. Releasing a key before the end of the duration cancels its long press.
+    pub fn process_at(&mut self, event: &MonomeEvent, now: Instant) {
+        if let MonomeEvent::GridKey { x, y, direction } = *event {
+            self.held.retain(|&(key, _, _)| key != (x, y));
+            if direction == KeyDirection::Down {
+                self.held.push(((x, y), now, false));
+            }
+        }
+    }
+
+    /// Returns the keys that have been held long enough since the last call, as `(x, y)`. Each
+    /// long press is returned once.
+    pub fn poll(&mut self) -> Vec<(i32, i32)> {
+        self.poll_at(Instant::now())
+    }
+
+    /// Returns the keys that have been held long enough at `now`, since the last call, as
+    /// `(x, y)`. Each long press is returned once.
+    pub fn poll_at(&mut self, now: Instant) -> Vec<(i32, i32)> {
+        let duration = self.duration;
+        let mut long_presses = Vec::new();
+        for (key, pressed, reported) in self.held.iter_mut() {
+            if !*reported && now.duration_since(*pressed) >= duration {
+                *reported = true;
+                long_presses.push(*key);
+            }
+        }
+        long_presses
+    }
+
+    /// Get the progress of the long press on a key, between 0.0 when it has just been pressed
+    /// and 1.0 when it has been held long enough, or `None` if it is not held.
+    pub fn progress(&self, x: i32, y: i32) -> Option<f32> {
+        self.progress_at(x, y, Instant::now())
+    }
+
+    /// Get the progress of the long press on a key at `now`, between 0.0 when it has just been
+    /// pressed and 1.0 when it has been held long enough, or `None` if it is not held.
+    pub fn progress_at(&self, x: i32, y: i32, now: Instant) -> Option<f32> {
+        self.held
+            .iter()
+            .find(|&&(key, _, _)| key == (x, y))
+            .map(|&(_, pressed, _)| self.ratio(pressed, now))
+    }
+
+    /// Get the keys being held, as `(x, y)`, with the progress of their long press at `now`.
+    pub fn held_at(&self, now: Instant) -> Vec<((i32, i32), f32)> {
+        self.held
+            .iter()
+            .map(|&(key, pressed, _)| (key, self.ratio(pressed, now)))
+            .collect()
+    }
+
+    /// The fraction of the long press duration elapsed since `pressed`, at most 1.0.
+    fn ratio(&self, pressed: Instant, now: Instant) -> f32 {
+        let duration = self.duration.as_secs_f32();
+        if duration <= 0. {
+            return 1.;
+        }
+        (now.duration_since(pressed).as_secs_f32() / duration).min(1.)
+    }
+}
+
+/// A widget that shows the progress of the long presses of a `LongPressDetector`.
+///
+/// # Example
+///
+/// Fill the bottom row of a 16x8 grid while a key is held:
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{GridFrame, HoldProgress, KeyDirection, LongPressDetector, MonomeEvent};
+///
+/// let mut detector = LongPressDetector::new(Duration::from_secs(2));
+/// let start = Instant::now();
+/// detector.process_at(&MonomeEvent::GridKey { x: 3, y: 3, direction: KeyDirection::Down }, start);
+///
+/// let bar = HoldProgress::Bar { x: 0, y: 7, length: 16 };
+/// let mut frame = GridFrame::new(16, 8);
+/// bar.draw_at(&detector, &mut frame, start + Duration::from_secs(1));
+/// assert_eq!(frame.get(7, 7), Some(15));
+/// assert_eq!(frame.get(8, 7), Some(0));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum HoldProgress {
+    /// Each held key brightens as its long press progresses.
+    Cell,
+    /// A horizontal bar fills up as the most advanced long press progresses.
+    Bar {
+        /// The horizontal position of the start of the bar.
+        x: usize,
+        /// The row of the bar.
+        y: usize,
+        /// The length of the bar, in leds.
+        length: usize,
+    },
+}
+
+impl HoldProgress {
+    /// Draw the progress of the long presses in progress now on `frame`.
+    pub fn draw(&self, detector: &LongPressDetector, frame: &mut GridFrame) {
+        self.draw_at(detector, frame, Instant::now())
+    }
+
+    /// Draw the progress of the long presses in progress at `now` on `frame`.
+    pub fn draw_at(&self, detector: &LongPressDetector, frame: &mut GridFrame, now: Instant) {
+        match *self {
+            HoldProgress::Cell => {
+                for ((x, y), progress) in detector.held_at(now) {
+                    if x >= 0 && y >= 0 {
+                        frame.set(x as usize, y as usize, (progress * 15.).round() as u8);
+                    }
+                }
+            }
+            HoldProgress::Bar { x, y, length } => {
+                let progress = detector
+                    .held_at(now)
+                    .iter()
+                    .map(|&(_, progress)| progress)
+                    .fold(0., f32::max);
+                let filled = progress * length as f32;
+                for i in 0..length {
+                    // The last lit led shows the fraction of a led that is filled.
+                    let intensity = ((filled - i as f32).clamp(0., 1.) * 15.).round() as u8;
+                    frame.set(x + i, y, intensity);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: i32, y: i32, direction: KeyDirection) -> MonomeEvent {
+        MonomeEvent::GridKey { x, y, direction }
+    }
+
+    #[test]
+    fn long_press() {
+        let mut detector = LongPressDetector::new(Duration::from_millis(400));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        detector.process_at(&key(1, 1, KeyDirection::Down), at(0));
+        detector.process_at(&key(2, 2, KeyDirection::Down), at(100));
+        assert_eq!(detector.progress_at(1, 1, at(100)), Some(0.25));
+        assert_eq!(detector.poll_at(at(300)), vec![]);
+
+        // Released too early.
+        detector.process_at(&key(2, 2, KeyDirection::Up), at(300));
+        assert_eq!(detector.progress_at(2, 2, at(300)), None);
+
+        assert_eq!(detector.poll_at(at(400)), vec![(1, 1)]);
+        assert_eq!(detector.poll_at(at(500)), vec![]);
+        assert_eq!(detector.progress_at(1, 1, at(900)), Some(1.));
+
+        let mut frame = GridFrame::new(8, 8);
+        HoldProgress::Cell.draw_at(&detector, &mut frame, at(900));
+        assert_eq!(frame.get(1, 1), Some(15));
+
+        detector.process_at(&key(1, 1, KeyDirection::Up), at(1000));
+        detector.process_at(&key(0, 0, KeyDirection::Down), at(1000));
+        let bar = HoldProgress::Bar {
+            x: 0,
+            y: 7,
+            length: 8,
+        };
+        bar.draw_at(&detector, &mut frame, at(1200));
+        let row: Vec<u8> = (0..8).map(|x| frame.get(x, 7).unwrap()).collect();
+        assert_eq!(row, vec![15, 15, 15, 15, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,417 @@
+//! Detection of inactivity, so that an application can dim or blank its display when nobody is
+//! using the device.
+
+use std::time::{Duration, Instant};
+
+use crate::event::{KeyDirection, MonomeEvent};
+
+/// An event emitted by an `IdleDetector` for an event received from a device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdleEvent {
+    /// The device was idle, and the event received woke it up.
+    Woke,
+    /// An event to handle as usual.
+    Event(MonomeEvent),
+}
+
+/// Detects when a device has not been used for a while, and when it is used again. When the
+/// application blanks or dims its display while the device is idle, the key press that wakes it
+/// up is often an accidental tap on a key the user can't see: the detector can be configured to
+/// swallow it, along with its release, and to only report that the device woke up.
+///
+/// Key presses, encoder keys and encoder movements count as activity. Tilt events don't, since
+/// the sensors report small movements continuously.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{IdleDetector, IdleEvent, KeyDirection, MonomeEvent};
+///
+/// let start = Instant::now();
+/// let mut idle = IdleDetector::new_at(Duration::from_secs(60), start);
+/// idle.set_swallow_wake_key(true);
+///
+/// let later = start + Duration::from_secs(90);
+/// assert!(idle.is_idle_at(later));
+/// let press = MonomeEvent::GridKey { x: 0, y: 0, direction: KeyDirection::Down };
+/// assert_eq!(idle.process_at(press, later), vec![IdleEvent::Woke]);
+/// assert!(!idle.is_idle_at(later));
+/// ```
+#[derive(Debug, Clone)]
+pub struct IdleDetector {
+    /// How long without activity before the device is considered idle.
+    timeout: Duration,
+    /// The time of the last activity.
+    last_activity: Instant,
+    /// Whether the key press that wakes the device up is swallowed.
+    swallow_wake_key: bool,
+    /// The release of the key press that has been swallowed, to swallow as well.
+    swallowed_release: Option<MonomeEvent>,
+}
+
+impl IdleDetector {
+    /// Create a detector for which the device is idle after `timeout` without activity,
+    /// starting now.
+    pub fn new(timeout: Duration) -> IdleDetector {
+        IdleDetector::new_at(timeout, Instant::now())
+    }
+
+    /// Create a detector for which the device is idle after `timeout` without activity,
+    /// starting at `now`.
+    pub fn new_at(timeout: Duration, now: Instant) -> IdleDetector {
+        IdleDetector {
+            timeout,
+            last_activity: now,
+            swallow_wake_key: false,
+            swallowed_release: None,
+        }
+    }
+
+    /// Set how long without activity before the device is considered idle.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Get how long without activity before the device is considered idle.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set whether the key press that wakes the device up is swallowed, and only reported as
+    /// `IdleEvent::Woke`. This is off by default.
+    pub fn set_swallow_wake_key(&mut self, swallow: bool) {
+        self.swallow_wake_key = swallow;
+    }
+
+    /// Returns true if the key press that wakes the device up is swallowed.
+    pub fn swallow_wake_key(&self) -> bool {
+        self.swallow_wake_key
+    }
+
+    /// Returns true if the device is idle now.
+    pub fn is_idle(&self) -> bool {
+        self.is_idle_at(Instant::now())
+    }
+
+    /// Returns true if the device is idle at `now`.
+    pub fn is_idle_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_activity) >= self.timeout
+    }
+
+    /// Get how long the device has been without activity at `now`.
+    pub fn inactive_for_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_activity)
+    }
+
+    /// Process an event received from a device now. See `process_at`.
+    pub fn process(&mut self, event: MonomeEvent) -> Vec<IdleEvent> {
+        self.process_at(event, Instant::now())
+    }
+
+    /// Process an event received from a device at `now`, and returns the events to handle: the
+    /// event itself, preceded by `IdleEvent::Woke` if it woke the device up. If the wake key is
+    /// swallowed, a key press that wakes the device up and its release are not returned.
+    pub fn process_at(&mut self, event: MonomeEvent, now: Instant) -> Vec<IdleEvent> {
+        if self.swallowed_release.as_ref() == Some(&event) {
+            self.swallowed_release = None;
+            self.last_activity = now;
+            return Vec::new();
+        }
+        let release = match event {
+            MonomeEvent::GridKey {
+                x,
+                y,
+                direction: KeyDirection::Down,
+            } => Some(MonomeEvent::GridKey {
+                x,
+                y,
+                direction: KeyDirection::Up,
+            }),
+            MonomeEvent::EncoderKey {
+                n,
+                direction: KeyDirection::Down,
+            } => Some(MonomeEvent::EncoderKey {
+                n,
+                direction: KeyDirection::Up,
+            }),
+            MonomeEvent::GridKey { .. }
+            | MonomeEvent::EncoderKey { .. }
+            | MonomeEvent::EncoderDelta { .. } => None,
+            _ => return vec![IdleEvent::Event(event)],
+        };
+        let woke = self.is_idle_at(now);
+        self.last_activity = now;
+        if !woke {
+            return vec![IdleEvent::Event(event)];
+        }
+        if self.swallow_wake_key && release.is_some() {
+            self.swallowed_release = release;
+            return vec![IdleEvent::Woke];
+        }
+        vec![IdleEvent::Woke, IdleEvent::Event(event)]
+    }
+
+    /// Mark the device as used at `now`, for example when the application changes what it
+    /// displays, so that it is not considered idle.
+    pub fn touch_at(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+}
+
+/// Dims the display of a device gradually when it has not been used for a while, and brightens
+/// it back when it is used again, for example to spare the leds of an installation that is
+/// always on. It returns a factor by which to scale the intensities of the leds, like
+/// `IntensityScaling`, that ramps from 1.0 down to the idle level once the device is idle, and
+/// back up to 1.0 when it wakes up.
+///
+/// The level only applies to the intensities it is given: when set on a `Monome` of the
+/// `monome-rs` crate, only the grid updates made with `set_all_intensity` are dimmed.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::IdleDimmer;
+///
+/// let start = Instant::now();
+/// let mut dimmer = IdleDimmer::new_at(Duration::from_secs(60), 0.2, start);
+/// dimmer.set_ramps(Duration::from_secs(10), Duration::from_millis(500));
+///
+/// assert_eq!(dimmer.level_at(start + Duration::from_secs(60)), 1.0);
+/// let level = dimmer.level_at(start + Duration::from_secs(65));
+/// assert!((level - 0.6).abs() < 1e-4);
+///
+/// let mut leds = [10; 128];
+/// dimmer.apply_at(&mut leds, start + Duration::from_secs(80));
+/// assert_eq!(leds[0], 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IdleDimmer {
+    /// Detects when the device is idle.
+    detector: IdleDetector,
+    /// The factor applied to the intensities while the device is idle.
+    idle_level: f32,
+    /// How long it takes to dim from 1.0 to the idle level.
+    dim_duration: Duration,
+    /// How long it takes to brighten from the idle level to 1.0.
+    wake_duration: Duration,
+    /// The current factor.
+    level: f32,
+    /// When the factor has been updated last.
+    updated: Instant,
+}
+
+impl IdleDimmer {
+    /// Create a dimmer that dims the display to `idle_level`, between 0.0 and 1.0, after
+    /// `timeout` without activity, starting now.
+    pub fn new(timeout: Duration, idle_level: f32) -> IdleDimmer {
+        IdleDimmer::new_at(timeout, idle_level, Instant::now())
+    }
+
+    /// Create a dimmer that dims the display to `idle_level`, between 0.0 and 1.0, after
+    /// `timeout` without activity, starting at `now`. The display dims in 5 seconds, and
+    /// brightens back in 250 milliseconds.
+    pub fn new_at(timeout: Duration, idle_level: f32, now: Instant) -> IdleDimmer {
+        IdleDimmer {
+            detector: IdleDetector::new_at(timeout, now),
+            idle_level: idle_level.clamp(0., 1.),
+            dim_duration: Duration::from_secs(5),
+            wake_duration: Duration::from_millis(250),
+            level: 1.,
+            updated: now,
+        }
+    }
+
+    /// Set how long it takes to dim the display once the device is idle, and to brighten it
+    /// back when it wakes up.
+    pub fn set_ramps(&mut self, dim: Duration, wake: Duration) {
+        self.dim_duration = dim;
+        self.wake_duration = wake;
+    }
+
+    /// Set the factor applied to the intensities while the device is idle, between 0.0 and 1.0.
+    pub fn set_idle_level(&mut self, idle_level: f32) {
+        self.idle_level = idle_level.clamp(0., 1.);
+    }
+
+    /// Get the factor applied to the intensities while the device is idle.
+    pub fn idle_level(&self) -> f32 {
+        self.idle_level
+    }
+
+    /// Get the detector that tells when the device is idle, to configure it.
+    pub fn detector_mut(&mut self) -> &mut IdleDetector {
+        &mut self.detector
+    }
+
+    /// Process an event received from a device now. See `process_at`.
+    pub fn process(&mut self, event: MonomeEvent) -> Vec<IdleEvent> {
+        self.process_at(event, Instant::now())
+    }
+
+    /// Process an event received from a device at `now`, and returns the events to handle, as
+    /// `IdleDetector::process_at` does. Activity makes the display brighten back.
+    pub fn process_at(&mut self, event: MonomeEvent, now: Instant) -> Vec<IdleEvent> {
+        self.level_at(now);
+        self.detector.process_at(event, now)
+    }
+
+    /// Get the factor by which to scale the intensities now. See `level_at`.
+    pub fn level(&mut self) -> f32 {
+        self.level_at(Instant::now())
+    }
+
+    /// Get the factor by which to scale the intensities at `now`, between the idle level and
+    /// 1.0. This moves the factor towards its target for the time elapsed since it was last
+    /// asked for.
+    pub fn level_at(&mut self, now: Instant) -> f32 {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.updated = self.updated.max(now);
+        let range = 1. - self.idle_level;
+        if self.detector.is_idle_at(now) {
+            // Only dim for the time the device has been idle.
+            let idle_for = self
+                .detector
+                .inactive_for_at(now)
+                .saturating_sub(self.detector.timeout());
+            let step = ramp_step(range, elapsed.min(idle_for), self.dim_duration);
+            self.level = (self.level - step).max(self.idle_level);
+        } else {
+            let step = ramp_step(range, elapsed, self.wake_duration);
+            self.level = (self.level + step).min(1.);
+        }
+        self.level
+    }
+
+    /// Returns true if the factor doesn't change now. See `is_settled_at`.
+    pub fn is_settled(&mut self) -> bool {
+        self.is_settled_at(Instant::now())
+    }
+
+    /// Returns true if the factor has reached its target at `now`: the idle level if the device
+    /// is idle, and 1.0 otherwise. This moves the factor as `level_at` does.
+    pub fn is_settled_at(&mut self, now: Instant) -> bool {
+        let level = self.level_at(now);
+        let target = if self.detector.is_idle_at(now) {
+            self.idle_level
+        } else {
+            1.
+        };
+        level == target
+    }
+
+    /// Scale the intensities of `leds` by the factor at `now`. The results are rounded.
+    pub fn apply_at(&mut self, leds: &mut [u8], now: Instant) {
+        let level = self.level_at(now);
+        if level < 1. {
+            for led in leds.iter_mut() {
+                *led = (f32::from(*led) * level).round() as u8;
+            }
+        }
+    }
+}
+
+/// The change of a factor that changes by `range` in `duration`, after `elapsed`.
+fn ramp_step(range: f32, elapsed: Duration, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        range
+    } else {
+        range * (elapsed.as_secs_f32() / duration.as_secs_f32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle() {
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+        let key = |direction| MonomeEvent::GridKey {
+            x: 1,
+            y: 2,
+            direction,
+        };
+        let tilt = MonomeEvent::Tilt {
+            n: 0,
+            x: 1,
+            y: 2,
+            z: 3,
+        };
+
+        let mut idle = IdleDetector::new_at(Duration::from_secs(10), start);
+        assert!(!idle.is_idle_at(at(9)));
+        assert_eq!(
+            idle.process_at(key(KeyDirection::Down), at(5)),
+            vec![IdleEvent::Event(key(KeyDirection::Down))]
+        );
+        assert!(!idle.is_idle_at(at(14)));
+        // Tilt doesn't count as activity.
+        idle.process_at(tilt.clone(), at(14));
+        assert!(idle.is_idle_at(at(15)));
+        assert_eq!(
+            idle.process_at(key(KeyDirection::Up), at(20)),
+            vec![IdleEvent::Woke, IdleEvent::Event(key(KeyDirection::Up))]
+        );
+
+        idle.set_swallow_wake_key(true);
+        assert_eq!(
+            idle.process_at(key(KeyDirection::Down), at(40)),
+            vec![IdleEvent::Woke]
+        );
+        assert_eq!(
+            idle.process_at(tilt.clone(), at(41)),
+            vec![IdleEvent::Event(tilt)]
+        );
+        assert!(idle.process_at(key(KeyDirection::Up), at(41)).is_empty());
+        assert_eq!(
+            idle.process_at(key(KeyDirection::Down), at(42)),
+            vec![IdleEvent::Event(key(KeyDirection::Down))]
+        );
+
+        // Encoder movements wake the device up, but are not swallowed.
+        let delta = MonomeEvent::EncoderDelta { n: 0, delta: 1 };
+        assert_eq!(
+            idle.process_at(delta.clone(), at(60)),
+            vec![IdleEvent::Woke, IdleEvent::Event(delta)]
+        );
+    }
+
+    #[test]
+    fn dimmer() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        let mut dimmer = IdleDimmer::new_at(Duration::from_secs(1), 0.5, start);
+        dimmer.set_ramps(Duration::from_secs(1), Duration::from_millis(100));
+
+        assert_eq!(dimmer.level_at(at(500)), 1.);
+        assert!(dimmer.is_settled_at(at(500)));
+        // The dimming starts when the device becomes idle, not when the level was last asked.
+        assert!(close(dimmer.level_at(at(1500)), 0.75));
+        assert!(!dimmer.is_settled_at(at(1500)));
+        assert!(close(dimmer.level_at(at(5000)), 0.5));
+        assert!(dimmer.is_settled_at(at(5000)));
+
+        let press = MonomeEvent::GridKey {
+            x: 0,
+            y: 0,
+            direction: KeyDirection::Down,
+        };
+        assert_eq!(
+            dimmer.process_at(press.clone(), at(5000)),
+            vec![IdleEvent::Woke, IdleEvent::Event(press)]
+        );
+        assert!(close(dimmer.level_at(at(5050)), 0.75));
+        assert_eq!(dimmer.level_at(at(5200)), 1.);
+
+        let mut leds = [15, 4];
+        dimmer.apply_at(&mut leds, at(5300));
+        assert_eq!(leds, [15, 4]);
+        dimmer.set_ramps(Duration::from_secs(0), Duration::from_secs(0));
+        dimmer.apply_at(&mut leds, at(7000));
+        assert_eq!(leds, [8, 2]);
+    }
+}
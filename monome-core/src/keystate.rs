@@ -0,0 +1,100 @@
+//! Tracking of the grid keys currently held down.
+
+use crate::event::{KeyDirection, MonomeEvent};
+
+/// Tracks the grid keys currently held down, from the key events of a grid, for interactions
+/// that depend on the keys held while another one is pressed, e.g. holding a step of a
+/// sequencer to lock the parameters edited meanwhile.
+///
+/// The `Monome` of the `monome-rs` crate tracks the keys of its grid itself, see its `is_down`
+/// and `held_keys` methods.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{KeyDirection, KeyStateTracker, MonomeEvent};
+///
+/// let mut keys = KeyStateTracker::new();
+/// keys.process(&MonomeEvent::GridKey { x: 3, y: 0, direction: KeyDirection::Down });
+/// keys.process(&MonomeEvent::GridKey { x: 5, y: 2, direction: KeyDirection::Down });
+/// keys.process(&MonomeEvent::GridKey { x: 3, y: 0, direction: KeyDirection::Up });
+///
+/// assert!(!keys.is_down(3, 0));
+/// assert!(keys.is_down(5, 2));
+/// assert_eq!(keys.held().collect::<Vec<_>>(), vec![(5, 2)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyStateTracker {
+    /// The keys held down, as `(x, y)`, in the order in which they have been pressed.
+    held: Vec<(i32, i32)>,
+}
+
+impl KeyStateTracker {
+    /// Create a tracker for which no key is held down.
+    pub fn new() -> KeyStateTracker {
+        KeyStateTracker::default()
+    }
+
+    /// Process an event received from a device. Returns true if the event is a grid key press
+    /// or release. Events other than grid keys are ignored.
+    pub fn process(&mut self, event: &MonomeEvent) -> bool {
+        match *event {
+            MonomeEvent::GridKey { x, y, direction } => {
+                self.held.retain(|&key| key != (x, y));
+                if direction == KeyDirection::Down {
+                    self.held.push((x, y));
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the key at `x`, `y` is held down.
+    pub fn is_down(&self, x: i32, y: i32) -> bool {
+        self.held.contains(&(x, y))
+    }
+
+    /// Get the keys held down, as `(x, y)`, in the order in which they have been pressed.
+    pub fn held(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.held.iter().cloned()
+    }
+
+    /// Get the number of keys held down.
+    pub fn count(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Forget the keys held down, e.g. when the device has been disconnected, and return them, in
+    /// the order in which they have been pressed.
+    pub fn release_all(&mut self) -> Vec<(i32, i32)> {
+        std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: i32, y: i32, direction: KeyDirection) -> MonomeEvent {
+        MonomeEvent::GridKey { x, y, direction }
+    }
+
+    #[test]
+    fn held_keys() {
+        let mut keys = KeyStateTracker::new();
+        assert!(keys.process(&key(0, 0, KeyDirection::Down)));
+        assert!(keys.process(&key(1, 0, KeyDirection::Down)));
+        // A repeated press doesn't hold the key twice, and moves it last.
+        assert!(keys.process(&key(0, 0, KeyDirection::Down)));
+        assert_eq!(keys.held().collect::<Vec<_>>(), vec![(1, 0), (0, 0)]);
+        assert!(!keys.process(&MonomeEvent::EncoderDelta { n: 0, delta: 1 }));
+        // Releasing a key that is not held does nothing.
+        keys.process(&key(7, 7, KeyDirection::Up));
+        assert_eq!(keys.count(), 2);
+
+        assert_eq!(keys.release_all(), vec![(1, 0), (0, 0)]);
+        assert!(!keys.is_down(0, 0));
+        assert_eq!(keys.count(), 0);
+    }
+}
@@ -0,0 +1,124 @@
+//! Proportional layouts, to divide a grid into regions computed from its actual size.
+
+/// A region of a grid, as `(x, y, width, height)`, as used by `RegionLocks`.
+type Region = (usize, usize, usize, usize);
+
+/// Returns the region covering the whole of a grid of `width` by `height` keys, to be divided
+/// with `split_columns`, `split_rows` and `split_cells`.
+pub fn full_region(width: usize, height: usize) -> Region {
+    (0, 0, width, height)
+}
+
+/// Divides `length` into parts proportional to `weights`, returned as `(offset, length)`. The
+/// boundaries are rounded to the nearest key, so that the parts always cover the whole length,
+/// without gaps or overlaps.
+fn split(length: usize, weights: &[u32]) -> Vec<(usize, usize)> {
+    let total: u64 = weights.iter().map(|&weight| u64::from(weight)).sum();
+    if total == 0 {
+        return weights.iter().map(|_| (0, 0)).collect();
+    }
+    let mut parts = Vec::with_capacity(weights.len());
+    let mut cumulated = 0;
+    let mut start = 0;
+    for &weight in weights {
+        cumulated += u64::from(weight);
+        let end = ((length as u64 * cumulated + total / 2) / total) as usize;
+        parts.push((start, end - start));
+        start = end;
+    }
+    parts
+}
+
+/// Divides `region` into columns, from left to right, whose widths are proportional to
+/// `weights`, for example percentages. The columns always cover the whole region, so that the
+/// same layout works on a 64, a 128 or a 256.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{full_region, split_columns};
+///
+/// // A quarter of the grid for the controls, the rest for the sequencer.
+/// let on_128 = split_columns(full_region(16, 8), &[25, 75]);
+/// assert_eq!(on_128, vec![(0, 0, 4, 8), (4, 0, 12, 8)]);
+/// let on_64 = split_columns(full_region(8, 8), &[25, 75]);
+/// assert_eq!(on_64, vec![(0, 0, 2, 8), (2, 0, 6, 8)]);
+/// ```
+pub fn split_columns(region: Region, weights: &[u32]) -> Vec<Region> {
+    let (x, y, width, height) = region;
+    split(width, weights)
+        .into_iter()
+        .map(|(offset, length)| (x + offset, y, length, height))
+        .collect()
+}
+
+/// Divides `region` into rows, from top to bottom, whose heights are proportional to `weights`,
+/// like `split_columns`.
+pub fn split_rows(region: Region, weights: &[u32]) -> Vec<Region> {
+    let (x, y, width, height) = region;
+    split(height, weights)
+        .into_iter()
+        .map(|(offset, length)| (x, y + offset, width, length))
+        .collect()
+}
+
+/// Divides `region` into `columns` by `rows` cells of about the same size, for example to lay
+/// out a grid of widgets. The cells are returned in row order.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{full_region, split_cells};
+///
+/// let cells = split_cells(full_region(16, 16), 4, 2);
+/// assert_eq!(cells.len(), 8);
+/// assert_eq!(cells[5], (4, 8, 4, 8));
+/// ```
+pub fn split_cells(region: Region, columns: usize, rows: usize) -> Vec<Region> {
+    let mut cells = Vec::with_capacity(columns * rows);
+    for row in split_rows(region, &vec![1; rows]) {
+        cells.extend(split_columns(row, &vec![1; columns]));
+    }
+    cells
+}
+
+/// Returns the coordinates of the key at `x`, `y` relative to the top left corner of `region`,
+/// or `None` if the key is outside of the region, to route key presses to the widget of the
+/// region.
+pub fn region_local(region: Region, x: usize, y: usize) -> Option<(usize, usize)> {
+    let (rx, ry, width, height) = region;
+    if x >= rx && y >= ry && x < rx + width && y < ry + height {
+        Some((x - rx, y - ry))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        assert_eq!(
+            split_rows((2, 1, 4, 7), &[1, 1, 1]),
+            vec![(2, 1, 4, 2), (2, 3, 4, 3), (2, 6, 4, 2)]
+        );
+        assert_eq!(
+            split_columns(full_region(16, 16), &[0, 50, 0, 50]),
+            vec![(0, 0, 0, 16), (0, 0, 8, 16), (8, 0, 0, 16), (8, 0, 8, 16)]
+        );
+        assert_eq!(
+            split_columns(full_region(8, 8), &[0, 0]),
+            vec![(0, 0, 0, 8), (0, 0, 0, 8)]
+        );
+        // The cells always cover the whole region.
+        let cells = split_cells(full_region(16, 8), 3, 3);
+        assert_eq!(cells.iter().map(|c| c.2 * c.3).sum::<usize>(), 128);
+        assert!(split_cells(full_region(16, 8), 0, 2).is_empty());
+
+        assert_eq!(region_local((4, 2, 4, 4), 5, 5), Some((1, 3)));
+        assert_eq!(region_local((4, 2, 4, 4), 8, 2), None);
+        assert_eq!(region_local((4, 2, 4, 4), 3, 2), None);
+    }
+}
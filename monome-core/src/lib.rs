@@ -0,0 +1,86 @@
+#![deny(missing_docs)]
+
+//! The protocol used to talk to monome devices (Grid or Arc), independently from any transport.
+//!
+//! This crate builds the OSC messages sent to devices, parses the OSC messages received from
+//! them into events, and provides types to describe what to draw on them. It doesn't do any
+//! networking, and can be used with any way of exchanging OSC packets with serialosc or a device.
+//! The `monome-rs` crate uses it on top of tokio, and re-exports everything it contains.
+
+mod action;
+mod blink;
+mod capabilities;
+mod chord;
+mod compositor;
+mod control;
+mod curve;
+mod decay;
+mod dedup;
+mod detent;
+mod encoder;
+mod event;
+mod filter;
+mod frame;
+mod framelog;
+mod history;
+mod hold;
+mod idle;
+mod keystate;
+mod layout;
+mod link;
+mod locks;
+mod menu;
+mod mirror;
+mod orientation;
+mod osc;
+mod param;
+mod quantize;
+mod rate;
+mod ring;
+mod scale;
+mod scaling;
+mod sprite;
+mod tween;
+mod update;
+
+pub use crate::action::{ActionBinding, ActionMap};
+pub use crate::blink::BlinkScheduler;
+pub use crate::capabilities::Capabilities;
+pub use crate::chord::{ChordDetector, KeyGesture};
+pub use crate::compositor::Compositor;
+pub use crate::control::{ControlChannel, ControlEvent, ControlMessage};
+pub use crate::curve::BrightnessCurve;
+pub use crate::decay::DecayBuffer;
+pub use crate::dedup::EventDeduplicator;
+pub use crate::detent::{DetentFeedback, DetentedEncoder};
+pub use crate::encoder::{
+    EncoderGesture, EncoderGestureDetector, PushTurnEvent, PushTurnTracker, VirtualEncoder,
+};
+pub use crate::event::{parse, KeyDirection, MonomeDeviceType, MonomeEvent};
+pub use crate::filter::EventFilter;
+pub use crate::frame::GridFrame;
+pub use crate::framelog::{FrameLogReader, FrameLogWriter};
+pub use crate::history::EventHistory;
+pub use crate::hold::{HoldProgress, LongPressDetector};
+pub use crate::idle::{IdleDetector, IdleDimmer, IdleEvent};
+pub use crate::keystate::KeyStateTracker;
+pub use crate::layout::{full_region, region_local, split_cells, split_columns, split_rows};
+pub use crate::link::{LinkConditions, LinkSimulator};
+pub use crate::locks::RegionLocks;
+pub use crate::menu::{Menu, MenuEvent};
+pub use crate::mirror::RingMirror;
+pub use crate::orientation::{rotate_coordinates, KeyOrientation};
+pub use crate::osc::{
+    build_osc_message, system_time_to_timetag, timetag_to_system_time, toidx, IntoAddrAndArgs,
+};
+pub use crate::param::ParamEditor;
+pub use crate::quantize::EventQuantizer;
+pub use crate::rate::RateLimiter;
+pub use crate::ring::{
+    ring_angle_to_led, ring_arc_leds, ring_led_to_angle, RingDirection, RingLayout, RING_LEDS,
+};
+pub use crate::scale::Scale;
+pub use crate::scaling::IntensityScaling;
+pub use crate::sprite::{blit, Sprite};
+pub use crate::tween::{Animator, Easing, Looping, Tween};
+pub use crate::update::{plan_quad_update, plan_ring_update, QuadUpdate, RingUpdate};
@@ -0,0 +1,243 @@
+//! Simulation of the packet loss, reordering and latency of a network link, for testing.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How bad a simulated link is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkConditions {
+    /// The probability that a packet is lost, between 0.0 and 1.0.
+    pub loss: f32,
+    /// The probability that a packet is delivered after the packet sent after it, between 0.0
+    /// and 1.0.
+    pub reorder: f32,
+    /// The minimum time it takes for a packet to be delivered.
+    pub latency: Duration,
+    /// The maximum additional time, chosen randomly for each packet, it takes for a packet to be
+    /// delivered.
+    pub jitter: Duration,
+}
+
+impl Default for LinkConditions {
+    /// A perfect link: packets are delivered immediately, in order.
+    fn default() -> LinkConditions {
+        LinkConditions {
+            loss: 0.,
+            reorder: 0.,
+            latency: Duration::from_secs(0),
+            jitter: Duration::from_secs(0),
+        }
+    }
+}
+
+/// Simulates the realities of UDP between an application and a device: packets, or events, can
+/// be lost, reordered, and delivered late. This is meant to be put between a fake device and an
+/// application, or the other way around, to test how they cope with it before they fail on
+/// stage. The random decisions are made from a seed, so that a failing test can be reproduced.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::{LinkConditions, LinkSimulator};
+///
+/// let conditions = LinkConditions {
+///     loss: 0.1,
+///     latency: Duration::from_millis(5),
+///     jitter: Duration::from_millis(10),
+///     ..LinkConditions::default()
+/// };
+/// let mut link = LinkSimulator::new(conditions, 42);
+/// let start = Instant::now();
+/// for i in 0..100 {
+///     link.send_at(i, start);
+/// }
+/// assert!(link.receive_at(start + Duration::from_millis(4)).is_empty());
+/// let received = link.receive_at(start + Duration::from_millis(15));
+/// assert_eq!(received.len() + link.lost() as usize, 100);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LinkSimulator<T> {
+    /// How bad the link is.
+    conditions: LinkConditions,
+    /// The state of the random number generator.
+    state: u64,
+    /// The packets in flight, with the time at which they are delivered, in delivery order.
+    in_flight: VecDeque<(Instant, T)>,
+    /// A packet held back to be delivered after the next one.
+    held: Option<T>,
+    /// The number of packets lost.
+    lost: u64,
+}
+
+impl<T> LinkSimulator<T> {
+    /// Create a link with `conditions`, making its random decisions from `seed`.
+    pub fn new(conditions: LinkConditions, seed: u64) -> LinkSimulator<T> {
+        LinkSimulator {
+            conditions,
+            // The generator must not start from 0.
+            state: seed ^ 0x9e37_79b9_7f4a_7c15,
+            in_flight: VecDeque::new(),
+            held: None,
+            lost: 0,
+        }
+    }
+
+    /// Change how bad the link is, for the packets sent from now on.
+    pub fn set_conditions(&mut self, conditions: LinkConditions) {
+        self.conditions = conditions;
+    }
+
+    /// Get how bad the link is.
+    pub fn conditions(&self) -> LinkConditions {
+        self.conditions
+    }
+
+    /// Send a packet on the link now. See `send_at`.
+    pub fn send(&mut self, packet: T) {
+        self.send_at(packet, Instant::now())
+    }
+
+    /// Send a packet on the link at `now`. The packet may be lost, delivered after the next
+    /// packet sent, and is delayed by the latency and a random part of the jitter.
+    pub fn send_at(&mut self, packet: T, now: Instant) {
+        if self.chance(self.conditions.loss) {
+            self.lost += 1;
+            return;
+        }
+        let jitter = self.conditions.jitter.mul_f64(self.next_f64());
+        let delivery = now + self.conditions.latency + jitter;
+        if let Some(held) = self.held.take() {
+            self.schedule(delivery, packet);
+            self.schedule(delivery, held);
+        } else if self.chance(self.conditions.reorder) {
+            // Delivered with the next packet, after it.
+            self.held = Some(packet);
+        } else {
+            self.schedule(delivery, packet);
+        }
+    }
+
+    /// Returns the packets delivered by now, in the order in which they are delivered. See
+    /// `receive_at`.
+    pub fn receive(&mut self) -> Vec<T> {
+        self.receive_at(Instant::now())
+    }
+
+    /// Returns the packets delivered at `now`, in the order in which they are delivered.
+    pub fn receive_at(&mut self, now: Instant) -> Vec<T> {
+        let mut delivered = Vec::new();
+        while let Some(&(delivery, _)) = self.in_flight.front() {
+            if delivery > now {
+                break;
+            }
+            if let Some((_, packet)) = self.in_flight.pop_front() {
+                delivered.push(packet);
+            }
+        }
+        delivered
+    }
+
+    /// Returns the number of packets that have been sent but not received yet, including a
+    /// packet held back for reordering.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len() + usize::from(self.held.is_some())
+    }
+
+    /// Returns the number of packets lost so far.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Inserts a packet in flight, after the packets delivered at the same time or before.
+    fn schedule(&mut self, delivery: Instant, packet: T) {
+        let index = self
+            .in_flight
+            .iter()
+            .position(|&(other, _)| other > delivery)
+            .unwrap_or(self.in_flight.len());
+        self.in_flight.insert(index, (delivery, packet));
+    }
+
+    /// Returns true with a probability of `probability`.
+    fn chance(&mut self, probability: f32) -> bool {
+        probability > 0. && self.next_f64() < f64::from(probability)
+    }
+
+    /// Returns a random number between 0.0 and 1.0, with xorshift64*.
+    fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let random = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (random >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // A perfect link delivers everything immediately, in order.
+        let mut link = LinkSimulator::new(LinkConditions::default(), 1);
+        for i in 0..10 {
+            link.send_at(i, start);
+        }
+        assert_eq!(link.receive_at(start), (0..10).collect::<Vec<_>>());
+
+        // Latency without jitter keeps the order.
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(10),
+            ..LinkConditions::default()
+        };
+        let mut link = LinkSimulator::new(conditions, 1);
+        link.send_at(0, at(0));
+        link.send_at(1, at(5));
+        assert!(link.receive_at(at(9)).is_empty());
+        assert_eq!(link.receive_at(at(10)), vec![0]);
+        assert_eq!(link.in_flight(), 1);
+        assert_eq!(link.receive_at(at(15)), vec![1]);
+
+        // Each held back packet is delivered after the next one.
+        let conditions = LinkConditions {
+            reorder: 1.,
+            ..LinkConditions::default()
+        };
+        let mut link = LinkSimulator::new(conditions, 1);
+        for i in 0..4 {
+            link.send_at(i, start);
+        }
+        assert_eq!(link.receive_at(start), vec![1, 0, 3, 2]);
+
+        let conditions = LinkConditions {
+            loss: 1.,
+            ..LinkConditions::default()
+        };
+        let mut link = LinkSimulator::new(conditions, 1);
+        link.send_at(0, start);
+        assert_eq!(link.lost(), 1);
+        assert_eq!(link.in_flight(), 0);
+
+        // The same seed gives the same outcome.
+        let conditions = LinkConditions {
+            loss: 0.3,
+            reorder: 0.3,
+            latency: Duration::from_millis(1),
+            jitter: Duration::from_millis(20),
+        };
+        let run = |seed| {
+            let mut link = LinkSimulator::new(conditions, seed);
+            for i in 0..50 {
+                link.send_at(i, at(i));
+            }
+            link.receive_at(at(100))
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+}
@@ -0,0 +1,147 @@
+//! Ownership of regions of a grid by the components of an application.
+
+use log::*;
+
+use crate::frame::GridFrame;
+
+/// Gives the components of an application that share a grid ownership of regions of it, so that
+/// they don't fight over the same leds. Each component claims its regions, draws on its own
+/// frame, and its frame is merged into the frame sent to the grid: only the cells of its regions
+/// are copied, and the cells it lit outside of them are reported as conflicts.
+///
+/// The `Monome` of the `monome-rs` crate holds locks of its own, enforced on the outputs it
+/// returns for an owner, see its `claim_region` and `output_for` methods.
+///
+/// # Example
+///
+/// A sequencer owns the top half of a grid, and a mixer the bottom half:
+///
+/// ```
+/// use monome_core::{GridFrame, RegionLocks};
+///
+/// let mut locks = RegionLocks::new();
+/// locks.claim("sequencer", (0, 0, 16, 4)).unwrap();
+/// locks.claim("mixer", (0, 4, 16, 4)).unwrap();
+/// assert_eq!(locks.claim("mixer", (0, 3, 16, 1)), Err("sequencer".to_string()));
+///
+/// let mut mixer = GridFrame::new(16, 8);
+/// mixer.set(0, 7, 15);
+/// mixer.set(0, 0, 15);
+///
+/// let mut frame = GridFrame::new(16, 8);
+/// let conflicts = locks.merge("mixer", &mixer, &mut frame);
+/// assert_eq!(frame.get(0, 7), Some(15));
+/// assert_eq!(frame.get(0, 0), Some(0));
+/// assert_eq!(conflicts, vec![(0, 0)]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RegionLocks {
+    /// The regions claimed, as `(x, y, width, height)`, with their owner.
+    regions: Vec<(String, (usize, usize, usize, usize))>,
+}
+
+/// Returns true if the regions `a` and `b` have cells in common.
+fn overlap(a: (usize, usize, usize, usize), b: (usize, usize, usize, usize)) -> bool {
+    a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+}
+
+impl RegionLocks {
+    /// Create a set of locks where no region is claimed.
+    pub fn new() -> RegionLocks {
+        RegionLocks {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Claim `region`, as `(x, y, width, height)`, for `owner`. An owner can claim multiple
+    /// regions. Returns the name of the other owner if part of the region is already claimed.
+    pub fn claim(
+        &mut self,
+        owner: &str,
+        region: (usize, usize, usize, usize),
+    ) -> Result<(), String> {
+        if let Some((holder, _)) = self
+            .regions
+            .iter()
+            .find(|(holder, claimed)| holder != owner && overlap(*claimed, region))
+        {
+            return Err(holder.clone());
+        }
+        self.regions.push((owner.to_string(), region));
+        Ok(())
+    }
+
+    /// Release all the regions claimed by `owner`.
+    pub fn release(&mut self, owner: &str) {
+        self.regions.retain(|(holder, _)| holder != owner);
+    }
+
+    /// Get the owner of the cell at `x`, `y`, if it is claimed.
+    pub fn owner_at(&self, x: usize, y: usize) -> Option<&str> {
+        self.regions
+            .iter()
+            .find(|(_, region)| overlap(*region, (x, y, 1, 1)))
+            .map(|(holder, _)| holder.as_str())
+    }
+
+    /// Copy the cells of the regions of `owner` from `source`, the frame it has drawn, to
+    /// `target`. Returns the cells that `owner` has lit outside of its regions, that are not
+    /// copied.
+    pub fn merge(
+        &self,
+        owner: &str,
+        source: &GridFrame,
+        target: &mut GridFrame,
+    ) -> Vec<(usize, usize)> {
+        let mut conflicts = Vec::new();
+        let width = source.width().min(target.width());
+        let height = source.height().min(target.height());
+        for y in 0..height {
+            for x in 0..width {
+                let intensity = source.get(x, y).unwrap_or(0);
+                if self.owner_at(x, y) == Some(owner) {
+                    target.set(x, y, intensity);
+                } else if intensity != 0 {
+                    conflicts.push((x, y));
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            warn!(
+                "{} drew {} leds outside of its regions, ignored.",
+                owner,
+                conflicts.len()
+            );
+        }
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks() {
+        let mut locks = RegionLocks::new();
+        assert_eq!(locks.claim("a", (0, 0, 2, 2)), Ok(()));
+        assert_eq!(locks.claim("a", (1, 1, 2, 2)), Ok(()));
+        assert_eq!(locks.claim("b", (2, 2, 2, 2)), Err("a".to_string()));
+        assert_eq!(locks.claim("b", (3, 0, 1, 4)), Ok(()));
+        assert_eq!(locks.owner_at(2, 2), Some("a"));
+        assert_eq!(locks.owner_at(3, 3), Some("b"));
+        assert_eq!(locks.owner_at(0, 3), None);
+
+        let mut source = GridFrame::new(4, 4);
+        source.fill(9);
+        let mut target = GridFrame::new(4, 4);
+        let conflicts = locks.merge("b", &source, &mut target);
+        assert_eq!(target.get(3, 1), Some(9));
+        assert_eq!(target.get(2, 2), Some(0));
+        assert_eq!(conflicts.len(), 12);
+
+        locks.release("a");
+        assert_eq!(locks.owner_at(0, 0), None);
+        assert_eq!(locks.claim("b", (2, 2, 2, 2)), Ok(()));
+    }
+}
@@ -0,0 +1,240 @@
+//! A menu widget, to choose an item from a list on a grid.
+
+use crate::event::{KeyDirection, MonomeEvent};
+use crate::frame::GridFrame;
+
+/// Intensity of the rows of the items that are not highlighted.
+const ITEM_INTENSITY: u8 = 4;
+/// Intensity of the row of the highlighted item.
+const HIGHLIGHT_INTENSITY: u8 = 15;
+/// Intensity of the control keys that can be used.
+const CONTROL_INTENSITY: u8 = 8;
+
+/// An event emitted by a `Menu` in response to key presses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuEvent {
+    /// An item has been highlighted, but not chosen yet.
+    Highlighted(usize),
+    /// An item has been chosen, either by pressing the confirm key, or by pressing the
+    /// highlighted item again.
+    Selected(usize),
+    /// The cancel key has been pressed.
+    Cancelled,
+}
+
+/// A scrollable list of items displayed on a region of a grid, one item per row. The rightmost
+/// column of the region holds the control keys, from the top: page up, page down, and at the
+/// bottom: cancel, and confirm. The region must be at least 2 leds wide and 4 leds high.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{GridFrame, KeyDirection, Menu, MenuEvent, MonomeEvent};
+///
+/// let mut menu = Menu::new(20, (0, 0, 16, 8));
+/// let press = |x, y| MonomeEvent::GridKey { x, y, direction: KeyDirection::Down };
+///
+/// assert_eq!(menu.process(&press(3, 2)), Some(MenuEvent::Highlighted(2)));
+/// // Page down, then choose the first item of the second page.
+/// menu.process(&press(15, 1));
+/// menu.process(&press(0, 0));
+/// assert_eq!(menu.process(&press(15, 7)), Some(MenuEvent::Selected(8)));
+///
+/// let mut frame = GridFrame::new(16, 8);
+/// menu.draw(&mut frame);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Menu {
+    /// The number of items in the list.
+    item_count: usize,
+    /// The region of the grid used by the menu, as `(x, y, width, height)`.
+    region: (usize, usize, usize, usize),
+    /// The index of the item displayed on the first row.
+    scroll: usize,
+    /// The index of the highlighted item, if any.
+    highlighted: Option<usize>,
+}
+
+impl Menu {
+    /// Create a menu of `item_count` items, displayed on `region`, as `(x, y, width, height)`.
+    pub fn new(item_count: usize, region: (usize, usize, usize, usize)) -> Menu {
+        Menu {
+            item_count,
+            region,
+            scroll: 0,
+            highlighted: None,
+        }
+    }
+
+    /// Change the number of items in the list. The highlight is removed if it is past the end of
+    /// the new list.
+    pub fn set_item_count(&mut self, item_count: usize) {
+        self.item_count = item_count;
+        if self.highlighted.is_some_and(|i| i >= item_count) {
+            self.highlighted = None;
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    /// Get the number of items in the list.
+    pub fn item_count(&self) -> usize {
+        self.item_count
+    }
+
+    /// Get the index of the highlighted item, if any.
+    pub fn highlighted(&self) -> Option<usize> {
+        self.highlighted
+    }
+
+    /// Highlight an item, scrolling so that it is visible, or remove the highlight.
+    pub fn set_highlighted(&mut self, item: Option<usize>) {
+        self.highlighted = item.filter(|&i| i < self.item_count);
+        if let Some(i) = self.highlighted {
+            let rows = self.rows();
+            if i < self.scroll {
+                self.scroll = i;
+            } else if i >= self.scroll + rows {
+                self.scroll = i + 1 - rows;
+            }
+        }
+    }
+
+    /// Get the index of the item displayed on the first row.
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll
+    }
+
+    /// Scroll up by one page.
+    pub fn page_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(self.rows());
+    }
+
+    /// Scroll down by one page.
+    pub fn page_down(&mut self) {
+        self.scroll = (self.scroll + self.rows()).min(self.max_scroll());
+    }
+
+    /// Process an event received from a device. Returns an event if the menu reacted to a key
+    /// press. Key presses outside of the region of the menu are ignored.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<MenuEvent> {
+        let (x, y) = match *event {
+            MonomeEvent::GridKey {
+                x,
+                y,
+                direction: KeyDirection::Down,
+            } if x >= 0 && y >= 0 => (x as usize, y as usize),
+            _ => return None,
+        };
+        let (rx, ry, width, height) = self.region;
+        if x < rx || y < ry || x >= rx + width || y >= ry + height {
+            return None;
+        }
+        let row = y - ry;
+        if x == rx + width - 1 {
+            // Control column.
+            if row == 0 {
+                self.page_up();
+            } else if row == 1 {
+                self.page_down();
+            } else if row == height - 2 {
+                return Some(MenuEvent::Cancelled);
+            } else if row == height - 1 {
+                return self.highlighted.map(MenuEvent::Selected);
+            }
+            return None;
+        }
+        let item = self.scroll + row;
+        if item >= self.item_count {
+            return None;
+        }
+        if self.highlighted == Some(item) {
+            Some(MenuEvent::Selected(item))
+        } else {
+            self.highlighted = Some(item);
+            Some(MenuEvent::Highlighted(item))
+        }
+    }
+
+    /// Draw the menu on the region of `frame`.
+    pub fn draw(&self, frame: &mut GridFrame) {
+        let (rx, ry, width, height) = self.region;
+        for row in 0..height {
+            let item = self.scroll + row;
+            let intensity = if item >= self.item_count {
+                0
+            } else if self.highlighted == Some(item) {
+                HIGHLIGHT_INTENSITY
+            } else {
+                ITEM_INTENSITY
+            };
+            for x in rx..rx + width.saturating_sub(1) {
+                frame.set(x, ry + row, intensity);
+            }
+        }
+        if width == 0 || height < 4 {
+            return;
+        }
+        let control = |enabled: bool| if enabled { CONTROL_INTENSITY } else { 0 };
+        let column = rx + width - 1;
+        for row in 2..height - 2 {
+            frame.set(column, ry + row, 0);
+        }
+        frame.set(column, ry, control(self.scroll > 0));
+        frame.set(column, ry + 1, control(self.scroll < self.max_scroll()));
+        frame.set(column, ry + height - 2, CONTROL_INTENSITY);
+        frame.set(column, ry + height - 1, control(self.highlighted.is_some()));
+    }
+
+    /// The number of items displayed at once.
+    fn rows(&self) -> usize {
+        self.region.3.max(1)
+    }
+
+    /// The largest index of the item displayed on the first row.
+    fn max_scroll(&self) -> usize {
+        self.item_count.saturating_sub(self.rows())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(x: i32, y: i32) -> MonomeEvent {
+        MonomeEvent::GridKey {
+            x,
+            y,
+            direction: KeyDirection::Down,
+        }
+    }
+
+    #[test]
+    fn menu() {
+        let mut menu = Menu::new(6, (8, 0, 8, 4));
+        assert_eq!(menu.process(&press(0, 0)), None);
+        assert_eq!(menu.process(&press(15, 3)), None);
+        assert_eq!(menu.process(&press(9, 1)), Some(MenuEvent::Highlighted(1)));
+        assert_eq!(menu.process(&press(10, 1)), Some(MenuEvent::Selected(1)));
+
+        menu.process(&press(15, 1));
+        assert_eq!(menu.scroll_offset(), 2);
+        assert_eq!(menu.process(&press(8, 3)), Some(MenuEvent::Highlighted(5)));
+        menu.process(&press(15, 0));
+        assert_eq!(menu.scroll_offset(), 0);
+        assert_eq!(menu.process(&press(15, 3)), Some(MenuEvent::Selected(5)));
+        assert_eq!(menu.process(&press(15, 2)), Some(MenuEvent::Cancelled));
+
+        menu.set_highlighted(Some(4));
+        assert_eq!(menu.scroll_offset(), 1);
+        let mut frame = GridFrame::new(16, 4);
+        menu.draw(&mut frame);
+        assert_eq!(frame.get(8, 0), Some(ITEM_INTENSITY));
+        assert_eq!(frame.get(14, 3), Some(HIGHLIGHT_INTENSITY));
+        assert_eq!(frame.get(15, 0), Some(CONTROL_INTENSITY));
+        assert_eq!(frame.get(7, 0), Some(0));
+
+        menu.set_item_count(3);
+        assert_eq!(menu.highlighted(), None);
+        assert_eq!(menu.scroll_offset(), 0);
+    }
+}
@@ -0,0 +1,171 @@
+//! A value shown and edited both as a fader on a grid and on the ring of an arc.
+
+use crate::event::{KeyDirection, MonomeEvent};
+use crate::frame::GridFrame;
+use crate::ring::{RingLayout, RING_LEDS};
+
+/// Mirrors a value, between 0.0 and 1.0, between a horizontal fader on a row of a grid and a
+/// ring of an arc: pressing a key of the fader sets the value, turning the encoder changes it
+/// finely, and both displays always show the same value.
+///
+/// Events from both devices are passed to the same mirror, which doesn't need to know which
+/// device sent them.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{GridFrame, KeyDirection, MonomeEvent, RingMirror};
+///
+/// // A fader on the 8 first keys of the top row, mirrored on the second ring.
+/// let mut mirror = RingMirror::new((0, 0, 8), 1);
+/// let press = MonomeEvent::GridKey { x: 7, y: 0, direction: KeyDirection::Down };
+/// assert_eq!(mirror.process(&press), Some(1.0));
+/// let turn = MonomeEvent::EncoderDelta { n: 1, delta: -64 };
+/// assert_eq!(mirror.process(&turn), Some(0.75));
+///
+/// let mut frame = GridFrame::new(16, 8);
+/// mirror.draw_grid(&mut frame);
+/// let ring = mirror.ring();
+/// assert_eq!(ring.iter().filter(|&&led| led > 0).count(), 48);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingMirror {
+    /// The value, between 0.0 and 1.0.
+    value: f32,
+    /// The fader on the grid, as `(x, y, width)`.
+    fader: (usize, usize, usize),
+    /// The encoder whose ring mirrors the value.
+    ring: usize,
+    /// How the value is laid out on the ring.
+    layout: RingLayout,
+    /// The change of value for an encoder delta of 1.
+    fine_step: f32,
+}
+
+impl RingMirror {
+    /// Create a mirror for a value at 0.0, shown on the fader `fader` of a grid, as
+    /// `(x, y, width)`, and on the ring of encoder `ring`, filled clockwise from the top.
+    pub fn new(fader: (usize, usize, usize), ring: usize) -> RingMirror {
+        RingMirror {
+            value: 0.,
+            fader,
+            ring,
+            layout: RingLayout::default(),
+            fine_step: 1. / 256.,
+        }
+    }
+
+    /// Set how the value is laid out on the ring.
+    pub fn set_layout(&mut self, layout: RingLayout) {
+        self.layout = layout;
+    }
+
+    /// Set the change of value for an encoder delta of 1. The default is 1/256, so that four
+    /// full turns of the encoder cover the whole range.
+    pub fn set_fine_step(&mut self, step: f32) {
+        self.fine_step = step;
+    }
+
+    /// Get the value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Set the value, for example when it is changed by the application. It is clamped to
+    /// [0.0, 1.0].
+    pub fn set_value(&mut self, value: f32) {
+        self.value = value.clamp(0., 1.);
+    }
+
+    /// Process an event received from a grid or an arc. Returns the new value, if it has been
+    /// changed.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<f32> {
+        match *event {
+            MonomeEvent::GridKey {
+                x,
+                y,
+                direction: KeyDirection::Down,
+            } if x >= 0 && y >= 0 => {
+                let (x, y) = (x as usize, y as usize);
+                let (fx, fy, width) = self.fader;
+                if y != fy || x < fx || x >= fx + width {
+                    return None;
+                }
+                // The left key is the minimum, the right key the maximum.
+                self.value = if width > 1 {
+                    (x - fx) as f32 / (width - 1) as f32
+                } else {
+                    1.
+                };
+                Some(self.value)
+            }
+            MonomeEvent::EncoderDelta { n, delta } if n == self.ring => {
+                self.set_value(self.value + delta as f32 * self.fine_step);
+                Some(self.value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Draw the fader on `frame`, lit from its left end up to the value.
+    pub fn draw_grid(&self, frame: &mut GridFrame) {
+        let (fx, fy, width) = self.fader;
+        let lit = if width > 1 {
+            (self.value * (width - 1) as f32).round() as usize + 1
+        } else {
+            usize::from(self.value > 0.)
+        };
+        for column in 0..width {
+            frame.set(fx + column, fy, if column < lit { 15 } else { 0 });
+        }
+    }
+
+    /// Get the ring to display on the encoder, filled up to the value.
+    pub fn ring(&self) -> [u8; 64] {
+        let mut ring = [0; RING_LEDS];
+        for led in self.layout.fill(self.value) {
+            ring[led] = 15;
+        }
+        ring
+    }
+
+    /// Get the encoder whose ring mirrors the value.
+    pub fn encoder(&self) -> usize {
+        self.ring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirror() {
+        let mut mirror = RingMirror::new((2, 1, 5), 0);
+        let press = |x, y| MonomeEvent::GridKey {
+            x,
+            y,
+            direction: KeyDirection::Down,
+        };
+        assert_eq!(mirror.process(&press(1, 1)), None);
+        assert_eq!(mirror.process(&press(3, 0)), None);
+        assert_eq!(mirror.process(&press(4, 1)), Some(0.5));
+        assert_eq!(mirror.ring().iter().filter(|&&led| led > 0).count(), 32);
+        assert_eq!(
+            mirror.process(&MonomeEvent::EncoderDelta { n: 1, delta: 1 }),
+            None
+        );
+        assert_eq!(
+            mirror.process(&MonomeEvent::EncoderDelta { n: 0, delta: 1000 }),
+            Some(1.)
+        );
+
+        let mut frame = GridFrame::new(8, 2);
+        mirror.set_value(0.25);
+        mirror.draw_grid(&mut frame);
+        assert_eq!(&frame.leds()[8..], &[0, 0, 15, 15, 0, 0, 0, 0]);
+        mirror.set_value(0.);
+        mirror.draw_grid(&mut frame);
+        assert_eq!(&frame.leds()[8..], &[0, 0, 15, 0, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,144 @@
+//! Coordinates of grid keys in a fixed orientation, whatever the rotation of the device.
+
+use crate::event::MonomeEvent;
+
+/// Returns the number of quarter turns of a rotation in degrees, between 0 and 3.
+fn quarter_turns(degrees: i32) -> i32 {
+    (degrees / 90).rem_euclid(4)
+}
+
+/// Rotates the coordinates of a key by `degrees` clockwise, on a grid that is `width` keys wide
+/// and `height` keys high before the rotation. `degrees` is a multiple of 90, negative for
+/// counterclockwise rotations, and other values are rounded towards 0 to a multiple of 90.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::rotate_coordinates;
+///
+/// // The top left key of a 16x8 grid is at the top right after a quarter turn.
+/// assert_eq!(rotate_coordinates(0, 0, 16, 8, 90), (7, 0));
+/// assert_eq!(rotate_coordinates(0, 0, 16, 8, 180), (15, 7));
+/// ```
+pub fn rotate_coordinates(x: i32, y: i32, width: i32, height: i32, degrees: i32) -> (i32, i32) {
+    match quarter_turns(degrees) {
+        1 => (height - 1 - y, x),
+        2 => (width - 1 - x, height - 1 - y),
+        3 => (y, width - 1 - x),
+        _ => (x, y),
+    }
+}
+
+/// Converts the coordinates of the grid key events received from a device, that depend on the
+/// rotation of the device, to a fixed orientation chosen by the application. An application that
+/// rotates the device, for example to follow how it is placed, doesn't have to rotate the
+/// coordinates of the keys itself.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{KeyDirection, KeyOrientation, MonomeEvent};
+///
+/// // A 16x8 grid is rotated by 180 degrees, but the application wants the coordinates of the
+/// // keys as if it was not rotated.
+/// let orientation = KeyOrientation::new((16, 8), 180, 0);
+/// let key = MonomeEvent::GridKey { x: 0, y: 0, direction: KeyDirection::Down };
+/// assert_eq!(
+///     orientation.apply(key),
+///     MonomeEvent::GridKey { x: 15, y: 7, direction: KeyDirection::Down }
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyOrientation {
+    /// The size of the device without rotation.
+    size: (i32, i32),
+    /// The rotation of the device, in degrees.
+    rotation: i32,
+    /// The orientation in which the application wants the coordinates, in degrees.
+    orientation: i32,
+}
+
+impl KeyOrientation {
+    /// Create a conversion for a device of `size`, as `(width, height)` without rotation, that
+    /// has a rotation of `rotation` degrees, to coordinates as if it had a rotation of
+    /// `orientation` degrees.
+    pub fn new(size: (i32, i32), rotation: i32, orientation: i32) -> KeyOrientation {
+        KeyOrientation {
+            size,
+            rotation,
+            orientation,
+        }
+    }
+
+    /// Set the rotation of the device, in degrees.
+    pub fn set_rotation(&mut self, rotation: i32) {
+        self.rotation = rotation;
+    }
+
+    /// Get the rotation of the device, in degrees.
+    pub fn rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Get the orientation in which the coordinates are converted, in degrees.
+    pub fn orientation(&self) -> i32 {
+        self.orientation
+    }
+
+    /// Converts the coordinates of a grid key event. Other events are returned as they are.
+    pub fn apply(&self, event: MonomeEvent) -> MonomeEvent {
+        match event {
+            MonomeEvent::GridKey { x, y, direction } => {
+                // The size of the device, as it is rotated.
+                let (width, height) = if quarter_turns(self.rotation) % 2 == 1 {
+                    (self.size.1, self.size.0)
+                } else {
+                    self.size
+                };
+                let (x, y) =
+                    rotate_coordinates(x, y, width, height, self.orientation - self.rotation);
+                MonomeEvent::GridKey { x, y, direction }
+            }
+            event => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::KeyDirection;
+
+    #[test]
+    fn orientation() {
+        // Four quarter turns are a full turn.
+        let mut point = (3, 1);
+        let mut size = (16, 8);
+        for _ in 0..4 {
+            point = rotate_coordinates(point.0, point.1, size.0, size.1, 90);
+            size = (size.1, size.0);
+        }
+        assert_eq!(point, (3, 1));
+        assert_eq!(rotate_coordinates(3, 1, 16, 8, -90), (1, 12));
+        assert_eq!(rotate_coordinates(3, 1, 16, 8, 450), (6, 3));
+
+        let key = |x, y| MonomeEvent::GridKey {
+            x,
+            y,
+            direction: KeyDirection::Up,
+        };
+        // The device is rotated by 90 degrees: it is 8 keys wide and 16 keys high.
+        let mut orientation = KeyOrientation::new((16, 8), 90, 0);
+        assert_eq!(orientation.apply(key(7, 0)), key(0, 0));
+        assert_eq!(orientation.apply(key(0, 15)), key(15, 7));
+        orientation.set_rotation(0);
+        assert_eq!(orientation.apply(key(7, 0)), key(7, 0));
+        let tilt = MonomeEvent::Tilt {
+            n: 0,
+            x: 1,
+            y: 2,
+            z: 3,
+        };
+        assert_eq!(orientation.apply(tilt.clone()), tilt);
+    }
+}
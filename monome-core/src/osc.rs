@@ -0,0 +1,178 @@
+//! Construction of the OSC messages sent to devices.
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rosc::{OscMessage, OscPacket, OscType};
+
+/// The number of seconds between the epoch of OSC timetags, 1900-01-01, and the UNIX epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// From a x and y position, and a stride, returns the offset at which the element is in an array.
+pub fn toidx(x: i32, y: i32, width: i32) -> usize {
+    (y * width + x) as usize
+}
+
+/// Returns an osc packet from a address and arguments
+pub fn build_osc_message(addr: &str, args: Vec<OscType>) -> OscPacket {
+    let message = OscMessage {
+        addr: addr.to_owned(),
+        args: Some(args),
+    };
+    OscPacket::Message(message)
+}
+
+/// Returns the time of an OSC timetag, as found in bundles, or `None` if it is not a timetag, if
+/// it means "immediately", or if it is before the UNIX epoch.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use monome_core::{system_time_to_timetag, timetag_to_system_time};
+/// use rosc::OscType;
+///
+/// assert_eq!(timetag_to_system_time(&OscType::Time(0, 1)), None);
+/// let time = UNIX_EPOCH + Duration::from_millis(1500);
+/// let timetag = system_time_to_timetag(time);
+/// let roundtrip = timetag_to_system_time(&timetag).unwrap();
+/// assert!(roundtrip.duration_since(time).unwrap() < Duration::from_micros(1));
+/// ```
+pub fn timetag_to_system_time(timetag: &OscType) -> Option<SystemTime> {
+    let (seconds, fraction) = match *timetag {
+        OscType::Time(0, 1) => return None,
+        OscType::Time(seconds, fraction) => (u64::from(seconds), u64::from(fraction)),
+        _ => return None,
+    };
+    let seconds = seconds.checked_sub(NTP_UNIX_OFFSET)?;
+    let nanos = (fraction * 1_000_000_000) >> 32;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds) + Duration::from_nanos(nanos))
+}
+
+/// Returns the OSC timetag of `time`, to send a bundle to be handled at this time. Times that
+/// can't be represented, before 1970 or after 2036, mean "immediately".
+pub fn system_time_to_timetag(time: SystemTime) -> OscType {
+    let since_epoch = match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch,
+        Err(_) => return OscType::Time(0, 1),
+    };
+    match u32::try_from(since_epoch.as_secs() + NTP_UNIX_OFFSET) {
+        Ok(seconds) => {
+            let fraction = (u64::from(since_epoch.subsec_nanos()) << 32) / 1_000_000_000;
+            OscType::Time(seconds, fraction as u32)
+        }
+        Err(_) => OscType::Time(0, 1),
+    }
+}
+
+/// Converts an to a Monome method argument to a OSC address fragment and suitable OscType,
+/// performing an eventual conversion.
+pub trait IntoAddrAndArgs<'a, B> {
+    /// Converts an to a Monome method argument to a OSC address fragment and suitable OscType,
+    /// performing an eventual conversion.
+    fn as_addr_frag_and_args(&self) -> (String, B);
+}
+
+/// Used to make a call with an intensity value, adds the `"level/"` portion in the address.
+impl<'a> IntoAddrAndArgs<'a, OscType> for i32 {
+    fn as_addr_frag_and_args(&self) -> (String, OscType) {
+        ("level/".to_string(), OscType::Int(*self))
+    }
+}
+
+/// Used to make an on/off call, converts to 0 or 1.
+impl<'a> IntoAddrAndArgs<'a, OscType> for bool {
+    fn as_addr_frag_and_args(&self) -> (String, OscType) {
+        ("".to_string(), OscType::Int(if *self { 1 } else { 0 }))
+    }
+}
+
+impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for &'a [u8; 64] {
+    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
+        // TODO: error handling both valid: either 64 or more intensity values, or 8 masks
+        let mut osctype_vec = Vec::with_capacity(64);
+        for item in self.iter().map(|i| OscType::Int(i32::from(*i))) {
+            osctype_vec.push(item);
+        }
+        ("level/".to_string(), osctype_vec)
+    }
+}
+
+impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for u8 {
+    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
+        // TODO: error handling both valid: either 64 or more intensity values, or 8 masks
+        let osctype_vec = vec![OscType::Int(i32::from(*self))];
+        ("".to_string(), osctype_vec)
+    }
+}
+
+impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for &'a [u8; 8] {
+    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
+        // TODO: error handling both valid: either 64 or more intensity values, or 8 masks
+        let mut osctype_vec = Vec::with_capacity(8);
+        for item in self.iter().map(|i| OscType::Int(i32::from(*i))) {
+            osctype_vec.push(item);
+        }
+        ("".to_string(), osctype_vec)
+    }
+}
+
+/// Used to convert vectors of bools for on/off calls, packs into a 8-bit integer mask.
+impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for &'a [bool; 64] {
+    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
+        // TODO: error handling
+        assert!(self.len() >= 64);
+        let mut masks = [0_u8; 8];
+        for i in 0..8 {
+            // for each row
+            let mut mask: u8 = 0;
+            for j in (0..8).rev() {
+                // create mask
+                let idx = toidx(j, i, 8);
+                mask = mask.rotate_left(1) | if self[idx] { 1 } else { 0 };
+            }
+            masks[i as usize] = mask;
+        }
+        let mut osctype_vec = Vec::with_capacity(8);
+        for item in masks.iter().map(|i| OscType::Int(i32::from(*i))) {
+            osctype_vec.push(item);
+        }
+        ("".to_string(), osctype_vec)
+    }
+}
+
+/// Used to set a row or a column of any length on or off, one bool per led: packs them into as
+/// many 8-bit masks as needed, the first led of each mask being its lowest bit. The leds past the
+/// end of the slice, up to the end of its last mask, are set off.
+impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for [bool] {
+    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
+        let masks = self
+            .chunks(8)
+            .map(|leds| {
+                let mask = leds
+                    .iter()
+                    .enumerate()
+                    .fold(0, |mask, (i, &on)| mask | (i32::from(on) << i));
+                OscType::Int(mask)
+            })
+            .collect();
+        ("".to_string(), masks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bool_slices() {
+        let mut leds = [false; 16];
+        leds[0] = true;
+        leds[9] = true;
+        let (frag, args) = leds[..].as_addr_frag_and_args();
+        assert_eq!(frag, "");
+        assert_eq!(args, vec![OscType::Int(1), OscType::Int(2)]);
+        let (_, args) = [true; 10][..].as_addr_frag_and_args();
+        assert_eq!(args, vec![OscType::Int(0xff), OscType::Int(0b11)]);
+    }
+}
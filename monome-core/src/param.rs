@@ -0,0 +1,213 @@
+//! A parameter editor using a grid for selection and coarse editing, and an arc for fine
+//! editing.
+
+use crate::event::{KeyDirection, MonomeEvent};
+use crate::frame::GridFrame;
+use crate::ring::RING_LEDS;
+
+/// Edits a set of parameters, between 0.0 and 1.0, with a grid and an arc together. Each column
+/// of a region of the grid shows a parameter as a bar: pressing a key in a column selects this
+/// parameter and sets it coarsely to the height of the key. The encoders of the arc then edit
+/// the selected parameter and the following ones finely, and their rings show their values.
+///
+/// Events from both devices are passed to the same editor, which doesn't need to know which
+/// device sent them.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{GridFrame, KeyDirection, MonomeEvent, ParamEditor};
+///
+/// let mut editor = ParamEditor::new(8, (0, 0, 8, 8));
+/// // From the grid: select the third parameter, and set it to its maximum.
+/// let press = MonomeEvent::GridKey { x: 2, y: 0, direction: KeyDirection::Down };
+/// assert_eq!(editor.process(&press), Some((2, 1.0)));
+/// // From the arc: lower it a bit with the first encoder.
+/// let turn = MonomeEvent::EncoderDelta { n: 0, delta: -64 };
+/// assert_eq!(editor.process(&turn), Some((2, 0.75)));
+///
+/// let mut frame = GridFrame::new(16, 8);
+/// editor.draw_grid(&mut frame);
+/// let ring = editor.ring(0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParamEditor {
+    /// The values of the parameters, between 0.0 and 1.0.
+    values: Vec<f32>,
+    /// The region of the grid used by the editor, as `(x, y, width, height)`.
+    region: (usize, usize, usize, usize),
+    /// The index of the selected parameter, edited by the first encoder.
+    selected: usize,
+    /// The change of value for an encoder delta of 1.
+    fine_step: f32,
+}
+
+impl ParamEditor {
+    /// Create an editor for `count` parameters, all at 0.0, displayed on `region` of a grid, as
+    /// `(x, y, width, height)`. Parameters past the width of the region can only be edited with
+    /// the arc.
+    pub fn new(count: usize, region: (usize, usize, usize, usize)) -> ParamEditor {
+        ParamEditor {
+            values: vec![0.; count],
+            region,
+            selected: 0,
+            fine_step: 1. / 256.,
+        }
+    }
+
+    /// Set the change of value for an encoder delta of 1. The default is 1/256, so that four
+    /// full turns of an encoder cover the whole range.
+    pub fn set_fine_step(&mut self, step: f32) {
+        self.fine_step = step;
+    }
+
+    /// Get the value of a parameter, or `None` if it doesn't exist.
+    pub fn value(&self, param: usize) -> Option<f32> {
+        self.values.get(param).cloned()
+    }
+
+    /// Set the value of a parameter. The value is clamped between 0.0 and 1.0.
+    pub fn set_value(&mut self, param: usize, value: f32) {
+        if let Some(v) = self.values.get_mut(param) {
+            *v = value.clamp(0., 1.);
+        }
+    }
+
+    /// Get the index of the selected parameter.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Select a parameter, to be edited by the first encoder.
+    pub fn select(&mut self, param: usize) {
+        if param < self.values.len() {
+            self.selected = param;
+        }
+    }
+
+    /// Get the parameter edited by encoder `n`, if any.
+    pub fn param_for_encoder(&self, n: usize) -> Option<usize> {
+        let param = self.selected + n;
+        if param < self.values.len() {
+            Some(param)
+        } else {
+            None
+        }
+    }
+
+    /// Process an event received from a grid or an arc. Returns the index and the new value of
+    /// the parameter that has been changed, if any.
+    pub fn process(&mut self, event: &MonomeEvent) -> Option<(usize, f32)> {
+        match *event {
+            MonomeEvent::GridKey {
+                x,
+                y,
+                direction: KeyDirection::Down,
+            } if x >= 0 && y >= 0 => {
+                let (x, y) = (x as usize, y as usize);
+                let (rx, ry, width, height) = self.region;
+                if x < rx || y < ry || x >= rx + width || y >= ry + height {
+                    return None;
+                }
+                let param = x - rx;
+                if param >= self.values.len() {
+                    return None;
+                }
+                self.selected = param;
+                // The bottom row is the minimum, the top row the maximum.
+                let value = if height > 1 {
+                    (height - 1 - (y - ry)) as f32 / (height - 1) as f32
+                } else {
+                    1.
+                };
+                self.values[param] = value;
+                Some((param, value))
+            }
+            MonomeEvent::EncoderDelta { n, delta } => {
+                let param = self.param_for_encoder(n)?;
+                let value = (self.values[param] + delta as f32 * self.fine_step).clamp(0., 1.);
+                self.values[param] = value;
+                Some((param, value))
+            }
+            _ => None,
+        }
+    }
+
+    /// Draw the parameters as bars on the region of `frame`. The selected parameter is
+    /// brighter.
+    pub fn draw_grid(&self, frame: &mut GridFrame) {
+        let (rx, ry, width, height) = self.region;
+        for column in 0..width {
+            let (filled, intensity) = match self.values.get(column) {
+                Some(value) => {
+                    let filled = (value * height as f32).round() as usize;
+                    (filled, if column == self.selected { 15 } else { 6 })
+                }
+                None => (0, 0),
+            };
+            for row in 0..height {
+                let lit = height - row <= filled;
+                frame.set(rx + column, ry + row, if lit { intensity } else { 0 });
+            }
+        }
+    }
+
+    /// Get the ring to display on encoder `n`, filled clockwise from the top in proportion to
+    /// the value of the parameter it edits, with the last led dimmed to show the fraction of a
+    /// led.
+    pub fn ring(&self, n: usize) -> [u8; 64] {
+        let mut ring = [0; RING_LEDS];
+        if let Some(param) = self.param_for_encoder(n) {
+            let filled = self.values[param] * RING_LEDS as f32;
+            for (i, led) in ring.iter_mut().enumerate() {
+                *led = ((filled - i as f32).clamp(0., 1.) * 15.).round() as u8;
+            }
+        }
+        ring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editor() {
+        let mut editor = ParamEditor::new(3, (4, 0, 4, 5));
+        let press = |x, y| MonomeEvent::GridKey {
+            x,
+            y,
+            direction: KeyDirection::Down,
+        };
+        assert_eq!(editor.process(&press(0, 0)), None);
+        // Only three parameters, the fourth column is empty.
+        assert_eq!(editor.process(&press(7, 0)), None);
+        assert_eq!(editor.process(&press(5, 2)), Some((1, 0.5)));
+        assert_eq!(editor.selected(), 1);
+
+        assert_eq!(
+            editor.process(&MonomeEvent::EncoderDelta { n: 1, delta: 32 }),
+            Some((2, 0.125))
+        );
+        assert_eq!(
+            editor.process(&MonomeEvent::EncoderDelta { n: 0, delta: -1000 }),
+            Some((1, 0.))
+        );
+        assert_eq!(
+            editor.process(&MonomeEvent::EncoderDelta { n: 2, delta: 1 }),
+            None
+        );
+
+        let ring = editor.ring(1);
+        assert_eq!(&ring[..9], &[15, 15, 15, 15, 15, 15, 15, 15, 0]);
+        assert_eq!(editor.ring(3), [0; 64]);
+
+        editor.set_value(0, 1.);
+        let mut frame = GridFrame::new(8, 5);
+        editor.draw_grid(&mut frame);
+        assert_eq!(frame.get(4, 0), Some(6));
+        assert_eq!(frame.get(5, 4), Some(0));
+        assert_eq!(frame.get(6, 4), Some(6));
+        assert_eq!(frame.get(6, 3), Some(0));
+    }
+}
@@ -0,0 +1,110 @@
+//! Quantization of key and encoder events to the ticks of a clock.
+
+use crate::event::MonomeEvent;
+
+/// Holds the key and encoder events received between two ticks of a clock, and delivers them on
+/// the next tick, so that what is recorded from a grid or an arc aligns to a musical grid. The
+/// deltas of an encoder received between two ticks are accumulated into a single event. Other
+/// events are not held.
+///
+/// The order of the events of a given key or encoder is preserved: the deltas of an encoder are
+/// only accumulated if no key event of the same encoder has been received in between.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{EventQuantizer, KeyDirection, MonomeEvent};
+///
+/// let mut quantizer = EventQuantizer::new();
+/// assert_eq!(quantizer.push(MonomeEvent::EncoderDelta { n: 0, delta: 3 }), None);
+/// assert_eq!(quantizer.push(MonomeEvent::EncoderDelta { n: 0, delta: -1 }), None);
+/// // On the next tick of the application clock:
+/// assert_eq!(quantizer.tick(), vec![MonomeEvent::EncoderDelta { n: 0, delta: 2 }]);
+/// assert!(quantizer.tick().is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EventQuantizer {
+    /// The events held until the next tick, in the order in which they have been received.
+    held: Vec<MonomeEvent>,
+}
+
+impl EventQuantizer {
+    /// Create a quantizer that doesn't hold any event.
+    pub fn new() -> EventQuantizer {
+        EventQuantizer { held: Vec::new() }
+    }
+
+    /// Process an event. Returns the event if it is not quantized, and should be handled right
+    /// away, or `None` if it is held until the next tick.
+    pub fn push(&mut self, event: MonomeEvent) -> Option<MonomeEvent> {
+        match event {
+            MonomeEvent::GridKey { .. } | MonomeEvent::EncoderKey { .. } => {
+                self.held.push(event);
+            }
+            MonomeEvent::EncoderDelta { n, delta } => {
+                let last = self.held.iter_mut().rev().find(|held| match **held {
+                    MonomeEvent::EncoderDelta { n: m, .. }
+                    | MonomeEvent::EncoderKey { n: m, .. } => m == n,
+                    _ => false,
+                });
+                match last {
+                    Some(MonomeEvent::EncoderDelta { delta: held, .. }) => *held += delta,
+                    _ => self.held.push(event),
+                }
+            }
+            _ => return Some(event),
+        }
+        None
+    }
+
+    /// Returns true if events are held until the next tick.
+    pub fn is_empty(&self) -> bool {
+        self.held.is_empty()
+    }
+
+    /// Take the events held since the previous tick, in the order in which they have been
+    /// received. Encoder deltas that cancelled out are not returned.
+    pub fn tick(&mut self) -> Vec<MonomeEvent> {
+        let mut events: Vec<MonomeEvent> = self.held.drain(..).collect();
+        events.retain(|event| !matches!(event, MonomeEvent::EncoderDelta { delta: 0, .. }));
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::KeyDirection;
+
+    #[test]
+    fn quantize() {
+        let mut quantizer = EventQuantizer::new();
+        let size = MonomeEvent::Size {
+            width: 0,
+            height: 0,
+        };
+        assert_eq!(quantizer.push(size.clone()), Some(size));
+        assert!(quantizer.is_empty());
+
+        let delta = |n, delta| MonomeEvent::EncoderDelta { n, delta };
+        let key = |n, direction| MonomeEvent::EncoderKey { n, direction };
+        quantizer.push(delta(0, 1));
+        quantizer.push(delta(1, 4));
+        quantizer.push(delta(0, 1));
+        quantizer.push(key(0, KeyDirection::Down));
+        quantizer.push(delta(0, 5));
+        quantizer.push(delta(1, -4));
+        quantizer.push(delta(2, 1));
+        quantizer.push(delta(0, 1));
+        assert_eq!(
+            quantizer.tick(),
+            vec![
+                delta(0, 2),
+                key(0, KeyDirection::Down),
+                delta(0, 6),
+                delta(2, 1)
+            ]
+        );
+        assert!(quantizer.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+//! Limitation of the rate of the messages sent to a device.
+
+use std::time::Instant;
+
+/// Caps the rate of the messages sent to a device, so that an application that sends too much
+/// doesn't flood serialosc and the USB link. Bursts of up to a second worth of messages are
+/// allowed, after which messages are only allowed at the rate: the messages over it are dropped.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use monome_core::RateLimiter;
+///
+/// let mut limiter = RateLimiter::new(100);
+/// let now = Instant::now();
+/// assert!((0..100).all(|_| limiter.allow_at(now)));
+/// assert!(!limiter.allow_at(now));
+/// assert!(limiter.allow_at(now + Duration::from_millis(10)));
+/// assert_eq!(limiter.dropped(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// The number of messages allowed per second.
+    rate: u32,
+    /// The number of messages that can be sent right away, up to `rate`.
+    available: f64,
+    /// When a message has last been allowed or dropped, if ever.
+    last: Option<Instant>,
+    /// The number of messages dropped so far.
+    dropped: u64,
+}
+
+impl RateLimiter {
+    /// Create a limiter that allows `rate` messages per second, and at least one.
+    pub fn new(rate: u32) -> RateLimiter {
+        let rate = rate.max(1);
+        RateLimiter {
+            rate,
+            available: f64::from(rate),
+            last: None,
+            dropped: 0,
+        }
+    }
+
+    /// Get the number of messages allowed per second.
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Get the number of messages dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns true if a message can be sent now, false if it must be dropped.
+    pub fn allow(&mut self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    /// Returns true if a message can be sent at `now`, false if it must be dropped.
+    pub fn allow_at(&mut self, now: Instant) -> bool {
+        let rate = f64::from(self.rate);
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.available = (self.available + elapsed * rate).min(rate);
+        }
+        self.last = Some(now);
+        if self.available >= 1. {
+            self.available -= 1.;
+            true
+        } else {
+            self.dropped += 1;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn refill() {
+        let mut limiter = RateLimiter::new(10);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert_eq!((0..20).filter(|_| limiter.allow_at(at(0))).count(), 10);
+        // A message every 100ms.
+        assert!(!limiter.allow_at(at(50)));
+        assert!(limiter.allow_at(at(150)));
+        assert!(!limiter.allow_at(at(160)));
+        // The burst is capped to a second worth of messages.
+        assert_eq!((0..20).filter(|_| limiter.allow_at(at(10_000))).count(), 10);
+        assert_eq!(limiter.dropped(), 22);
+        assert_eq!(RateLimiter::new(0).rate(), 1);
+    }
+}
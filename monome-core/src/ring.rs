@@ -0,0 +1,242 @@
+//! Conversions between the leds of an arc ring, angles, and normalized values.
+
+/// The number of leds on an arc ring.
+pub const RING_LEDS: usize = 64;
+
+/// The direction in which values increase around a ring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RingDirection {
+    /// Values increase clockwise, in the same direction as the led indices.
+    Clockwise,
+    /// Values increase counter-clockwise.
+    CounterClockwise,
+}
+
+/// Describes how normalized values, between 0.0 and 1.0, are laid out around the 64 leds of an
+/// arc ring: which led is at 0.0, and in which direction values increase. A value of 1.0 is a
+/// full turn, and is at the same place as 0.0.
+///
+/// Led indices are the ones of the device: led 0 is at the top, and indices increase
+/// clockwise. Angles are in degrees, clockwise from the top, and don't depend on the layout.
+///
+/// # Example
+///
+/// A knob that starts at the bottom and increases counter-clockwise:
+///
+/// ```
+/// use monome_core::{RingDirection, RingLayout};
+///
+/// let layout = RingLayout::new(32, RingDirection::CounterClockwise);
+/// assert_eq!(layout.value_to_led(0.0), 32);
+/// assert_eq!(layout.value_to_led(0.25), 16);
+/// assert_eq!(layout.led_to_value(48), 0.75);
+/// assert_eq!(layout.value_to_angle(0.25), 90.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RingLayout {
+    /// The led at which the value is 0.0.
+    start: usize,
+    /// The direction in which values increase.
+    direction: RingDirection,
+}
+
+impl Default for RingLayout {
+    fn default() -> RingLayout {
+        RingLayout::new(0, RingDirection::Clockwise)
+    }
+}
+
+impl RingLayout {
+    /// Create a layout where 0.0 is at led `start`, and values increase in `direction`.
+    pub fn new(start: usize, direction: RingDirection) -> RingLayout {
+        RingLayout {
+            start: start % RING_LEDS,
+            direction,
+        }
+    }
+
+    /// Get the led at which the value is 0.0.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Get the direction in which values increase.
+    pub fn direction(&self) -> RingDirection {
+        self.direction
+    }
+
+    /// Get the led that shows `value`. Values outside of [0.0, 1.0] wrap around.
+    pub fn value_to_led(&self, value: f32) -> usize {
+        let offset = (value.rem_euclid(1.) * RING_LEDS as f32) as usize % RING_LEDS;
+        self.offset_to_led(offset)
+    }
+
+    /// Get the value at the position of `led`, between 0.0 and 1.0 (excluded).
+    pub fn led_to_value(&self, led: usize) -> f32 {
+        self.led_to_offset(led) as f32 / RING_LEDS as f32
+    }
+
+    /// Get the angle, in degrees clockwise from the top, at which `value` is.
+    pub fn value_to_angle(&self, value: f32) -> f32 {
+        let angle = ring_led_to_angle(self.start) + self.sign() * value * 360.;
+        angle.rem_euclid(360.)
+    }
+
+    /// Get the value at an angle, in degrees clockwise from the top, between 0.0 and 1.0
+    /// (excluded).
+    pub fn angle_to_value(&self, angle: f32) -> f32 {
+        let turns = (angle - ring_led_to_angle(self.start)) / 360.;
+        (self.sign() * turns).rem_euclid(1.)
+    }
+
+    /// Get the leds between the value 0.0 and `value`, in the direction of the layout, for
+    /// example to show the value as a filled arc. No led is lit for 0.0, and all the leds are
+    /// lit for 1.0.
+    pub fn fill(&self, value: f32) -> Vec<usize> {
+        let count = (value.clamp(0., 1.) * RING_LEDS as f32).round() as usize;
+        (0..count)
+            .map(|offset| self.offset_to_led(offset))
+            .collect()
+    }
+
+    /// The led at `offset` leds from the start, in the direction of the layout.
+    fn offset_to_led(&self, offset: usize) -> usize {
+        match self.direction {
+            RingDirection::Clockwise => (self.start + offset) % RING_LEDS,
+            RingDirection::CounterClockwise => (self.start + RING_LEDS - offset) % RING_LEDS,
+        }
+    }
+
+    /// The number of leds from the start to `led`, in the direction of the layout.
+    fn led_to_offset(&self, led: usize) -> usize {
+        let led = led % RING_LEDS;
+        match self.direction {
+            RingDirection::Clockwise => (led + RING_LEDS - self.start) % RING_LEDS,
+            RingDirection::CounterClockwise => (self.start + RING_LEDS - led) % RING_LEDS,
+        }
+    }
+
+    /// 1.0 if values increase clockwise, -1.0 otherwise.
+    fn sign(&self) -> f32 {
+        match self.direction {
+            RingDirection::Clockwise => 1.,
+            RingDirection::CounterClockwise => -1.,
+        }
+    }
+}
+
+/// Get the angle of `led`, in degrees clockwise from the top.
+pub fn ring_led_to_angle(led: usize) -> f32 {
+    (led % RING_LEDS) as f32 * 360. / RING_LEDS as f32
+}
+
+/// Get the led closest to an angle, in degrees clockwise from the top. Angles outside of
+/// [0, 360] wrap around.
+pub fn ring_angle_to_led(angle: f32) -> usize {
+    (angle.rem_euclid(360.) * RING_LEDS as f32 / 360.).round() as usize % RING_LEDS
+}
+
+/// Get the leds lit by an arc that starts at `start_angle`, in degrees clockwise from the top,
+/// and sweeps `sweep` degrees clockwise, or counter-clockwise if `sweep` is negative, with their
+/// intensities, sorted by led. The arc can wrap around the top of the ring, and covers a full
+/// turn at most.
+///
+/// Each led covers the angles from its own angle to the one of the next led clockwise, and is
+/// lit in proportion to the part of it that the arc covers: endpoints that fall between two leds
+/// are drawn dimmer. The leds that would be lit at intensity 0 are not returned.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::ring_arc_leds;
+///
+/// // A quarter turn, wrapping around the top.
+/// let leds = ring_arc_leds(315., 90., 15);
+/// assert_eq!(leds.len(), 16);
+/// assert_eq!(leds[0], (0, 15));
+/// assert_eq!(leds[15], (63, 15));
+/// // Half a led past the start.
+/// assert_eq!(ring_arc_leds(2.8125, 11.25, 10), vec![(0, 5), (1, 10), (2, 5)]);
+/// ```
+pub fn ring_arc_leds(start_angle: f32, sweep: f32, intensity: u8) -> Vec<(usize, u8)> {
+    let led_angle = 360. / RING_LEDS as f32;
+    let sweep = sweep.clamp(-360., 360.);
+    let start_angle = if sweep < 0. {
+        start_angle + sweep
+    } else {
+        start_angle
+    };
+    // In leds from the top, the end being past the last led when the arc wraps around.
+    let start = start_angle.rem_euclid(360.) / led_angle;
+    let end = start + sweep.abs() / led_angle;
+
+    let mut coverage = [0f32; RING_LEDS];
+    for i in start.floor() as usize..end.ceil() as usize {
+        let covered = end.min(i as f32 + 1.) - start.max(i as f32);
+        coverage[i % RING_LEDS] += covered.max(0.);
+    }
+    let intensity = f32::from(intensity.min(15));
+    coverage
+        .iter()
+        .enumerate()
+        .filter_map(|(led, covered)| {
+            let level = (intensity * covered.min(1.)).round() as u8;
+            if level > 0 {
+                Some((led, level))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(ring_led_to_angle(16), 90.);
+        assert_eq!(ring_angle_to_led(-90.), 48);
+        assert_eq!(ring_angle_to_led(359.), 0);
+
+        let layout = RingLayout::default();
+        assert_eq!(layout.value_to_led(0.5), 32);
+        assert_eq!(layout.value_to_led(1.), 0);
+        assert_eq!(layout.value_to_led(-0.25), 48);
+        assert_eq!(layout.angle_to_value(270.), 0.75);
+        assert_eq!(layout.fill(0.05), vec![0, 1, 2]);
+
+        let layout = RingLayout::new(40, RingDirection::CounterClockwise);
+        for led in 0..RING_LEDS {
+            assert_eq!(layout.value_to_led(layout.led_to_value(led)), led);
+            let angle = layout.value_to_angle(layout.led_to_value(led));
+            assert_eq!(ring_angle_to_led(angle), led);
+        }
+        assert_eq!(layout.angle_to_value(ring_led_to_angle(39)), 1. / 64.);
+        assert_eq!(layout.fill(3. / 64.), vec![40, 39, 38]);
+        assert!(layout.fill(0.).is_empty());
+        assert_eq!(layout.fill(1.).len(), RING_LEDS);
+    }
+
+    #[test]
+    fn arcs() {
+        let leds: Vec<usize> = ring_arc_leds(270., 180., 15)
+            .into_iter()
+            .map(|(led, _)| led)
+            .collect();
+        assert_eq!(leds, (0..16).chain(48..64).collect::<Vec<_>>());
+        // A negative sweep ends at the start angle.
+        assert_eq!(ring_arc_leds(90., -90., 15), ring_arc_leds(0., 90., 15));
+        assert_eq!(ring_arc_leds(-5.625, -11.25, 15), vec![(61, 15), (62, 15)]);
+        // More than a full turn lights each led once.
+        assert_eq!(ring_arc_leds(100., 720., 9), ring_arc_leds(0., 360., 9));
+        assert_eq!(ring_arc_leds(3., 360., 9).len(), RING_LEDS);
+        assert!(ring_arc_leds(3., 360., 9)
+            .iter()
+            .all(|&(_, level)| level == 9));
+        assert!(ring_arc_leds(42., 0., 15).is_empty());
+        // A sliver of a led is too dim to be lit.
+        assert!(ring_arc_leds(0., 0.1, 15).is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Musical scales, to map grid keys to pitches and to show which keys are in a scale.
+
+use crate::frame::GridFrame;
+
+/// The number of semitones in an octave.
+const OCTAVE: i32 = 12;
+
+/// A musical scale: a root note, as a MIDI note number, and the intervals of the notes of the
+/// scale from the root, in semitones, within an octave. Instruments use it to map the keys of a
+/// grid to pitches, either as an isomorphic keyboard, where each key is a semitone and each row
+/// is a fixed interval above the row below it, or by scale degree, as the rows of a step
+/// sequencer.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::Scale;
+///
+/// // C major, from middle C.
+/// let scale = Scale::major(60);
+/// assert!(scale.contains(64));
+/// assert!(!scale.contains(61));
+/// // The degrees continue in the next octaves.
+/// assert_eq!(scale.note(7), 72);
+/// assert_eq!(scale.note(-1), 59);
+/// // A whole octave lower.
+/// assert_eq!(scale.octave_shift(-1).root(), 48);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    /// The root note, as a MIDI note number.
+    root: i32,
+    /// The intervals of the notes from the root, in semitones, sorted, between 0 and 11.
+    intervals: Vec<i32>,
+}
+
+impl Scale {
+    /// Create a scale from its root note and the intervals of its notes from the root, in
+    /// semitones. The intervals are taken modulo an octave, and the root is always part of the
+    /// scale.
+    pub fn new(root: i32, intervals: &[i32]) -> Scale {
+        let mut intervals: Vec<i32> = intervals
+            .iter()
+            .map(|interval| interval.rem_euclid(OCTAVE))
+            .chain(std::iter::once(0))
+            .collect();
+        intervals.sort_unstable();
+        intervals.dedup();
+        Scale { root, intervals }
+    }
+
+    /// The major scale from `root`.
+    pub fn major(root: i32) -> Scale {
+        Scale::new(root, &[0, 2, 4, 5, 7, 9, 11])
+    }
+
+    /// The natural minor scale from `root`.
+    pub fn minor(root: i32) -> Scale {
+        Scale::new(root, &[0, 2, 3, 5, 7, 8, 10])
+    }
+
+    /// The major pentatonic scale from `root`.
+    pub fn major_pentatonic(root: i32) -> Scale {
+        Scale::new(root, &[0, 2, 4, 7, 9])
+    }
+
+    /// The minor pentatonic scale from `root`.
+    pub fn minor_pentatonic(root: i32) -> Scale {
+        Scale::new(root, &[0, 3, 5, 7, 10])
+    }
+
+    /// The chromatic scale from `root`: every note is in it.
+    pub fn chromatic(root: i32) -> Scale {
+        Scale::new(root, &(0..OCTAVE).collect::<Vec<_>>())
+    }
+
+    /// Get the root note.
+    pub fn root(&self) -> i32 {
+        self.root
+    }
+
+    /// Set the root note, keeping the intervals.
+    pub fn set_root(&mut self, root: i32) {
+        self.root = root;
+    }
+
+    /// Get the intervals of the notes from the root, in semitones, sorted.
+    pub fn intervals(&self) -> &[i32] {
+        &self.intervals
+    }
+
+    /// Returns the same scale, `octaves` octaves higher, or lower if negative.
+    pub fn octave_shift(&self, octaves: i32) -> Scale {
+        Scale {
+            root: self.root + octaves * OCTAVE,
+            intervals: self.intervals.clone(),
+        }
+    }
+
+    /// Returns true if `note` is in the scale, in any octave.
+    pub fn contains(&self, note: i32) -> bool {
+        self.intervals
+            .contains(&(note - self.root).rem_euclid(OCTAVE))
+    }
+
+    /// Returns true if `note` is the root of the scale, in any octave.
+    pub fn is_root(&self, note: i32) -> bool {
+        (note - self.root).rem_euclid(OCTAVE) == 0
+    }
+
+    /// Returns the note of the scale degree `degree`, 0 being the root. Degrees past the last
+    /// note of the scale continue in the next octaves, and negative degrees go below the root.
+    pub fn note(&self, degree: i32) -> i32 {
+        let len = self.intervals.len() as i32;
+        let octave = degree.div_euclid(len);
+        self.root + octave * OCTAVE + self.intervals[degree.rem_euclid(len) as usize]
+    }
+
+    /// Returns the note of the key at `x`, `y` of an isomorphic keyboard on a grid `height` keys
+    /// high: the bottom left key is the root, each key is a semitone above the key on its left,
+    /// and each row is `row_offset` semitones above the row below it, e.g. 5 for a layout in
+    /// fourths.
+    pub fn isomorphic_note(&self, x: i32, y: i32, height: i32, row_offset: i32) -> i32 {
+        self.root + x + (height - 1 - y) * row_offset
+    }
+
+    /// Draws the notes of the scale of the isomorphic keyboard described in `isomorphic_note`
+    /// on `frame`: the roots with `root_intensity`, the other notes of the scale with
+    /// `in_scale_intensity`, and the notes outside of the scale are turned off.
+    pub fn draw_isomorphic(
+        &self,
+        frame: &mut GridFrame,
+        row_offset: i32,
+        root_intensity: u8,
+        in_scale_intensity: u8,
+    ) {
+        let height = frame.height();
+        for y in 0..height {
+            for x in 0..frame.width() {
+                let note = self.isomorphic_note(x as i32, y as i32, height as i32, row_offset);
+                let intensity = if self.is_root(note) {
+                    root_intensity
+                } else if self.contains(note) {
+                    in_scale_intensity
+                } else {
+                    0
+                };
+                frame.set(x, y, intensity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale() {
+        let scale = Scale::minor_pentatonic(57);
+        assert_eq!(scale.intervals(), &[0, 3, 5, 7, 10]);
+        assert_eq!(scale.note(0), 57);
+        assert_eq!(scale.note(5), 69);
+        assert_eq!(scale.note(-2), 52);
+        assert!(scale.contains(45));
+        assert!(scale.is_root(33));
+        assert!(!scale.contains(58));
+        assert_eq!(Scale::new(0, &[14, 2, 12]).intervals(), &[0, 2]);
+        assert_eq!(Scale::chromatic(0).intervals().len(), 12);
+
+        // An isomorphic keyboard in fourths, on a 4x2 grid.
+        let scale = Scale::major(60);
+        assert_eq!(scale.isomorphic_note(0, 1, 2, 5), 60);
+        assert_eq!(scale.isomorphic_note(3, 0, 2, 5), 68);
+        let mut frame = GridFrame::new(4, 2);
+        scale.draw_isomorphic(&mut frame, 5, 15, 4);
+        // 65 66 67 68
+        // 60 61 62 63
+        assert_eq!(frame.leds(), &[4, 0, 4, 0, 15, 0, 4, 0]);
+    }
+}
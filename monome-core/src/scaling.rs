@@ -0,0 +1,112 @@
+//! Scaling of the intensity of regions of a grid, to set up a visual hierarchy.
+
+/// A set of regions of a grid, each with a factor by which the intensity of its leds is scaled
+/// when a frame is drawn. Where regions overlap, the one set last applies. Leds outside of all
+/// the regions are not scaled.
+///
+/// # Example
+///
+/// Dim the sequencer steps, except for the playhead row:
+///
+/// ```
+/// use monome_core::IntensityScaling;
+///
+/// let mut scaling = IntensityScaling::new();
+/// scaling.set((0, 0, 16, 8), 0.5);
+/// scaling.set((0, 7, 16, 1), 1.0);
+///
+/// let mut leds = [10; 128];
+/// scaling.apply(&mut leds, 16);
+/// assert_eq!(leds[0], 5);
+/// assert_eq!(leds[127], 10);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntensityScaling {
+    /// The regions, as `(x, y, width, height)`, with their factor, in the order they have been
+    /// set.
+    regions: Vec<((usize, usize, usize, usize), f32)>,
+}
+
+impl IntensityScaling {
+    /// Create a scaling that leaves all the leds unchanged.
+    pub fn new() -> IntensityScaling {
+        IntensityScaling {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Scale the intensity of the leds of `region`, as `(x, y, width, height)`, by `factor`.
+    /// Setting the same region again replaces its factor, without changing which regions it
+    /// overlaps.
+    pub fn set(&mut self, region: (usize, usize, usize, usize), factor: f32) {
+        let factor = factor.max(0.);
+        match self.regions.iter_mut().find(|(r, _)| *r == region) {
+            Some(existing) => existing.1 = factor,
+            None => self.regions.push((region, factor)),
+        }
+    }
+
+    /// Stop scaling the leds of `region`.
+    pub fn remove(&mut self, region: (usize, usize, usize, usize)) {
+        self.regions.retain(|&(r, _)| r != region);
+    }
+
+    /// Remove all the regions.
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Returns true if no region is scaled.
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Get the factor by which the led at `x`, `y` is scaled.
+    pub fn factor_at(&self, x: usize, y: usize) -> f32 {
+        self.regions
+            .iter()
+            .rev()
+            .find(|&&((rx, ry, width, height), _)| {
+                x >= rx && y >= ry && x < rx + width && y < ry + height
+            })
+            .map_or(1., |&(_, factor)| factor)
+    }
+
+    /// Scale the intensities of `leds`, packed in row order for a grid `width` leds wide. The
+    /// results are rounded, and clamped to 15.
+    pub fn apply(&self, leds: &mut [u8], width: usize) {
+        if self.regions.is_empty() || width == 0 {
+            return;
+        }
+        for (i, led) in leds.iter_mut().enumerate() {
+            let factor = self.factor_at(i % width, i / width);
+            *led = (f32::from(*led) * factor).round().min(15.) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaling() {
+        let mut scaling = IntensityScaling::new();
+        let mut leds = [8; 16];
+        scaling.apply(&mut leds, 4);
+        assert_eq!(leds, [8; 16]);
+
+        scaling.set((0, 0, 2, 4), 0.25);
+        scaling.set((1, 1, 2, 2), 2.);
+        scaling.apply(&mut leds, 4);
+        assert_eq!(leds, [2, 2, 8, 8, 2, 15, 15, 8, 2, 15, 15, 8, 2, 2, 8, 8]);
+
+        scaling.set((0, 0, 2, 4), 0.);
+        assert_eq!(scaling.factor_at(0, 0), 0.);
+        assert_eq!(scaling.factor_at(1, 1), 2.);
+        scaling.remove((1, 1, 2, 2));
+        assert_eq!(scaling.factor_at(1, 1), 0.);
+        scaling.clear();
+        assert!(scaling.is_empty());
+    }
+}
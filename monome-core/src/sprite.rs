@@ -0,0 +1,153 @@
+//! Small images of led intensities, drawn on frames at any position.
+
+use crate::frame::GridFrame;
+
+/// A small image of led intensities, between 0 and 15, that can be drawn on a frame at any
+/// position with `blit`. The leds of a sprite that have its transparent intensity, if any, leave
+/// the frame untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    /// The width of the sprite, in leds.
+    width: usize,
+    /// The height of the sprite, in leds.
+    height: usize,
+    /// The intensities of the leds, packed in row order.
+    leds: Vec<u8>,
+    /// The intensity that is not drawn, if any.
+    transparent: Option<u8>,
+}
+
+impl Sprite {
+    /// Create a sprite of `width` by `height` leds, all off, without transparent intensity.
+    pub fn new(width: usize, height: usize) -> Sprite {
+        Sprite {
+            width,
+            height,
+            leds: vec![0; width * height],
+            transparent: None,
+        }
+    }
+
+    /// Create a sprite from its rows of intensities. The sprite is as wide as the longest row,
+    /// and the missing leds of the shorter rows are off.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use monome_core::Sprite;
+    /// let arrow = Sprite::from_rows(&[&[0, 15, 0], &[15, 15, 15], &[0, 15]]);
+    /// assert_eq!((arrow.width(), arrow.height()), (3, 3));
+    /// assert_eq!(arrow.get(2, 2), Some(0));
+    /// ```
+    pub fn from_rows(rows: &[&[u8]]) -> Sprite {
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut sprite = Sprite::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            sprite.leds[y * width..y * width + row.len()].copy_from_slice(row);
+        }
+        sprite
+    }
+
+    /// Get the width of this sprite.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of this sprite.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the intensity of the led at `x`, `y`, or `None` if it is outside of the sprite.
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x < self.width && y < self.height {
+            Some(self.leds[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Set the intensity of the led at `x`, `y`. This does nothing if it is outside of the
+    /// sprite.
+    pub fn set(&mut self, x: usize, y: usize, intensity: u8) {
+        if x < self.width && y < self.height {
+            self.leds[y * self.width + x] = intensity;
+        }
+    }
+
+    /// Get the intensity that is not drawn, if any.
+    pub fn transparent(&self) -> Option<u8> {
+        self.transparent
+    }
+
+    /// Set the intensity that is not drawn, or `None` to draw all the leds of this sprite.
+    pub fn set_transparent(&mut self, transparent: Option<u8>) {
+        self.transparent = transparent;
+    }
+}
+
+/// Draw `sprite` on `frame`, with its top left led at `x`, `y`. The position can be partly or
+/// entirely outside of the frame: the leds that fall outside of it are not drawn, and neither
+/// are the leds that have the transparent intensity of the sprite.
+///
+/// # Example
+///
+/// Move a ball that enters the frame from the left, over a background:
+///
+/// ```
+/// use monome_core::{blit, GridFrame, Sprite};
+/// let mut ball = Sprite::from_rows(&[&[0, 15, 0], &[15, 15, 15], &[0, 15, 0]]);
+/// ball.set_transparent(Some(0));
+/// let mut frame = GridFrame::new(16, 8);
+/// frame.fill(2);
+/// blit(&mut frame, &ball, -1, 0);
+/// assert_eq!(frame.get(0, 0), Some(15));
+/// assert_eq!(frame.get(1, 0), Some(2));
+/// ```
+pub fn blit(frame: &mut GridFrame, sprite: &Sprite, x: isize, y: isize) {
+    for j in 0..sprite.height {
+        let frame_y = y + j as isize;
+        if frame_y < 0 {
+            continue;
+        }
+        for i in 0..sprite.width {
+            let frame_x = x + i as isize;
+            if frame_x < 0 {
+                continue;
+            }
+            let intensity = sprite.leds[j * sprite.width + i];
+            if sprite.transparent == Some(intensity) {
+                continue;
+            }
+            // `set` ignores the leds past the right and bottom edges.
+            frame.set(frame_x as usize, frame_y as usize, intensity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipping_and_transparency() {
+        let mut sprite = Sprite::from_rows(&[&[1, 2], &[3, 4]]);
+        let mut frame = GridFrame::new(4, 4);
+        frame.fill(9);
+
+        blit(&mut frame, &sprite, 3, 3);
+        assert_eq!(frame.get(3, 3), Some(1));
+        blit(&mut frame, &sprite, -1, -1);
+        assert_eq!(frame.get(0, 0), Some(4));
+        assert_eq!(frame.get(1, 0), Some(9));
+        blit(&mut frame, &sprite, -5, 10);
+        assert_eq!(frame.leds().iter().filter(|&&led| led == 9).count(), 14);
+
+        sprite.set_transparent(Some(2));
+        blit(&mut frame, &sprite, 1, 1);
+        assert_eq!(frame.get(1, 1), Some(1));
+        assert_eq!(frame.get(2, 1), Some(9));
+        assert_eq!(frame.get(1, 2), Some(3));
+        assert_eq!(frame.get(2, 2), Some(4));
+    }
+}
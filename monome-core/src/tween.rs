@@ -0,0 +1,274 @@
+//! Values animated over time, to animate led intensities or the properties of widgets.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::frame::GridFrame;
+
+/// How a tween progresses from its start value to its end value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    /// At a constant speed.
+    Linear,
+    /// Slowly at first, then faster.
+    EaseIn,
+    /// Fast at first, then slower.
+    EaseOut,
+    /// Slowly at first and at the end, faster in the middle.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Get the progress of a tween at `t`, the fraction of its duration elapsed, between 0.0 and
+    /// 1.0.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    -1. + (4. - 2. * t) * t
+                }
+            }
+        }
+    }
+}
+
+/// What a tween does when its duration has elapsed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Looping {
+    /// It stops at its end value.
+    Once,
+    /// It starts again from its start value.
+    Repeat,
+    /// It goes back to its start value, then forth again, and so on.
+    PingPong,
+}
+
+/// A value going from a start value to an end value over a duration, advanced with `tick()`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use monome_core::{Easing, Tween};
+///
+/// let mut tween = Tween::new(0., 15., Duration::from_millis(300), Easing::Linear);
+/// assert_eq!(tween.tick(Duration::from_millis(150)), 7.5);
+/// assert_eq!(tween.tick(Duration::from_millis(500)), 15.);
+/// assert!(tween.is_finished());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tween {
+    /// The value at the start.
+    from: f32,
+    /// The value at the end.
+    to: f32,
+    /// How long it takes to go from the start value to the end value.
+    duration: Duration,
+    /// How the value progresses.
+    easing: Easing,
+    /// What to do when the duration has elapsed.
+    looping: Looping,
+    /// The time elapsed since the start.
+    elapsed: Duration,
+}
+
+impl Tween {
+    /// Create a tween from `from` to `to` over `duration`, that stops at its end value.
+    pub fn new(from: f32, to: f32, duration: Duration, easing: Easing) -> Tween {
+        Tween {
+            from,
+            to,
+            duration,
+            easing,
+            looping: Looping::Once,
+            elapsed: Duration::from_secs(0),
+        }
+    }
+
+    /// Set what this tween does when its duration has elapsed.
+    pub fn set_looping(&mut self, looping: Looping) {
+        self.looping = looping;
+    }
+
+    /// Advance this tween by `dt`, and return its new value.
+    pub fn tick(&mut self, dt: Duration) -> f32 {
+        self.elapsed += dt;
+        self.value()
+    }
+
+    /// Get the current value of this tween.
+    pub fn value(&self) -> f32 {
+        if self.duration == Duration::from_secs(0) {
+            return self.to;
+        }
+        let cycles = (self.elapsed.as_secs_f64() / self.duration.as_secs_f64()) as f32;
+        let t = match self.looping {
+            Looping::Once => cycles.min(1.),
+            Looping::Repeat => cycles.fract(),
+            Looping::PingPong => {
+                if (cycles as u64).is_multiple_of(2) {
+                    cycles.fract()
+                } else {
+                    1. - cycles.fract()
+                }
+            }
+        };
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    /// Returns true if this tween stops at its end value, and has reached it.
+    pub fn is_finished(&self) -> bool {
+        self.looping == Looping::Once && self.elapsed >= self.duration
+    }
+}
+
+/// A set of tweens advanced together: tweens of the intensities of leds, drawn on frames, and
+/// named tweens, whose values an application reads to animate anything else, such as the
+/// properties of a widget.
+///
+/// # Example
+///
+/// Fade a led out, and slide a cursor along a row:
+///
+/// ```
+/// use std::time::Duration;
+/// use monome_core::{Animator, Easing, GridFrame, Tween};
+///
+/// let second = Duration::from_secs(1);
+/// let mut animator = Animator::new();
+/// animator.animate_led(0, 0, Tween::new(15., 0., second, Easing::Linear));
+/// animator.animate("cursor", Tween::new(0., 15., second, Easing::EaseInOut));
+///
+/// let mut frame = GridFrame::new(16, 8);
+/// animator.tick(second / 2);
+/// animator.draw(&mut frame);
+/// assert_eq!(frame.get(0, 0), Some(8));
+/// assert_eq!(animator.value("cursor"), Some(7.5));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Animator {
+    /// The tweens of the intensities of leds, by position.
+    leds: HashMap<(usize, usize), Tween>,
+    /// The named tweens.
+    properties: HashMap<String, Tween>,
+}
+
+impl Animator {
+    /// Create an animator without tweens.
+    pub fn new() -> Animator {
+        Animator::default()
+    }
+
+    /// Animate the intensity of the led at `x`, `y` with `tween`, replacing its current tween,
+    /// if any. The tween is removed once it has finished and its end value has been drawn.
+    pub fn animate_led(&mut self, x: usize, y: usize, tween: Tween) {
+        self.leds.insert((x, y), tween);
+    }
+
+    /// Animate the value named `name` with `tween`, replacing its current tween, if any. The
+    /// tween is kept, with its end value, once it has finished, until it is removed.
+    pub fn animate<S>(&mut self, name: S, tween: Tween)
+    where
+        S: Into<String>,
+    {
+        self.properties.insert(name.into(), tween);
+    }
+
+    /// Get the current value named `name`, or `None` if it is not animated.
+    pub fn value(&self, name: &str) -> Option<f32> {
+        self.properties.get(name).map(Tween::value)
+    }
+
+    /// Stop animating the value named `name`.
+    pub fn remove(&mut self, name: &str) {
+        self.properties.remove(name);
+    }
+
+    /// Stop animating the led at `x`, `y`.
+    pub fn remove_led(&mut self, x: usize, y: usize) {
+        self.leds.remove(&(x, y));
+    }
+
+    /// Returns true if no tween is in progress.
+    pub fn is_idle(&self) -> bool {
+        self.leds.is_empty() && self.properties.values().all(Tween::is_finished)
+    }
+
+    /// Advance all the tweens by `dt`.
+    pub fn tick(&mut self, dt: Duration) {
+        for tween in self.leds.values_mut().chain(self.properties.values_mut()) {
+            tween.tick(dt);
+        }
+    }
+
+    /// Draw the current intensities of the animated leds on `frame`.
+    pub fn draw(&mut self, frame: &mut GridFrame) {
+        self.draw_with(|x, y, intensity| frame.set(x, y, intensity));
+    }
+
+    /// Call `set` with the position and the current intensity of each animated led, rounded
+    /// and clamped between 0 and 15, e.g. to draw them on something else than a `GridFrame`.
+    pub fn draw_with<F>(&mut self, mut set: F)
+    where
+        F: FnMut(usize, usize, u8),
+    {
+        for (&(x, y), tween) in self.leds.iter() {
+            set(x, y, tween.value().round().clamp(0., 15.) as u8);
+        }
+        self.leds.retain(|_, tween| !tween.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looping() {
+        let second = Duration::from_secs(1);
+        let mut tween = Tween::new(0., 10., second, Easing::Linear);
+        tween.set_looping(Looping::Repeat);
+        assert_eq!(tween.tick(second * 5 / 4), 2.5);
+        assert!(!tween.is_finished());
+
+        let mut tween = Tween::new(0., 10., second, Easing::Linear);
+        tween.set_looping(Looping::PingPong);
+        assert_eq!(tween.tick(second * 5 / 4), 7.5);
+        assert_eq!(tween.tick(second), 2.5);
+
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.25), 0.125);
+        assert_eq!(Easing::EaseInOut.apply(1.5), 1.);
+    }
+
+    #[test]
+    fn finished_leds_are_removed_once_drawn() {
+        let mut animator = Animator::new();
+        animator.animate_led(
+            1,
+            2,
+            Tween::new(0., 15., Duration::from_millis(10), Easing::Linear),
+        );
+        animator.animate(
+            "x",
+            Tween::new(0., 1., Duration::from_millis(10), Easing::Linear),
+        );
+        animator.tick(Duration::from_millis(20));
+        assert!(!animator.is_idle());
+
+        let mut frame = GridFrame::new(4, 4);
+        animator.draw(&mut frame);
+        assert_eq!(frame.get(1, 2), Some(15));
+        assert!(animator.is_idle());
+        assert_eq!(animator.value("x"), Some(1.));
+        animator.remove("x");
+        assert_eq!(animator.value("x"), None);
+    }
+}
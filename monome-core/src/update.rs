@@ -0,0 +1,295 @@
+//! The choice of the messages that update a quad of a grid, or the ring of an arc encoder, with
+//! the fewest bytes.
+
+use crate::ring::RING_LEDS;
+
+/// The length of the addresses of the messages that update leds, without the prefix:
+/// `/grid/led/level/map`, `/grid/led/level/row`, `/grid/led/level/col` and `/grid/led/level/set`
+/// all have the same length.
+const LED_ADDRESS_LEN: usize = 19;
+
+/// The length of `/ring/set` and `/ring/map`, the addresses of the messages that update a single
+/// led or a whole ring, without the prefix.
+const RING_SET_ADDRESS_LEN: usize = 9;
+
+/// The length of `/ring/range`, without the prefix.
+const RING_RANGE_ADDRESS_LEN: usize = 11;
+
+/// The size of the IPv4 and UDP headers of each datagram.
+const DATAGRAM_OVERHEAD: usize = 28;
+
+/// How to send the changes of an 8x8 quad of a grid to the device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuadUpdate {
+    /// Nothing changed, nothing needs to be sent.
+    Unchanged,
+    /// Send the whole quad with a single `/grid/led/level/map` message.
+    Map,
+    /// Send the rows of the quad that changed, by index in the quad, with a
+    /// `/grid/led/level/row` message each.
+    Rows(Vec<usize>),
+    /// Send the columns of the quad that changed, by index in the quad, with a
+    /// `/grid/led/level/col` message each.
+    Columns(Vec<usize>),
+    /// Send the leds that changed, as `(x, y)` in the quad, with a `/grid/led/level/set`
+    /// message each.
+    Cells(Vec<(usize, usize)>),
+}
+
+impl QuadUpdate {
+    /// Returns the number of messages needed for this update.
+    pub fn message_count(&self) -> usize {
+        match *self {
+            QuadUpdate::Unchanged => 0,
+            QuadUpdate::Map => 1,
+            QuadUpdate::Rows(ref rows) => rows.len(),
+            QuadUpdate::Columns(ref columns) => columns.len(),
+            QuadUpdate::Cells(ref cells) => cells.len(),
+        }
+    }
+}
+
+/// How to send the changes of the ring of an arc encoder to the device.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RingUpdate {
+    /// Nothing changed, nothing needs to be sent.
+    Unchanged,
+    /// Send the whole ring with a single `/ring/map` message.
+    Map,
+    /// Send the runs of consecutive leds that changed to the same intensity, as
+    /// `(start, end, intensity)`, `end` being inclusive: a `/ring/set` message for each run of a
+    /// single led, and a `/ring/range` message for each longer run. A run can wrap around the
+    /// ring, in which case `end` is lower than `start`.
+    Runs(Vec<(usize, usize, u8)>),
+}
+
+impl RingUpdate {
+    /// Returns the number of messages needed for this update.
+    pub fn message_count(&self) -> usize {
+        match *self {
+            RingUpdate::Unchanged => 0,
+            RingUpdate::Map => 1,
+            RingUpdate::Runs(ref runs) => runs.len(),
+        }
+    }
+}
+
+/// Returns the size on the wire of an OSC message that updates leds, with a prefix of
+/// `prefix_len` bytes and `ints` integer arguments.
+fn led_message_size(prefix_len: usize, ints: usize) -> usize {
+    message_size(prefix_len + LED_ADDRESS_LEN, ints)
+}
+
+/// Returns the size on the wire of an OSC message whose address is `addr_len` bytes long, with
+/// `ints` integer arguments.
+fn message_size(addr_len: usize, ints: usize) -> usize {
+    let padded = |len: usize| (len + 4) & !3;
+    padded(addr_len) + padded(ints + 1) + 4 * ints + DATAGRAM_OVERHEAD
+}
+
+/// Chooses how to update a quad whose intensities were `previous` to `next`, both in row order,
+/// for a device whose prefix is `prefix_len` bytes long: sparse changes are sent led by led, or
+/// by row or column, and dense changes as a whole quad, whichever takes the fewest bytes on the
+/// wire. On a tie, the update with the fewest messages wins.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{plan_quad_update, QuadUpdate};
+///
+/// let previous = [0; 64];
+/// let mut next = [0; 64];
+/// next[9] = 15;
+/// assert_eq!(plan_quad_update(&previous, &next, 7), QuadUpdate::Cells(vec![(1, 1)]));
+/// next = [8; 64];
+/// assert_eq!(plan_quad_update(&previous, &next, 7), QuadUpdate::Map);
+/// ```
+pub fn plan_quad_update(previous: &[u8; 64], next: &[u8; 64], prefix_len: usize) -> QuadUpdate {
+    let mut cells = Vec::new();
+    let mut rows = Vec::new();
+    let mut columns = Vec::new();
+    for y in 0..8 {
+        for x in 0..8 {
+            if previous[y * 8 + x] != next[y * 8 + x] {
+                cells.push((x, y));
+                if !rows.contains(&y) {
+                    rows.push(y);
+                }
+                if !columns.contains(&x) {
+                    columns.push(x);
+                }
+            }
+        }
+    }
+    if cells.is_empty() {
+        return QuadUpdate::Unchanged;
+    }
+    columns.sort_unstable();
+
+    let map = led_message_size(prefix_len, 2 + 64);
+    let line = led_message_size(prefix_len, 2 + 8);
+    let cell = led_message_size(prefix_len, 3);
+    // From the fewest messages to the most, so that the first of the cheapest wins.
+    let candidates = [
+        (map, QuadUpdate::Map),
+        (line * rows.len(), QuadUpdate::Rows(rows)),
+        (line * columns.len(), QuadUpdate::Columns(columns)),
+        (cell * cells.len(), QuadUpdate::Cells(cells)),
+    ];
+    let mut best = None;
+    for (cost, update) in candidates.iter() {
+        match best {
+            Some((best_cost, _)) if best_cost <= *cost => {}
+            _ => best = Some((*cost, update)),
+        }
+    }
+    best.map(|(_, update)| update.clone()).unwrap()
+}
+
+/// Chooses how to update a ring whose intensities were `previous` to `next`, for a device whose
+/// prefix is `prefix_len` bytes long: the leds that changed are sent by runs of the same
+/// intensity, or the whole ring is sent, whichever takes the fewest bytes on the wire. On a tie,
+/// the update with the fewest messages wins.
+///
+/// # Example
+///
+/// ```
+/// use monome_core::{plan_ring_update, RingUpdate};
+///
+/// let previous = [0; 64];
+/// let mut next = [0; 64];
+/// for led in 10..20 {
+///     next[led] = 15;
+/// }
+/// assert_eq!(plan_ring_update(&previous, &next, 7), RingUpdate::Runs(vec![(10, 19, 15)]));
+/// for (led, intensity) in next.iter_mut().enumerate() {
+///     *intensity = (led / 4) as u8;
+/// }
+/// assert_eq!(plan_ring_update(&previous, &next, 7), RingUpdate::Map);
+/// ```
+pub fn plan_ring_update(
+    previous: &[u8; RING_LEDS],
+    next: &[u8; RING_LEDS],
+    prefix_len: usize,
+) -> RingUpdate {
+    let mut runs: Vec<(usize, usize, u8)> = Vec::new();
+    for led in 0..RING_LEDS {
+        if previous[led] == next[led] {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == led && run.2 == next[led] => run.1 = led,
+            _ => runs.push((led, led, next[led])),
+        }
+    }
+    if runs.is_empty() {
+        return RingUpdate::Unchanged;
+    }
+    // A run that ends on the last led continues with the run that starts on the first one.
+    if runs.len() > 1 {
+        let (first, last) = (runs[0], runs[runs.len() - 1]);
+        if first.0 == 0 && last.1 == RING_LEDS - 1 && first.2 == last.2 {
+            runs[0] = (last.0, first.1, first.2);
+            runs.pop();
+        }
+    }
+
+    let set = message_size(prefix_len + RING_SET_ADDRESS_LEN, 3);
+    let range = message_size(prefix_len + RING_RANGE_ADDRESS_LEN, 4);
+    let map = message_size(prefix_len + RING_SET_ADDRESS_LEN, 1 + RING_LEDS);
+    let runs_cost: usize = runs
+        .iter()
+        .map(|&(start, end, _)| if start == end { set } else { range })
+        .sum();
+    if (map, 1) < (runs_cost, runs.len()) {
+        RingUpdate::Map
+    } else {
+        RingUpdate::Runs(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quad_update() {
+        // "/plop/grid/led/level/set", 2 ints and an intensity.
+        assert_eq!(led_message_size(5, 3), 28 + 8 + 12 + 28);
+
+        let previous = [0; 64];
+        assert_eq!(
+            plan_quad_update(&previous, &previous, 5),
+            QuadUpdate::Unchanged
+        );
+
+        // A few leds in a row: a row message is cheaper than three set messages.
+        let mut next = previous;
+        for x in 2..5 {
+            next[3 * 8 + x] = 15;
+        }
+        assert_eq!(
+            plan_quad_update(&previous, &next, 5),
+            QuadUpdate::Rows(vec![3])
+        );
+
+        // A column.
+        let mut next = previous;
+        for y in 0..8 {
+            next[y * 8 + 6] = 4;
+        }
+        let update = plan_quad_update(&previous, &next, 5);
+        assert_eq!(update, QuadUpdate::Columns(vec![6]));
+        assert_eq!(update.message_count(), 1);
+
+        // Two leds far apart.
+        let mut next = previous;
+        next[0] = 1;
+        next[63] = 1;
+        assert_eq!(
+            plan_quad_update(&previous, &next, 5),
+            QuadUpdate::Cells(vec![(0, 0), (7, 7)])
+        );
+
+        // Most of the quad.
+        let mut next = previous;
+        for led in next.iter_mut().step_by(3) {
+            *led = 9;
+        }
+        assert_eq!(plan_quad_update(&previous, &next, 5), QuadUpdate::Map);
+    }
+
+    #[test]
+    fn ring_update() {
+        let previous = [0; RING_LEDS];
+        assert_eq!(
+            plan_ring_update(&previous, &previous, 5),
+            RingUpdate::Unchanged
+        );
+
+        let mut next = previous;
+        next[3] = 7;
+        next[4] = 7;
+        next[5] = 8;
+        let update = plan_ring_update(&previous, &next, 5);
+        assert_eq!(update, RingUpdate::Runs(vec![(3, 4, 7), (5, 5, 8)]));
+        assert_eq!(update.message_count(), 2);
+
+        // Around the top of the ring.
+        let mut next = previous;
+        for led in (0..4).chain(60..64) {
+            next[led] = 15;
+        }
+        assert_eq!(
+            plan_ring_update(&previous, &next, 5),
+            RingUpdate::Runs(vec![(60, 3, 15)])
+        );
+
+        // Every other led.
+        let mut next = previous;
+        for led in next.iter_mut().step_by(2) {
+            *led = 1;
+        }
+        assert_eq!(plan_ring_update(&previous, &next, 5), RingUpdate::Map);
+    }
+}
@@ -0,0 +1,204 @@
+//! A frame buffer for the rings of an arc, that only sends the leds that changed to the device.
+
+use log::*;
+use rosc::OscType;
+
+use crate::{plan_ring_update, Monome, MonomeDeviceType, RingUpdate, RING_LEDS};
+
+/// The intensities of the rings of an arc, drawn locally and sent to a device with `flush()`.
+/// The buffer remembers what has been sent, and for each ring that changed since, sends the
+/// cheapest messages: the runs of leds that changed with `/ring/set` and `/ring/range`, or the
+/// whole ring with `/ring/map`.
+///
+/// # Example
+///
+/// Spin a led around the first ring, sending two leds per frame:
+///
+/// ```no_run
+/// use monome::{ArcFrameBuffer, Monome};
+/// let mut monome = Monome::new("/prefix").unwrap();
+/// let mut buffer = ArcFrameBuffer::for_device(&monome);
+/// for led in 0..256 {
+///     buffer.fill(0, 0);
+///     buffer.set(0, led, 15);
+///     buffer.flush(&mut monome);
+///     std::thread::sleep(std::time::Duration::from_millis(20));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArcFrameBuffer {
+    /// The intensities of the leds of each ring.
+    rings: Vec<[u8; RING_LEDS]>,
+    /// The intensities last sent for each ring, or `None` if they are not known, in which case
+    /// the whole ring is sent on the next flush.
+    sent: Vec<Option<[u8; RING_LEDS]>>,
+}
+
+impl ArcFrameBuffer {
+    /// Create a buffer for `encoders` rings, all off. All the rings are sent on the first flush.
+    pub fn new(encoders: usize) -> ArcFrameBuffer {
+        ArcFrameBuffer {
+            rings: vec![[0; RING_LEDS]; encoders],
+            sent: vec![None; encoders],
+        }
+    }
+
+    /// Create a buffer for the encoders of `monome`, see `Monome::capabilities`.
+    pub fn for_device(monome: &Monome) -> ArcFrameBuffer {
+        ArcFrameBuffer::new(monome.capabilities().encoders)
+    }
+
+    /// Get the number of rings of this buffer.
+    pub fn encoders(&self) -> usize {
+        self.rings.len()
+    }
+
+    /// Get the intensities of the leds of the ring `n`, or `None` if there is no such ring.
+    pub fn ring(&self, n: usize) -> Option<&[u8; RING_LEDS]> {
+        self.rings.get(n)
+    }
+
+    /// Get the intensity of the led `led` of the ring `n`, modulo 64, or `None` if there is no
+    /// such ring.
+    pub fn get(&self, n: usize, led: usize) -> Option<u8> {
+        self.rings.get(n).map(|ring| ring[led % RING_LEDS])
+    }
+
+    /// Set the intensity of the led `led` of the ring `n`, modulo 64, between 0 and 15. This
+    /// does nothing if there is no such ring.
+    pub fn set(&mut self, n: usize, led: usize, intensity: u8) {
+        if let Some(ring) = self.rings.get_mut(n) {
+            ring[led % RING_LEDS] = intensity;
+        }
+    }
+
+    /// Set all the leds of the ring `n` to `intensity`.
+    pub fn fill(&mut self, n: usize, intensity: u8) {
+        if let Some(ring) = self.rings.get_mut(n) {
+            *ring = [intensity; RING_LEDS];
+        }
+    }
+
+    /// Set all the leds of the ring `n` to `leds`.
+    pub fn set_ring(&mut self, n: usize, leds: &[u8; RING_LEDS]) {
+        if let Some(ring) = self.rings.get_mut(n) {
+            *ring = *leds;
+        }
+    }
+
+    /// Returns true if the ring `n` has changed since it has last been sent.
+    pub fn is_dirty(&self, n: usize) -> bool {
+        match (self.rings.get(n), self.sent.get(n)) {
+            (Some(ring), Some(Some(sent))) => ring != sent,
+            (Some(_), _) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Forget what has been sent, so that all the rings are sent whole on the next flush, e.g.
+    /// after the leds of the device have been changed by other means.
+    pub fn invalidate(&mut self) {
+        for sent in self.sent.iter_mut() {
+            *sent = None;
+        }
+    }
+
+    /// Send the leds that changed since the last flush to `monome`. A ring whose messages could
+    /// not all be queued is sent whole on the next flush. Returns the number of messages that
+    /// have been sent.
+    pub fn flush(&mut self, monome: &mut Monome) -> usize {
+        if monome.device_type != MonomeDeviceType::Arc {
+            error!("Called arc method on something that is not an arc.");
+            return 0;
+        }
+        let prefix_len = monome.prefix.len();
+        let mut count = 0;
+        for (n, (ring, sent)) in self.rings.iter().zip(self.sent.iter_mut()).enumerate() {
+            let update = match *sent {
+                Some(ref previous) => plan_ring_update(previous, ring, prefix_len),
+                None => RingUpdate::Map,
+            };
+            let encoder = OscType::Int(n as i32);
+            let mut queued = true;
+            match update {
+                RingUpdate::Unchanged => continue,
+                RingUpdate::Map => {
+                    let mut args = Vec::with_capacity(1 + RING_LEDS);
+                    args.push(encoder);
+                    args.extend(ring.iter().map(|&led| OscType::Int(i32::from(led))));
+                    queued &= monome.send("/ring/map", args);
+                    count += 1;
+                }
+                RingUpdate::Runs(runs) => {
+                    for (start, end, intensity) in runs {
+                        let intensity = OscType::Int(i32::from(intensity));
+                        queued &= if start == end {
+                            let led = OscType::Int(start as i32);
+                            monome.send("/ring/set", vec![encoder.clone(), led, intensity])
+                        } else {
+                            let (start, end) =
+                                (OscType::Int(start as i32), OscType::Int(end as i32));
+                            monome.send("/ring/range", vec![encoder.clone(), start, end, intensity])
+                        };
+                        count += 1;
+                    }
+                }
+            }
+            *sent = if queued { Some(*ring) } else { None };
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_arc;
+    use rosc::decoder::decode;
+    use rosc::OscPacket;
+    use std::time::Instant;
+
+    #[test]
+    fn flush_changes() {
+        let (mut monome, sent) = test_arc("arc 2");
+        let messages = || -> Vec<(String, Vec<OscType>)> {
+            sent.try_iter()
+                .map(
+                    |(_, bytes): (Instant, Vec<u8>)| match decode(&bytes).unwrap() {
+                        OscPacket::Message(message) => (message.addr, message.args.unwrap()),
+                        packet => panic!("unexpected packet {:?}", packet),
+                    },
+                )
+                .collect()
+        };
+
+        let mut buffer = ArcFrameBuffer::for_device(&monome);
+        assert_eq!(buffer.encoders(), 2);
+        assert_eq!(buffer.flush(&mut monome), 2);
+        assert!(messages().iter().all(|(addr, _)| addr == "/plop/ring/map"));
+        assert_eq!(buffer.flush(&mut monome), 0);
+
+        buffer.set(1, 66, 15);
+        buffer.set(1, 3, 15);
+        buffer.set(0, 10, 4);
+        assert!(buffer.is_dirty(1));
+        assert_eq!(buffer.get(1, 2), Some(15));
+        assert_eq!(buffer.flush(&mut monome), 2);
+        let int = OscType::Int;
+        assert_eq!(
+            messages(),
+            vec![
+                ("/plop/ring/set".to_string(), vec![int(0), int(10), int(4)]),
+                (
+                    "/plop/ring/range".to_string(),
+                    vec![int(1), int(2), int(3), int(15)]
+                ),
+            ]
+        );
+        assert!(!buffer.is_dirty(1));
+
+        buffer.invalidate();
+        assert_eq!(buffer.flush(&mut monome), 2);
+        assert_eq!(messages().len(), 2);
+    }
+}
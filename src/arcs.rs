@@ -0,0 +1,244 @@
+//! Several arcs used as a single device with more encoders.
+
+use crate::{Monome, MonomeError, MonomeEvent, RING_LEDS};
+
+/// Several arcs aggregated into a single surface, whose encoders are numbered across all the
+/// arcs, in the order in which they are passed: with two arcs with four encoders each, the
+/// encoders of the second arc are the encoders 4 to 7 of the surface. An application written for
+/// a surface works unchanged with any number of arcs.
+///
+/// The events of the arcs are received with `poll()`, with the encoder numbers of the surface.
+/// The rings are drawn locally, and the rings that changed are sent to the arcs with `flush()`.
+///
+/// # Example
+///
+/// ```no_run
+/// use monome::{ArcSurface, Monome, MonomeDeviceType, MonomeEvent};
+///
+/// let arcs = Monome::enumerate_devices()
+///     .unwrap()
+///     .iter()
+///     .filter(|device| device.device_type() == MonomeDeviceType::Arc)
+///     .map(|device| Monome::from_device(device, "/prefix").unwrap())
+///     .collect();
+/// let mut surface = ArcSurface::new(arcs).unwrap();
+/// let mut positions = vec![0_i32; surface.encoders()];
+/// loop {
+///     while let Some(MonomeEvent::EncoderDelta { n, delta }) = surface.poll() {
+///         positions[n] = (positions[n] + delta).rem_euclid(64);
+///         surface.fill(n, 0);
+///         surface.set(n, positions[n] as usize, 15);
+///     }
+///     surface.flush();
+/// }
+/// ```
+pub struct ArcSurface {
+    /// The client objects of the arcs.
+    arcs: Vec<Monome>,
+    /// The number of the first encoder of each arc, in the surface.
+    offsets: Vec<usize>,
+    /// The intensities of the leds of each ring of the surface.
+    rings: Vec<[u8; RING_LEDS]>,
+    /// For each ring of the surface, whether it has changed since the last flush.
+    dirty: Vec<bool>,
+    /// The arc to poll first, so that a busy arc doesn't delay the others.
+    next: usize,
+}
+
+impl ArcSurface {
+    /// Aggregate `arcs` into a surface. The number of encoders of each arc is deduced from its
+    /// model, see `Monome::capabilities`. Devices that are not arcs have no encoder, and their
+    /// events are passed through unchanged.
+    ///
+    /// Returns `MonomeError::NoDevice` if none of `arcs` is an arc.
+    pub fn new(arcs: Vec<Monome>) -> Result<ArcSurface, MonomeError> {
+        let mut offsets = Vec::with_capacity(arcs.len());
+        let mut encoders = 0;
+        for arc in &arcs {
+            offsets.push(encoders);
+            encoders += arc.capabilities().encoders;
+        }
+        if encoders == 0 {
+            return Err(MonomeError::NoDevice);
+        }
+        Ok(ArcSurface {
+            arcs,
+            offsets,
+            rings: vec![[0; RING_LEDS]; encoders],
+            dirty: vec![true; encoders],
+            next: 0,
+        })
+    }
+
+    /// Get the number of encoders of this surface.
+    pub fn encoders(&self) -> usize {
+        self.rings.len()
+    }
+
+    /// Get the client objects of the arcs, in the order of their encoders.
+    pub fn arcs(&self) -> &[Monome] {
+        &self.arcs
+    }
+
+    /// Get the client objects of the arcs, to configure them.
+    pub fn arcs_mut(&mut self) -> &mut [Monome] {
+        &mut self.arcs
+    }
+
+    /// Returns the index of the arc that has the encoder `n` of this surface, and the number of
+    /// the encoder on this arc, or `None` if there is no such encoder.
+    pub fn locate(&self, n: usize) -> Option<(usize, usize)> {
+        if n >= self.encoders() {
+            return None;
+        }
+        let arc = self.offsets.iter().rposition(|&offset| offset <= n)?;
+        Some((arc, n - self.offsets[arc]))
+    }
+
+    /// Receive the next event of any arc. The encoder numbers of the encoder events are the
+    /// ones of this surface. The arcs are polled in turn, so that a busy arc doesn't delay the
+    /// others.
+    pub fn poll(&mut self) -> Option<MonomeEvent> {
+        let count = self.arcs.len();
+        for i in 0..count {
+            let index = (self.next + i) % count;
+            if let Some(event) = self.arcs[index].poll() {
+                self.next = (index + 1) % count;
+                let offset = self.offsets[index];
+                return Some(match event {
+                    MonomeEvent::EncoderDelta { n, delta } => MonomeEvent::EncoderDelta {
+                        n: offset + n,
+                        delta,
+                    },
+                    MonomeEvent::EncoderKey { n, direction } => MonomeEvent::EncoderKey {
+                        n: offset + n,
+                        direction,
+                    },
+                    event => event,
+                });
+            }
+        }
+        None
+    }
+
+    /// Get the intensities of the leds of the ring of encoder `n`, or `None` if there is no such
+    /// encoder.
+    pub fn ring(&self, n: usize) -> Option<&[u8; RING_LEDS]> {
+        self.rings.get(n)
+    }
+
+    /// Set the intensity of the led `index` of the ring of encoder `n`, between 0 and 15. The
+    /// index is taken modulo the number of leds of a ring. This does nothing if there is no
+    /// such encoder.
+    pub fn set(&mut self, n: usize, index: usize, intensity: u8) {
+        if let Some(ring) = self.rings.get_mut(n) {
+            let led = &mut ring[index % RING_LEDS];
+            if *led != intensity {
+                *led = intensity;
+                self.dirty[n] = true;
+            }
+        }
+    }
+
+    /// Set all the leds of the ring of encoder `n` to `intensity`.
+    pub fn fill(&mut self, n: usize, intensity: u8) {
+        self.set_ring(n, &[intensity; RING_LEDS]);
+    }
+
+    /// Set the intensities of all the leds of the ring of encoder `n`.
+    pub fn set_ring(&mut self, n: usize, values: &[u8; RING_LEDS]) {
+        if let Some(ring) = self.rings.get_mut(n) {
+            if ring != values {
+                *ring = *values;
+                self.dirty[n] = true;
+            }
+        }
+    }
+
+    /// Mark all the rings as changed, so that they are all sent on the next flush, e.g. after
+    /// the leds of the arcs have been changed by other means.
+    pub fn invalidate(&mut self) {
+        for dirty in self.dirty.iter_mut() {
+            *dirty = true;
+        }
+    }
+
+    /// Send the rings that changed since the last flush to their arcs, with one `/ring/map`
+    /// message each. Returns the number of rings that have been sent.
+    pub fn flush(&mut self) -> usize {
+        let mut sent = 0;
+        for n in 0..self.encoders() {
+            if !self.dirty[n] {
+                continue;
+            }
+            if let Some((arc, encoder)) = self.locate(n) {
+                self.arcs[arc].ring_map(encoder, &self.rings[n]);
+                self.dirty[n] = false;
+                sent += 1;
+            }
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_arc;
+    use rosc::decoder::decode;
+    use rosc::{OscPacket, OscType};
+    use std::time::Instant;
+
+    #[test]
+    fn aggregate_rings() {
+        let (first_arc, first) = test_arc("monome arc 2");
+        let (second_arc, second) = test_arc("monome arc 4");
+        let mut surface = ArcSurface::new(vec![first_arc, second_arc]).unwrap();
+        assert_eq!(surface.encoders(), 6);
+        assert_eq!(surface.locate(1), Some((0, 1)));
+        assert_eq!(surface.locate(2), Some((1, 0)));
+        assert_eq!(surface.locate(5), Some((1, 3)));
+        assert_eq!(surface.locate(6), None);
+
+        assert_eq!(surface.flush(), 6);
+        while first.try_recv().is_ok() {}
+        while second.try_recv().is_ok() {}
+        assert_eq!(surface.flush(), 0);
+
+        surface.set(3, 64 + 2, 15);
+        surface.fill(0, 0);
+        assert_eq!(surface.ring(3).unwrap()[2], 15);
+        assert_eq!(surface.flush(), 1);
+        assert!(first.try_recv().is_err());
+        let (_, bytes) = second.try_recv().unwrap();
+        match decode(&bytes).unwrap() {
+            OscPacket::Message(message) => {
+                assert_eq!(message.addr, "/plop/ring/map");
+                let args = message.args.unwrap();
+                assert_eq!(args[0], OscType::Int(1));
+                assert_eq!(args[3], OscType::Int(15));
+            }
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+
+        // The events queued when the arcs have been set up.
+        while surface.poll().is_some() {}
+        surface.arcs[1]
+            .q
+            .push((
+                Instant::now(),
+                MonomeEvent::EncoderDelta { n: 2, delta: -3 },
+            ))
+            .unwrap();
+        assert_eq!(
+            surface.poll(),
+            Some(MonomeEvent::EncoderDelta { n: 4, delta: -3 })
+        );
+        assert_eq!(surface.poll(), None);
+    }
+
+    #[test]
+    fn no_arc() {
+        assert!(ArcSurface::new(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Sending the messages issued between `Monome::begin_batch()` and `Monome::commit()` as OSC
+//! bundles.
+
+use std::mem;
+
+use log::*;
+use rosc::encoder::encode;
+use rosc::{OscBundle, OscMessage, OscPacket, OscType};
+
+use crate::Monome;
+
+/// The maximum size of a bundle, in bytes: larger batches are split into several bundles, so
+/// that each of them fits in a single datagram on common networks.
+const MAX_BUNDLE_SIZE: usize = 1400;
+
+/// The size of the header of a bundle, in bytes: `#bundle`, padded, and its timetag.
+const BUNDLE_HEADER_SIZE: usize = 16;
+
+impl Monome {
+    /// Start collecting the messages sent to the device, instead of sending them one by one,
+    /// until `commit()` sends them together as OSC bundles. Fewer and larger datagrams are sent,
+    /// which is cheaper when many leds change for each frame. This does nothing if messages are
+    /// already being collected.
+    ///
+    /// # Example
+    ///
+    /// Light the first row with a single datagram:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.begin_batch();
+    /// for x in 0..16 {
+    ///     monome.set(x, 0, true);
+    /// }
+    /// monome.commit();
+    /// ```
+    pub fn begin_batch(&mut self) {
+        if self.batch.is_none() {
+            self.batch = Some(Vec::new());
+        }
+    }
+
+    /// Returns true if the messages sent are being collected, between `begin_batch()` and
+    /// `commit()`.
+    pub fn is_batching(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    /// Send the messages collected since `begin_batch()`, in as few OSC bundles as possible,
+    /// and go back to sending messages one by one. Returns true if all the bundles have been
+    /// queued, or if there was nothing to send.
+    pub fn commit(&mut self) -> bool {
+        let messages = match self.batch.take() {
+            Some(messages) => messages,
+            None => return true,
+        };
+        self.switch_to_reconnected();
+        let mut queued = true;
+        for bytes in bundles(messages) {
+            queued &= self.tx.try_send(bytes);
+        }
+        queued
+    }
+}
+
+/// Encodes `messages`, in order, as bundles of at most `MAX_BUNDLE_SIZE` bytes, to be processed
+/// as soon as they are received. A message too large to share a bundle is sent in a bundle of
+/// its own.
+fn bundles(messages: Vec<OscMessage>) -> Vec<Vec<u8>> {
+    let mut contents = Vec::new();
+    let mut content = Vec::new();
+    let mut size = BUNDLE_HEADER_SIZE;
+    for message in messages {
+        let packet = OscPacket::Message(message);
+        // Each element of a bundle is preceded by its size.
+        let element_size = match encode(&packet) {
+            Ok(bytes) => 4 + bytes.len(),
+            Err(e) => {
+                error!("Could not encode {:?}: {:?}", packet, e);
+                continue;
+            }
+        };
+        if !content.is_empty() && size + element_size > MAX_BUNDLE_SIZE {
+            contents.push(mem::take(&mut content));
+            size = BUNDLE_HEADER_SIZE;
+        }
+        size += element_size;
+        content.push(packet);
+    }
+    if !content.is_empty() {
+        contents.push(content);
+    }
+    contents
+        .into_iter()
+        .filter_map(|content| {
+            let bundle = OscPacket::Bundle(OscBundle {
+                timetag: OscType::Time(0, 1),
+                content,
+            });
+            match encode(&bundle) {
+                Ok(bytes) => Some(bytes),
+                Err(e) => {
+                    error!("Could not encode {:?}: {:?}", bundle, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rosc::decoder::decode;
+
+    #[test]
+    fn split_bundles() {
+        let map = OscMessage {
+            addr: "/plop/grid/led/level/map".to_string(),
+            args: Some(vec![OscType::Int(0); 66]),
+        };
+        let set = OscMessage {
+            addr: "/plop/grid/led/set".to_string(),
+            args: Some(vec![OscType::Int(1); 3]),
+        };
+        assert!(bundles(Vec::new()).is_empty());
+
+        let encoded = bundles(vec![set.clone(), set.clone()]);
+        assert_eq!(encoded.len(), 1);
+        match decode(&encoded[0]).unwrap() {
+            OscPacket::Bundle(bundle) => assert_eq!(bundle.content.len(), 2),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+
+        // A map is close to 300 bytes: four of them fit in a bundle, not five.
+        let encoded = bundles(vec![map; 5]);
+        assert_eq!(encoded.len(), 2);
+        assert!(encoded.iter().all(|bytes| bytes.len() <= MAX_BUNDLE_SIZE));
+    }
+}
@@ -0,0 +1,169 @@
+//! A cache of the devices found by the last enumeration, to connect without enumerating.
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::*;
+
+use crate::{Monome, MonomeDevice, MonomeError, StartupOptions, SERIALOSC_PORT};
+
+/// The first line of a cache file, with the version of the format.
+const HEADER: &str = "# monome device cache v1";
+
+/// How long to wait for a cached device to answer, before enumerating the devices.
+const CACHED_SETUP_TIMEOUT_MS: u64 = 250;
+
+/// A file in which the devices found by the last enumeration are stored, so that an application
+/// that always talks to the same device, such as an installation, can connect to it without
+/// waiting for an enumeration. See `Monome::connect_cached`.
+///
+/// The file is a text file, with a header line, then a line per device, with its name, model,
+/// host, port and the port of its serialosc instance, separated by tabs.
+#[derive(Debug, Clone)]
+pub struct DeviceCache {
+    /// The path of the file.
+    path: PathBuf,
+}
+
+impl DeviceCache {
+    /// Create a cache stored in the file at `path`. The file doesn't need to exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> DeviceCache {
+        DeviceCache {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Get the path of the file of the cache.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the devices stored in the cache. Lines that can't be parsed are skipped.
+    pub fn load(&self) -> io::Result<Vec<MonomeDevice>> {
+        let contents = fs::read_to_string(&self.path)?;
+        let mut lines = contents.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a monome device cache",
+            ));
+        }
+        Ok(lines.filter_map(parse_device).collect())
+    }
+
+    /// Replace the devices stored in the cache with `devices`.
+    pub fn save(&self, devices: &[MonomeDevice]) -> io::Result<()> {
+        let mut contents = format!("{}\n", HEADER);
+        for device in devices {
+            contents.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                device.name, device.model, device.host, device.port, device.serialosc_port
+            ));
+        }
+        fs::write(&self.path, contents)
+    }
+}
+
+/// Parses a line of a cache file.
+fn parse_device(line: &str) -> Option<MonomeDevice> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    match fields.as_slice() {
+        [name, model, host, port, serialosc_port] => {
+            let mut device = MonomeDevice::new(
+                name,
+                model,
+                port.parse().ok()?,
+                serialosc_port.parse().ok()?,
+            );
+            device.host = host.parse::<IpAddr>().ok()?;
+            Some(device)
+        }
+        _ => None,
+    }
+}
+
+impl Monome {
+    /// Sets up the first device stored in `cache` right away, with a particular prefix, and falls
+    /// back to enumerating the devices on the standard serialosc port if the cache is empty, or
+    /// if the cached device doesn't answer quickly, for example because it has been plugged on
+    /// another port. The devices enumerated are then stored in the cache for the next time.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - the prefix to use for this device and this application
+    /// * `cache` - where the devices found by the last enumeration are stored
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::{DeviceCache, Monome};
+    /// let cache = DeviceCache::new("/var/cache/installation/devices");
+    /// let mut monome = Monome::connect_cached("/prefix", &cache).unwrap();
+    /// ```
+    pub fn connect_cached<S>(prefix: S, cache: &DeviceCache) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let prefix = prefix.into();
+        match cache.load() {
+            Ok(devices) => {
+                if let Some(device) = devices.first() {
+                    let options = StartupOptions {
+                        setup_timeout: Duration::from_millis(CACHED_SETUP_TIMEOUT_MS),
+                        ..StartupOptions::default()
+                    };
+                    match Monome::from_device_with_options(device, prefix.clone(), &options) {
+                        // Another device may have been plugged on the cached port since.
+                        Ok(monome) if monome.id == device.name => return Ok(monome),
+                        Ok(monome) => info!("{} is now on port {}", monome.id, monome.port),
+                        Err(e) => info!("Cached device {} not found: {}", device.name, e),
+                    }
+                }
+            }
+            Err(e) => debug!("Could not read {}: {}", cache.path.display(), e),
+        }
+
+        let devices = Monome::enumerate_devices_with_port(SERIALOSC_PORT)?;
+        if let Err(e) = cache.save(&devices) {
+            warn!("Could not write {}: {}", cache.path.display(), e);
+        }
+        match devices.first() {
+            Some(device) => Monome::from_device(device, prefix),
+            None => Err(MonomeError::NoDevice),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_cache() {
+        let path = std::env::temp_dir().join(format!("monome-cache-{}", std::process::id()));
+        let cache = DeviceCache::new(&path);
+        assert!(cache.load().is_err());
+
+        let host: IpAddr = "fe80::1".parse().unwrap();
+        let mut remote = MonomeDevice::new("m2", "monome arc 4", 1001, 12002);
+        remote.host = host;
+        let devices = vec![MonomeDevice::new("m1", "monome 128", 1000, 12002), remote];
+        cache.save(&devices).unwrap();
+        let loaded = cache.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].name(), "m1");
+        assert_eq!(loaded[0].model(), "monome 128");
+        assert_eq!(loaded[0].port(), 1000);
+        assert_eq!(loaded[1].host(), host);
+        assert_eq!(loaded[1].serialosc_port(), 12002);
+
+        fs::write(&path, format!("{}\nm3\tmonome 64\n", HEADER)).unwrap();
+        assert!(cache.load().unwrap().is_empty());
+        fs::write(&path, "m1\tmonome 128\t127.0.0.1\t1000\t12002\n").unwrap();
+        assert!(cache.load().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! Delivery of the events of a device on channels, in addition to or instead of `poll()`.
+
+use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
+use log::*;
+
+use crate::MonomeEvent;
+
+/// The number of events each channel can hold before events are dropped.
+pub(crate) const CHANNEL_CAPACITY: usize = 32;
+
+/// Receivers for the events of a device, split by category, so that each category can be
+/// consumed on its own thread without being delayed by the others. Receivers can be cloned and
+/// sent to other threads.
+///
+/// # Example
+///
+/// Handle key presses and tilt events on two different threads:
+///
+/// ```no_run
+/// use std::thread;
+/// use monome::{Monome, MonomeEvent};
+/// let mut monome = Monome::new("/prefix").unwrap();
+/// monome.tilt_all(true);
+/// let channels = monome.event_channels();
+///
+/// let tilt = channels.tilt;
+/// thread::spawn(move || {
+///     for event in tilt.iter() {
+///         println!("{:?}", event);
+///     }
+/// });
+///
+/// for event in channels.keys.iter() {
+///     if let MonomeEvent::GridKey { x, y, .. } = event {
+///         monome.set(x, y, true);
+///     }
+/// }
+/// ```
+pub struct EventChannels {
+    /// Receives the `GridKey` events.
+    pub keys: Receiver<MonomeEvent>,
+    /// Receives the `Tilt` events.
+    pub tilt: Receiver<MonomeEvent>,
+    /// Receives the `EncoderDelta` and `EncoderKey` events.
+    pub encoders: Receiver<MonomeEvent>,
+}
+
+/// The sending side of `EventChannels`, used by the transport.
+pub(crate) struct EventSenders {
+    keys: Sender<MonomeEvent>,
+    tilt: Sender<MonomeEvent>,
+    encoders: Sender<MonomeEvent>,
+}
+
+impl EventSenders {
+    /// Create a set of channels, returning both ends.
+    pub fn new() -> (EventSenders, EventChannels) {
+        let (keys, keys_rx) = bounded(CHANNEL_CAPACITY);
+        let (tilt, tilt_rx) = bounded(CHANNEL_CAPACITY);
+        let (encoders, encoders_rx) = bounded(CHANNEL_CAPACITY);
+        (
+            EventSenders {
+                keys,
+                tilt,
+                encoders,
+            },
+            EventChannels {
+                keys: keys_rx,
+                tilt: tilt_rx,
+                encoders: encoders_rx,
+            },
+        )
+    }
+
+    /// Send an event on the channel for its category. Returns the event if it doesn't belong to
+    /// any category.
+    pub fn route(&self, event: MonomeEvent) -> Result<(), MonomeEvent> {
+        let sender = match event {
+            MonomeEvent::GridKey { .. } => &self.keys,
+            MonomeEvent::Tilt { .. } => &self.tilt,
+            MonomeEvent::EncoderDelta { .. } | MonomeEvent::EncoderKey { .. } => &self.encoders,
+            _ => return Err(event),
+        };
+        match sender.try_send(event) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                error!("event channel full, dropping {:?}", event);
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+        Ok(())
+    }
+}
+
+/// The subscribers to all the events of a device.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    senders: Vec<Sender<MonomeEvent>>,
+}
+
+impl Subscribers {
+    /// Add a subscriber, and return the channel on which it receives the events.
+    pub fn subscribe(&mut self) -> Receiver<MonomeEvent> {
+        let (sender, receiver) = bounded(CHANNEL_CAPACITY);
+        self.senders.push(sender);
+        receiver
+    }
+
+    /// Send a copy of an event to each subscriber. Subscribers that have dropped their receiver
+    /// are removed.
+    pub fn broadcast(&mut self, event: &MonomeEvent) {
+        self.senders
+            .retain(|sender| match sender.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(event)) => {
+                    error!("subscriber channel full, dropping {:?}", event);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
@@ -0,0 +1,99 @@
+//! The errors returned by this library.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use rosc::OscError;
+
+use crate::MonomeDeviceType;
+
+/// The ways talking to serialosc or to a device can fail.
+#[derive(Debug)]
+pub enum MonomeError {
+    /// No local socket could be bound or set up to talk to serialosc or to a device.
+    Bind(io::Error),
+    /// serialosc could not be reached, for example because it is not running, in which case
+    /// nothing answers the enumeration of the devices before it times out.
+    SerialoscUnreachable(io::Error),
+    /// The device could not be reached while setting it up.
+    DeviceUnreachable(io::Error),
+    /// No device has been found.
+    NoDevice,
+    /// The device has gone: its network input and output have stopped, for example because it
+    /// has been unplugged.
+    DeviceGone,
+    /// Too many messages are waiting to be sent to the device.
+    SendQueueFull,
+    /// A message could not be encoded to be sent.
+    OscEncode(OscError),
+    /// A thread enumerating the devices of a serialosc instance panicked.
+    EnumerationPanicked,
+    /// The leds passed to set all the leds of a grid are fewer than the leds of the grid.
+    InvalidLength {
+        /// The number of leds of the grid.
+        expected: usize,
+        /// The number of leds passed.
+        actual: usize,
+    },
+    /// The offsets or the row or column index passed to set leds of a grid are outside of the
+    /// grid, or offsets are not multiples of 8.
+    InvalidPosition {
+        /// The horizontal offset, or the column index.
+        x: i32,
+        /// The vertical offset, or the row index.
+        y: i32,
+    },
+    /// A method of a type of device has been called on a device of another type, e.g. a grid
+    /// method on an arc.
+    WrongDeviceType {
+        /// The type of device the method is for.
+        expected: MonomeDeviceType,
+        /// The type of the device.
+        actual: MonomeDeviceType,
+    },
+}
+
+impl fmt::Display for MonomeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MonomeError::Bind(ref e) => write!(f, "Could not set up socket: {}", e),
+            MonomeError::SerialoscUnreachable(ref e) => {
+                write!(f, "Could not communicate with serialosc: {}", e)
+            }
+            MonomeError::DeviceUnreachable(ref e) => {
+                write!(f, "Could not communicate with the device: {}", e)
+            }
+            MonomeError::NoDevice => write!(f, "No devices detected"),
+            MonomeError::DeviceGone => write!(f, "The device is disconnected"),
+            MonomeError::SendQueueFull => write!(f, "Too many messages waiting to be sent"),
+            MonomeError::OscEncode(ref e) => write!(f, "Could not encode message: {:?}", e),
+            MonomeError::EnumerationPanicked => write!(f, "Enumeration thread panicked"),
+            MonomeError::InvalidLength { expected, actual } => write!(
+                f,
+                "Expected {} leds, one for each led of the grid, got {}",
+                expected, actual
+            ),
+            MonomeError::InvalidPosition { x, y } => write!(
+                f,
+                "Invalid position {}, {}: offsets must be multiples of 8, within the grid",
+                x, y
+            ),
+            MonomeError::WrongDeviceType {
+                ref expected,
+                ref actual,
+            } => write!(f, "Called {} method on an {}", expected, actual),
+        }
+    }
+}
+
+impl Error for MonomeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            MonomeError::Bind(ref e)
+            | MonomeError::SerialoscUnreachable(ref e)
+            | MonomeError::DeviceUnreachable(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Hooks to simulate failures on a live device, so that applications can test how they handle
+//! them. They are only available with the `failure-injection` feature.
+
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use log::*;
+
+use crate::{Monome, Outgoing};
+
+impl Monome {
+    /// Simulate the removal of the device: its network input and output stop, as when it is
+    /// unplugged, and `run()` returns, unless automatic reconnection is enabled. The device
+    /// itself is left alone, and is only used again if it is reconnected.
+    pub fn inject_device_removal(&mut self) {
+        info!("Injected removal of {}", self.id);
+        self.tx = Outgoing::closed();
+        self.priority_tx = Outgoing::closed();
+        self.shared.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// Simulate a restart of serialosc: the device is removed, then announced again. If
+    /// automatic reconnection is enabled, it is set up again, as it would be after a real
+    /// restart, which requires serialosc to be running, and the events describing its state are
+    /// returned by `poll()` again. Otherwise the device stays unusable.
+    pub fn inject_serialosc_restart(&mut self) {
+        self.inject_device_removal();
+        if self.reconnector.is_none() {
+            return;
+        }
+        let serialosc = self.serialosc;
+        let id = self.id.clone();
+        let q = self.q.clone();
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            if let Err(e) = Monome::reconnect(serialosc, &id, &q, &shared) {
+                error!("Could not reconnect to {}: {}", id, e);
+            }
+        });
+    }
+
+    /// Simulate an overflow of the event queue: the next `events` events received from the
+    /// device are dropped, as if the application was not polling fast enough.
+    pub fn inject_queue_overflow(&mut self, events: usize) {
+        self.shared.overflowing.store(events, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_grid;
+    use crate::{build_osc_message, local, KeyDirection, MonomeEvent, Transport};
+    use rosc::encoder::encode;
+    use rosc::OscType;
+
+    #[test]
+    fn queue_overflow() {
+        let (mut monome, _sent) = test_grid();
+        let transport = Transport::new(local(1), monome.q.clone(), monome.shared.clone());
+        let key = |x| {
+            let key = build_osc_message(
+                "/plop/grid/key",
+                vec![OscType::Int(x), OscType::Int(0), OscType::Int(1)],
+            );
+            encode(&key).unwrap()
+        };
+
+        monome.inject_queue_overflow(2);
+        for x in 0..3 {
+            transport.dispatch(&key(x));
+        }
+        let keys: Vec<_> = monome
+            .poll_all()
+            .into_iter()
+            .filter(|event| matches!(event, MonomeEvent::GridKey { .. }))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![MonomeEvent::GridKey {
+                x: 2,
+                y: 0,
+                direction: KeyDirection::Down
+            }]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Submission of frames of led intensities built on other threads.
+
+use std::sync::Arc;
+
+use crossbeam::queue::ArrayQueue;
+
+use monome_core::GridFrame;
+
+/// A lock-free queue to submit frames built on other threads to the thread that talks to the
+/// device. Cloning a `FrameQueue` gives another handle to the same queue.
+///
+/// # Example
+///
+/// Compute frames on a worker thread, and draw the most recent one at 30 frames per second:
+///
+/// ```no_run
+/// use std::{thread, time};
+/// use monome::{FrameQueue, GridFrame, Monome};
+///
+/// let mut monome = Monome::new("/prefix").unwrap();
+/// let queue = FrameQueue::new(4);
+/// let submitter = queue.clone();
+/// let (width, height) = (monome.width(), monome.height());
+///
+/// thread::spawn(move || {
+///     let mut i = 0;
+///     loop {
+///         let mut frame = GridFrame::new(width, height);
+///         frame.set(i % width, 0, 15);
+///         submitter.submit(frame);
+///         i += 1;
+///     }
+/// });
+///
+/// loop {
+///     if let Some(frame) = queue.take_latest() {
+///         monome.draw_frame(&frame);
+///     }
+///     thread::sleep(time::Duration::from_millis(33));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FrameQueue {
+    /// The frames that have been submitted and not taken yet.
+    queue: Arc<ArrayQueue<GridFrame>>,
+}
+
+impl FrameQueue {
+    /// Create a queue that holds at most `capacity` frames. When it is full, the oldest frame is
+    /// dropped to make room for new ones.
+    pub fn new(capacity: usize) -> FrameQueue {
+        FrameQueue {
+            queue: Arc::new(ArrayQueue::new(capacity.max(1))),
+        }
+    }
+
+    /// Submit a complete frame.
+    pub fn submit(&self, mut frame: GridFrame) {
+        loop {
+            match self.queue.push(frame) {
+                Ok(()) => return,
+                Err(crossbeam::queue::PushError(f)) => {
+                    frame = f;
+                    let _ = self.queue.pop();
+                }
+            }
+        }
+    }
+
+    /// Take the most recently submitted frame, dropping all the older ones. Returns `None` if no
+    /// frame has been submitted since the last call.
+    pub fn take_latest(&self) -> Option<GridFrame> {
+        let mut latest = None;
+        while let Ok(frame) = self.queue.pop() {
+            latest = Some(frame);
+        }
+        latest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_frame() {
+        let queue = FrameQueue::new(2);
+        assert_eq!(queue.take_latest(), None);
+        for i in 0..5 {
+            let mut frame = GridFrame::new(8, 8);
+            frame.set(i, 0, 15);
+            queue.submit(frame);
+        }
+        let latest = queue.take_latest().unwrap();
+        assert_eq!(latest.get(4, 0), Some(15));
+        assert_eq!(latest.get(3, 0), Some(0));
+        assert_eq!(queue.take_latest(), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! A frame buffer that only sends the quads that changed to the device.
+
+use std::time::Duration;
+
+use log::*;
+use rosc::OscType;
+
+use crate::{Animator, Monome, MonomeDeviceType};
+
+/// A grid of led intensities, drawn locally and sent to a device with `flush()`. The buffer
+/// tracks which 8x8 quads have changed since the last flush, and only sends those, with one
+/// `/grid/led/level/map` message each.
+///
+/// Unlike `Monome::set_all_intensity`, the intensities are sent as is: the scaling and idle
+/// dimming of the device are not applied.
+///
+/// # Example
+///
+/// Move a led across the first row, sending a single quad when it stays in the same quad:
+///
+/// ```no_run
+/// use monome::{GridFrameBuffer, Monome};
+/// let mut monome = Monome::new("/prefix").unwrap();
+/// let mut buffer = GridFrameBuffer::for_device(&monome);
+/// for x in 0..monome.width() {
+///     buffer.fill(0);
+///     buffer.set(x, 0, 15);
+///     buffer.flush(&mut monome);
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GridFrameBuffer {
+    /// The width of the buffer, in leds.
+    width: usize,
+    /// The height of the buffer, in leds.
+    height: usize,
+    /// The intensities of the leds, packed in row order.
+    leds: Vec<u8>,
+    /// For each quad, in row order, whether it has changed since the last flush.
+    dirty: Vec<bool>,
+}
+
+impl GridFrameBuffer {
+    /// Create a buffer of `width` by `height` leds, all off. All the quads are sent on the first
+    /// flush.
+    pub fn new(width: usize, height: usize) -> GridFrameBuffer {
+        let quads = width.div_ceil(8) * height.div_ceil(8);
+        GridFrameBuffer {
+            width,
+            height,
+            leds: vec![0; width * height],
+            dirty: vec![true; quads],
+        }
+    }
+
+    /// Create a buffer of the size of `monome`.
+    pub fn for_device(monome: &Monome) -> GridFrameBuffer {
+        GridFrameBuffer::new(monome.width(), monome.height())
+    }
+
+    /// Get the width of this buffer.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of this buffer.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the intensity of the led at `x`, `y`, or `None` if it is outside of the buffer.
+    pub fn get(&self, x: usize, y: usize) -> Option<u8> {
+        if x < self.width && y < self.height {
+            Some(self.leds[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Set the intensity of the led at `x`, `y`, between 0 and 15. This does nothing if it is
+    /// outside of the buffer.
+    pub fn set(&mut self, x: usize, y: usize, intensity: u8) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let led = &mut self.leds[y * self.width + x];
+        if *led != intensity {
+            *led = intensity;
+            let quad = self.quad_index(x, y);
+            self.dirty[quad] = true;
+        }
+    }
+
+    /// Set all the leds of this buffer to `intensity`.
+    pub fn fill(&mut self, intensity: u8) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.set(x, y, intensity);
+            }
+        }
+    }
+
+    /// Get the intensities of the leds of this buffer, packed in row order.
+    pub fn leds(&self) -> &[u8] {
+        &self.leds
+    }
+
+    /// Returns true if the quad whose top left led is at `x_offset`, `y_offset` has changed
+    /// since the last flush.
+    pub fn is_dirty(&self, x_offset: usize, y_offset: usize) -> bool {
+        x_offset < self.width
+            && y_offset < self.height
+            && self.dirty[self.quad_index(x_offset, y_offset)]
+    }
+
+    /// Mark all the quads as changed, so that they are all sent on the next flush, e.g. after
+    /// the leds of the device have been changed by other means.
+    pub fn invalidate(&mut self) {
+        for dirty in self.dirty.iter_mut() {
+            *dirty = true;
+        }
+    }
+
+    /// Send the quads that changed since the last flush to `monome`. Leds of a quad that are
+    /// outside of the buffer, on grids whose size is not a multiple of 8, are sent off. A quad
+    /// whose message could not be queued is sent again on the next flush. Returns the number of
+    /// quads that have been sent.
+    pub fn flush(&mut self, monome: &mut Monome) -> usize {
+        if monome.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
+            return 0;
+        }
+        let quads_wide = self.width.div_ceil(8);
+        let mut sent = 0;
+        for (quad, dirty) in self.dirty.iter_mut().enumerate() {
+            if !*dirty {
+                continue;
+            }
+            let x_offset = (quad % quads_wide) * 8;
+            let y_offset = (quad / quads_wide) * 8;
+            let mut args = Vec::with_capacity(66);
+            args.push(OscType::Int(x_offset as i32));
+            args.push(OscType::Int(y_offset as i32));
+            for y in y_offset..y_offset + 8 {
+                for x in x_offset..x_offset + 8 {
+                    let intensity = if x < self.width && y < self.height {
+                        self.leds[y * self.width + x]
+                    } else {
+                        0
+                    };
+                    args.push(OscType::Int(i32::from(intensity)));
+                }
+            }
+            if monome.send("/grid/led/level/map", args) {
+                *dirty = false;
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    /// Advance the tweens of `animator` by `dt`, draw its animated leds on this buffer, and send
+    /// the quads that changed to `monome`. This is meant to be called once per frame. Returns
+    /// the number of quads that have been sent.
+    ///
+    /// # Example
+    ///
+    /// Fade the first row in, at 30 frames per second:
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use monome::{Animator, Easing, GridFrameBuffer, Monome, Tween};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut buffer = GridFrameBuffer::for_device(&monome);
+    /// let mut animator = Animator::new();
+    /// for x in 0..monome.width() {
+    ///     let duration = Duration::from_millis(100 * x as u64);
+    ///     animator.animate_led(x, 0, Tween::new(0., 15., duration, Easing::EaseIn));
+    /// }
+    /// let frame = Duration::from_millis(33);
+    /// while !animator.is_idle() {
+    ///     buffer.flush_animated(&mut animator, frame, &mut monome);
+    ///     std::thread::sleep(frame);
+    /// }
+    /// ```
+    pub fn flush_animated(
+        &mut self,
+        animator: &mut Animator,
+        dt: Duration,
+        monome: &mut Monome,
+    ) -> usize {
+        animator.tick(dt);
+        animator.draw_with(|x, y, intensity| self.set(x, y, intensity));
+        self.flush(monome)
+    }
+
+    /// Returns the index of the quad that contains the led at `x`, `y`.
+    fn quad_index(&self, x: usize, y: usize) -> usize {
+        (y / 8) * self.width.div_ceil(8) + x / 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_grid;
+    use crate::{Easing, Tween};
+    use rosc::decoder::decode;
+    use rosc::OscPacket;
+    use std::time::Instant;
+
+    #[test]
+    fn flush_dirty_quads() {
+        let (mut monome, sent) = test_grid();
+        let offsets = || -> Vec<(i32, i32)> {
+            sent.try_iter()
+                .map(
+                    |(_, bytes): (Instant, Vec<u8>)| match decode(&bytes).unwrap() {
+                        OscPacket::Message(message) => {
+                            assert_eq!(message.addr, "/plop/grid/led/level/map");
+                            match message.args.unwrap()[..2] {
+                                [OscType::Int(x), OscType::Int(y)] => (x, y),
+                                ref args => panic!("unexpected arguments {:?}", args),
+                            }
+                        }
+                        packet => panic!("unexpected packet {:?}", packet),
+                    },
+                )
+                .collect()
+        };
+
+        let mut buffer = GridFrameBuffer::for_device(&monome);
+        assert_eq!(buffer.flush(&mut monome), 2);
+        assert_eq!(offsets(), vec![(0, 0), (8, 0)]);
+        assert_eq!(buffer.flush(&mut monome), 0);
+
+        buffer.set(9, 3, 15);
+        buffer.set(0, 0, 0);
+        assert!(buffer.is_dirty(8, 0));
+        assert!(!buffer.is_dirty(0, 0));
+        assert_eq!(buffer.flush(&mut monome), 1);
+        assert_eq!(offsets(), vec![(8, 0)]);
+        assert_eq!(buffer.get(9, 3), Some(15));
+
+        buffer.invalidate();
+        assert_eq!(buffer.flush(&mut monome), 2);
+        assert_eq!(offsets().len(), 2);
+
+        let mut animator = Animator::new();
+        let duration = Duration::from_millis(100);
+        animator.animate_led(12, 7, Tween::new(0., 10., duration, Easing::Linear));
+        assert_eq!(
+            buffer.flush_animated(&mut animator, duration / 2, &mut monome),
+            1
+        );
+        assert_eq!(offsets(), vec![(8, 0)]);
+        assert_eq!(buffer.get(12, 7), Some(5));
+    }
+
+    #[test]
+    fn partial_quads() {
+        let mut buffer = GridFrameBuffer::new(12, 10);
+        buffer.set(11, 9, 4);
+        buffer.set(12, 9, 4);
+        assert_eq!(buffer.get(11, 9), Some(4));
+        assert_eq!(buffer.get(12, 9), None);
+        assert_eq!(buffer.dirty.len(), 4);
+        assert_eq!(buffer.quad_index(11, 9), 3);
+    }
+}
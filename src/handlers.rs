@@ -0,0 +1,160 @@
+//! Functions called for each kind of event, registered on a `Monome`, for applications that
+//! prefer callbacks to matching on `MonomeEvent`.
+
+use std::mem;
+
+use crate::{KeyDirection, Monome, MonomeEvent};
+
+/// A function called with a grid key press or release.
+type KeyHandler = Box<dyn FnMut(&mut Monome, i32, i32, KeyDirection) + Send>;
+/// A function called with a tilt update.
+type TiltHandler = Box<dyn FnMut(&mut Monome, i32, i32, i32, i32) + Send>;
+/// A function called with an encoder delta.
+type EncoderDeltaHandler = Box<dyn FnMut(&mut Monome, usize, i32) + Send>;
+/// A function called with an encoder key press or release.
+type EncoderKeyHandler = Box<dyn FnMut(&mut Monome, usize, KeyDirection) + Send>;
+
+/// The functions registered for each kind of event, in the order of their registration.
+#[derive(Default)]
+pub(crate) struct Handlers {
+    keys: Vec<KeyHandler>,
+    tilt: Vec<TiltHandler>,
+    encoder_deltas: Vec<EncoderDeltaHandler>,
+    encoder_keys: Vec<EncoderKeyHandler>,
+    /// Whether the functions have been removed while the functions of an event were being
+    /// called.
+    cleared: bool,
+}
+
+impl Handlers {
+    /// Add the functions of `other` after these ones.
+    fn extend(&mut self, other: Handlers) {
+        self.keys.extend(other.keys);
+        self.tilt.extend(other.tilt);
+        self.encoder_deltas.extend(other.encoder_deltas);
+        self.encoder_keys.extend(other.encoder_keys);
+    }
+}
+
+impl Monome {
+    /// Register a function called with the position and direction of each grid key press or
+    /// release dispatched, by `run()` or `dispatch()`.
+    ///
+    /// # Example
+    ///
+    /// Light up the keys while they are held down:
+    ///
+    /// ```no_run
+    /// use monome::{KeyDirection, Monome};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.on_key(|monome, x, y, direction| {
+    ///     monome.set(x, y, direction == KeyDirection::Down);
+    /// });
+    /// monome.run(|_, _| {});
+    /// ```
+    pub fn on_key<F>(&mut self, handler: F)
+    where
+        F: FnMut(&mut Monome, i32, i32, KeyDirection) + Send + 'static,
+    {
+        self.handlers.keys.push(Box::new(handler));
+    }
+
+    /// Register a function called with the sensor, and the pitch, roll and yaw of each tilt
+    /// update dispatched, by `run()` or `dispatch()`.
+    pub fn on_tilt<F>(&mut self, handler: F)
+    where
+        F: FnMut(&mut Monome, i32, i32, i32, i32) + Send + 'static,
+    {
+        self.handlers.tilt.push(Box::new(handler));
+    }
+
+    /// Register a function called with the encoder and the delta of each encoder movement
+    /// dispatched, by `run()` or `dispatch()`.
+    ///
+    /// # Example
+    ///
+    /// Show the position of each encoder on its ring:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut positions = [0; 4];
+    /// monome.on_encoder_delta(move |monome, n, delta| {
+    ///     positions[n] = (positions[n] + delta).rem_euclid(64);
+    ///     monome.ring_all(n, 0);
+    ///     monome.ring_set(n, positions[n] as u32, 15);
+    /// });
+    /// monome.run(|_, _| {});
+    /// ```
+    pub fn on_encoder_delta<F>(&mut self, handler: F)
+    where
+        F: FnMut(&mut Monome, usize, i32) + Send + 'static,
+    {
+        self.handlers.encoder_deltas.push(Box::new(handler));
+    }
+
+    /// Register a function called with the encoder and the direction of each encoder key press
+    /// or release dispatched, by `run()` or `dispatch()`.
+    pub fn on_encoder_key<F>(&mut self, handler: F)
+    where
+        F: FnMut(&mut Monome, usize, KeyDirection) + Send + 'static,
+    {
+        self.handlers.encoder_keys.push(Box::new(handler));
+    }
+
+    /// Remove all the functions registered with `on_key()`, `on_tilt()`, `on_encoder_delta()`
+    /// and `on_encoder_key()`. When called from one of them, the functions of the current event
+    /// that have not been called yet still are.
+    pub fn clear_handlers(&mut self) {
+        self.handlers = Handlers {
+            cleared: true,
+            ..Handlers::default()
+        };
+    }
+
+    /// Call the functions registered for the kind of `event`, in the order of their
+    /// registration, e.g. with the events returned by `poll()`. `run()` does this for each
+    /// event. Returns true if at least one function has been called.
+    ///
+    /// The functions registered while the functions of an event are being called are only
+    /// called from the next event on.
+    pub fn dispatch(&mut self, event: &MonomeEvent) -> bool {
+        let mut handlers = mem::take(&mut self.handlers);
+        handlers.cleared = false;
+        let called = match *event {
+            MonomeEvent::GridKey { x, y, direction } => {
+                for handler in handlers.keys.iter_mut() {
+                    handler(self, x, y, direction);
+                }
+                !handlers.keys.is_empty()
+            }
+            MonomeEvent::Tilt { n, x, y, z } => {
+                for handler in handlers.tilt.iter_mut() {
+                    handler(self, n, x, y, z);
+                }
+                !handlers.tilt.is_empty()
+            }
+            MonomeEvent::EncoderDelta { n, delta } => {
+                for handler in handlers.encoder_deltas.iter_mut() {
+                    handler(self, n, delta);
+                }
+                !handlers.encoder_deltas.is_empty()
+            }
+            MonomeEvent::EncoderKey { n, direction } => {
+                for handler in handlers.encoder_keys.iter_mut() {
+                    handler(self, n, direction);
+                }
+                !handlers.encoder_keys.is_empty()
+            }
+            _ => false,
+        };
+        let registered = mem::replace(&mut self.handlers, handlers);
+        if registered.cleared {
+            self.handlers = registered;
+            self.handlers.cleared = false;
+        } else {
+            self.handlers.extend(registered);
+        }
+        called
+    }
+}
@@ -0,0 +1,165 @@
+//! Measurement of the time spent by messages inside this library.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The number of buckets of a `LatencyHistogram`. The last bucket holds all the durations longer
+/// than about one second.
+const BUCKET_COUNT: usize = 21;
+
+/// A histogram of durations, with buckets whose upper bound doubles each time, starting at one
+/// microsecond.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    /// The number of durations in each bucket.
+    buckets: [u64; BUCKET_COUNT],
+    /// The number of durations recorded.
+    count: u64,
+    /// The sum of all the durations recorded.
+    total: Duration,
+    /// The longest duration recorded.
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    /// Record a duration in this histogram.
+    pub fn record(&mut self, duration: Duration) {
+        let micros = duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros());
+        let mut bucket = 0;
+        while bucket < BUCKET_COUNT - 1 && micros >= (1 << bucket) {
+            bucket += 1;
+        }
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Get the number of durations recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Get the mean of the durations recorded.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::from_secs(0);
+        }
+        self.total / self.count as u32
+    }
+
+    /// Get the longest duration recorded.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Get an upper bound for the duration under which `percentile` percent of the durations
+    /// have been recorded.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let target = (self.count as f64 * percentile / 100.).ceil() as u64;
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target && seen > 0 {
+                return Duration::from_micros(1 << i).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Get the buckets of this histogram, as a list of upper bound and number of durations
+    /// recorded below this bound.
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (Duration::from_micros(1 << i), *count))
+            .collect()
+    }
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "count: {}, mean: {:?}, p50: {:?}, p99: {:?}, max: {:?}",
+            self.count,
+            self.mean(),
+            self.percentile(50.),
+            self.percentile(99.),
+            self.max
+        )
+    }
+}
+
+/// The latencies measured inside this library for a device.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyReport {
+    /// The time between a call that sends a message to a device (e.g. `set()`) and the moment
+    /// the message is sent on the socket.
+    pub send: LatencyHistogram,
+    /// The time between the moment a message is received on the socket and the moment it is
+    /// returned by `poll()`.
+    pub receive: LatencyHistogram,
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "send: {}\nreceive: {}", self.send, self.receive)
+    }
+}
+
+/// Latency measurements shared between a `Monome` and its transport.
+#[derive(Default)]
+pub(crate) struct LatencyTracker {
+    /// Whether latencies are being recorded.
+    enabled: AtomicBool,
+    /// The latencies recorded so far.
+    report: Mutex<LatencyReport>,
+}
+
+impl LatencyTracker {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn record_send(&self, duration: Duration) {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.report.lock().unwrap().send.record(duration);
+        }
+    }
+
+    pub fn record_receive(&self, duration: Duration) {
+        if self.enabled.load(Ordering::Relaxed) {
+            self.report.lock().unwrap().receive.record(duration);
+        }
+    }
+
+    pub fn report(&self) -> LatencyReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.report.lock().unwrap() = LatencyReport::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = LatencyHistogram::default();
+        for i in 1..=100 {
+            histogram.record(Duration::from_micros(i * 10));
+        }
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.max(), Duration::from_micros(1000));
+        assert_eq!(histogram.mean(), Duration::from_micros(505));
+        assert_eq!(histogram.percentile(50.), Duration::from_micros(512));
+        assert_eq!(histogram.percentile(100.), Duration::from_micros(1000));
+    }
+}
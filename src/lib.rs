@@ -2,19 +2,14 @@
 
 //! Use monome devices (Grid or Arc) in rust.
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
-use std::sync::Arc;
-
-use futures::future::Either;
-use tokio::net::UdpSocket;
-use tokio::prelude::*;
-use tokio::timer::Delay;
-
-use futures::sync::mpsc::{Sender, Receiver};
+use std::time::{Duration, Instant, SystemTime};
 
 use rosc::decoder::decode;
 use rosc::encoder::encode;
@@ -22,9 +17,75 @@ use rosc::{OscMessage, OscPacket, OscType};
 
 use crossbeam::queue::ArrayQueue;
 
-use futures::*;
 use log::*;
 
+mod arcframebuffer;
+mod arcs;
+mod batch;
+mod cache;
+mod channels;
+mod error;
+#[cfg(feature = "failure-injection")]
+mod failure;
+mod frame;
+mod framebuffer;
+mod handlers;
+mod latency;
+mod logical;
+mod manager;
+mod mono;
+mod output;
+#[cfg(unix)]
+mod readiness;
+mod renderer;
+#[cfg(feature = "async")]
+mod runtime;
+mod shadow;
+mod startup;
+#[cfg(feature = "async")]
+mod stream;
+
+pub use crate::arcframebuffer::ArcFrameBuffer;
+pub use crate::arcs::ArcSurface;
+pub use crate::cache::DeviceCache;
+pub use crate::channels::EventChannels;
+pub use crate::error::MonomeError;
+pub use crate::frame::FrameQueue;
+pub use crate::framebuffer::GridFrameBuffer;
+pub use crate::latency::{LatencyHistogram, LatencyReport};
+pub use crate::manager::{MonitoredMessage, MonomeManager};
+pub use crate::output::MonomeOutput;
+#[cfg(unix)]
+pub use crate::readiness::EventReadiness;
+pub use crate::renderer::GridRenderer;
+pub use crate::startup::{DevicePreference, StartupOptions};
+#[cfg(feature = "async")]
+pub use crate::stream::EventStream;
+
+pub use monome_core::{
+    blit, full_region, plan_quad_update, plan_ring_update, region_local, ring_angle_to_led,
+    ring_arc_leds, ring_led_to_angle, rotate_coordinates, split_cells, split_columns, split_rows,
+    system_time_to_timetag, timetag_to_system_time, ActionBinding, ActionMap, Animator,
+    BlinkScheduler, BrightnessCurve, Capabilities, ChordDetector, Compositor, ControlChannel,
+    ControlEvent, ControlMessage, DecayBuffer, DetentFeedback, DetentedEncoder, Easing,
+    EncoderGesture, EncoderGestureDetector, EventDeduplicator, EventFilter, EventHistory,
+    EventQuantizer, FrameLogReader, FrameLogWriter, GridFrame, HoldProgress, IdleDetector,
+    IdleDimmer, IdleEvent, IntensityScaling, IntoAddrAndArgs, KeyDirection, KeyGesture,
+    KeyOrientation, KeyStateTracker, LinkConditions, LinkSimulator, LongPressDetector, Looping,
+    Menu, MenuEvent, MonomeDeviceType, MonomeEvent, ParamEditor, PushTurnEvent, PushTurnTracker,
+    QuadUpdate, RateLimiter, RegionLocks, RingDirection, RingLayout, RingMirror, RingUpdate, Scale,
+    Sprite, Tween, VirtualEncoder, RING_LEDS,
+};
+
+use monome_core::{build_osc_message, parse, toidx};
+
+use crate::channels::{EventSenders, Subscribers};
+use crate::handlers::Handlers;
+use crate::latency::LatencyTracker;
+use crate::logical::LedOrientation;
+use crate::shadow::LedShadow;
+use crate::startup::choose_device;
+
 /// The default port at which serialosc is running.
 pub const SERIALOSC_PORT: i32 = 12002;
 
@@ -35,31 +96,155 @@ const START_PORT: i32 = 10_000;
 /// library considers all the devices to have been received.
 const DEVICE_ENUMERATION_TIMEOUT_MS: u64 = 500;
 
-/// From a x and y position, and a stride, returns the offset at which the element is in an array.
-fn toidx(x: i32, y: i32, width: i32) -> usize {
-    (y * width + x) as usize
+/// The number of milliseconds to wait for a device to answer while setting it up, by default.
+const SETUP_TIMEOUT_MS: u64 = 2000;
+
+/// The minimum number of milliseconds between two batches of events handled by `Monome::run`,
+/// so that an application redraws at most about 60 times per second.
+const RUN_FRAME_PERIOD_MS: u64 = 16;
+
+/// The number of messages that can wait to be sent to a device. Messages sent when it is full are
+/// dropped.
+const OUTGOING_CAPACITY: usize = 16;
+
+/// The address of the local host, on which serialosc usually runs.
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Returns the address of `port` on the local host.
+fn local(port: i32) -> SocketAddr {
+    SocketAddr::new(LOCALHOST, port as u16)
+}
+
+/// Returns the messages that light `leds`, sorted by led, on the encoder `n`: a `/ring/range`
+/// message for each run of consecutive leds at the same intensity, that doesn't wrap around the
+/// top of the ring, and a `/ring/set` message for each led alone.
+fn ring_arc_messages(n: usize, leds: &[(usize, u8)]) -> Vec<(&'static str, Vec<OscType>)> {
+    let mut messages = Vec::new();
+    let mut i = 0;
+    while i < leds.len() {
+        let (start, intensity) = leds[i];
+        let mut end = start;
+        while leds.get(i + 1) == Some(&(end + 1, intensity)) {
+            end += 1;
+            i += 1;
+        }
+        i += 1;
+        let int = |value: usize| OscType::Int(value as i32);
+        if start == end {
+            messages.push(("/ring/set", vec![int(n), int(start), int(intensity.into())]));
+        } else {
+            messages.push((
+                "/ring/range",
+                vec![int(n), int(start), int(end), int(intensity.into())],
+            ));
+        }
+    }
+    messages
+}
+
+/// Returns the arguments of a `/ring/map` message for the encoder `n`, from the address fragment
+/// and the values converted by `IntoAddrAndArgs`: intensities, or masks of 8 leds each, the first
+/// led being the lowest bit, whose leds are at full intensity when on. The leds past the last
+/// mask are off.
+fn ring_map_args(n: usize, frag: &str, values: Vec<OscType>) -> Vec<OscType> {
+    let mut args = Vec::with_capacity(1 + RING_LEDS);
+    args.push(OscType::Int(n as i32));
+    if frag == "level/" {
+        args.extend(values);
+    } else {
+        let on = |led: usize| match values.get(led / 8) {
+            Some(&OscType::Int(mask)) => (mask >> (led % 8)) & 1 == 1,
+            _ => false,
+        };
+        args.extend((0..RING_LEDS).map(|led| OscType::Int(if on(led) { 15 } else { 0 })));
+    }
+    args
+}
+
+/// Replaces the intensities of the leds in the arguments `args` of a message sent to `addr`, if
+/// it sets leds to intensities, by the levels to send for them with `curve`.
+fn apply_brightness_curve(curve: &BrightnessCurve, addr: &str, args: &mut [OscType]) {
+    // The index of the first intensity in the arguments, after the coordinates.
+    let first = [
+        ("/grid/led/level/set", 2),
+        ("/grid/led/level/all", 0),
+        ("/grid/led/level/map", 2),
+        ("/grid/led/level/row", 2),
+        ("/grid/led/level/col", 2),
+        ("/ring/set", 2),
+        ("/ring/all", 1),
+        ("/ring/map", 1),
+        ("/ring/range", 3),
+    ]
+    .iter()
+    .find(|(suffix, _)| addr.ends_with(suffix))
+    .map(|&(_, first)| first);
+    if let Some(first) = first {
+        for arg in args.iter_mut().skip(first) {
+            if let OscType::Int(ref mut intensity) = *arg {
+                *intensity = i32::from(curve.map((*intensity).clamp(0, 15) as u8));
+            }
+        }
+    }
+}
+
+/// Returns the address on which to bind a socket talking to `peer`, of the same family: the
+/// local host if `peer` is on the local host, all the interfaces otherwise, so that a remote host
+/// can reach it.
+fn bind_ip(peer: IpAddr) -> IpAddr {
+    match peer {
+        IpAddr::V4(ip) if ip.is_loopback() => LOCALHOST,
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(ip) if ip.is_loopback() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+/// Returns the address at which `peer` can reach `socket`, to tell serialosc or a device where
+/// to send its messages. When the socket is bound to all the interfaces, this is the address of
+/// the interface used to reach `peer`.
+fn reply_addr(socket: &std::net::UdpSocket, peer: SocketAddr) -> Result<SocketAddr, MonomeError> {
+    let local_addr = socket.local_addr().map_err(MonomeError::Bind)?;
+    if !local_addr.ip().is_unspecified() {
+        return Ok(local_addr);
+    }
+    // Connecting a UDP socket doesn't send anything, but picks the route to `peer`.
+    let probe = std::net::UdpSocket::bind(SocketAddr::new(local_addr.ip(), 0))
+        .map_err(MonomeError::Bind)?;
+    probe
+        .connect(peer)
+        .map_err(MonomeError::SerialoscUnreachable)?;
+    let ip = probe.local_addr().map_err(MonomeError::Bind)?.ip();
+    Ok(SocketAddr::new(ip, local_addr.port()))
 }
 
-/// Returns an osc packet from a address and arguments
-fn build_osc_message(addr: &str, args: Vec<OscType>) -> OscPacket {
-    let message = OscMessage {
-        addr: addr.to_owned(),
-        args: Some(args),
+/// Converts the timetag of a bundle to an instant, using the time at which it has been received,
+/// `received_at`, as a reference. Returns `received_at` if the bundle has no timetag.
+fn timetag_to_instant(timetag: &OscType, received_at: Instant) -> Instant {
+    let sent_at = match timetag_to_system_time(timetag) {
+        Some(sent_at) => sent_at,
+        None => return received_at,
     };
-    OscPacket::Message(message)
+    let now = SystemTime::now();
+    match sent_at.duration_since(now) {
+        Ok(ahead) => received_at + ahead,
+        Err(behind) => received_at
+            .checked_sub(behind.duration())
+            .unwrap_or(received_at),
+    }
 }
 
-fn new_bound_socket() -> UdpSocket {
+/// Binds a socket to talk to `peer`, on the first free port starting at `START_PORT`.
+fn new_bound_socket(peer: IpAddr) -> Result<std::net::UdpSocket, MonomeError> {
+    let ip = bind_ip(peer);
     let mut port = START_PORT;
     loop {
-        let server_addr = format!("127.0.0.1:{}", port).parse().unwrap();
-        let bind_result = UdpSocket::bind(&server_addr);
-        match bind_result {
-            Ok(socket) => break socket,
+        match std::net::UdpSocket::bind(SocketAddr::new(ip, port as u16)) {
+            Ok(socket) => break Ok(socket),
             Err(e) => {
                 warn!("bind error: {}", e.to_string());
-                if port > 65535 {
-                    panic!("Could not bind socket: port exhausted");
+                if port >= 65535 {
+                    break Err(MonomeError::Bind(e));
                 }
             }
         }
@@ -67,6 +252,176 @@ fn new_bound_socket() -> UdpSocket {
     }
 }
 
+/// Binds a socket on which to listen to the device changes reported by serialosc on `host`,
+/// that wakes up regularly to notice when the listener is stopped.
+fn new_listener_socket(host: IpAddr) -> Result<std::net::UdpSocket, MonomeError> {
+    let socket = new_bound_socket(host)?;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .map_err(MonomeError::Bind)?;
+    Ok(socket)
+}
+
+/// Asks the serialosc instance running at `addr` to notify `socket` of the devices added and
+/// removed, and calls `callback` for each of them, until `stop` is set.
+fn listen_device_changes<F>(
+    socket: std::net::UdpSocket,
+    addr: SocketAddr,
+    stop: &AtomicBool,
+    mut callback: F,
+) where
+    F: FnMut(DeviceChangeEvent),
+{
+    let server_addr = match reply_addr(&socket, addr) {
+        Ok(server_addr) => server_addr,
+        Err(e) => {
+            error!("Could not listen to device changes: {}", e);
+            return;
+        }
+    };
+    let packet = build_osc_message(
+        "/serialosc/notify",
+        vec![
+            OscType::String(server_addr.ip().to_string()),
+            OscType::Int(i32::from(server_addr.port())),
+        ],
+    );
+    let bytes = match encode(&packet) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Could not encode {:?}: {:?}", packet, e);
+            return;
+        }
+    };
+    // True if we've received a add or remove message from serialosc recently, and we need
+    // to tell it to notify this program in the future.
+    // This is necessary, because other messages can be received on this socket, notably the
+    // undocumented /sys/connect and /sys/disconnect messages (without any arguments).
+    let mut need_notify_msg = true;
+    let mut buf = vec![0u8; 1024];
+    while !stop.load(Ordering::SeqCst) {
+        if need_notify_msg {
+            if let Err(e) = socket.send_to(&bytes, addr) {
+                error!("Could not send to serialosc: {}", e);
+                break;
+            }
+            need_notify_msg = false;
+        }
+
+        let amt = match socket.recv_from(&mut buf) {
+            Ok((amt, _)) => amt,
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(e) => {
+                error!("Could not receive from serialosc: {}", e);
+                break;
+            }
+        };
+        match decode(&buf[..amt]) {
+            Ok(OscPacket::Message(message)) => {
+                if let Some(ref args) = message.args {
+                    if message.addr.starts_with("/serialosc/add") {
+                        need_notify_msg = true;
+                        if let Some(OscType::String(ref id)) = args.first() {
+                            callback(DeviceChangeEvent::Added(id.to_string()));
+                        }
+                    } else if message.addr.starts_with("/serialosc/remove") {
+                        if let Some(OscType::String(ref id)) = args.first() {
+                            need_notify_msg = true;
+                            callback(DeviceChangeEvent::Removed(id.to_string()));
+                        }
+                    } else {
+                        debug!(
+                            "⇦ Unexpected message receive on device change event socket {:?}",
+                            message
+                        );
+                    }
+                }
+            }
+            _ => {
+                debug!("⇦ Could not decode {:?}", &buf[..amt]);
+            }
+        }
+    }
+}
+
+/// A thread listening to device changes: its id, the flag that tells it to stop, and the
+/// thread.
+type DeviceChangeListener = (usize, Arc<AtomicBool>, thread::JoinHandle<()>);
+
+/// The threads listening to device changes.
+static DEVICE_CHANGE_LISTENERS: Mutex<Vec<DeviceChangeListener>> = Mutex::new(Vec::new());
+
+/// The id of the next thread listening to device changes.
+static NEXT_DEVICE_CHANGE_LISTENER: AtomicUsize = AtomicUsize::new(0);
+
+/// Adds a thread listening to device changes, and the flag that tells it to stop, to the
+/// listeners stopped by `stop_device_change_listeners`. Returns its id.
+fn register_device_change_listener(
+    stopped: Arc<AtomicBool>,
+    listener: thread::JoinHandle<()>,
+) -> usize {
+    let id = NEXT_DEVICE_CHANGE_LISTENER.fetch_add(1, Ordering::SeqCst);
+    DEVICE_CHANGE_LISTENERS
+        .lock()
+        .unwrap()
+        .push((id, stopped, listener));
+    id
+}
+
+/// A handle to a callback registered with `register_device_change_callback` or
+/// `register_device_change_callback_with_port`, to stop it with `cancel`. The callback keeps
+/// being called after the handle is dropped, until `stop_device_change_listeners` is called.
+#[derive(Debug)]
+pub struct DeviceChangeSubscription {
+    /// The id of the thread calling the callback.
+    id: usize,
+}
+
+impl DeviceChangeSubscription {
+    /// Returns true while the callback can be called: it has not been stopped, and the thread
+    /// calling it has not exited because of an error.
+    pub fn is_active(&self) -> bool {
+        DEVICE_CHANGE_LISTENERS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(id, _, listener)| *id == self.id && !listener.is_finished())
+    }
+
+    /// Stop the callback, and wait for the thread calling it to exit. serialosc only notifies
+    /// the application of the next change after each request, so it is not asked to notify it
+    /// anymore, and the callback is not called after this returns.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let subscription =
+    ///     Monome::register_device_change_callback(|event| println!("{:?}", event)).unwrap();
+    /// // ...
+    /// subscription.cancel();
+    /// ```
+    pub fn cancel(self) {
+        let listener = {
+            let mut listeners = DEVICE_CHANGE_LISTENERS.lock().unwrap();
+            match listeners.iter().position(|(id, _, _)| *id == self.id) {
+                Some(index) => listeners.remove(index),
+                // Already stopped by `stop_device_change_listeners`.
+                None => return,
+            }
+        };
+        let (_, stopped, listener) = listener;
+        stopped.store(true, Ordering::SeqCst);
+        if listener.join().is_err() {
+            error!("A device change callback panicked");
+        }
+    }
+}
+
 /// An enum filled when a device has been added or removed, along with its name.
 #[derive(Debug)]
 pub enum DeviceChangeEvent {
@@ -76,25 +431,37 @@ pub enum DeviceChangeEvent {
     Removed(String),
 }
 
+/// The informations about a device, received from serialosc during setup.
 #[derive(Debug)]
 struct MonomeInfo {
+    /// The prefix requested for the device. A reply with another prefix is stale.
+    requested_prefix: String,
     port: Option<i32>,
     host: Option<String>,
     prefix: Option<String>,
     id: Option<String>,
     size: Option<(i32, i32)>,
     rotation: Option<i32>,
+    /// The model of the device, if it is known from the enumeration. It is not part of the
+    /// replies of the device.
+    model: Option<String>,
+    /// The address of the serialosc instance that reported the device, if it is known from the
+    /// enumeration.
+    serialosc: Option<SocketAddr>,
 }
 
 impl MonomeInfo {
-    fn new() -> MonomeInfo {
+    fn new(requested_prefix: &str) -> MonomeInfo {
         MonomeInfo {
+            requested_prefix: requested_prefix.to_string(),
             port: None,
             host: None,
             prefix: None,
             id: None,
             size: None,
             rotation: None,
+            model: None,
+            serialosc: None,
         }
     }
     fn complete(&self) -> bool {
@@ -105,128 +472,615 @@ impl MonomeInfo {
             && self.size.is_some()
             && self.rotation.is_some()
     }
+    /// Fill the informations from a message received during setup, or from each message of a
+    /// bundle. Anything else than a well-formed `/sys` reply is ignored, because the device can
+    /// send events, or replies to a previous application, while it is being set up.
     fn fill(&mut self, packet: OscPacket) {
-        match packet {
-            OscPacket::Message(message) => {
-                if message.addr.starts_with("/sys") {
-                    if let Some(args) = message.args {
-                        if message.addr.starts_with("/sys/port") {
-                            if let OscType::Int(port) = args[0] {
-                                self.port = Some(port);
-                            }
-                        } else if message.addr.starts_with("/sys/host") {
-                            if let OscType::String(ref host) = args[0] {
-                                self.host = Some(host.to_string());
-                            }
-                        } else if message.addr.starts_with("/sys/id") {
-                            if let OscType::String(ref id) = args[0] {
-                                self.id = Some(id.to_string());
-                            }
-                        } else if message.addr.starts_with("/sys/prefix") {
-                            if let OscType::String(ref prefix) = args[0] {
-                                self.prefix = Some(prefix.to_string());
-                            }
-                        } else if message.addr.starts_with("/sys/rotation") {
-                            if let OscType::Int(rotation) = args[0] {
-                                self.rotation = Some(rotation);
-                            }
-                        } else if message.addr.starts_with("/sys/size") {
-                            if let OscType::Int(x) = args[0] {
-                                if let OscType::Int(y) = args[1] {
-                                    self.size = Some((x, y));
-                                }
-                            }
+        let message = match packet {
+            OscPacket::Message(message) => message,
+            OscPacket::Bundle(bundle) => {
+                for packet in bundle.content {
+                    self.fill(packet);
+                }
+                return;
+            }
+        };
+        let args = message.args.unwrap_or_default();
+        match (message.addr.as_str(), args.as_slice()) {
+            ("/sys/port", [OscType::Int(port)]) => self.port = Some(*port),
+            ("/sys/host", [OscType::String(host)]) => self.host = Some(host.to_string()),
+            ("/sys/id", [OscType::String(id)]) => self.id = Some(id.to_string()),
+            ("/sys/prefix", [OscType::String(prefix)]) => {
+                // serialosc adds the leading slash if it's missing.
+                if prefix.trim_start_matches('/') == self.requested_prefix.trim_start_matches('/') {
+                    self.prefix = Some(prefix.to_string());
+                } else {
+                    debug!("⇦ Ignoring stale prefix {} during setup", prefix);
+                }
+            }
+            ("/sys/rotation", [OscType::Int(rotation)]) => self.rotation = Some(*rotation),
+            ("/sys/size", [OscType::Int(x), OscType::Int(y)]) => self.size = Some((*x, *y)),
+            (addr, _) => debug!("⇦ Ignoring {} during setup", addr),
+        }
+    }
+    /// Fill the informations from a datagram received during setup.
+    fn fill_datagram(&mut self, buf: &[u8]) {
+        match decode(buf) {
+            Ok(packet) => self.fill(packet),
+            Err(e) => debug!("⇦ Could not decode datagram during setup: {:?}", e),
+        }
+    }
+}
+
+/// Builds the messages that tell a device to send its events to `local_addr`, with `prefix`, and
+/// to send its informations.
+fn setup_packets(local_addr: SocketAddr, prefix: &str) -> Result<Vec<Vec<u8>>, MonomeError> {
+    let packets = [
+        build_osc_message(
+            "/sys/port",
+            vec![OscType::Int(i32::from(local_addr.port()))],
+        ),
+        build_osc_message(
+            "/sys/host",
+            vec![OscType::String(local_addr.ip().to_string())],
+        ),
+        build_osc_message("/sys/prefix", vec![OscType::String(prefix.to_string())]),
+        build_osc_message("/sys/info", vec![]),
+    ];
+    packets
+        .iter()
+        .map(|packet| encode(packet).map_err(MonomeError::OscEncode))
+        .collect()
+}
+
+/// The error returned when a device doesn't answer in time while setting it up.
+fn setup_timed_out() -> MonomeError {
+    MonomeError::DeviceUnreachable(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "the device did not answer in time",
+    ))
+}
+
+/// The error returned when serialosc doesn't answer in time while enumerating the devices.
+fn enumeration_timed_out() -> MonomeError {
+    MonomeError::SerialoscUnreachable(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "serialosc did not answer in time",
+    ))
+}
+
+/// Builds the message that asks serialosc to send the list of devices to `local_addr`.
+fn enumeration_request(local_addr: SocketAddr) -> Result<Vec<u8>, MonomeError> {
+    let packet = build_osc_message(
+        "/serialosc/list",
+        vec![
+            OscType::String(local_addr.ip().to_string()),
+            OscType::Int(i32::from(local_addr.port())),
+        ],
+    );
+    encode(&packet).map_err(MonomeError::OscEncode)
+}
+
+/// A message received from serialosc while enumerating the devices.
+enum Listed {
+    /// A device.
+    Device(MonomeDevice),
+    /// The end of the list.
+    End,
+    /// Another message, for example a tilt message or a keypress, that can be received in the
+    /// meantime.
+    Ignored,
+}
+
+/// Parses a datagram received from the serialosc instance running at `serialosc`, while
+/// enumerating the devices. Returns what each of its messages lists, in order: a bundle can list
+/// several devices.
+fn parse_listed(buf: &[u8], serialosc: SocketAddr) -> Vec<Listed> {
+    let mut listed = Vec::new();
+    match decode(buf) {
+        Ok(packet) => parse_listed_packet(packet, serialosc, &mut listed),
+        Err(e) => debug!("⇦ Could not decode datagram during enumeration: {:?}", e),
+    }
+    listed
+}
+
+/// Appends what a packet received while enumerating the devices lists to `listed`.
+fn parse_listed_packet(packet: OscPacket, serialosc: SocketAddr, listed: &mut Vec<Listed>) {
+    match packet {
+        OscPacket::Message(message) => listed.push(parse_listed_message(message, serialosc)),
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                parse_listed_packet(packet, serialosc, listed);
+            }
+        }
+    }
+}
+
+/// Parses a message received while enumerating the devices.
+fn parse_listed_message(message: OscMessage, serialosc: SocketAddr) -> Listed {
+    if message.addr != "/serialosc/device" {
+        return Listed::Ignored;
+    }
+    match message.args {
+        Some(args) => match args.as_slice() {
+            [OscType::String(ref name), OscType::String(ref device_type), OscType::Int(port)] => {
+                let serialosc_port = i32::from(serialosc.port());
+                let mut device = MonomeDevice::new(name, device_type, *port, serialosc_port);
+                device.host = serialosc.ip();
+                Listed::Device(device)
+            }
+            _ => Listed::Ignored,
+        },
+        None => Listed::End,
+    }
+}
+
+/// Identifies a function registered with `Monome::add_event_notifier()`, to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventNotifierId(usize);
+
+/// A function called each time an event has been queued for `poll()`.
+type EventNotifier = Box<dyn Fn() + Send>;
+
+/// Where events are sent instead of the queue read by `poll()`.
+enum EventSink {
+    /// The channel returned by `event_receiver()`.
+    Channel(crossbeam::channel::Sender<MonomeEvent>),
+    /// The channel returned by `std_event_receiver()`.
+    StdChannel(std::sync::mpsc::SyncSender<MonomeEvent>),
+    /// The channel read by the stream returned by `event_stream()`.
+    #[cfg(feature = "async")]
+    Stream(tokio::sync::mpsc::Sender<MonomeEvent>),
+}
+
+/// State shared between a `Monome` and its `Transport`.
+struct Shared {
+    /// The prefix of the device, used to parse the messages received.
+    prefix: Mutex<String>,
+    /// The events the client object is interested in.
+    filter: Mutex<EventFilter>,
+    /// The latency measurements for this device.
+    latency: LatencyTracker,
+    /// The channels to which events are sent, instead of the event queue, if any.
+    channels: Mutex<Option<EventSenders>>,
+    /// The subscribers that receive a copy of every event.
+    subscribers: Mutex<Subscribers>,
+    /// Functions called each time an event has been queued for `poll()`.
+    notifiers: Mutex<Vec<(EventNotifierId, EventNotifier)>>,
+    /// The id of the next function registered to be called when events are queued.
+    next_notifier: AtomicUsize,
+    /// The number of times events have been queued for `poll()`, and the condition variable
+    /// signaled each time, on which `poll_timeout()` waits.
+    queued: (Mutex<u64>, Condvar),
+    /// The channel to which events are sent, instead of the queue read by `poll()`, if any.
+    receiver: Mutex<Option<EventSink>>,
+    /// The suppression of duplicate key events, if enabled.
+    dedup: Mutex<Option<EventDeduplicator>>,
+    /// Set when the transport has stopped, after which the device can't be used anymore.
+    stopped: AtomicBool,
+    /// The last events received, if enabled.
+    history: Mutex<Option<EventHistory>>,
+    /// The threads of the transport, if it runs on threads, to wait for when closing.
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+    /// The conversion of the coordinates of the keys to a fixed orientation, if enabled.
+    orientation: Mutex<Option<KeyOrientation>>,
+    /// Incremented each time the device is set up again, so that the transports of the
+    /// previous connections don't affect the current one.
+    generation: AtomicUsize,
+    /// The port of the device and the channels to its new transport, after it has been set up
+    /// again, until the client object switches to them.
+    reconnected: Mutex<Option<(i32, Outgoing, Outgoing)>>,
+    /// The conversion of the coordinates of the leds set to the rotation of the device, if
+    /// logical coordinates are enabled.
+    led_orientation: Mutex<Option<LedOrientation>>,
+    /// The intensity from which leds are on, if the intensities are converted for a grid whose
+    /// leds can only be on or off.
+    monochrome_threshold: Mutex<Option<u8>>,
+    /// The mapping of the intensities of the leds to the levels sent, if any.
+    curve: Mutex<Option<BrightnessCurve>>,
+    /// Set when leds have been changed by a `MonomeOutput`, so that the client object doesn't
+    /// know what the device displays anymore.
+    leds_changed: AtomicBool,
+    /// The last intensities written to the leds of the grid, by the client object and by its
+    /// `MonomeOutput`s.
+    shadow: Mutex<LedShadow>,
+    /// The regions of the grid claimed by the owners of `MonomeOutput`s.
+    locks: Mutex<RegionLocks>,
+    /// Whether the led messages that don't change any led are dropped.
+    skip_redundant_leds: AtomicBool,
+    /// Caps the rate of the led messages sent, by the client object and by its `MonomeOutput`s,
+    /// if enabled.
+    rate_limiter: Mutex<Option<RateLimiter>>,
+    /// The number of events to drop as if the event queue was full.
+    #[cfg(feature = "failure-injection")]
+    overflowing: AtomicUsize,
+}
+
+impl Shared {
+    /// Wakes up the threads waiting in `poll_timeout()`, and calls the notifiers, when events
+    /// are available from `poll()`.
+    fn notify_queued(&self) {
+        let (ref count, ref queued) = self.queued;
+        *count.lock().unwrap() += 1;
+        queued.notify_all();
+        for (_, notify) in self.notifiers.lock().unwrap().iter() {
+            notify();
+        }
+    }
+
+    fn new(prefix: &str) -> Shared {
+        Shared {
+            prefix: Mutex::new(prefix.to_string()),
+            filter: Mutex::new(EventFilter::all()),
+            latency: LatencyTracker::default(),
+            channels: Mutex::new(None),
+            subscribers: Mutex::new(Subscribers::default()),
+            notifiers: Mutex::new(Vec::new()),
+            next_notifier: AtomicUsize::new(0),
+            queued: (Mutex::new(0), Condvar::new()),
+            receiver: Mutex::new(None),
+            dedup: Mutex::new(None),
+            stopped: AtomicBool::new(false),
+            history: Mutex::new(None),
+            threads: Mutex::new(Vec::new()),
+            orientation: Mutex::new(None),
+            generation: AtomicUsize::new(0),
+            led_orientation: Mutex::new(None),
+            monochrome_threshold: Mutex::new(None),
+            curve: Mutex::new(None),
+            leds_changed: AtomicBool::new(false),
+            shadow: Mutex::new(LedShadow::new((0, 0))),
+            locks: Mutex::new(RegionLocks::new()),
+            skip_redundant_leds: AtomicBool::new(false),
+            rate_limiter: Mutex::new(None),
+            reconnected: Mutex::new(None),
+            #[cfg(feature = "failure-injection")]
+            overflowing: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sends a message to `addr`, without prefix, with `args`, with `send`, unless it only sets
+    /// leds to the intensities they are known to have and redundant messages are skipped, or it
+    /// is over the rate cap. `send` returns true if the message has been queued, after which the
+    /// leds it sets are recorded. If it hasn't, they are forgotten, since the message may have
+    /// been partly sent. Returns true if the message has been queued or skipped.
+    fn send_filtered<F>(&self, addr: &str, args: Vec<OscType>, send: F) -> bool
+    where
+        F: FnOnce(Vec<OscType>) -> bool,
+    {
+        let leds = addr.starts_with("/grid/led/");
+        if leds
+            && self.skip_redundant_leds.load(Ordering::SeqCst)
+            && self.shadow.lock().unwrap().is_redundant(addr, &args)
+        {
+            return true;
+        }
+        if leds || addr.starts_with("/ring/") {
+            if let Some(ref mut limiter) = *self.rate_limiter.lock().unwrap() {
+                if !limiter.allow() {
+                    return false;
+                }
+            }
+        }
+        let written = if leds { Some(args.clone()) } else { None };
+        let queued = send(args);
+        if let Some(args) = written {
+            let mut shadow = self.shadow.lock().unwrap();
+            if queued {
+                shadow.update(addr, &args);
+            } else {
+                shadow.forget_writes(addr, &args);
+            }
+        }
+        queued
+    }
+
+    /// Converts a message sent to `addr`, without prefix, with `args`, to the messages to send
+    /// to the device, if logical coordinates are enabled or the leds of the device can only be
+    /// on or off.
+    fn convert(&self, addr: &str, args: Vec<OscType>) -> Vec<(String, Vec<OscType>)> {
+        let mut messages = match *self.led_orientation.lock().unwrap() {
+            Some(ref orientation) => orientation.convert(addr, args),
+            None => vec![(addr.to_string(), args)],
+        };
+        if let Some(threshold) = *self.monochrome_threshold.lock().unwrap() {
+            messages = messages
+                .into_iter()
+                .flat_map(|(addr, args)| mono::threshold(&addr, args, threshold))
+                .collect();
+        }
+        messages
+    }
+}
+
+/// The channel on which the client object sends serialized OSC messages to its transport, along
+/// with the time at which they have been sent.
+#[derive(Clone)]
+enum Outgoing {
+    /// To a transport running on threads.
+    Thread(crossbeam::channel::Sender<(Instant, Vec<u8>)>),
+    /// To a transport running on a tokio runtime.
+    #[cfg(feature = "async")]
+    Task(tokio::sync::mpsc::Sender<(Instant, Vec<u8>)>),
+}
+
+impl Outgoing {
+    /// A channel that is disconnected, to replace a channel that has been closed.
+    fn closed() -> Outgoing {
+        Outgoing::Thread(crossbeam::channel::bounded(0).0)
+    }
+
+    /// Queues a message to send, or drops it if too many messages are waiting to be sent.
+    /// Returns true if the message has been queued.
+    fn try_send(&self, bytes: Vec<u8>) -> bool {
+        let full = match *self {
+            Outgoing::Thread(ref sender) => match sender.try_send((Instant::now(), bytes)) {
+                Ok(()) => return true,
+                Err(e) => e.is_full(),
+            },
+            #[cfg(feature = "async")]
+            Outgoing::Task(ref sender) => match sender.try_send((Instant::now(), bytes)) {
+                Ok(()) => return true,
+                Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => true,
+                Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => false,
+            },
+        };
+        if full {
+            warn!("The queue of the messages to send to the device is full, message dropped.");
+        } else {
+            warn!("The transport of the device has stopped, message dropped.");
+        }
+        false
+    }
+
+    /// Queues a message to send, waiting until there is room for it.
+    #[cfg(feature = "async")]
+    async fn send(&self, bytes: Vec<u8>) -> Result<(), MonomeError> {
+        match *self {
+            Outgoing::Thread(ref sender) => {
+                let mut message = (Instant::now(), bytes);
+                loop {
+                    match sender.try_send(message) {
+                        Ok(()) => return Ok(()),
+                        Err(crossbeam::channel::TrySendError::Full(m)) => message = m,
+                        Err(crossbeam::channel::TrySendError::Disconnected(_)) => {
+                            return Err(MonomeError::DeviceGone)
                         }
                     }
+                    tokio::time::sleep(Duration::from_millis(1)).await;
                 }
             }
-            OscPacket::Bundle(_bundle) => {
-                error!("Bundle during setup!?");
-            }
+            Outgoing::Task(ref sender) => sender
+                .send((Instant::now(), bytes))
+                .await
+                .map_err(|_| MonomeError::DeviceGone),
         }
     }
 }
 
-/// `Transport` implements the network input and output to and from serialosc.
+/// `Transport` implements the network input and output to and from serialosc. It runs either on
+/// threads, or on a tokio runtime.
 struct Transport {
-    /// The port for this device. This is the first free port starting at 10000.
-    device_port: i32,
-    /// This is the socket with with we send and receive to and from the device.
-    socket: UdpSocket,
-    /// This is the channel we use to forward the received OSC messages to the client object.
-    tx: Arc<ArrayQueue<Vec<u8>>>,
-    /// This is where Transport receives the OSC messages to send.
-    rx: Receiver<Vec<u8>>,
+    /// The address of this device: the host of the serialosc instance that reported it, and the
+    /// port serialosc exposes it on.
+    device_addr: SocketAddr,
+    /// This is the channel we use to forward the events received to the client object, along
+    /// with the time at which they have been received.
+    tx: Arc<ArrayQueue<(Instant, MonomeEvent)>>,
+    /// The state shared with the client object.
+    shared: Arc<Shared>,
+    /// The connection to the device this transport is for, see `Shared::generation`.
+    generation: usize,
+    /// Set when this transport has stopped.
+    stopped: AtomicBool,
 }
 
 impl Transport {
     pub fn new(
-        device_port: i32,
-        socket: UdpSocket,
-        tx: Arc<ArrayQueue<Vec<u8>>>,
-        rx: Receiver<Vec<u8>>,
+        device_addr: SocketAddr,
+        tx: Arc<ArrayQueue<(Instant, MonomeEvent)>>,
+        shared: Arc<Shared>,
     ) -> Transport {
+        let generation = shared.generation.load(Ordering::SeqCst);
         Transport {
-            device_port,
-            socket,
+            device_addr,
             tx,
-            rx,
+            shared,
+            generation,
+            stopped: AtomicBool::new(false),
         }
     }
-}
 
-impl Future for Transport {
-    type Item = ();
-    type Error = io::Error;
+    /// Runs the transport on threads, sending and receiving with `socket`, and returns the
+    /// channels on which the client object sends the messages to the device, the second one
+    /// being sent first.
+    fn spawn(self, socket: std::net::UdpSocket) -> Result<(Outgoing, Outgoing), MonomeError> {
+        let (sender, receiver) =
+            crossbeam::channel::bounded::<(Instant, Vec<u8>)>(OUTGOING_CAPACITY);
+        let (priority_sender, priority_receiver) =
+            crossbeam::channel::bounded::<(Instant, Vec<u8>)>(OUTGOING_CAPACITY);
+        let send_socket = socket.try_clone().map_err(MonomeError::Bind)?;
+        // Wake up regularly to notice when the transport has stopped.
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .map_err(MonomeError::Bind)?;
+        let addr = self.device_addr;
+        let transport = Arc::new(self);
 
-    fn poll(&mut self) -> Poll<(), io::Error> {
-        loop {
-            match self.rx.poll() {
-                Ok(fut) => {
-                    match fut {
-                        Async::Ready(b) => {
-                            let device_address = format!("127.0.0.1:{}", self.device_port);
-                            let addr: SocketAddr = device_address.parse().unwrap();
-                            // This happens when shutting down usually
-                            if b.is_some() {
-                                let _amt =
-                                    try_ready!(self.socket.poll_send_to(&b.unwrap(), &addr));
-                            } else {
-                                break;
-                            }
-                        }
-                        Async::NotReady => {
-                            break;
+        let sending = transport.clone();
+        let sender_thread = thread::spawn(move || {
+            loop {
+                // Send the messages of the priority channel first.
+                let message = match priority_receiver.try_recv() {
+                    Ok(message) => Ok(message),
+                    Err(_) => crossbeam::channel::select! {
+                        recv(priority_receiver) -> message => message,
+                        recv(receiver) -> message => message,
+                    },
+                };
+                match message {
+                    Ok((sent_at, b)) => sending.record_send(send_socket.send_to(&b, addr), sent_at),
+                    // The client object has been dropped: a closed channel is always ready, so
+                    // send what is left on both before stopping.
+                    Err(_) => {
+                        for (sent_at, b) in priority_receiver.try_iter().chain(receiver.try_iter())
+                        {
+                            sending.record_send(send_socket.send_to(&b, addr), sent_at);
                         }
+                        break;
                     }
                 }
-                Err(e) => {
-                    error!("Error on future::mpsc {:?}", e);
-                }
             }
-        }
+            sending.stop();
+        });
 
-        loop {
+        let shared = transport.shared.clone();
+        let receiver_thread = thread::spawn(move || {
             let mut buf = vec![0; 1024];
-            match self.socket.poll_recv(&mut buf) {
-                Ok(fut) => match fut {
-                    Async::Ready(_ready) => match self.tx.push(buf) {
-                        Ok(()) => {
-                            continue;
-                        }
-                        Err(e) => {
-                            error!("receive from monome, {}", e);
-                        }
-                    },
-                    Async::NotReady => {
-                        return Ok(Async::NotReady);
+            while !transport.stopped.load(Ordering::SeqCst) {
+                match socket.recv_from(&mut buf) {
+                    Ok((amt, _)) => transport.dispatch(&buf[..amt]),
+                    Err(ref e)
+                        if e.kind() == io::ErrorKind::WouldBlock
+                            || e.kind() == io::ErrorKind::TimedOut => {}
+                    Err(e) => {
+                        error!("server error = {:?}", e);
+                        break;
                     }
-                },
-                Err(e) => {
-                    return Err(e);
                 }
             }
+            transport.stop();
+        });
+        shared
+            .threads
+            .lock()
+            .unwrap()
+            .extend([sender_thread, receiver_thread]);
+
+        Ok((Outgoing::Thread(sender), Outgoing::Thread(priority_sender)))
+    }
+
+    /// Records the outcome of sending a message to the device, and how long it waited to be
+    /// sent.
+    fn record_send(&self, result: io::Result<usize>, sent_at: Instant) {
+        match result {
+            Ok(_) => self.shared.latency.record_send(sent_at.elapsed()),
+            Err(e) => error!("Could not send to the device: {:?}", e),
+        }
+    }
+
+    /// Marks the transport as stopped, and the device as disconnected if this transport is for
+    /// the current connection.
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if self.shared.generation.load(Ordering::SeqCst) == self.generation {
+            self.shared.stopped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Decodes a datagram received from the device, and forwards the event it contains to the
+    /// client object, if it is interested in it.
+    fn dispatch(&self, buf: &[u8]) {
+        let received_at = Instant::now();
+        let packet = match decode(buf) {
+            Ok(packet) => packet,
+            Err(e) => {
+                error!("Could not decode datagram from the device: {:?}", e);
+                return;
+            }
+        };
+        self.dispatch_packet(packet, received_at);
+    }
+
+    /// Forwards the events of a packet to the client object, timestamped with `received_at`.
+    /// The messages of a bundle are timestamped with its timetag instead, if it has one, so that
+    /// they keep the timing of the sender.
+    fn dispatch_packet(&self, packet: OscPacket, received_at: Instant) {
+        let message = match packet {
+            OscPacket::Message(message) => message,
+            OscPacket::Bundle(bundle) => {
+                let sent_at = timetag_to_instant(&bundle.timetag, received_at);
+                for packet in bundle.content {
+                    self.dispatch_packet(packet, sent_at);
+                }
+                return;
+            }
+        };
+        if !self.shared.filter.lock().unwrap().accepts(&message) {
+            return;
+        }
+        let packet = OscPacket::Message(message);
+        let prefix = self.shared.prefix.lock().unwrap().clone();
+        let event = match parse(&prefix, packet) {
+            Some(event) => event,
+            None => return,
+        };
+        let event = match *self.shared.orientation.lock().unwrap() {
+            Some(ref orientation) => orientation.apply(event),
+            None => event,
+        };
+        if let Some(ref mut dedup) = *self.shared.dedup.lock().unwrap() {
+            if !dedup.accept_at(&event, received_at) {
+                debug!("duplicate event suppressed: {:?}", event);
+                return;
+            }
+        }
+        if let Some(ref mut history) = *self.shared.history.lock().unwrap() {
+            history.push_at(event.clone(), received_at);
+        }
+        self.shared.subscribers.lock().unwrap().broadcast(&event);
+        let event = match *self.shared.channels.lock().unwrap() {
+            Some(ref channels) => match channels.route(event) {
+                Ok(()) => return,
+                Err(event) => event,
+            },
+            None => event,
+        };
+        let receiver = self.shared.receiver.lock().unwrap();
+        match *receiver {
+            Some(EventSink::Channel(ref sender)) => {
+                if let Err(crossbeam::channel::TrySendError::Full(event)) = sender.try_send(event) {
+                    error!("event channel full, dropping {:?}", event);
+                }
+                return;
+            }
+            Some(EventSink::StdChannel(ref sender)) => {
+                if let Err(std::sync::mpsc::TrySendError::Full(event)) = sender.try_send(event) {
+                    error!("event channel full, dropping {:?}", event);
+                }
+                return;
+            }
+            #[cfg(feature = "async")]
+            Some(EventSink::Stream(ref sender)) => {
+                if let Err(tokio::sync::mpsc::error::TrySendError::Full(event)) =
+                    sender.try_send(event)
+                {
+                    error!("event stream full, dropping {:?}", event);
+                }
+                return;
+            }
+            None => {}
+        }
+        #[cfg(feature = "failure-injection")]
+        {
+            let overflowing = &self.shared.overflowing;
+            let dropping = overflowing
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if dropping {
+                error!(
+                    "receive from monome, injected overflow, dropping {:?}",
+                    event
+                );
+                return;
+            }
+        }
+        if let Err(e) = self.tx.push((received_at, event)) {
+            error!("receive from monome, {}", e);
+            return;
         }
+        drop(receiver);
+        self.shared.notify_queued();
     }
 }
 
@@ -248,214 +1102,118 @@ pub struct Monome {
     rotation: i32,
     /// THe x and y size for this device.
     size: (i32, i32),
-    /// A channel that allows receiving serialized OSC messages from a device.
-    q: Arc<ArrayQueue<Vec<u8>>>,
+    /// The model of this device, e.g. "monome 128", if it is known.
+    model: Option<String>,
+    /// A channel that allows receiving events from a device.
+    q: Arc<ArrayQueue<(Instant, MonomeEvent)>>,
     /// A channel that allows sending serialized OSC messages to a device.
-    tx: Sender<Vec<u8>>,
+    tx: Outgoing,
+    /// A channel that allows sending serialized OSC messages to a device, ahead of the messages
+    /// sent on `tx`.
+    priority_tx: Outgoing,
+    /// The state shared with the transport.
+    shared: Arc<Shared>,
+    /// Events synthesized by this client object, returned by `poll()` before received events.
+    pending: VecDeque<MonomeEvent>,
+    /// The grid keys currently held down.
+    keys: KeyStateTracker,
+    /// The functions called for each kind of event by `dispatch()`.
+    handlers: Handlers,
+    /// The offsets of the quads to update first when updating the whole grid.
+    quad_order: Vec<(i32, i32)>,
+    /// The last intensities sent when updating the whole grid, if frame debugging is enabled.
+    debug_frame: Option<Vec<u8>>,
+    /// The scaling applied to the intensities when updating the whole grid.
+    scaling: IntensityScaling,
+    /// The log to which the frames drawn are written, if any.
+    frame_log: Option<FrameLogWriter<Box<dyn io::Write + Send>>>,
+    /// Dims the display when the device is idle, if enabled.
+    dimmer: Option<IdleDimmer>,
+    /// The last intensities sent when updating the whole grid, before dimming, and the dimming
+    /// level they were sent with, if idle dimming is enabled.
+    undimmed: Option<(Vec<u8>, f32)>,
+    /// Maps keys and chords to actions, if set.
+    actions: Option<ActionMap>,
+    /// Whether `set_all_intensity` only sends what changed, with the cheapest messages.
+    adaptive_updates: bool,
+    /// The intensities the device is known to display, if adaptive updates are enabled and no
+    /// led has been changed by other means since they were sent.
+    leds_sent: Option<Vec<u8>>,
+    /// The messages collected since `begin_batch()`, sent as bundles by `commit()`.
+    batch: Option<Vec<OscMessage>>,
+    /// The address of the serialosc instance that reported this device.
+    serialosc: SocketAddr,
+    /// The thread that sets the device up again when it is plugged back, with the flag that
+    /// tells it to stop, if automatic reconnection is enabled.
+    reconnector: Option<(Arc<AtomicBool>, thread::JoinHandle<()>)>,
 }
 
-/// Whether a key press is going up or down
 #[derive(Debug)]
-pub enum KeyDirection {
-    /// The key has been released.
-    Up,
-    /// The key has been pressed.
-    Down,
-}
-
-/// An event received from a monome device. This can be either a key press or release, a tilt
-/// event, an encoder rotation event, or an encoder press or release.
-pub enum MonomeEvent {
-    /// A key press or release
-    GridKey {
-        /// The horizontal offset at which the key has been pressed.
-        x: i32,
-        /// The vertical offset at which the key has been pressed.
-        y: i32,
-        /// Whether the key has been pressed (`Down`), or released (`Up`).
-        direction: KeyDirection,
-    },
-    /// A update about the tilt of this device.
-    Tilt {
-        /// Which sensor sent this tilt update.
-        n: i32,
-        /// The pitch of this device.
-        x: i32,
-        /// The roll of this device.
-        y: i32,
-        /// The yaw of this device.
-        z: i32,
-    },
-    /// An encoder delta information
-    EncoderDelta {
-        /// Which encoder is sending the event.
-        n: usize,
-        /// The delta of this movement on this encoder.
-        delta: i32,
-    },
-    /// A key press on an encoder (only available on some older devices).
-    EncoderKey {
-        /// Which encoder is sending the event.
-        n: usize,
-        /// Whether the encoder key has been pressed (`Down`), or released (`Up`).
-        direction: KeyDirection,
-    },
-}
-
-/// Converts an to a Monome method argument to a OSC address fragment and suitable OscType,
-/// performing an eventual conversion.
-pub trait IntoAddrAndArgs<'a, B> {
-    /// Converts an to a Monome method argument to a OSC address fragment and suitable OscType,
-    /// performing an eventual conversion.
-    fn as_addr_frag_and_args(&self) -> (String, B);
-}
-
-/// Used to make a call with an intensity value, adds the `"level/"` portion in the address.
-impl<'a> IntoAddrAndArgs<'a, OscType> for i32 {
-    fn as_addr_frag_and_args(&self) -> (String, OscType) {
-        ("level/".to_string(), OscType::Int(*self))
-    }
+/// A struct with basic informations about a Monome device, available without having set it up
+pub struct MonomeDevice {
+    /// Name of the device with serial number
+    name: String,
+    /// Device type
+    device_type: MonomeDeviceType,
+    /// Model of the device, as reported by serialosc, e.g. "monome 128"
+    model: String,
+    /// Port at which this device is available
+    port: i32,
+    /// Host of the serialosc instance that reported this device, on which the device is
+    host: IpAddr,
+    /// Port of the serialosc instance that reported this device
+    serialosc_port: i32,
 }
 
-/// Used to make an on/off call, converts to 0 or 1.
-impl<'a> IntoAddrAndArgs<'a, OscType> for bool {
-    fn as_addr_frag_and_args(&self) -> (String, OscType) {
-        ("".to_string(), OscType::Int(if *self { 1 } else { 0 }))
+impl fmt::Display for MonomeDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.name, self.device_type, self.port)
     }
 }
 
-impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for &'a [u8; 64] {
-    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
-        // TODO: error handling both valid: either 64 or more intensity values, or 8 masks
-        let mut osctype_vec = Vec::with_capacity(64);
-        for item in self.iter().map(|i| OscType::Int(i32::from(*i))) {
-            osctype_vec.push(item);
+impl MonomeDevice {
+    fn new(name: &str, device_type: &str, port: i32, serialosc_port: i32) -> MonomeDevice {
+        MonomeDevice {
+            name: name.to_string(),
+            device_type: device_type.into(),
+            model: device_type.to_string(),
+            port,
+            host: LOCALHOST,
+            serialosc_port,
         }
-        ("level/".to_string(), osctype_vec)
     }
-}
-
-impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for u8 {
-    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
-        // TODO: error handling both valid: either 64 or more intensity values, or 8 masks
-        let mut osctype_vec = Vec::with_capacity(1);
-        osctype_vec.push(OscType::Int(i32::from(*self)));
-        ("".to_string(), osctype_vec)
+    /// The address of the device: its host, and the port on which serialosc exposes it.
+    fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port as u16)
     }
-}
-
-impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for &'a [u8; 8] {
-    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
-        // TODO: error handling both valid: either 64 or more intensity values, or 8 masks
-        let mut osctype_vec = Vec::with_capacity(8);
-        for item in self.iter().map(|i| OscType::Int(i32::from(*i))) {
-            osctype_vec.push(item);
-        }
-        ("".to_string(), osctype_vec)
+    /// The address of the serialosc instance that reported this device.
+    fn serialosc_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.serialosc_port as u16)
     }
-}
-
-/// Used to convert vectors of bools for on/off calls, packs into a 8-bit integer mask.
-impl<'a> IntoAddrAndArgs<'a, Vec<OscType>> for &'a [bool; 64] {
-    fn as_addr_frag_and_args(&self) -> (String, Vec<OscType>) {
-        // TODO: error handling
-        assert!(self.len() >= 64);
-        let mut masks = [0 as u8; 8];
-        for i in 0..8 {
-            // for each row
-            let mut mask: u8 = 0;
-            for j in (0..8).rev() {
-                // create mask
-                let idx = toidx(j, i, 8);
-                mask = mask.rotate_left(1) | if self[idx] { 1 } else { 0 };
-            }
-            masks[i as usize] = mask;
-        }
-        let mut osctype_vec = Vec::with_capacity(8);
-        for item in masks.iter().map(|i| OscType::Int(i32::from(*i))) {
-            osctype_vec.push(item);
-        }
-        ("".to_string(), osctype_vec)
+    /// Return the device type.
+    pub fn device_type(&self) -> MonomeDeviceType {
+        self.device_type.clone()
     }
-}
-
-/// A type of device, either Grid (of various size), Arc (with 2 or 4 encoders), or unknown.
-#[derive(PartialEq, Clone)]
-pub enum MonomeDeviceType {
-    /// The type for a monome grid.
-    Grid,
-    /// The type for a monome arc.
-    Arc,
-    /// Unknown device, please file an issue.
-    Unknown,
-}
-
-impl From<&str> for MonomeDeviceType {
-    fn from(string: &str) -> MonomeDeviceType {
-        if string.contains("arc") {
-            MonomeDeviceType::Arc
-        } else {
-            MonomeDeviceType::Grid
-        }
-    }
-}
-
-impl fmt::Display for MonomeDeviceType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            if *self == MonomeDeviceType::Grid {
-                "grid"
-            } else {
-                "arc"
-            }
-        )
-    }
-}
-
-impl fmt::Debug for MonomeDeviceType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self)
-    }
-}
-
-#[derive(Debug)]
-/// A struct with basic informations about a Monome device, available without having set it up
-pub struct MonomeDevice {
-    /// Name of the device with serial number
-    name: String,
-    /// Device type
-    device_type: MonomeDeviceType,
-    /// Port at which this device is available
-    port: i32,
-}
-
-impl fmt::Display for MonomeDevice {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {} ({})", self.name, self.device_type, self.port)
-    }
-}
-
-impl MonomeDevice {
-    fn new(name: &str, device_type: &str, port: i32) -> MonomeDevice {
-        MonomeDevice {
-            name: name.to_string(),
-            device_type: device_type.into(),
-            port,
-        }
-    }
-    /// Return the device type.
-    pub fn device_type(&self) -> MonomeDeviceType {
-        return self.device_type.clone();
+    /// Return the model of the device, as reported by serialosc, e.g. "monome 128".
+    pub fn model(&self) -> String {
+        self.model.clone()
     }
     /// Return the device name.
     pub fn name(&self) -> String {
-        return self.name.clone();
+        self.name.clone()
     }
     /// Return the port on which this device is.
     pub fn port(&self) -> i32 {
-        return self.port;
+        self.port
+    }
+    /// Return the host on which this device is, that runs the serialosc instance that reported
+    /// it.
+    pub fn host(&self) -> IpAddr {
+        self.host
+    }
+    /// Return the port of the serialosc instance that reported this device.
+    pub fn serialosc_port(&self) -> i32 {
+        self.serialosc_port
     }
 }
 
@@ -465,95 +1223,51 @@ impl Monome {
     /// # Arguments
     ///
     /// * `serialosc_port`: the port on which serialosc is running
-    /// - `callback`: a function that is called whenever a device is added or removed.
+    /// - `callback`: a function or a closure that is called, on a thread of its own, whenever a
+    ///   device is added or removed.
     ///
     /// # Example
     ///
-    /// Print a message, on a machine where serialosc runs on port 1234.
+    /// Count the devices added, on a machine where serialosc runs on port 1234.
     ///
     /// ```no_run
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
     /// use monome::Monome;
     /// use monome::DeviceChangeEvent;
-    /// Monome::register_device_change_callback_with_port(1234, |event| {
-    ///     match event {
-    ///         DeviceChangeEvent::Added(id) => {
-    ///             println!("Device {} added", id);
-    ///         }
-    ///         DeviceChangeEvent::Removed(id) => {
-    ///             println!("Device {} removed", id);
-    ///         }
+    /// let added = Arc::new(AtomicUsize::new(0));
+    /// let counter = added.clone();
+    /// let subscription = Monome::register_device_change_callback_with_port(1234, move |event| {
+    ///     if let DeviceChangeEvent::Added(_) = event {
+    ///         counter.fetch_add(1, Ordering::SeqCst);
     ///     }
-    /// });
+    /// })
+    /// .unwrap();
+    /// assert!(subscription.is_active());
     /// ```
-    pub fn register_device_change_callback_with_port(
+    pub fn register_device_change_callback_with_port<F>(
         serialosc_port: i32,
-        callback: fn(DeviceChangeEvent),
-    ) {
-        let mut socket = new_bound_socket();
-
-        thread::spawn(move || {
-            let server_port = socket.local_addr().unwrap().port();
-            let addr = format!("127.0.0.1:{}", serialosc_port).parse().unwrap();
-            let packet = build_osc_message(
-                "/serialosc/notify",
-                vec![
-                    OscType::String("127.0.0.1".to_string()),
-                    OscType::Int(i32::from(server_port)),
-                ],
-            );
-            let mut bytes: Vec<u8>;
-            // True if we've received a add or remove message from serialosc recently, and we need
-            // to tell it to notify this program in the future.
-            // This is necessary, because other messages can be received on this socket, notably the
-            // undocumented /sys/connect and /sys/disconnect messages (without any arguments).
-            let mut need_notify_msg = true;
-            loop {
-                bytes = encode(&packet).unwrap();
-                if need_notify_msg {
-                    socket = socket
-                        .send_dgram(bytes, &addr)
-                        .wait()
-                        .map(|(s, _)| s)
-                        .unwrap();
-                    need_notify_msg = false;
-                }
+        callback: F,
+    ) -> Result<DeviceChangeSubscription, MonomeError>
+    where
+        F: FnMut(DeviceChangeEvent) + Send + 'static,
+    {
+        let socket = new_listener_socket(LOCALHOST)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
 
-                socket = socket.recv_dgram(vec![0u8; 1024]).and_then(|(socket, data, _, _)| {
-                    match decode(&data).unwrap() {
-                        OscPacket::Message(message) => {
-                            if let Some(ref args) = message.args {
-                                if message.addr.starts_with("/serialosc/add") {
-                                    need_notify_msg = true;
-                                    if let OscType::String(ref id) = args[0] {
-                                        callback(DeviceChangeEvent::Added(id.to_string()));
-                                    }
-                                } else if message.addr.starts_with("/serialosc/remove") {
-                                    if let OscType::String(ref id) = args[0] {
-                                        need_notify_msg = true;
-                                        callback(DeviceChangeEvent::Removed(id.to_string()));
-                                    }
-                                } else {
-                                    debug!("⇦ Unexpected message receive on device change event socket {:?}", message);
-                                }
-                            }
-                        }
-                        _ => {
-                            debug!("⇦ Could not decode {:?}", data);
-                        }
-                    }
-                    Ok(socket)
-                })
-                .wait()
-                    .map(|socket| socket)
-                    .unwrap();
-            }
+        let listener = thread::spawn(move || {
+            listen_device_changes(socket, local(serialosc_port), &stop, callback);
         });
+        let id = register_device_change_listener(stopped, listener);
+        Ok(DeviceChangeSubscription { id })
     }
     /// Register for device added/removed notifications, on the default serialosc port
     ///
     /// # Arguments
     ///
-    /// - `callback`: a function that is called whenever a device is added or removed.
+    /// - `callback`: a function or a closure that is called, on a thread of its own, whenever a
+    ///   device is added or removed.
     ///
     /// # Example
     ///
@@ -569,84 +1283,171 @@ impl Monome {
     ///             println!("Device {} removed", id);
     ///         }
     ///     }
-    /// });
+    /// })
+    /// .unwrap();
     /// ```
-    pub fn register_device_change_callback(callback: fn(DeviceChangeEvent)) {
+    pub fn register_device_change_callback<F>(
+        callback: F,
+    ) -> Result<DeviceChangeSubscription, MonomeError>
+    where
+        F: FnMut(DeviceChangeEvent) + Send + 'static,
+    {
         Monome::register_device_change_callback_with_port(SERIALOSC_PORT, callback)
     }
-    fn setup<S>(
-        prefix: S,
-        device: &MonomeDevice,
-    ) -> Result<(MonomeInfo, UdpSocket, String, MonomeDeviceType, i32), String>
-    where
-        S: Into<String>,
+    /// Receive the device added/removed notifications on a channel, on the default serialosc
+    /// port, to handle them in the event loop of the application instead of in a callback. See
+    /// `device_changes_with_port`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use monome::{DeviceChangeEvent, Monome};
+    /// let changes = Monome::device_changes().unwrap();
+    /// loop {
+    ///     while let Ok(change) = changes.try_recv() {
+    ///         if let DeviceChangeEvent::Added(id) = change {
+    ///             println!("Device {} added", id);
+    ///         }
+    ///     }
+    ///     // Handle the other events of the application...
+    ///     # std::thread::sleep(Duration::from_millis(16));
+    /// }
+    /// ```
+    pub fn device_changes() -> Result<crossbeam::channel::Receiver<DeviceChangeEvent>, MonomeError>
     {
-        let (name, device_type, port) = (
-            device.name.to_string(),
-            device.device_type.clone(),
-            device.port,
-        );
-
-        let device_address = format!("127.0.0.1:{}", port);
-        let add = device_address.parse();
-        let addr: SocketAddr = add.unwrap();
-
-        let socket = new_bound_socket();
-        let server_port = socket.local_addr().unwrap().port();
-        let packet = build_osc_message("/sys/port", vec![OscType::Int(i32::from(server_port))]);
-        let bytes: Vec<u8> = encode(&packet).unwrap();
-        let socket = socket
-            .send_dgram(bytes, &addr)
-            .wait()
-            .map(|(s, _)| s)
-            .unwrap();
-
-        let local_addr = socket.local_addr().unwrap().ip();
-        let packet = build_osc_message("/sys/host", vec![OscType::String(local_addr.to_string())]);
-        let bytes: Vec<u8> = encode(&packet).unwrap();
-        let socket = socket
-            .send_dgram(bytes, &addr)
-            .wait()
-            .map(|(s, _)| s)
-            .unwrap();
-
-        let packet = build_osc_message("/sys/prefix", vec![OscType::String(prefix.into())]);
-        let bytes: Vec<u8> = encode(&packet).unwrap();
-        let socket = socket
-            .send_dgram(bytes, &addr)
-            .wait()
-            .map(|(s, _)| s)
-            .unwrap();
+        Monome::device_changes_with_port(SERIALOSC_PORT)
+    }
+    /// Receive the device added/removed notifications on a channel, on a non-standard serialosc
+    /// port. The thread listening to the notifications exits when a notification arrives after
+    /// the receiver has been dropped, or when `stop_device_change_listeners` is called.
+    ///
+    /// # Arguments
+    ///
+    /// * `serialosc_port`: the port on which serialosc is running
+    pub fn device_changes_with_port(
+        serialosc_port: i32,
+    ) -> Result<crossbeam::channel::Receiver<DeviceChangeEvent>, MonomeError> {
+        Monome::device_changes_on(LOCALHOST, serialosc_port)
+    }
+    /// Receive the device added/removed notifications on a channel, from serialosc running on
+    /// another host, for example a Raspberry Pi on the local network. See
+    /// `device_changes_with_port`.
+    ///
+    /// # Arguments
+    ///
+    /// * `host`: the host on which serialosc is running
+    /// * `serialosc_port`: the port on which serialosc is running
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::IpAddr;
+    /// use monome::{Monome, SERIALOSC_PORT};
+    /// let host: IpAddr = "192.168.1.20".parse().unwrap();
+    /// let changes = Monome::device_changes_on(host, SERIALOSC_PORT).unwrap();
+    /// for change in changes {
+    ///     println!("{:?}", change);
+    /// }
+    /// ```
+    pub fn device_changes_on(
+        host: IpAddr,
+        serialosc_port: i32,
+    ) -> Result<crossbeam::channel::Receiver<DeviceChangeEvent>, MonomeError> {
+        let socket = new_listener_socket(host)?;
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
 
-        let packet = build_osc_message("/sys/info", vec![]);
-        let bytes: Vec<u8> = encode(&packet).unwrap();
-        let mut socket = socket
-            .send_dgram(bytes, &addr)
-            .wait()
-            .map(|(s, _)| s)
-            .unwrap();
+        let serialosc = SocketAddr::new(host, serialosc_port as u16);
+        let listener = thread::spawn(move || {
+            listen_device_changes(socket, serialosc, &stop, |event| {
+                // Nobody is listening anymore.
+                if sender.send(event).is_err() {
+                    stop.store(true, Ordering::SeqCst);
+                }
+            });
+        });
+        register_device_change_listener(stopped, listener);
+        Ok(receiver)
+    }
+    /// Stop all the threads listening to device changes, registered with
+    /// `register_device_change_callback` or `register_device_change_callback_with_port`, and wait
+    /// for them to exit. The callbacks are not called anymore, and serialosc is not asked to
+    /// notify their ports again. This should be called before the application exits.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// Monome::register_device_change_callback(|event| println!("{:?}", event)).unwrap();
+    /// // ...
+    /// Monome::stop_device_change_listeners();
+    /// ```
+    pub fn stop_device_change_listeners() {
+        let listeners: Vec<_> = DEVICE_CHANGE_LISTENERS.lock().unwrap().drain(..).collect();
+        for (_, stopped, _) in &listeners {
+            stopped.store(true, Ordering::SeqCst);
+        }
+        for (_, _, listener) in listeners {
+            if listener.join().is_err() {
+                error!("A device change callback panicked");
+            }
+        }
+    }
+    /// Tell the device at `addr` to send its events to a new socket, with `prefix`, and wait
+    /// for its informations, for at most `timeout`.
+    fn setup(
+        prefix: &str,
+        addr: SocketAddr,
+        timeout: Duration,
+    ) -> Result<(MonomeInfo, std::net::UdpSocket), MonomeError> {
+        let socket = new_bound_socket(addr.ip())?;
+        let info = Monome::setup_on(&socket, prefix, addr, timeout)?;
+        Ok((info, socket))
+    }
+    /// Tell the device at `addr` to send its events to `socket`, with `prefix`, and wait for its
+    /// informations, for at most `timeout`.
+    fn setup_on(
+        socket: &std::net::UdpSocket,
+        prefix: &str,
+        addr: SocketAddr,
+        timeout: Duration,
+    ) -> Result<MonomeInfo, MonomeError> {
+        let local_addr = reply_addr(socket, addr)?;
+        for bytes in setup_packets(local_addr, prefix)? {
+            socket
+                .send_to(&bytes, addr)
+                .map_err(MonomeError::DeviceUnreachable)?;
+        }
 
-        let mut info = MonomeInfo::new();
+        let mut info = MonomeInfo::new(prefix);
+        let mut buf = vec![0u8; 1024];
 
         // Loop until we've received all the /sys/info messages
-        let socket = loop {
-            socket = socket
-                .recv_dgram(vec![0u8; 1024])
-                .and_then(|(socket, data, _, _)| {
-                    let packet = decode(&data).unwrap();
-                    info.fill(packet);
-                    Ok(socket)
-                })
-                .wait()
-                .map(|socket| socket)
-                .unwrap();
-
-            if info.complete() {
-                break socket;
+        let deadline = Instant::now() + timeout;
+        while !info.complete() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Err(setup_timed_out());
             }
-        };
+            socket
+                .set_read_timeout(Some(remaining))
+                .map_err(MonomeError::Bind)?;
+            let amt = match socket.recv_from(&mut buf) {
+                Ok((amt, _)) => amt,
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Err(setup_timed_out())
+                }
+                Err(e) => return Err(MonomeError::DeviceUnreachable(e)),
+            };
+            info.fill_datagram(&buf[..amt]);
+        }
 
-        Ok((info, socket, name, device_type, port))
+        Ok(info)
     }
     /// Enumerate all monome devices on a non-standard serialosc port.
     ///
@@ -675,70 +1476,155 @@ impl Monome {
     ///         }
     ///     }
     /// ```
-    pub fn enumerate_devices_with_port(serialosc_port: i32) -> Result<Vec<MonomeDevice>, String> {
-        let socket = new_bound_socket();
+    pub fn enumerate_devices_with_port(
+        serialosc_port: i32,
+    ) -> Result<Vec<MonomeDevice>, MonomeError> {
+        Monome::enumerate_devices_on(LOCALHOST, serialosc_port)
+    }
+    /// Enumerate all monome devices of the serialosc instance running on another host, for
+    /// example a Raspberry Pi on the local network. The devices returned are set up on that host
+    /// by `from_device` and the other constructors taking a `MonomeDevice`.
+    ///
+    /// # Arguments
+    ///
+    /// * `host`: the host on which serialosc is running
+    /// * `serialosc_port`: the port on which serialosc is running
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::IpAddr;
+    /// use monome::{Monome, SERIALOSC_PORT};
+    /// let host: IpAddr = "192.168.1.20".parse().unwrap();
+    /// let devices = Monome::enumerate_devices_on(host, SERIALOSC_PORT).unwrap();
+    /// let mut monome = Monome::from_device(&devices[0], "/prefix").unwrap();
+    /// ```
+    pub fn enumerate_devices_on(
+        host: IpAddr,
+        serialosc_port: i32,
+    ) -> Result<Vec<MonomeDevice>, MonomeError> {
+        Monome::enumerate_devices_at(SocketAddr::new(host, serialosc_port as u16))
+    }
+    /// Enumerate all monome devices of the serialosc instance running at `serialosc`, an IPv4 or
+    /// IPv6 address. See `enumerate_devices_on`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let devices = Monome::enumerate_devices_at("[::1]:12002".parse().unwrap()).unwrap();
+    /// ```
+    pub fn enumerate_devices_at(serialosc: SocketAddr) -> Result<Vec<MonomeDevice>, MonomeError> {
         let mut devices = Vec::<MonomeDevice>::new();
-        let server_port = socket.local_addr().unwrap().port();
-        let server_ip = socket.local_addr().unwrap().ip().to_string();
-
-        let packet = build_osc_message(
-            "/serialosc/list",
-            vec![
-                OscType::String(server_ip),
-                OscType::Int(i32::from(server_port)),
-            ],
-        );
-
-        let bytes: Vec<u8> = encode(&packet).unwrap();
-
-        let addr = format!("127.0.0.1:{}", serialosc_port).parse().unwrap();
-        let (mut socket, _) = socket.send_dgram(bytes, &addr).wait().unwrap();
+        Monome::enumerate_devices_with(serialosc, |device| devices.push(device))?;
+        Ok(devices)
+    }
+    /// Enumerate all monome devices on a non-standard serialosc port, from an asynchronous
+    /// context. This must be called from within a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * `serialosc_port`: the port on which serialosc is running
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::{Monome, SERIALOSC_PORT};
+    /// # async fn run() {
+    /// let devices = Monome::enumerate_devices_async(SERIALOSC_PORT).await.unwrap();
+    /// for device in &devices {
+    ///     println!("{}", device);
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn enumerate_devices_async(
+        serialosc_port: i32,
+    ) -> Result<Vec<MonomeDevice>, MonomeError> {
+        let mut devices = Vec::<MonomeDevice>::new();
+        runtime::enumerate_devices(local(serialosc_port), |device| devices.push(device)).await?;
+        Ok(devices)
+    }
+    /// Enumerate all monome devices on a non-standard port on which serialosc runs, returning
+    /// each device as soon as serialosc reports it, instead of waiting for the enumeration to be
+    /// complete. This is useful to populate a device picker without delay.
+    ///
+    /// The devices are received on the returned channel, that is disconnected when the
+    /// enumeration is complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `serialosc_port`: the port on which serialosc is running
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    ///     use monome::{Monome, SERIALOSC_PORT};
+    ///     for device in Monome::enumerate_devices_streaming(SERIALOSC_PORT) {
+    ///         println!("{}", device);
+    ///     }
+    ///     println!("Enumeration complete");
+    /// ```
+    pub fn enumerate_devices_streaming(
+        serialosc_port: i32,
+    ) -> crossbeam::channel::Receiver<MonomeDevice> {
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        thread::spawn(move || {
+            let enumeration = Monome::enumerate_devices_with(local(serialosc_port), |device| {
+                // The receiver may have been dropped, if the application is not interested
+                // anymore.
+                let _ = sender.send(device);
+            });
+            if let Err(e) = enumeration {
+                error!("{}", e);
+            }
+        });
+        receiver
+    }
+    /// Ask the serialosc instance at `serialosc` for the list of devices, and call `found` for
+    /// each device reported, until the enumeration is complete.
+    fn enumerate_devices_with<F>(serialosc: SocketAddr, mut found: F) -> Result<(), MonomeError>
+    where
+        F: FnMut(MonomeDevice),
+    {
+        let socket = new_bound_socket(serialosc.ip())?;
+        let bytes = enumeration_request(reply_addr(&socket, serialosc)?)?;
+        socket
+            .send_to(&bytes, serialosc)
+            .map_err(MonomeError::SerialoscUnreachable)?;
         // loop until we find the device list message. It can be that some other messages are
         // received in the meantime, for example, tilt messages, or keypresses. Ignore them
         // here. If no message have been received for 500ms, consider we have all the messages and
         // carry on.
+        socket
+            .set_read_timeout(Some(Duration::from_millis(DEVICE_ENUMERATION_TIMEOUT_MS)))
+            .map_err(MonomeError::Bind)?;
+        let mut buf = vec![0u8; 1024];
+        // Whether serialosc has answered, to tell it from a serialosc that is not running.
+        let mut answered = false;
         loop {
-            let fut = socket.recv_dgram(vec![0u8; 1024]).select2(Delay::new(
-                Instant::now() + Duration::from_millis(DEVICE_ENUMERATION_TIMEOUT_MS),
-            ));
-            let task = tokio::runtime::current_thread::block_on_all(fut);
-            socket = match task {
-                Ok(Either::A(((s, data, _, _), _))) => {
-                    socket = s;
-                    let packet = decode(&data).unwrap();
-
-                    match packet {
-                        OscPacket::Message(message) => {
-                            if message.addr == "/serialosc/device" {
-                                if let Some(args) = &message.args {
-                                    if let [OscType::String(ref name), OscType::String(ref device_type), OscType::Int(port)] =
-                                        args.as_slice()
-                                    {
-                                        devices.push(MonomeDevice::new(name, device_type, *port));
-                                    }
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        OscPacket::Bundle(_bundle) => {
-                            eprintln!("Unexpected bundle received during setup");
-                        }
-                    };
-
-                    socket
-                }
-                Ok(Either::B(_)) => {
-                    // timeout
-                    break;
-                }
-                Err(e) => {
-                    panic!("{:?}", e);
+            let amt = match socket.recv_from(&mut buf) {
+                Ok((amt, _)) => amt,
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    if !answered {
+                        return Err(enumeration_timed_out());
+                    }
+                    return Ok(());
                 }
+                Err(e) => return Err(MonomeError::SerialoscUnreachable(e)),
             };
+            answered = true;
+            for listed in parse_listed(&buf[..amt], serialosc) {
+                match listed {
+                    Listed::Device(device) => found(device),
+                    Listed::End => return Ok(()),
+                    Listed::Ignored => {}
+                }
+            }
         }
-
-        Ok(devices)
     }
     /// Enumerate all monome devices on the standard port on which serialosc runs (12002).
     ///
@@ -767,9 +1653,62 @@ impl Monome {
     ///         }
     ///      }
     /// ```
-    pub fn enumerate_devices() -> Result<Vec<MonomeDevice>, String> {
+    pub fn enumerate_devices() -> Result<Vec<MonomeDevice>, MonomeError> {
         Monome::enumerate_devices_with_port(SERIALOSC_PORT)
     }
+    /// Enumerate all monome devices on multiple serialosc instances, for setups that run more
+    /// than one serialosc daemon. The instances are queried in parallel, and their device lists
+    /// merged. A device reported by more than one instance is only listed once, with the first
+    /// instance that reported it.
+    ///
+    /// # Arguments
+    ///
+    /// * `serialosc_ports`: the ports on which serialosc instances are running, for example a
+    ///   list or a range.
+    ///
+    /// # Example
+    ///
+    /// Enumerate and display all monome device on ports 12002 to 12009:
+    ///
+    /// ```no_run
+    ///     use monome::Monome;
+    ///     let enumeration = Monome::enumerate_devices_with_ports(12002..12010);
+    ///     match enumeration {
+    ///         Ok(devices) => {
+    ///             for device in &devices {
+    ///                println!("{} (serialosc on {})", device, device.serialosc_port());
+    ///             }
+    ///         }
+    ///         Err(e) => {
+    ///             eprintln!("Error: {}", e);
+    ///         }
+    ///     }
+    /// ```
+    pub fn enumerate_devices_with_ports<I>(
+        serialosc_ports: I,
+    ) -> Result<Vec<MonomeDevice>, MonomeError>
+    where
+        I: IntoIterator<Item = i32>,
+    {
+        let threads: Vec<_> = serialosc_ports
+            .into_iter()
+            .map(|port| thread::spawn(move || Monome::enumerate_devices_with_port(port)))
+            .collect();
+
+        let mut devices = Vec::<MonomeDevice>::new();
+        for thread in threads {
+            let found = match thread.join() {
+                Ok(found) => found?,
+                Err(_) => return Err(MonomeError::EnumerationPanicked),
+            };
+            for device in found {
+                if !devices.iter().any(|d| d.name == device.name) {
+                    devices.push(device);
+                }
+            }
+        }
+        Ok(devices)
+    }
     /// Sets up the "first" monome device, with a particular prefix. When multiple devices are
     /// plugged in, it's unclear which one is activated, however this is rare.
     ///
@@ -794,7 +1733,7 @@ impl Monome {
     ///   }
     /// }
     /// ```
-    pub fn new<S>(prefix: S) -> Result<Monome, String>
+    pub fn new<S>(prefix: S) -> Result<Monome, MonomeError>
     where
         S: Into<String>,
     {
@@ -827,756 +1766,2823 @@ impl Monome {
     ///   }
     /// }
     /// ```
-    pub fn new_with_port<S>(prefix: S, serialosc_port: i32) -> Result<Monome, String>
+    pub fn new_with_port<S>(prefix: S, serialosc_port: i32) -> Result<Monome, MonomeError>
     where
         S: Into<String>,
     {
         let devices = Monome::enumerate_devices_with_port(serialosc_port)?;
         if devices.is_empty() {
-            return Err("No devices detected".to_string());
+            return Err(MonomeError::NoDevice);
         }
         Monome::from_device(&devices[0], prefix.into())
     }
-    /// Get a monome instance on which to call commands, from a `MonomeDevice`.
+    /// Sets up the monome device with the serial number `id`, e.g. "m1000123", with a particular
+    /// prefix. This fails with `MonomeError::NoDevice` if this device is not plugged in, so that
+    /// an application using several devices always talks to the intended one.
     ///
     /// # Arguments
     ///
-    /// * `device`: a `MonomeDevice` acquired through `enumerate_devices`.
-    /// * `prefix`: the prefix to use for this device and this application
+    /// * `prefix` - the prefix to use for this device and this application
+    /// * `id` - the serial number of the device, as reported by serialosc.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use monome::Monome;
-    /// let enumeration = Monome::enumerate_devices();
-    /// match enumeration {
-    ///     Ok(devices) => {
-    ///         for device in &devices {
-    ///             println!("{}", device);
-    ///             match Monome::from_device(device, "prefix") {
-    ///                 Ok(m) => {
-    ///                     println!("Monome setup:\n{}", m);
-    ///                 }
-    ///                 Err(e) => {
-    ///                     println!("Error setting up {} ({})", device, e);
-    ///                 }
-    ///             }
-    ///         }
-    ///     }
-    ///     Err(e) => {
-    ///         eprintln!("Error: {}", e);
-    ///     }
-    /// }
+    /// let mut left = Monome::new_with_id("/left", "m1000123").unwrap();
+    /// let mut right = Monome::new_with_id("/right", "m1000456").unwrap();
     /// ```
-    pub fn from_device<S>(device: &MonomeDevice, prefix: S) -> Result<Monome, String>
+    pub fn new_with_id<S>(prefix: S, id: &str) -> Result<Monome, MonomeError>
     where
         S: Into<String>,
     {
-        let prefix = prefix.into();
-        let (info, socket, name, device_type, device_port) = Monome::setup(&*prefix, device)?;
-
-        let (sender, receiver) = futures::sync::mpsc::channel(16);
-        let q = Arc::new(ArrayQueue::new(32));
-        let q2 = q.clone();
-        let t = Transport::new(device_port, socket, q, receiver);
-
-        thread::spawn(move || {
-            tokio::run(t.map_err(|e| error!("server error = {:?}", e)));
-        });
-
-        Ok(Monome {
-            tx: sender,
-            q: q2,
-            name: name.to_string(),
-            device_type,
-            host: info.host.unwrap(),
-            id: info.id.unwrap(),
-            port: device_port,
-            prefix,
-            rotation: info.rotation.unwrap(),
-            size: info.size.unwrap(),
-        })
+        Monome::new_with_id_on(prefix, id, LOCALHOST, SERIALOSC_PORT)
     }
-
-    /// Set a single led on a grid on or off.
+    /// Sets up the monome device with the serial number `id`, with a particular prefix, from the
+    /// serialosc instance running on `host`, on `serialosc_port`. See `new_with_id`.
     ///
     /// # Arguments
     ///
-    /// - `x` - the horizontal position of the led to set.
-    /// - `y` - the vertical positino of the led to set.
-    /// - `arg` - either a bool, true to set a led On, false to set it Off, or a number between 0
-    /// and 16, 0 being led off, 16 being full led brightness.
+    /// * `prefix` - the prefix to use for this device and this application
+    /// * `id` - the serial number of the device, as reported by serialosc.
+    /// * `host`: the host on which serialosc is running
+    /// * `serialosc_port`: the port on which serialosc is running
     ///
     /// # Example
     ///
-    /// Set the led on the second row and second column to On, and also the third row and second
-    /// column to mid-brightness:
-    ///
     /// ```no_run
-    /// # use monome::Monome;
-    /// # let mut monome = Monome::new("/prefix").unwrap();
-    /// monome.set(1 /* 2nd, 0-indexed */,
-    ///            1 /* 2nd, 0-indexed */,
-    ///            true);
-    /// monome.set(1 /* 2nd, 0-indexed */,
-    ///            2 /* 3nd, 0-indexed */,
-    ///            8);
+    /// use std::net::IpAddr;
+    /// use monome::{Monome, SERIALOSC_PORT};
+    /// let host: IpAddr = "192.168.1.20".parse().unwrap();
+    /// let monome = Monome::new_with_id_on("/prefix", "m1000123", host, SERIALOSC_PORT);
     /// ```
-    pub fn set<'a, A>(&mut self, x: i32, y: i32, arg: A)
+    pub fn new_with_id_on<S>(
+        prefix: S,
+        id: &str,
+        host: IpAddr,
+        serialosc_port: i32,
+    ) -> Result<Monome, MonomeError>
     where
-        A: IntoAddrAndArgs<'a, OscType>,
+        S: Into<String>,
     {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
+        let devices = Monome::enumerate_devices_on(host, serialosc_port)?;
+        match devices.iter().find(|device| device.name == id) {
+            Some(device) => Monome::from_device(device, prefix.into()),
+            None => Err(MonomeError::NoDevice),
         }
-        let (frag, arg) = arg.as_addr_frag_and_args();
-        self.send(
-            &format!("/grid/led/{}set", frag).to_string(),
-            vec![OscType::Int(x), OscType::Int(y), arg],
-        );
     }
-
-    /// Set all led of the grid to an intensity
+    /// Sets up the first monome device whose serial number or model contains `name`, e.g. "arc"
+    /// or "monome 128", with a particular prefix. The devices are considered in the order of
+    /// their serial numbers, so the same device is chosen each time. This fails with
+    /// `MonomeError::NoDevice` if no device matches.
     ///
     /// # Arguments
     ///
-    /// * `intensity` - either a bool, true for led On or false for led Off, or a number between 0
-    /// and 16, 0 being led off, and 16 being full led brightness.
+    /// * `prefix` - the prefix to use for this device and this application
+    /// * `name` - a part of the serial number or of the model of the device.
     ///
     /// # Example
     ///
-    /// On a grid, set all led to medium brightness, then turn it on:
-    ///
     /// ```no_run
-    /// # use monome::Monome;
-    /// let mut monome = Monome::new("/prefix").unwrap();
-    /// monome.all(8);
-    /// monome.all(false);
+    /// use monome::Monome;
+    /// let mut arc = Monome::new_with_name("/arc", "arc").unwrap();
     /// ```
-    pub fn all<'a, A>(&mut self, arg: A)
+    pub fn new_with_name<S>(prefix: S, name: &str) -> Result<Monome, MonomeError>
     where
-        A: IntoAddrAndArgs<'a, OscType>,
+        S: Into<String>,
     {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
-        }
-        let (frag, arg) = arg.as_addr_frag_and_args();
-        self.send(&format!("/grid/led/{}all", frag).to_string(), vec![arg]);
+        Monome::new_with_name_on(prefix, name, LOCALHOST, SERIALOSC_PORT)
     }
-
-    /// Set all the leds of a monome in one call.
+    /// Sets up the first monome device whose serial number or model contains `name`, with a
+    /// particular prefix, from the serialosc instance running on `host`, on `serialosc_port`.
+    /// See `new_with_name`.
     ///
     /// # Arguments
     ///
-    /// * `leds` - a vector of 64 booleans for a monome 64, 128 elements for a monome 128, and 256
-    /// elements for a monome 256, packed in row order.
+    /// * `prefix` - the prefix to use for this device and this application
+    /// * `name` - a part of the serial number or of the model of the device.
+    /// * `host`: the host on which serialosc is running
+    /// * `serialosc_port`: the port on which serialosc is running
     ///
     /// # Example
     ///
-    /// One a monome 128, do a checkerboard pattern:
-    ///
     /// ```no_run
-    /// # use monome::Monome;
-    /// let mut monome = Monome::new("/prefix").unwrap();
-    /// let mut grid = [false; 128];
-    /// for i in 0..128 {
-    ///   grid[i] = (i + 1) % 2 == 0;
-    /// }
-    /// monome.set_all(&grid);
+    /// use std::net::IpAddr;
+    /// use monome::{Monome, SERIALOSC_PORT};
+    /// let host: IpAddr = "192.168.1.20".parse().unwrap();
+    /// let mut arc = Monome::new_with_name_on("/arc", "arc", host, SERIALOSC_PORT).unwrap();
     /// ```
-    pub fn set_all(&mut self, leds: &[bool]) {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
-        }
-        let width_in_quad = self.size.0 / 8;
-        let height_in_quad = self.size.1 / 8;
-        let width = self.size.0;
-        let quad_size: i32 = 8;
-
-        let mut masks = [0 as u8; 8];
-        for a in 0..height_in_quad {
-            for b in 0..width_in_quad {
-                for i in 0..8 {
-                    // for each row
-                    let mut mask: u8 = 0;
-                    for j in (0..8).rev() {
-                        // create mask
-                        let idx = toidx(b * quad_size + j, a * quad_size + i, width);
-                        mask = mask.rotate_left(1) | if leds[idx] { 1 } else { 0 };
-                    }
-                    masks[i as usize] = mask;
-                }
-                self.map(b * 8, a * 8, &masks);
-            }
+    pub fn new_with_name_on<S>(
+        prefix: S,
+        name: &str,
+        host: IpAddr,
+        serialosc_port: i32,
+    ) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let mut devices = Monome::enumerate_devices_on(host, serialosc_port)?;
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        match devices
+            .iter()
+            .find(|device| device.name.contains(name) || device.model.contains(name))
+        {
+            Some(device) => Monome::from_device(device, prefix.into()),
+            None => Err(MonomeError::NoDevice),
         }
     }
-
-    /// Set all the leds of a monome in one call.
+    /// Sets up a monome device, with a particular prefix, and applies `options` before returning
+    /// it. When multiple devices are plugged in, the device is chosen according to the
+    /// preferences of `options`, and the "first" device is used if none of them matches.
     ///
     /// # Arguments
     ///
-    /// * `leds` - a vector of 64 integers in [0, 15] for a monome 64, 128 elements for a monome
-    /// 128, and 256 elements for a monome 256, packed in row order.
+    /// * `prefix` - the prefix to use for this device and this application
+    /// * `options` - what to do when setting up the device.
     ///
     /// # Example
     ///
-    /// One a monome 128, do a gradient
+    /// Set up a monome, with a prefix of "/prefix", and turn off all its leds:
     ///
     /// ```no_run
-    /// # use monome::Monome;
-    ///
-    /// let mut m = Monome::new("/prefix").unwrap();
-    /// let mut grid: Vec<u8> = vec!(0; 128);
-    /// for i in 0..8 {
-    ///     for j in 0..16 {
-    ///         grid[i * 16 + j] = (2 * i) as u8;
-    ///     }
-    /// }
-    /// m.set_all_intensity(&grid);
+    /// use monome::{Monome, StartupOptions};
+    /// let mut options = StartupOptions::default();
+    /// options.clear_leds = true;
+    /// let m = Monome::new_with_options("/prefix", &options);
     /// ```
-    pub fn set_all_intensity(&mut self, leds: &[u8]) {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
-        }
-        let width_in_quad = self.size.0 / 8;
-        let height_in_quad = self.size.1 / 8;
-        let width = self.size.0;
-        let quad_size = 8;
-
-        let mut quad = [0 as u8; 64];
-        for a in 0..height_in_quad {
-            for b in 0..width_in_quad {
-                // Get the quad into an array
-                for i in 0..8 as i32 {
-                    for j in 0..8 as i32 {
-                        let idx = toidx(b * quad_size + j, a * quad_size + i, width);
-                        quad[(i * 8 + j) as usize] = leds[idx];
-                    }
-                }
-                self.map(b * 8, a * 8, &quad);
-            }
-        }
+    pub fn new_with_options<S>(prefix: S, options: &StartupOptions) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let devices = Monome::enumerate_devices_with_port(SERIALOSC_PORT)?;
+        let device = match choose_device(&devices, &options.preferences) {
+            Some(device) => device,
+            None => return Err(MonomeError::NoDevice),
+        };
+        Monome::from_device_with_options(device, prefix.into(), options)
     }
-
-    /// Set the value an 8x8 quad of led on a monome grid.
+    /// Get a monome instance on which to call commands, from a `MonomeDevice`.
     ///
     /// # Arguments
     ///
-    /// * `x_offset` - at which offset, that must be a multiple of 8, to set the quad.
-    /// * `y_offset` - at which offset, that must be a multiple of 8, to set the quad.
-    /// * `masks` - a vector of 8 unsigned 8-bit integers that is a mask representing the leds to
-    /// light up, or a vector of 64 bools, true for led On, false for led Off, packed in row order,
-    /// or a vector of 64 integers between 0 and 15, for the brightness of each led, packed in
-    /// row order.
+    /// * `device`: a `MonomeDevice` acquired through `enumerate_devices`.
+    /// * `prefix`: the prefix to use for this device and this application
     ///
     /// # Example
     ///
-    /// On a monome 128, draw a triangle in the lower left half of the rightmost half, and a
-    /// gradient on the leftmost half.
     /// ```no_run
-    /// # extern crate monome;
-    /// # use monome::Monome;
-    /// let mut monome = Monome::new("/prefix").unwrap();
-    /// let mut v = [0; 64];
-    /// for i in 0..64 {
-    ///     v[i] = (i / 4) as u8;
+    /// use monome::Monome;
+    /// let enumeration = Monome::enumerate_devices();
+    /// match enumeration {
+    ///     Ok(devices) => {
+    ///         for device in &devices {
+    ///             println!("{}", device);
+    ///             match Monome::from_device(device, "prefix") {
+    ///                 Ok(m) => {
+    ///                     println!("Monome setup:\n{}", m);
+    ///                 }
+    ///                 Err(e) => {
+    ///                     println!("Error setting up {} ({})", device, e);
+    ///                 }
+    ///             }
+    ///         }
+    ///     }
+    ///     Err(e) => {
+    ///         eprintln!("Error: {}", e);
+    ///     }
     /// }
-    /// monome.map(0, 0, &v);
-    /// monome.map(8, 0, &[1, 3, 7, 15, 32, 63, 127, 0b11111111]);
     /// ```
-    pub fn map<'a, A>(&mut self, x_offset: i32, y_offset: i32, masks: A)
+    pub fn from_device<S>(device: &MonomeDevice, prefix: S) -> Result<Monome, MonomeError>
     where
-        A: IntoAddrAndArgs<'a, Vec<OscType>> + Sized,
+        S: Into<String>,
     {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
-        }
-        let (frag, mut arg) = masks.as_addr_frag_and_args();
-
-        let mut args = Vec::with_capacity(2 + arg.len());
+        Monome::from_device_with_options(device, prefix, &StartupOptions::default())
+    }
 
-        args.push(OscType::Int(x_offset));
-        args.push(OscType::Int(y_offset));
-        args.append(&mut arg);
+    /// Get a monome instance on which to call commands, from a `MonomeDevice`, and apply
+    /// `options` before returning it.
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: a `MonomeDevice` acquired through `enumerate_devices`.
+    /// * `prefix`: the prefix to use for this device and this application
+    /// * `options` - what to do when setting up the device. The device preferences are ignored.
+    pub fn from_device_with_options<S>(
+        device: &MonomeDevice,
+        prefix: S,
+        options: &StartupOptions,
+    ) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let prefix = prefix.into();
+        let (mut info, socket) = Monome::setup(&prefix, device.addr(), options.setup_timeout)?;
+        info.model = Some(device.model.clone());
+        info.serialosc = Some(device.serialosc_addr());
 
-        self.send(&format!("/grid/led/{}map", frag), args);
+        Monome::start(
+            info,
+            device.name.clone(),
+            device.device_type.clone(),
+            device.addr(),
+            prefix,
+            options,
+            |transport| transport.spawn(socket),
+        )
     }
 
-    /// Set a full row of a grid, using one or more 8-bit mask(s), or a vector containing booleans
-    /// or integer intensity values.
+    /// Get a monome instance on which to call commands, from a `MonomeDevice`, with its network
+    /// input and output running on the tokio runtime of `handle` instead of on threads of its
+    /// own. This allows a single runtime to host many devices, and the application to control
+    /// when it shuts down. This blocks during the setup of the device, and must not be called
+    /// from an asynchronous context: use `from_device_async` there.
     ///
     /// # Arguments
     ///
-    /// * `x_offset` - at which 8 button offset to start setting the leds. This is always 0 for a
-    /// 64, and can be 8 for a 128 or 256.
-    /// * `y` - which row to set, 0-indexed. This must be lower than the number of rows of the
-    /// device.
-    /// * `leds` - either the list of masks that determine the pattern to light on for a particular
-    /// 8 led long section, or a vector of either int or bool, one element for each led.
+    /// * `handle`: the handle of the runtime on which to run the input and output.
+    /// * `device`: a `MonomeDevice` acquired through `enumerate_devices`.
+    /// * `prefix`: the prefix to use for this device and this application
     ///
     /// # Example
     ///
-    /// On a monome 128, light up every other led of the right half of the 3rd  row:
-    ///
     /// ```no_run
-    /// # use monome::Monome;
-    /// let mut monome = Monome::new("/prefix").unwrap();
-    /// monome.row(8 /* rightmost half */,
-    ///            2 /* 3rd row, 0 indexed */,
-    ///            &0b01010101u8 /* every other led, 85 in decimal */);
+    /// use monome::Monome;
+    ///
+    /// let runtime = tokio::runtime::Builder::new_multi_thread()
+    ///     .enable_all()
+    ///     .build()
+    ///     .unwrap();
+    /// let devices = Monome::enumerate_devices().unwrap();
+    /// let monomes: Vec<Monome> = devices
+    ///     .iter()
+    ///     .map(|device| Monome::from_device_on(runtime.handle(), device, "/prefix").unwrap())
+    ///     .collect();
     /// ```
-    pub fn row<'a, A>(&mut self, x_offset: i32, y: i32, leds: &A)
+    #[cfg(feature = "async")]
+    pub fn from_device_on<S>(
+        handle: &tokio::runtime::Handle,
+        device: &MonomeDevice,
+        prefix: S,
+    ) -> Result<Monome, MonomeError>
     where
-        A: IntoAddrAndArgs<'a, Vec<OscType>>,
+        S: Into<String>,
     {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
-        }
-        let (frag, arg) = leds.as_addr_frag_and_args();
+        let prefix = prefix.into();
+        let options = StartupOptions::default();
+        let (mut info, socket) = Monome::setup(&prefix, device.addr(), options.setup_timeout)?;
+        info.model = Some(device.model.clone());
+        info.serialosc = Some(device.serialosc_addr());
 
-        let mut args = Vec::with_capacity((2 + arg.len()) as usize);
+        Monome::start(
+            info,
+            device.name.clone(),
+            device.device_type.clone(),
+            device.addr(),
+            prefix,
+            &options,
+            |transport| transport.spawn_on(handle, socket),
+        )
+    }
 
-        args.push(OscType::Int(x_offset));
-        args.push(OscType::Int(y));
-        args.append(&mut arg.to_vec());
+    /// Get a monome instance on which to call commands, from a `MonomeDevice`, from an
+    /// asynchronous context. This must be called from within a tokio runtime, on which the
+    /// network input and output of the device then runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: a `MonomeDevice` acquired through `enumerate_devices_async`.
+    /// * `prefix`: the prefix to use for this device and this application
+    #[cfg(feature = "async")]
+    pub async fn from_device_async<S>(
+        device: &MonomeDevice,
+        prefix: S,
+    ) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let prefix = prefix.into();
+        let options = StartupOptions::default();
+        let (mut info, socket) =
+            runtime::setup(&prefix, device.addr(), options.setup_timeout).await?;
+        info.model = Some(device.model.clone());
+        info.serialosc = Some(device.serialosc_addr());
 
-        self.send(&format!("/grid/led/{}row", frag), args);
+        Monome::start(
+            info,
+            device.name.clone(),
+            device.device_type.clone(),
+            device.addr(),
+            prefix,
+            &options,
+            |transport| Ok(transport.spawn_task(socket)),
+        )
     }
 
-    /// Set a full column of a grid, using one or more 8-bit mask(s), or a vector containing
-    /// booleans or integer intensity values.
+    /// Sets up the "first" monome device, with a particular prefix, from an asynchronous
+    /// context. This must be called from within a tokio runtime, on which the network input and
+    /// output of the device then runs.
     ///
     /// # Arguments
     ///
-    /// * `x` - which column to set 0-indexed. This must be lower than the number of columns of the
-    /// device.
-    /// * `y_offset` - at which 8 button offset to start setting the leds. This is always 0 for a
-    /// 64, and can be 8 for a 128 or 256.
-    /// * `leds` - either the list of masks that determine the pattern to light on for a particular
-    /// 8 led long section, or a vector of either int or bool, one element for each led.
+    /// * `prefix` - the prefix to use for this device and this application
     ///
     /// # Example
     ///
-    /// On a monome 256, light up every other led of the bottom half of the 3rd column from the
-    /// right:
-    ///
     /// ```no_run
     /// use monome::Monome;
-    /// let mut monome = Monome::new("/prefix").unwrap();
-    /// monome.col(2 /* 3rd column, 0-indexed */,
-    ///            8 /* bottom half */,
-    ///            &0b01010101u8 /* every other led, 85 in decimal */);
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let mut monome = Monome::connect_async("/prefix").await.unwrap();
+    ///     monome.all(true);
+    /// }
     /// ```
-    pub fn col<'a, A>(&mut self, x: i32, y_offset: i32, leds: &A)
+    #[cfg(feature = "async")]
+    pub async fn connect_async<S>(prefix: S) -> Result<Monome, MonomeError>
     where
-        A: IntoAddrAndArgs<'a, Vec<OscType>>,
+        S: Into<String>,
     {
-        if self.device_type != MonomeDeviceType::Grid {
-            error!("Called grid method on something that is not an grid.");
-            return;
+        let devices = Monome::enumerate_devices_async(SERIALOSC_PORT).await?;
+        if devices.is_empty() {
+            return Err(MonomeError::NoDevice);
         }
-        let (frag, mut arg) = leds.as_addr_frag_and_args();
+        Monome::from_device_async(&devices[0], prefix).await
+    }
 
-        let mut args = Vec::with_capacity((2 + arg.len()) as usize);
+    /// Get a monome instance on which to call commands, from the port of a device, without
+    /// asking serialosc for the list of devices. This is faster than the other constructors, but
+    /// the port of the device must be known in advance, for example from a configuration file.
+    ///
+    /// The name of the device is its id, and its type is deduced from its size: devices without
+    /// leds in a grid layout are considered to be arcs.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_port`: the port on which serialosc exposes the device.
+    /// * `prefix`: the prefix to use for this device and this application
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// match Monome::connect(15432, "/prefix") {
+    ///     Ok(m) => {
+    ///         println!("Monome setup:\n{}", m);
+    ///     }
+    ///     Err(e) => {
+    ///         println!("Error setting up the device on port 15432: {}", e);
+    ///     }
+    /// }
+    /// ```
+    pub fn connect<S>(device_port: i32, prefix: S) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        Monome::connect_to(local(device_port), prefix)
+    }
+
+    /// Get a monome instance on which to call commands, from the address of a device, that can
+    /// be on another host, without asking serialosc for the list of devices. See `connect`.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_addr`: the host of the device, and the port on which serialosc exposes it.
+    /// * `prefix`: the prefix to use for this device and this application
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let addr = "192.168.1.20:15432".parse().unwrap();
+    /// let mut monome = Monome::connect_to(addr, "/prefix").unwrap();
+    /// ```
+    pub fn connect_to<S>(device_addr: SocketAddr, prefix: S) -> Result<Monome, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let prefix = prefix.into();
+        let options = StartupOptions::default();
+        let (mut info, socket) = Monome::setup(&prefix, device_addr, options.setup_timeout)?;
+        info.serialosc = Some(SocketAddr::new(device_addr.ip(), SERIALOSC_PORT as u16));
+        let device_type = if info.size == Some((0, 0)) {
+            MonomeDeviceType::Arc
+        } else {
+            MonomeDeviceType::Grid
+        };
+        let name = info.id.clone().unwrap();
+
+        Monome::start(
+            info,
+            name,
+            device_type,
+            device_addr,
+            prefix,
+            &options,
+            |transport| transport.spawn(socket),
+        )
+    }
+
+    /// Create the client object for a device that has been set up, and start its transport with
+    /// `spawn`, that returns the channels on which to send messages to the device.
+    fn start<F>(
+        info: MonomeInfo,
+        name: String,
+        device_type: MonomeDeviceType,
+        device_addr: SocketAddr,
+        prefix: String,
+        options: &StartupOptions,
+        spawn: F,
+    ) -> Result<Monome, MonomeError>
+    where
+        F: FnOnce(Transport) -> Result<(Outgoing, Outgoing), MonomeError>,
+    {
+        let q = Arc::new(ArrayQueue::new(32));
+        let q2 = q.clone();
+        let shared = Arc::new(Shared::new(&prefix));
+        *shared.shadow.lock().unwrap() = LedShadow::new(info.size.unwrap());
+        let (sender, priority_sender) = spawn(Transport::new(device_addr, q, shared.clone()))?;
+
+        let mut monome = Monome {
+            tx: sender,
+            priority_tx: priority_sender,
+            shared,
+            pending: VecDeque::new(),
+            keys: KeyStateTracker::new(),
+            handlers: Handlers::default(),
+            quad_order: Vec::new(),
+            debug_frame: None,
+            scaling: IntensityScaling::new(),
+            frame_log: None,
+            dimmer: None,
+            actions: None,
+            adaptive_updates: false,
+            leds_sent: None,
+            batch: None,
+            undimmed: None,
+            serialosc: info.serialosc.unwrap_or_else(|| local(SERIALOSC_PORT)),
+            reconnector: None,
+            q: q2,
+            name,
+            device_type,
+            host: info.host.unwrap(),
+            id: info.id.unwrap(),
+            port: i32::from(device_addr.port()),
+            prefix,
+            rotation: info.rotation.unwrap(),
+            size: info.size.unwrap(),
+            model: info.model,
+        };
+        if monome.device_type == MonomeDeviceType::Grid && !monome.supports_quad_updates() {
+            info!(
+                "Grid size {}x{} is not a multiple of 8, some leds will be updated one by one.",
+                monome.size.0, monome.size.1
+            );
+        }
+        if monome.device_type == MonomeDeviceType::Grid && !monome.is_varibright() {
+            info!(
+                "Grid {} is not varibright, its leds will be on or off.",
+                monome.id
+            );
+            monome.set_monochrome_threshold(Some(8));
+        }
+        // These messages are queued before anything the application sends.
+        if let Some(intensity) = options.intensity {
+            if monome.device_type == MonomeDeviceType::Grid {
+                monome.intensity(intensity);
+            }
+        }
+        if options.clear_leds {
+            match monome.device_type {
+                MonomeDeviceType::Grid => monome.all(false),
+                MonomeDeviceType::Arc => {
+                    for n in 0..4 {
+                        monome.ring_all(n, 0);
+                    }
+                }
+                MonomeDeviceType::Unknown => {}
+            }
+        }
+        monome.resync();
+        Ok(monome)
+    }
+
+    /// Queue events describing the current state of the device: its size, rotation and prefix,
+    /// followed by a release event for each grid key currently held down. These events are
+    /// returned by `poll()` before any other event. This is done automatically when the device is
+    /// set up, so that an application can initialize its state from the events alone.
+    pub fn resync(&mut self) {
+        self.pending.push_back(MonomeEvent::Size {
+            width: self.size.0,
+            height: self.size.1,
+        });
+        self.pending.push_back(MonomeEvent::Rotation {
+            rotation: self.rotation,
+        });
+        self.pending.push_back(MonomeEvent::Prefix {
+            prefix: self.prefix.clone(),
+        });
+        for (x, y) in self.keys.release_all() {
+            self.pending.push_back(MonomeEvent::GridKey {
+                x,
+                y,
+                direction: KeyDirection::Up,
+            });
+        }
+    }
+
+    /// Returns true if the grid key at `x`, `y` is held down, according to the key events
+    /// returned by `poll()` so far.
+    ///
+    /// # Example
+    ///
+    /// Toggle the leds of the keys pressed while the top left key is held:
+    ///
+    /// ```no_run
+    /// use monome::{KeyDirection, Monome, MonomeEvent};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    ///
+    /// loop {
+    ///     if let Some(MonomeEvent::GridKey { x, y, direction: KeyDirection::Down }) = monome.poll() {
+    ///         if (x, y) != (0, 0) && monome.is_down(0, 0) {
+    ///             let on = monome.led(x, y) == 0;
+    ///             monome.set(x, y, on);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn is_down(&self, x: i32, y: i32) -> bool {
+        self.keys.is_down(x, y)
+    }
+
+    /// Get the grid keys held down, as `(x, y)`, in the order in which they have been pressed,
+    /// according to the key events returned by `poll()` so far.
+    pub fn held_keys(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.keys.held()
+    }
+
+    /// Set a single led on a grid on or off.
+    ///
+    /// # Arguments
+    ///
+    /// - `x` - the horizontal position of the led to set.
+    /// - `y` - the vertical positino of the led to set.
+    /// - `arg` - either a bool, true to set a led On, false to set it Off, or a number between 0
+    ///   and 16, 0 being led off, 16 being full led brightness.
+    ///
+    /// # Example
+    ///
+    /// Set the led on the second row and second column to On, and also the third row and second
+    /// column to mid-brightness:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// # let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set(1 /* 2nd, 0-indexed */,
+    ///            1 /* 2nd, 0-indexed */,
+    ///            true);
+    /// monome.set(1 /* 2nd, 0-indexed */,
+    ///            2 /* 3nd, 0-indexed */,
+    ///            8);
+    /// ```
+    pub fn set<'a, A>(&mut self, x: i32, y: i32, arg: A)
+    where
+        A: IntoAddrAndArgs<'a, OscType>,
+    {
+        if self.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
+            return;
+        }
+        let (frag, arg) = arg.as_addr_frag_and_args();
+        self.send(
+            &format!("/grid/led/{}set", frag).to_string(),
+            vec![OscType::Int(x), OscType::Int(y), arg],
+        );
+    }
+
+    /// Set a single led on a grid on or off, ahead of all the messages that have not been sent
+    /// yet. This is useful for critical visual feedback that must not be delayed by a large
+    /// update of the grid, such as a call to `set_all_intensity`.
+    ///
+    /// # Arguments
+    ///
+    /// - `x` - the horizontal position of the led to set.
+    /// - `y` - the vertical position of the led to set.
+    /// - `arg` - either a bool, true to set a led On, false to set it Off, or a number between 0
+    ///   and 16, 0 being led off, 16 being full led brightness.
+    ///
+    /// # Example
+    ///
+    /// Redraw the whole grid, and light up the playhead as soon as possible:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// # let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_all_intensity(&[4; 128]);
+    /// monome.set_urgent(3, 0, 15);
+    /// ```
+    pub fn set_urgent<'a, A>(&mut self, x: i32, y: i32, arg: A)
+    where
+        A: IntoAddrAndArgs<'a, OscType>,
+    {
+        if self.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
+            return;
+        }
+        let (frag, arg) = arg.as_addr_frag_and_args();
+        self.send_on(
+            &format!("/grid/led/{}set", frag),
+            vec![OscType::Int(x), OscType::Int(y), arg],
+            true,
+        );
+    }
+
+    /// Set all led of the grid to an intensity
+    ///
+    /// # Arguments
+    ///
+    /// * `intensity` - either a bool, true for led On or false for led Off, or a number between 0
+    ///   and 16, 0 being led off, and 16 being full led brightness.
+    ///
+    /// # Example
+    ///
+    /// On a grid, set all led to medium brightness, then turn it on:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.all(8);
+    /// monome.all(false);
+    /// ```
+    pub fn all<'a, A>(&mut self, arg: A)
+    where
+        A: IntoAddrAndArgs<'a, OscType>,
+    {
+        if self.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
+            return;
+        }
+        let (frag, arg) = arg.as_addr_frag_and_args();
+        self.send(&format!("/grid/led/{}all", frag).to_string(), vec![arg]);
+    }
+
+    /// Set all the leds of a monome in one call.
+    ///
+    /// # Arguments
+    ///
+    /// * `leds` - a vector of 64 booleans for a monome 64, 128 elements for a monome 128, and 256
+    ///   elements for a monome 256, packed in row order.
+    ///
+    /// Nothing is sent, and an error is logged, if there are fewer leds than on the grid: see
+    /// `try_set_all` to handle it.
+    ///
+    /// # Example
+    ///
+    /// One a monome 128, do a checkerboard pattern:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut grid = [false; 128];
+    /// for i in 0..128 {
+    ///   grid[i] = (i + 1) % 2 == 0;
+    /// }
+    /// monome.set_all(&grid);
+    /// ```
+    pub fn set_all(&mut self, leds: &[bool]) {
+        if self.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
+            return;
+        }
+        if let Err(e) = self.check_leds_len(leds.len()) {
+            error!("{}", e);
+            return;
+        }
+        if self.debug_frame.is_some() {
+            let intensities: Vec<u8> = leds.iter().map(|on| if *on { 15 } else { 0 }).collect();
+            self.log_frame_diff(&intensities);
+        }
+        let width = self.size.0;
+
+        let mut masks = [0_u8; 8];
+        for (x_offset, y_offset) in self.quad_offsets() {
+            for i in 0..8 {
+                // for each row
+                let mut mask: u8 = 0;
+                for j in (0..8).rev() {
+                    // create mask
+                    let idx = toidx(x_offset + j, y_offset + i, width);
+                    mask = mask.rotate_left(1) | if leds[idx] { 1 } else { 0 };
+                }
+                masks[i as usize] = mask;
+            }
+            // The quads are always on the grid.
+            let _ = self.map(x_offset, y_offset, &masks);
+        }
+
+        // Leds that are not part of a complete quad, on grids with a non-standard size.
+        for (x_offset, y) in self.partial_quad_rows() {
+            let mut mask: u8 = 0;
+            for j in (0..8).rev() {
+                let idx = toidx(x_offset + j, y, width);
+                mask = mask.rotate_left(1) | if leds[idx] { 1 } else { 0 };
+            }
+            let _ = self.row(x_offset, y, &mask);
+        }
+        for (x, y) in self.partial_quad_cells() {
+            let on = leds[toidx(x, y, width)];
+            self.set(x, y, on);
+        }
+    }
+
+    /// Set all the leds of a monome in one call.
+    ///
+    /// # Arguments
+    ///
+    /// * `leds` - a vector of 64 integers in [0, 15] for a monome 64, 128 elements for a monome
+    ///   128, and 256 elements for a monome 256, packed in row order.
+    ///
+    /// Nothing is sent, and an error is logged, if there are fewer leds than on the grid: see
+    /// `try_set_all_intensity` to handle it.
+    ///
+    /// # Example
+    ///
+    /// One a monome 128, do a gradient
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    ///
+    /// let mut m = Monome::new("/prefix").unwrap();
+    /// let mut grid: Vec<u8> = vec!(0; 128);
+    /// for i in 0..8 {
+    ///     for j in 0..16 {
+    ///         grid[i * 16 + j] = (2 * i) as u8;
+    ///     }
+    /// }
+    /// m.set_all_intensity(&grid);
+    /// ```
+    pub fn set_all_intensity(&mut self, leds: &[u8]) {
+        if self.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
+            return;
+        }
+        if let Err(e) = self.check_leds_len(leds.len()) {
+            error!("{}", e);
+            return;
+        }
+        let mut scaled = None;
+        if !self.scaling.is_empty() {
+            let mut leds = leds.to_vec();
+            self.scaling.apply(&mut leds, self.width());
+            scaled = Some(leds);
+        }
+        if let Some(ref mut dimmer) = self.dimmer {
+            let now = Instant::now();
+            self.undimmed = Some((leds.to_vec(), dimmer.level_at(now)));
+            let mut leds = scaled.unwrap_or_else(|| leds.to_vec());
+            dimmer.apply_at(&mut leds, now);
+            scaled = Some(leds);
+        }
+        let leds = scaled.as_deref().unwrap_or(leds);
+        if self.debug_frame.is_some() {
+            self.log_frame_diff(leds);
+        }
+        if self.adaptive_updates {
+            self.send_changes(leds);
+            return;
+        }
+        let width = self.size.0;
+
+        let mut quad = [0_u8; 64];
+        for (x_offset, y_offset) in self.quad_offsets() {
+            // Get the quad into an array
+            for i in 0..8_i32 {
+                for j in 0..8_i32 {
+                    let idx = toidx(x_offset + j, y_offset + i, width);
+                    quad[(i * 8 + j) as usize] = leds[idx];
+                }
+            }
+            // The quads are always on the grid.
+            let _ = self.map(x_offset, y_offset, &quad);
+        }
+
+        // Leds that are not part of a complete quad, on grids with a non-standard size.
+        for (x_offset, y) in self.partial_quad_rows() {
+            let mut args = vec![OscType::Int(x_offset), OscType::Int(y)];
+            for j in 0..8 {
+                args.push(OscType::Int(i32::from(leds[toidx(x_offset + j, y, width)])));
+            }
+            self.send("/grid/led/level/row", args);
+        }
+        for (x, y) in self.partial_quad_cells() {
+            let intensity = i32::from(leds[toidx(x, y, width)]);
+            self.set(x, y, intensity);
+        }
+    }
+
+    /// Set all the leds of a grid on or off, like `set_all`, but returns an error instead of
+    /// logging it if there are fewer leds in `leds` than on the grid, or if the device is not a
+    /// grid.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::{Monome, MonomeError};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// match monome.try_set_all(&[true; 64]) {
+    ///     Err(MonomeError::InvalidLength { expected, .. }) => println!("{} leds needed", expected),
+    ///     _ => {}
+    /// }
+    /// ```
+    pub fn try_set_all(&mut self, leds: &[bool]) -> Result<(), MonomeError> {
+        self.check_device_type(MonomeDeviceType::Grid)?;
+        self.check_leds_len(leds.len())?;
+        self.set_all(leds);
+        Ok(())
+    }
+
+    /// Set the intensity of all the leds of a grid, like `set_all_intensity`, but returns an
+    /// error instead of logging it if there are fewer leds in `leds` than on the grid, or if the
+    /// device is not a grid.
+    pub fn try_set_all_intensity(&mut self, leds: &[u8]) -> Result<(), MonomeError> {
+        self.check_device_type(MonomeDeviceType::Grid)?;
+        self.check_leds_len(leds.len())?;
+        self.set_all_intensity(leds);
+        Ok(())
+    }
+
+    /// Returns an error if `len` leds are fewer than the leds of this device.
+    fn check_leds_len(&self, len: usize) -> Result<(), MonomeError> {
+        let expected = self.width() * self.height();
+        if len < expected {
+            return Err(MonomeError::InvalidLength {
+                expected,
+                actual: len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sends the intensities of `leds` that differ from the ones the device is known to display,
+    /// choosing for each quad the messages that take the fewest bytes, or all of them if they are
+    /// not known.
+    fn send_changes(&mut self, leds: &[u8]) {
+        let width = self.size.0;
+        let mut previous = self.leds_sent.take();
+        if self.shared.leds_changed.swap(false, Ordering::SeqCst) {
+            previous = None;
+        }
+        let quad_at = |leds: &[u8], x_offset: i32, y_offset: i32| {
+            let mut quad = [0_u8; 64];
+            for i in 0..8_i32 {
+                for j in 0..8_i32 {
+                    quad[(i * 8 + j) as usize] = leds[toidx(x_offset + j, y_offset + i, width)];
+                }
+            }
+            quad
+        };
+
+        let mut updates = Vec::new();
+        for (x_offset, y_offset) in self.quad_offsets() {
+            let quad = quad_at(leds, x_offset, y_offset);
+            let update = match previous {
+                Some(ref previous) => plan_quad_update(
+                    &quad_at(previous, x_offset, y_offset),
+                    &quad,
+                    self.prefix.len(),
+                ),
+                None => QuadUpdate::Map,
+            };
+            updates.push((x_offset, y_offset, quad, update));
+        }
+        // Many small messages could overflow the queue of outgoing messages.
+        let messages: usize = updates.iter().map(|update| update.3.message_count()).sum();
+        if messages > OUTGOING_CAPACITY {
+            for update in updates.iter_mut() {
+                if update.3 != QuadUpdate::Unchanged {
+                    update.3 = QuadUpdate::Map;
+                }
+            }
+        }
+
+        let mut queued = true;
+        let level = |intensity: u8| OscType::Int(i32::from(intensity));
+        for (x_offset, y_offset, quad, update) in updates {
+            match update {
+                QuadUpdate::Unchanged => {}
+                QuadUpdate::Map => {
+                    let mut args = vec![OscType::Int(x_offset), OscType::Int(y_offset)];
+                    args.extend(quad.iter().map(|&intensity| level(intensity)));
+                    queued &= self.send("/grid/led/level/map", args);
+                }
+                QuadUpdate::Rows(rows) => {
+                    for y in rows {
+                        let mut args =
+                            vec![OscType::Int(x_offset), OscType::Int(y_offset + y as i32)];
+                        args.extend(
+                            quad[y * 8..y * 8 + 8]
+                                .iter()
+                                .map(|&intensity| level(intensity)),
+                        );
+                        queued &= self.send("/grid/led/level/row", args);
+                    }
+                }
+                QuadUpdate::Columns(columns) => {
+                    for x in columns {
+                        let mut args =
+                            vec![OscType::Int(x_offset + x as i32), OscType::Int(y_offset)];
+                        args.extend((0..8).map(|y| level(quad[y * 8 + x])));
+                        queued &= self.send("/grid/led/level/col", args);
+                    }
+                }
+                QuadUpdate::Cells(cells) => {
+                    for (x, y) in cells {
+                        let args = vec![
+                            OscType::Int(x_offset + x as i32),
+                            OscType::Int(y_offset + y as i32),
+                            level(quad[y * 8 + x]),
+                        ];
+                        queued &= self.send("/grid/led/level/set", args);
+                    }
+                }
+            }
+        }
+
+        // Leds that are not part of a complete quad, on grids with a non-standard size.
+        let changed = |x: i32, y: i32| match previous {
+            Some(ref previous) => previous[toidx(x, y, width)] != leds[toidx(x, y, width)],
+            None => true,
+        };
+        for (x_offset, y) in self.partial_quad_rows() {
+            if (0..8).any(|j| changed(x_offset + j, y)) {
+                let mut args = vec![OscType::Int(x_offset), OscType::Int(y)];
+                args.extend((0..8).map(|j| level(leds[toidx(x_offset + j, y, width)])));
+                queued &= self.send("/grid/led/level/row", args);
+            }
+        }
+        for (x, y) in self.partial_quad_cells() {
+            if changed(x, y) {
+                let args = vec![
+                    OscType::Int(x),
+                    OscType::Int(y),
+                    level(leds[toidx(x, y, width)]),
+                ];
+                queued &= self.send("/grid/led/level/set", args);
+            }
+        }
+
+        // If a message has been dropped, the leds are not known, and are all sent next time.
+        if queued {
+            self.leds_sent = Some(leds.to_vec());
+        }
+    }
+
+    /// Draw a frame on a grid. This is the same as calling `set_all_intensity` with the content
+    /// of the frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - a frame of the size of the device.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::{GridFrame, Monome};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut frame = GridFrame::new(monome.width(), monome.height());
+    /// frame.set(0, 0, 15);
+    /// monome.draw_frame(&frame);
+    /// ```
+    pub fn draw_frame(&mut self, frame: &GridFrame) {
+        if frame.width() != self.width() || frame.height() != self.height() {
+            error!(
+                "Frame size {}x{} does not match the device size {}x{}.",
+                frame.width(),
+                frame.height(),
+                self.width(),
+                self.height()
+            );
+            return;
+        }
+        self.set_all_intensity(frame.leds());
+        if let Some(ref mut log) = self.frame_log {
+            if let Err(e) = log.write_frame(frame) {
+                error!("Could not log frame, logging stopped: {}", e);
+                self.frame_log = None;
+            }
+        }
+    }
+
+    /// Write every frame drawn with `draw_frame` to `log`, with the time at which it has been
+    /// drawn, or stop logging if `None`. The log can be read back with a `FrameLogReader`, for
+    /// example to find out what an installation displayed overnight. Logging stops at the first
+    /// error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::{BufWriter, Write};
+    /// use monome::{FrameLogWriter, Monome};
+    ///
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let file = BufWriter::new(File::create("frames.log").unwrap());
+    /// let writer: Box<dyn Write + Send> = Box::new(file);
+    /// monome.set_frame_log(Some(FrameLogWriter::new(writer).unwrap()));
+    /// ```
+    pub fn set_frame_log(&mut self, log: Option<FrameLogWriter<Box<dyn io::Write + Send>>>) {
+        if let Some(ref mut previous) = self.frame_log {
+            if let Err(e) = previous.flush() {
+                error!("Could not flush the frame log: {}", e);
+            }
+        }
+        self.frame_log = log;
+    }
+
+    /// Set the order in which the quads are sent when setting all the leds of a grid, with
+    /// `set_all` or `set_all_intensity`. The quads in `order` are sent first, in this order, and
+    /// the others are sent afterwards, in row order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - the offsets, that must be multiples of 8, of the quads to send first.
+    ///
+    /// # Example
+    ///
+    /// On a monome 128, always update the rightmost half first, because it has the playhead:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_quad_order(vec![(8, 0)]);
+    /// monome.set_all_intensity(&[0; 128]);
+    /// ```
+    pub fn set_quad_order(&mut self, order: Vec<(i32, i32)>) {
+        self.quad_order = order;
+    }
+
+    /// Set the scaling applied to the intensity of regions of the grid when setting all its leds,
+    /// with `set_all_intensity` or `draw_frame`. This makes it possible to dim or brighten parts
+    /// of the grid without changing the code that draws them.
+    ///
+    /// # Example
+    ///
+    /// On a monome 128, dim everything but the bottom row:
+    ///
+    /// ```no_run
+    /// # use monome::{IntensityScaling, Monome};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut scaling = IntensityScaling::new();
+    /// scaling.set((0, 0, 16, 7), 0.3);
+    /// monome.set_intensity_scaling(scaling);
+    /// monome.set_all_intensity(&[15; 128]);
+    /// ```
+    pub fn set_intensity_scaling(&mut self, scaling: IntensityScaling) {
+        self.scaling = scaling;
+    }
+
+    /// Get the scaling applied to the intensity of regions of the grid.
+    pub fn intensity_scaling(&self) -> IntensityScaling {
+        self.scaling.clone()
+    }
+
+    /// Set the mapping applied to the intensity of every led sent to the device, by this object
+    /// and by its `MonomeOutput`s, or `None` to send the intensities unchanged. This corrects
+    /// the brightness of varibright leds, so that the intensities 0 to 15 look like an even
+    /// ramp. The leds already displayed are not sent again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::{BrightnessCurve, Monome};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_brightness_curve(Some(BrightnessCurve::gamma(2.2)));
+    /// for x in 0..16 {
+    ///     monome.set(x, 0, x);
+    /// }
+    /// ```
+    pub fn set_brightness_curve(&mut self, curve: Option<BrightnessCurve>) {
+        *self.shared.curve.lock().unwrap() = curve;
+        self.leds_sent = None;
+    }
+
+    /// Get the mapping applied to the intensity of every led sent to the device, if any.
+    pub fn brightness_curve(&self) -> Option<BrightnessCurve> {
+        *self.shared.curve.lock().unwrap()
+    }
+
+    /// Dim the display gradually when the device has not been used for a while, and brighten
+    /// it back when it is used again, with `dimmer`, or stop dimming it with `None`. The events
+    /// returned by `poll()` are passed to the dimmer, which may swallow the key press that wakes
+    /// the device up, and `poll()` returns `MonomeEvent::Woke` when the device wakes up.
+    ///
+    /// Only the intensities sent when updating the whole grid with `set_all_intensity` are
+    /// scaled by the level of the dimmer, after the intensity scaling: the leds set with the
+    /// other methods, or with a `MonomeOutput`, are not dimmed.
+    ///
+    /// Outside of `run()`, which does it on each frame, `update_idle_dimming` must be called
+    /// regularly for the display to dim and brighten while the application doesn't redraw it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use monome::{IdleDimmer, Monome};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// // After five minutes without activity, dim to 10% over 30 seconds.
+    /// let mut dimmer = IdleDimmer::new(Duration::from_secs(300), 0.1);
+    /// dimmer.set_ramps(Duration::from_secs(30), Duration::from_millis(200));
+    /// dimmer.detector_mut().set_swallow_wake_key(true);
+    /// monome.set_idle_dimming(Some(dimmer));
+    /// ```
+    pub fn set_idle_dimming(&mut self, dimmer: Option<IdleDimmer>) {
+        let undimmed = self.undimmed.take();
+        self.dimmer = dimmer;
+        // Redraw the last frame without dimming, or with the new dimmer.
+        if let Some((leds, _)) = undimmed {
+            self.set_all_intensity(&leds);
+        }
+    }
+
+    /// Redraw the last intensities sent when updating the whole grid if the level of the idle
+    /// dimming has changed since, so that the display dims and brightens progressively. This
+    /// does nothing if idle dimming is disabled, or if the whole grid has not been updated yet.
+    pub fn update_idle_dimming(&mut self) {
+        let level = match self.dimmer {
+            Some(ref mut dimmer) => dimmer.level(),
+            None => return,
+        };
+        let leds = match self.undimmed {
+            Some((ref leds, sent)) if sent != level => leds.clone(),
+            _ => return,
+        };
+        self.set_all_intensity(&leds);
+    }
+
+    /// Set the map from keys and chords to actions, or remove it. When set, `poll()` returns a
+    /// `MonomeEvent::Action` instead of each key press that triggers an action. The other events,
+    /// including the key releases, are returned unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::{ActionMap, Monome, MonomeEvent};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut actions = ActionMap::new();
+    /// actions.bind_key("play", 0, 7);
+    /// actions.bind_chord("clear", &[(0, 7), (15, 7)]);
+    /// monome.set_action_map(Some(actions));
+    ///
+    /// loop {
+    ///     while let Some(event) = monome.poll() {
+    ///         match event {
+    ///             MonomeEvent::Action { name } => println!("{}", name),
+    ///             _ => {}
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn set_action_map(&mut self, actions: Option<ActionMap>) {
+        self.actions = actions;
+    }
+
+    /// Get the map from keys and chords to actions, if set, for example to change its bindings.
+    pub fn action_map_mut(&mut self) -> Option<&mut ActionMap> {
+        self.actions.as_mut()
+    }
+
+    /// Enable or disable adaptive updates. When enabled, `set_all_intensity` and the methods
+    /// built on it only send the leds that changed since the previous call, choosing for each
+    /// quad between a whole quad, rows, columns or single leds, whichever takes the fewest bytes
+    /// on the wire. This is best for applications that redraw the whole grid often, but change
+    /// little of it.
+    ///
+    /// The leds changed with other methods, such as `set` or `all`, are not tracked: the whole
+    /// grid is sent again by the next call to `set_all_intensity` after them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_adaptive_updates(true);
+    /// let mut leds = [0; 128];
+    /// for i in 0..128 {
+    ///     // Only the led that changed is sent.
+    ///     leds[i] = 15;
+    ///     monome.set_all_intensity(&leds);
+    /// }
+    /// ```
+    pub fn set_adaptive_updates(&mut self, enabled: bool) {
+        self.adaptive_updates = enabled;
+        self.leds_sent = None;
+    }
+
+    /// Returns true if adaptive updates are enabled.
+    pub fn adaptive_updates(&self) -> bool {
+        self.adaptive_updates
+    }
+
+    /// Enable or disable dropping the grid led messages, sent by this object or by its
+    /// `MonomeOutput`s, that only set leds to the intensities they are known to have, from the
+    /// last intensities written, see `led()`. The leds are only known once they have been
+    /// written after the device has been set up. This is disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_skip_redundant_leds(true);
+    /// monome.set(0, 0, true);
+    /// // Not sent.
+    /// monome.set(0, 0, true);
+    /// ```
+    pub fn set_skip_redundant_leds(&mut self, enabled: bool) {
+        self.shared
+            .skip_redundant_leds
+            .store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns true if the grid led messages that don't change any led are dropped.
+    pub fn skip_redundant_leds(&self) -> bool {
+        self.shared.skip_redundant_leds.load(Ordering::SeqCst)
+    }
+
+    /// Cap the rate of the led messages sent by this object and by its `MonomeOutput`s, grid
+    /// and arc alike, to `rate` messages per second, or `None` to send them as fast as they
+    /// come, which is the default. Bursts of up to a second worth of messages are sent right
+    /// away, and the messages over the rate are then dropped, as when too many messages are
+    /// waiting to be sent. This protects serialosc and the USB link from an application that
+    /// sends too much.
+    ///
+    /// The messages over the rate are dropped, not delayed: the leds they set keep their
+    /// previous intensities until they are set again, so a burst can leave the device
+    /// displaying something else than what the application drew. Redrawing the whole grid, e.g.
+    /// with `set_all_intensity`, once the burst is over, brings it back in sync.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_max_message_rate(Some(500));
+    /// // Later on:
+    /// println!("{} messages dropped", monome.rate_limited_messages());
+    /// ```
+    pub fn set_max_message_rate(&mut self, rate: Option<u32>) {
+        *self.shared.rate_limiter.lock().unwrap() = rate.map(RateLimiter::new);
+    }
+
+    /// Get the number of led messages per second sent at most, if it is capped.
+    pub fn max_message_rate(&self) -> Option<u32> {
+        self.shared
+            .rate_limiter
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(RateLimiter::rate)
+    }
+
+    /// Get the number of led messages dropped because of the rate cap, since it has been set.
+    pub fn rate_limited_messages(&self) -> u64 {
+        self.shared
+            .rate_limiter
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, RateLimiter::dropped)
+    }
+
+    /// Enable or disable frame debugging. When enabled, each call to `set_all` or
+    /// `set_all_intensity` logs (at the `info` level) which quads and cells changed since the
+    /// previous call. The messages that are then sent are logged at the `debug` level.
+    pub fn set_frame_debugging(&mut self, enabled: bool) {
+        self.debug_frame = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Logs the difference between `leds` and the previous frame, and remembers `leds` as the
+    /// new previous frame.
+    fn log_frame_diff(&mut self, leds: &[u8]) {
+        let width = self.size.0;
+        let offsets = self.quad_offsets();
+        let previous = match self.debug_frame {
+            Some(ref mut previous) => previous,
+            None => return,
+        };
+        if previous.len() != leds.len() {
+            info!("frame: first frame, all quads sent");
+            *previous = leds.to_vec();
+            return;
+        }
+        let mut changed_quads = 0;
+        for (x_offset, y_offset) in offsets {
+            let mut changes = Vec::new();
+            for i in 0..8 {
+                for j in 0..8 {
+                    let idx = toidx(x_offset + j, y_offset + i, width);
+                    if previous[idx] != leds[idx] {
+                        changes.push(format!(
+                            "({},{}): {} -> {}",
+                            x_offset + j,
+                            y_offset + i,
+                            previous[idx],
+                            leds[idx]
+                        ));
+                    }
+                }
+            }
+            if !changes.is_empty() {
+                changed_quads += 1;
+                info!(
+                    "frame: quad ({},{}), {} cells changed: {}",
+                    x_offset,
+                    y_offset,
+                    changes.len(),
+                    changes.join(", ")
+                );
+            }
+        }
+        if changed_quads == 0 {
+            info!("frame: no change since the previous frame");
+        }
+        previous.copy_from_slice(leds);
+    }
+
+    /// Returns the offsets of all the quads of this grid, in the order in which they should be
+    /// sent.
+    fn quad_offsets(&self) -> Vec<(i32, i32)> {
+        let (width, height) = self.size;
+        let mut offsets: Vec<(i32, i32)> = self
+            .quad_order
+            .iter()
+            .filter(|&&(x, y)| x % 8 == 0 && y % 8 == 0 && x + 8 <= width && y + 8 <= height)
+            .cloned()
+            .collect();
+        for y in (0..height / 8).map(|y| y * 8) {
+            for x in (0..width / 8).map(|x| x * 8) {
+                if !offsets.contains(&(x, y)) {
+                    offsets.push((x, y));
+                }
+            }
+        }
+        offsets
+    }
+
+    /// Returns the 8 led long row sections of this grid, as `(x_offset, y)`, that are below the
+    /// last complete row of quads, when its height is not a multiple of 8.
+    fn partial_quad_rows(&self) -> Vec<(i32, i32)> {
+        let (width, height) = self.size;
+        let mut rows = Vec::new();
+        for y in height / 8 * 8..height {
+            for x in (0..width / 8 * 8).step_by(8) {
+                rows.push((x, y));
+            }
+        }
+        rows
+    }
+
+    /// Returns the leds of this grid, as `(x, y)`, that are to the right of the last complete
+    /// column of quads, when its width is not a multiple of 8.
+    fn partial_quad_cells(&self) -> Vec<(i32, i32)> {
+        let (width, height) = self.size;
+        let mut cells = Vec::new();
+        for y in 0..height {
+            for x in width / 8 * 8..width {
+                cells.push((x, y));
+            }
+        }
+        cells
+    }
+
+    /// Returns true if this device is a grid whose size is a multiple of 8 in both dimensions, so
+    /// that it can be updated entirely with 8x8 quads. Grids of other sizes are still fully
+    /// supported by `set_all` and `set_all_intensity`, but the leds that are not part of a
+    /// complete quad are updated by row or one by one, which is slower.
+    pub fn supports_quad_updates(&self) -> bool {
+        self.capabilities().quad_updates()
+    }
+
+    /// Get what this device can do, deduced from its type, its model and its size. The model is
+    /// only known for devices set up from a `MonomeDevice`.
+    ///
+    /// # Example
+    ///
+    /// Adapt the intensities used to the device:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let capabilities = monome.capabilities();
+    /// let dim = if capabilities.varibright { 4 } else { 0 };
+    /// monome.set(0, 0, dim);
+    /// ```
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::new(self.device_type.clone(), self.model.as_deref(), self.size)
+            .with_serial(&self.id)
+    }
+
+    /// Returns true if the leds of this device can be set to 16 levels of intensity, false if
+    /// they can only be on or off. This is deduced from the model and the serial number of the
+    /// device, see `capabilities()`.
+    pub fn is_varibright(&self) -> bool {
+        self.capabilities().varibright
+    }
+
+    /// Convert the intensities of the leds sent to this grid, by this object and by its
+    /// `MonomeOutput`s, to leds that are on when their intensity is `threshold` or more, and off
+    /// otherwise, or send them unchanged if `None`. This is enabled with a threshold of 8 when
+    /// the grid is not varibright, so that the intensities are not ignored by the device.
+    ///
+    /// # Example
+    ///
+    /// Only light the leds that are at full intensity:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_monochrome_threshold(Some(15));
+    /// monome.set_all_intensity(&[8; 128]);
+    /// ```
+    pub fn set_monochrome_threshold(&mut self, threshold: Option<u8>) {
+        *self.shared.monochrome_threshold.lock().unwrap() = threshold;
+        self.leds_sent = None;
+    }
+
+    /// Get the intensity from which leds are on, if the intensities sent to this grid are
+    /// converted to leds that are on or off.
+    pub fn monochrome_threshold(&self) -> Option<u8> {
+        *self.shared.monochrome_threshold.lock().unwrap()
+    }
+
+    /// Set the value an 8x8 quad of led on a monome grid.
+    ///
+    /// Returns `MonomeError::InvalidPosition`, and sends nothing, if the offsets are not multiples
+    /// of 8 or are outside of the grid. Returns `MonomeError::SendQueueFull` if the message has
+    /// been dropped because too many messages are waiting to be sent, and
+    /// `MonomeError::WrongDeviceType` if the device is not a grid.
+    ///
+    /// # Arguments
+    ///
+    /// * `x_offset` - at which offset, that must be a multiple of 8, to set the quad.
+    /// * `y_offset` - at which offset, that must be a multiple of 8, to set the quad.
+    /// * `masks` - a vector of 8 unsigned 8-bit integers that is a mask representing the leds to
+    ///   light up, or a vector of 64 bools, true for led On, false for led Off, packed in row order,
+    ///   or a vector of 64 integers between 0 and 15, for the brightness of each led, packed in
+    ///   row order.
+    ///
+    /// # Example
+    ///
+    /// On a monome 128, draw a triangle in the lower left half of the rightmost half, and a
+    /// gradient on the leftmost half.
+    /// ```no_run
+    /// # extern crate monome;
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut v = [0; 64];
+    /// for i in 0..64 {
+    ///     v[i] = (i / 4) as u8;
+    /// }
+    /// monome.map(0, 0, &v).unwrap();
+    /// monome.map(8, 0, &[1, 3, 7, 15, 32, 63, 127, 0b11111111]).unwrap();
+    /// ```
+    pub fn map<'a, A>(&mut self, x_offset: i32, y_offset: i32, masks: A) -> Result<(), MonomeError>
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>> + Sized,
+    {
+        self.check_device_type(MonomeDeviceType::Grid)?;
+        self.check_position(x_offset, y_offset, true, true)?;
+        let (frag, mut arg) = masks.as_addr_frag_and_args();
+
+        let mut args = Vec::with_capacity(2 + arg.len());
+
+        args.push(OscType::Int(x_offset));
+        args.push(OscType::Int(y_offset));
+        args.append(&mut arg);
+
+        if !self.send(&format!("/grid/led/{}map", frag), args) {
+            return Err(MonomeError::SendQueueFull);
+        }
+        Ok(())
+    }
+
+    /// Set a full row of a grid, using one or more 8-bit mask(s), or a vector containing booleans
+    /// or integer intensity values.
+    ///
+    /// Returns `MonomeError::InvalidPosition`, and sends nothing, if the offset is not a multiple
+    /// of 8, or if the offset or the row are outside of the grid. Returns
+    /// `MonomeError::SendQueueFull` if the message has been dropped because too many messages are
+    /// waiting to be sent, and `MonomeError::WrongDeviceType` if the device is not a grid.
+    ///
+    /// # Arguments
+    ///
+    /// * `x_offset` - at which 8 button offset to start setting the leds. This is always 0 for a
+    ///   64, and can be 8 for a 128 or 256.
+    /// * `y` - which row to set, 0-indexed. This must be lower than the number of rows of the
+    ///   device.
+    /// * `leds` - either the list of masks that determine the pattern to light on for a particular
+    ///   8 led long section, or a vector of either int or bool, one element for each led, or a
+    ///   slice of bools of any length, e.g. as long as the row, split into masks.
+    ///
+    /// # Example
+    ///
+    /// On a monome 128, light up every other led of the right half of the 3rd  row:
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.row(8 /* rightmost half */,
+    ///            2 /* 3rd row, 0 indexed */,
+    ///            &0b01010101u8 /* every other led, 85 in decimal */).unwrap();
+    ///
+    /// // Light up the whole 4th row.
+    /// monome.row(0, 3, &[true; 16][..]).unwrap();
+    /// ```
+    pub fn row<'a, A>(&mut self, x_offset: i32, y: i32, leds: &A) -> Result<(), MonomeError>
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>> + ?Sized,
+    {
+        self.check_device_type(MonomeDeviceType::Grid)?;
+        self.check_position(x_offset, y, true, false)?;
+        let (frag, arg) = leds.as_addr_frag_and_args();
+
+        let mut args = Vec::with_capacity(2 + arg.len());
+
+        args.push(OscType::Int(x_offset));
+        args.push(OscType::Int(y));
+        args.append(&mut arg.to_vec());
+
+        if !self.send(&format!("/grid/led/{}row", frag), args) {
+            return Err(MonomeError::SendQueueFull);
+        }
+        Ok(())
+    }
+
+    /// Set a full column of a grid, using one or more 8-bit mask(s), or a vector containing
+    /// booleans or integer intensity values.
+    ///
+    /// Returns `MonomeError::InvalidPosition`, and sends nothing, if the offset is not a multiple
+    /// of 8, or if the column or the offset are outside of the grid. Returns
+    /// `MonomeError::SendQueueFull` if the message has been dropped because too many messages are
+    /// waiting to be sent, and `MonomeError::WrongDeviceType` if the device is not a grid.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - which column to set 0-indexed. This must be lower than the number of columns of the
+    ///   device.
+    /// * `y_offset` - at which 8 button offset to start setting the leds. This is always 0 for a
+    ///   64, and can be 8 for a 128 or 256.
+    /// * `leds` - either the list of masks that determine the pattern to light on for a particular
+    ///   8 led long section, or a vector of either int or bool, one element for each led, or a
+    ///   slice of bools of any length, e.g. as long as the column, split into masks.
+    ///
+    /// # Example
+    ///
+    /// On a monome 256, light up every other led of the bottom half of the 3rd column from the
+    /// right:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.col(2 /* 3rd column, 0-indexed */,
+    ///            8 /* bottom half */,
+    ///            &0b01010101u8 /* every other led, 85 in decimal */).unwrap();
+    /// ```
+    pub fn col<'a, A>(&mut self, x: i32, y_offset: i32, leds: &A) -> Result<(), MonomeError>
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>> + ?Sized,
+    {
+        self.check_device_type(MonomeDeviceType::Grid)?;
+        self.check_position(x, y_offset, false, true)?;
+        let (frag, mut arg) = leds.as_addr_frag_and_args();
+
+        let mut args = Vec::with_capacity(2 + arg.len());
+
+        args.push(OscType::Int(x));
+        args.push(OscType::Int(y_offset));
+        args.append(&mut arg);
+
+        if !self.send(&format!("/grid/led/{}col", frag), args) {
+            return Err(MonomeError::SendQueueFull);
+        }
+        Ok(())
+    }
+
+    /// Returns an error if this device is not of type `expected`.
+    fn check_device_type(&self, expected: MonomeDeviceType) -> Result<(), MonomeError> {
+        if self.device_type != expected {
+            return Err(MonomeError::WrongDeviceType {
+                expected,
+                actual: self.device_type.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns an error if `x`, `y` is outside of the grid, or if the coordinates that are
+    /// offsets, as set by `x_offset` and `y_offset`, are not multiples of 8.
+    fn check_position(
+        &self,
+        x: i32,
+        y: i32,
+        x_offset: bool,
+        y_offset: bool,
+    ) -> Result<(), MonomeError> {
+        let aligned = |value: i32, offset: bool| !offset || value % 8 == 0;
+        if x < 0
+            || y < 0
+            || x >= self.size.0
+            || y >= self.size.1
+            || !aligned(x, x_offset)
+            || !aligned(y, y_offset)
+        {
+            return Err(MonomeError::InvalidPosition { x, y });
+        }
+        Ok(())
+    }
+
+    /// Set a single led, with intensity, on an Arc.
+    ///
+    /// # Arguments
+    ///
+    /// - `n` - the encoder to set a led on, 0-indexed.
+    /// - `index` - which led to set. 0 is the top led, and goes clockwise. This is modulo 64, so
+    ///   passing in 65 is the second led from the top, going clockwise.
+    /// - `intensity` - the intensity of the led 0 being off, 15 full brightness.
+    ///
+    /// # Example
+    ///
+    /// On an arc, make a circular gradient on the first encoder:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// for i in 0..64 {
+    ///   monome.set(0, i, i / 4);
+    /// }
+    /// ```
+    pub fn ring_set(&mut self, n: usize, index: u32, intensity: u32) {
+        if self.device_type != MonomeDeviceType::Arc {
+            error!("Called arc method on something that is not an arc.");
+            return;
+        }
+        let args = vec![
+            OscType::Int(n as i32),
+            OscType::Int(index as i32),
+            OscType::Int(intensity as i32),
+        ];
+
+        self.send("/ring/set", args);
+    }
+
+    /// Set all the led on an encoder to a particular intensity.
+    ///
+    /// # Arguments
+    ///
+    /// - `n` - the encoder to set the leds on, 0-indexed.
+    /// - `intensity` - the intensity of the leds: 0 being off, 15 full brightness.
+    ///
+    /// # Example
+    ///
+    /// On an arc, make a gradient accross all four encoders:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// for i in 0..4 {
+    ///   monome.ring_all(i, (i * 4) as u32);
+    /// }
+    /// ```
+    pub fn ring_all(&mut self, n: usize, intensity: u32) {
+        if self.device_type != MonomeDeviceType::Arc {
+            error!("Called arc method on something that is not an arc.");
+            return;
+        }
+        let args = vec![OscType::Int(n as i32), OscType::Int(intensity as i32)];
+
+        self.send("/ring/all", args);
+    }
+
+    /// Set a range of led to a particular intensity.
+    ///
+    /// # Arguments
+    ///
+    /// - `n` - the encoder to set the leds on, 0-indexed.
+    /// - `start_offset` - the encoder to start setting the led from, 0-indexed, modulo 64.
+    /// - `end_offset` - the encoder to end setting the led at 0-indexed, inclusive, modulo 64.
+    /// - `intensity` - the intensity of the leds: 0 being off, 15 full brightness.
+    ///
+    /// # Example
+    ///
+    /// On an arc, lit up halves:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.ring_range(0, 0, 32, 15);
+    /// monome.ring_range(1, 32, 64, 15);
+    /// monome.ring_range(2, 16, 48, 15);
+    /// monome.ring_range(3, 48, 16, 15);
+    /// ```
+    pub fn ring_range(&mut self, n: usize, start_offset: usize, end_offset: usize, intensity: u32) {
+        if self.device_type != MonomeDeviceType::Arc {
+            error!("Called arc method on something that is not an arc.");
+            return;
+        }
+        let args = vec![
+            OscType::Int(n as i32),
+            OscType::Int(start_offset as i32),
+            OscType::Int(end_offset as i32),
+            OscType::Int(intensity as i32),
+        ];
 
-        args.push(OscType::Int(x));
-        args.push(OscType::Int(y_offset));
-        args.append(&mut arg);
+        self.send("/ring/range", args);
+    }
 
-        self.send(&format!("/grid/led/{}col", frag), args);
+    /// Light an arc of leds, from an angle, without relying on the device to wrap ranges around
+    /// the top of the ring. The leds outside of the arc are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// - `n` - the encoder to set the leds on, 0-indexed.
+    /// - `start_angle` - the angle at which the arc starts, in degrees clockwise from the top.
+    /// - `sweep` - the angle covered by the arc, in degrees, clockwise, or counter-clockwise if
+    ///   negative. It is limited to a full turn.
+    /// - `intensity` - the intensity of the leds: 0 being off, 15 full brightness. The leds only
+    ///   partly covered by the arc, at its ends, are dimmer, see `ring_arc_leds`.
+    ///
+    /// # Example
+    ///
+    /// On an arc, light a quarter turn on each side of the top, and a quarter turn going
+    /// counter-clockwise from the right.
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.ring_arc(0, -45., 90., 15);
+    /// monome.ring_arc(1, 90., -90., 15);
+    /// ```
+    pub fn ring_arc(&mut self, n: usize, start_angle: f32, sweep: f32, intensity: u32) {
+        if self.device_type != MonomeDeviceType::Arc {
+            error!("Called arc method on something that is not an arc.");
+            return;
+        }
+        let intensity = intensity.min(15) as u8;
+        let leds = ring_arc_leds(start_angle, sweep, intensity);
+        for (addr, args) in ring_arc_messages(n, &leds) {
+            self.send(addr, args);
+        }
     }
 
-    /// Set a single led, with intensity, on an Arc.
+    /// Set all leds on an encoder to specific values.
     ///
     /// # Arguments
     ///
-    /// - `n` - the encoder to set a led on, 0-indexed.
-    /// - `index` - which led to set. 0 is the top led, and goes clockwise. This is modulo 64, so
-    /// passing in 65 is the second led from the top, going clockwise.
-    /// - `intensity` - the intensity of the led 0 being off, 15 full brightness.
+    /// - `n` - the encoder to set the leds on, 0-indexed.
+    /// - `values` - an array of 64 values between 0 an 16, one for each led, or an array of 64
+    ///   bools, true for a led on at full intensity, or an array of 8 unsigned 8-bit masks, the
+    ///   first led of each mask being its lowest bit.
     ///
     /// # Example
     ///
-    /// On an arc, make a circular gradient on the first encoder:
+    /// On an arc, make a gradient on an encoder, and light every other led of the next one.
     ///
     /// ```no_run
     /// use monome::Monome;
     /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let mut v: [u8; 64] = [0; 64];
+    ///
     /// for i in 0..64 {
-    ///   monome.set(0, i, i / 4);
+    ///     v[i] = (i / 4) as u8;
     /// }
+    /// monome.ring_map(0, &v);
+    /// monome.ring_map(1, &[0b01010101u8; 8]);
     /// ```
-    pub fn ring_set(&mut self, n: usize, index: u32, intensity: u32) {
-        if self.device_type != MonomeDeviceType::Arc {
-            error!("Called arc method on something that is not an arc.");
+    pub fn ring_map<'a, A>(&mut self, n: usize, values: A)
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>>,
+    {
+        let (frag, values) = values.as_addr_frag_and_args();
+        self.send("/ring/map", ring_map_args(n, &frag, values));
+    }
+
+    /// Set the global intensity of the leds of a grid, between 0 and 15, by which the intensity
+    /// of each led is scaled by the device. This dims or brightens the whole grid without sending
+    /// the leds again.
+    ///
+    /// # Example
+    ///
+    /// Dim the grid, then restore its full intensity:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.intensity(4);
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    /// monome.intensity(15);
+    /// ```
+    pub fn intensity(&mut self, level: i32) {
+        if self.device_type != MonomeDeviceType::Grid {
+            error!("Called grid method on something that is not an grid.");
             return;
         }
-        let mut args = Vec::with_capacity(3);
-        args.push(OscType::Int(n as i32));
-        args.push(OscType::Int(index as i32));
-        args.push(OscType::Int(intensity as i32));
+        self.send(
+            "/grid/led/intensity",
+            vec![OscType::Int(level.clamp(0, 15))],
+        );
+    }
 
-        self.send("/ring/set", args);
+    /// Enable or disable all tilt sensors (usually, there is only one), which allows receiving the
+    /// `/<prefix>/tilt/` events, with the n,x,y,z coordinates as parameters.
+    pub fn tilt_all(&mut self, on: bool) {
+        self.send(
+            "/tilt/set",
+            vec![OscType::Int(0), OscType::Int(if on { 1 } else { 0 })],
+        );
     }
 
-    /// Set all the led on an encoder to a particular intensity.
+    /// Set the rotation for this device. This is either 0, 90, 180 or 270
+    pub fn set_rotation(&mut self, rotation: i32) {
+        self.send_no_prefix("/sys/rotation", vec![OscType::Int(rotation)]);
+        self.rotation = rotation;
+        self.leds_sent = None;
+        if let Some(ref mut orientation) = *self.shared.orientation.lock().unwrap() {
+            orientation.set_rotation(rotation);
+        }
+        if let Some(ref mut orientation) = *self.shared.led_orientation.lock().unwrap() {
+            orientation.set_rotation(rotation);
+        }
+    }
+
+    /// Deliver the coordinates of the grid keys as if the device had a rotation of
+    /// `orientation` degrees, whatever its actual rotation, or as the device reports them if
+    /// `None`. This allows an application to rotate the device with `set_rotation` without
+    /// having to rotate the coordinates of the keys itself.
+    ///
+    /// # Example
+    ///
+    /// The device is placed upside down, but the application handles the keys as if it was
+    /// not:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_key_orientation(Some(0));
+    /// monome.set_rotation(180);
+    /// ```
+    pub fn set_key_orientation(&mut self, orientation: Option<i32>) {
+        *self.shared.orientation.lock().unwrap() = orientation
+            .map(|orientation| KeyOrientation::new(self.size, self.rotation, orientation));
+    }
+
+    /// Get the orientation in which the coordinates of the grid keys are delivered, if they are
+    /// converted.
+    pub fn key_orientation(&self) -> Option<i32> {
+        self.shared
+            .orientation
+            .lock()
+            .unwrap()
+            .map(|orientation| orientation.orientation())
+    }
+
+    /// Address the leds and receive the keys of a grid as if it had no rotation, whatever its
+    /// actual rotation, if `enabled`, so that an application can ignore the rotation: the
+    /// coordinates of the leds set, by this object and by its `MonomeOutput`s, are converted to
+    /// the rotation of the device, and the coordinates of the keys are delivered as with
+    /// `set_key_orientation(Some(0))`. The size of the grid is then always the size returned by
+    /// `width()` and `height()`.
+    ///
+    /// Disabling it also stops converting the coordinates of the keys.
+    ///
+    /// # Example
+    ///
+    /// The top left led of the grid as the application sees it is lit, whatever the rotation:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_logical_coordinates(true);
+    /// monome.set_rotation(90);
+    /// monome.set(0, 0, true);
+    /// ```
+    pub fn set_logical_coordinates(&mut self, enabled: bool) {
+        *self.shared.led_orientation.lock().unwrap() = if enabled {
+            Some(LedOrientation::new(self.size, self.rotation))
+        } else {
+            None
+        };
+        self.set_key_orientation(if enabled { Some(0) } else { None });
+        self.leds_sent = None;
+    }
+
+    /// Returns true if the leds and the keys are addressed as if the grid had no rotation.
+    pub fn logical_coordinates(&self) -> bool {
+        self.shared.led_orientation.lock().unwrap().is_some()
+    }
+
+    /// Set the prefix for this device.
+    pub fn set_prefix(&mut self, prefix: String) {
+        self.send_no_prefix("/sys/prefix", vec![OscType::String(prefix.clone())]);
+        *self.shared.prefix.lock().unwrap() = prefix.clone();
+        self.prefix = prefix;
+    }
+
+    /// Get the name of this device.
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Get the type for this device (for example `"monome 128"`).
+    pub fn device_type(&self) -> MonomeDeviceType {
+        self.device_type.clone()
+    }
+
+    /// Get the port for this device.
+    pub fn port(&self) -> i32 {
+        self.port
+    }
+
+    /// Get the host for this device is at.
+    pub fn host(&self) -> String {
+        self.host.clone()
+    }
+
+    /// Get the id of this device.
+    pub fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    /// Get the current prefix of this device.
+    pub fn prefix(&self) -> String {
+        self.prefix.clone()
+    }
+
+    /// Get the current rotation of this device.
+    pub fn rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Get the size of this device, as a `(width, height)`.
+    pub fn size(&self) -> (i32, i32) {
+        self.size
+    }
+    /// Get the width of this device.
+    pub fn width(&self) -> usize {
+        self.size.0 as usize
+    }
+
+    /// Get the height of this device.
+    pub fn height(&self) -> usize {
+        self.size.1 as usize
+    }
+
+    /// Get the intensity last written to the led at `x`, `y` of a grid, between 0 and 15, or 0
+    /// if it is outside of the grid, by this object, by `send_async` or by a `MonomeOutput`. Leds
+    /// set on read as 15, and leds set off as 0.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set(3, 4, 9);
+    /// assert_eq!(monome.led(3, 4), 9);
+    /// ```
+    pub fn led(&self, x: i32, y: i32) -> u8 {
+        self.shared.shadow.lock().unwrap().get(x, y).unwrap_or(0)
+    }
+
+    /// Get the intensities last written to all the leds of a grid, packed in row order, as
+    /// `led()` returns them.
+    pub fn leds(&self) -> Vec<u8> {
+        self.shared.shadow.lock().unwrap().leds().to_vec()
+    }
+
+    /// Adds the prefix, packs the OSC message into an u8 vector and sends it to the transport.
+    /// Returns true if the message has been queued.
+    fn send(&mut self, addr: &str, args: Vec<OscType>) -> bool {
+        self.send_on(addr, args, false)
+    }
+
+    /// Adds the prefix, packs the OSC message into an u8 vector and sends it to the transport,
+    /// on the channel whose messages are sent first if `priority` is set. Returns true if the
+    /// message has been queued.
+    fn send_on(&mut self, addr: &str, args: Vec<OscType>, priority: bool) -> bool {
+        let shared = self.shared.clone();
+        shared.send_filtered(addr, args, |args| {
+            let mut queued = true;
+            for (addr, args) in shared.convert(addr, args) {
+                let with_prefix = format!("{}{}", self.prefix, addr);
+                queued &= self.send_no_prefix_on(&with_prefix, args, priority);
+            }
+            queued
+        })
+    }
+
+    /// Packs the OSC message into an u8 vector and sends it to the transport. Returns true if the
+    /// message has been queued.
+    fn send_no_prefix(&mut self, addr: &str, args: Vec<OscType>) -> bool {
+        self.send_no_prefix_on(addr, args, false)
+    }
+
+    /// Packs the OSC message into an u8 vector and sends it to the transport, on the channel
+    /// whose messages are sent first if `priority` is set. Messages sent first are never
+    /// batched. Returns true if the message has been queued.
+    fn send_no_prefix_on(&mut self, addr: &str, mut args: Vec<OscType>, priority: bool) -> bool {
+        // The leds are not known anymore.
+        if addr.contains("/grid/led/") {
+            self.leds_sent = None;
+        }
+        if let Some(ref curve) = *self.shared.curve.lock().unwrap() {
+            apply_brightness_curve(curve, addr, &mut args);
+        }
+        let message = OscMessage {
+            addr: addr.to_owned(),
+            args: Some(args),
+        };
+        if let (false, Some(ref mut batch)) = (priority, &mut self.batch) {
+            batch.push(message);
+            return true;
+        }
+        let packet = OscPacket::Message(message);
+        debug!("⇨ {:?}", packet);
+        self.switch_to_reconnected();
+        let tx = if priority {
+            &self.priority_tx
+        } else {
+            &self.tx
+        };
+        match encode(&packet) {
+            Ok(bytes) => tx.try_send(bytes),
+            Err(e) => {
+                error!("Could not encode {:?}: {:?}", packet, e);
+                false
+            }
+        }
+    }
+
+    /// Send an OSC message to the device, from an asynchronous context. The prefix of this device
+    /// is prepended to `addr`. Unlike the other methods, that drop messages when too many are
+    /// waiting to be sent, this waits until the message can be queued. This must be called from
+    /// within a tokio runtime. Led messages are converted as the ones sent by the other methods,
+    /// with the logical coordinates, the monochrome threshold and the brightness curve.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - the address of the message, without the prefix, e.g. "/grid/led/all".
+    /// * `args` - the arguments of the message.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// use rosc::OscType;
+    /// # async fn run() {
+    /// let monome = Monome::connect_async("/prefix").await.unwrap();
+    /// monome.send_async("/grid/led/all", vec![OscType::Int(1)]).await.unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, addr: &str, args: Vec<OscType>) -> Result<(), MonomeError> {
+        let written = args.clone();
+        let mut packets = Vec::new();
+        for (addr, mut args) in self.shared.convert(addr, args) {
+            let addr = format!("{}{}", self.prefix, addr);
+            if let Some(ref curve) = *self.shared.curve.lock().unwrap() {
+                apply_brightness_curve(curve, &addr, &mut args);
+            }
+            // The leds displayed are not the ones this object knows anymore.
+            if addr.contains("/grid/led/") {
+                self.shared.leds_changed.store(true, Ordering::SeqCst);
+            }
+            let packet = build_osc_message(&addr, args);
+            debug!("⇨ {:?}", packet);
+            packets.push(encode(&packet).map_err(MonomeError::OscEncode)?);
+        }
+        for bytes in packets {
+            if let Err(e) = self.tx.send(bytes).await {
+                self.shared
+                    .shadow
+                    .lock()
+                    .unwrap()
+                    .forget_writes(addr, &written);
+                return Err(e);
+            }
+        }
+        self.shared.shadow.lock().unwrap().update(addr, &written);
+        Ok(())
+    }
+
+    /// Deliver the grid key, tilt and encoder events of this device on separate channels, one per
+    /// category, instead of returning them from `poll()`. This allows consuming each category on
+    /// its own thread, so that a high rate of events in one category doesn't delay the others.
+    /// The other events are still returned by `poll()`. Calling this again replaces the channels
+    /// returned previously.
+    ///
+    /// Grid keys received on a channel are not tracked by this object, and are not released by
+    /// `resync()`.
+    pub fn event_channels(&mut self) -> EventChannels {
+        let (senders, channels) = EventSenders::new();
+        *self.shared.channels.lock().unwrap() = Some(senders);
+        channels
+    }
+
+    /// Deliver the events of this device on a channel, instead of returning them from `poll()`.
+    /// This replaces the channel returned by `std_event_receiver()` or the stream returned by
+    /// `event_stream()`, if any. This allows waiting on the events of the device along with other
+    /// sources, for example with the `select!` macro of `crossbeam-channel` (re-exported as
+    /// `crossbeam::channel`). Events that have not been received yet with `poll()` are sent on the
+    /// channel first. Calling this again replaces the channel returned previously.
+    ///
+    /// # Example
+    ///
+    /// Wait for events from the device, or a timeout:
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use crossbeam::channel::{after, select};
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let events = monome.event_receiver();
+    /// loop {
+    ///     select! {
+    ///         recv(events) -> event => println!("{:?}", event),
+    ///         recv(after(Duration::from_secs(1))) -> _ => println!("nothing happened"),
+    ///     }
+    /// }
+    /// ```
+    pub fn event_receiver(&mut self) -> crossbeam::channel::Receiver<MonomeEvent> {
+        // Hold the lock while draining, so that the transport can't queue new events for
+        // `poll()` in the meantime.
+        let shared = self.shared.clone();
+        let mut receiver = shared.receiver.lock().unwrap();
+        *receiver = None;
+        let events = self.poll_all();
+        let capacity = events.len().max(channels::CHANNEL_CAPACITY);
+        let (sender, events_receiver) = crossbeam::channel::bounded(capacity);
+        for event in events {
+            let _ = sender.try_send(event);
+        }
+        *receiver = Some(EventSink::Channel(sender));
+        events_receiver
+    }
+
+    /// Deliver the events of this device on a channel of the standard library, instead of
+    /// returning them from `poll()`, for applications that only use the standard library to wait
+    /// for events. This is the same as `event_receiver()`, which it replaces, as well as the
+    /// stream returned by `event_stream()`, if any. Events that have not been received yet with
+    /// `poll()` are sent on the channel first. Calling this again replaces the channel returned
+    /// previously.
+    ///
+    /// # Example
+    ///
+    /// Wait for events from the device on another thread, with a timeout:
+    ///
+    /// ```no_run
+    /// use std::sync::mpsc::RecvTimeoutError;
+    /// use std::thread;
+    /// use std::time::Duration;
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let events = monome.std_event_receiver();
+    /// thread::spawn(move || loop {
+    ///     match events.recv_timeout(Duration::from_secs(1)) {
+    ///         Ok(event) => println!("{:?}", event),
+    ///         Err(RecvTimeoutError::Timeout) => println!("nothing happened"),
+    ///         Err(RecvTimeoutError::Disconnected) => break,
+    ///     }
+    /// });
+    /// ```
+    pub fn std_event_receiver(&mut self) -> std::sync::mpsc::Receiver<MonomeEvent> {
+        // Hold the lock while draining, so that the transport can't queue new events for
+        // `poll()` in the meantime.
+        let shared = self.shared.clone();
+        let mut receiver = shared.receiver.lock().unwrap();
+        *receiver = None;
+        let events = self.poll_all();
+        let capacity = events.len().max(channels::CHANNEL_CAPACITY);
+        let (sender, events_receiver) = std::sync::mpsc::sync_channel(capacity);
+        for event in events {
+            let _ = sender.try_send(event);
+        }
+        *receiver = Some(EventSink::StdChannel(sender));
+        events_receiver
+    }
+
+    /// Deliver the events of this device as an asynchronous stream, instead of returning them
+    /// from `poll()`, so that an asynchronous application can wait for them instead of calling
+    /// `poll()` in a loop. Events that have not been received yet with `poll()` are returned by
+    /// the stream first. Calling this again, or calling `event_receiver()` or
+    /// `std_event_receiver()`, ends the stream returned previously.
+    ///
+    /// See `EventStream` for an example.
+    #[cfg(feature = "async")]
+    pub fn event_stream(&mut self) -> EventStream {
+        // Hold the lock while draining, so that the transport can't queue new events for
+        // `poll()` in the meantime.
+        let shared = self.shared.clone();
+        let mut receiver = shared.receiver.lock().unwrap();
+        *receiver = None;
+        let events = self.poll_all();
+        let capacity = events.len().max(channels::CHANNEL_CAPACITY);
+        let (sender, events_receiver) = tokio::sync::mpsc::channel(capacity);
+        for event in events {
+            let _ = sender.try_send(event);
+        }
+        *receiver = Some(EventSink::Stream(sender));
+        EventStream::new(events_receiver)
+    }
+
+    /// Subscribe to the events of this device. Each subscriber receives a copy of every event
+    /// received from the device, in addition to the copy returned by `poll()` or sent on the
+    /// channels returned by `event_channels()`. This allows, for example, a recorder, a user
+    /// interface, and the main application logic to observe the same events. Subscribers that
+    /// don't consume their events quickly enough miss events.
+    ///
+    /// # Example
+    ///
+    /// Record all the events on another thread:
     ///
-    /// # Arguments
+    /// ```no_run
+    /// use std::thread;
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let events = monome.subscribe();
     ///
-    /// - `n` - the encoder to set the leds on, 0-indexed.
-    /// - `intensity` - the intensity of the leds: 0 being off, 15 full brightness.
+    /// thread::spawn(move || {
+    ///     let mut recording = Vec::new();
+    ///     for event in events.iter() {
+    ///         recording.push(event);
+    ///     }
+    /// });
+    /// ```
+    pub fn subscribe(&mut self) -> crossbeam::channel::Receiver<MonomeEvent> {
+        self.shared.subscribers.lock().unwrap().subscribe()
+    }
+
+    /// Register a function that is called, on the thread that receives the messages from the
+    /// device, each time an event is available from `poll()`. This allows waking up an external
+    /// event loop, instead of calling `poll()` periodically. The function must return quickly.
+    /// Returns an id with which the function can be removed, see `remove_event_notifier()`.
     ///
     /// # Example
     ///
-    /// On an arc, make a gradient accross all four encoders:
+    /// Wake up a thread blocked on a channel when events are available:
     ///
     /// ```no_run
+    /// use std::sync::mpsc::sync_channel;
     /// use monome::Monome;
     /// let mut monome = Monome::new("/prefix").unwrap();
-    /// for i in 0..4 {
-    ///   monome.ring_all(i, (i * 4) as u32);
+    /// let (wake, woken) = sync_channel(1);
+    /// monome.add_event_notifier(move || {
+    ///     let _ = wake.try_send(());
+    /// });
+    /// loop {
+    ///     woken.recv().unwrap();
+    ///     while let Some(event) = monome.poll() {
+    ///         println!("{:?}", event);
+    ///     }
     /// }
     /// ```
-    pub fn ring_all(&mut self, n: usize, intensity: u32) {
-        if self.device_type != MonomeDeviceType::Arc {
-            error!("Called arc method on something that is not an arc.");
-            return;
-        }
-        let mut args = Vec::with_capacity(2);
-        args.push(OscType::Int(n as i32));
-        args.push(OscType::Int(intensity as i32));
+    pub fn add_event_notifier<F>(&mut self, notify: F) -> EventNotifierId
+    where
+        F: Fn() + Send + 'static,
+    {
+        let id = EventNotifierId(self.shared.next_notifier.fetch_add(1, Ordering::SeqCst));
+        self.shared
+            .notifiers
+            .lock()
+            .unwrap()
+            .push((id, Box::new(notify)));
+        id
+    }
 
-        self.send("/ring/all", args);
+    /// Remove a function registered with `add_event_notifier()`. Returns false if it has already
+    /// been removed.
+    pub fn remove_event_notifier(&mut self, id: EventNotifierId) -> bool {
+        let mut notifiers = self.shared.notifiers.lock().unwrap();
+        let count = notifiers.len();
+        notifiers.retain(|(notifier, _)| *notifier != id);
+        notifiers.len() != count
     }
 
-    /// Set a range of led to a particular intensity.
-    ///
-    /// # Arguments
-    ///
-    /// - `n` - the encoder to set the leds on, 0-indexed.
-    /// - `start_offset` - the encoder to start setting the led from, 0-indexed, modulo 64.
-    /// - `end_offset` - the encoder to end setting the led at 0-indexed, inclusive, modulo 64.
-    /// - `intensity` - the intensity of the leds: 0 being off, 15 full brightness.
+    /// Get a file descriptor that becomes readable when events are available from `poll()`, to
+    /// register this device with an external event loop.
+    #[cfg(unix)]
+    pub fn event_readiness(&mut self) -> io::Result<EventReadiness> {
+        let (readiness, notify) = EventReadiness::new()?;
+        self.add_event_notifier(notify);
+        Ok(readiness)
+    }
+
+    /// Set which events this device should receive. Events that are not accepted by the filter are
+    /// dropped as soon as they are received, and are never returned by `poll()`. By default, all
+    /// the events are received.
     ///
     /// # Example
     ///
-    /// On an arc, lit up halves:
+    /// Ignore tilt events, that can be numerous:
     ///
     /// ```no_run
-    /// use monome::Monome;
+    /// # use monome::{EventFilter, Monome};
     /// let mut monome = Monome::new("/prefix").unwrap();
-    /// monome.ring_range(0, 0, 32, 15);
-    /// monome.ring_range(1, 32, 64, 15);
-    /// monome.ring_range(2, 16, 48, 15);
-    /// monome.ring_range(3, 48, 16, 15);
+    /// let mut filter = EventFilter::all();
+    /// filter.tilt = false;
+    /// monome.set_event_filter(filter);
     /// ```
-    pub fn ring_range(&mut self, n: usize, start_offset: usize, end_offset: usize, intensity: u32) {
-        if self.device_type != MonomeDeviceType::Arc {
-            error!("Called arc method on something that is not an arc.");
-            return;
-        }
-        let mut args = Vec::with_capacity(4);
-        args.push(OscType::Int(n as i32));
-        args.push(OscType::Int(start_offset as i32));
-        args.push(OscType::Int(end_offset as i32));
-        args.push(OscType::Int(intensity as i32));
+    pub fn set_event_filter(&mut self, filter: EventFilter) {
+        *self.shared.filter.lock().unwrap() = filter;
+    }
 
-        self.send("/ring/range", args);
+    /// Get the filter that decides which events this device receives.
+    pub fn event_filter(&self) -> EventFilter {
+        *self.shared.filter.lock().unwrap()
     }
 
-    /// Set all leds on an encoder to specific values.
+    /// Enable or disable the suppression of duplicate key events. When enabled, a grid or encoder
+    /// key event that repeats the previous event of the same key, in the same direction, within
+    /// `window`, is dropped as soon as it is received. This works around some combinations of
+    /// serialosc and firmware that occasionally deliver duplicate key messages. This is disabled
+    /// by default.
     ///
-    /// # Arguments
+    /// # Example
     ///
-    /// - `n` - the encoder to set the leds on, 0-indexed.
-    /// - `values` - an array of 64 values between 0 an 16, one for each led.
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_event_deduplication(Some(Duration::from_millis(10)));
+    /// // Later on:
+    /// println!("{} duplicates suppressed", monome.suppressed_duplicates());
+    /// ```
+    pub fn set_event_deduplication(&mut self, window: Option<Duration>) {
+        *self.shared.dedup.lock().unwrap() = window.map(EventDeduplicator::new);
+    }
+
+    /// Get the number of duplicate key events suppressed since deduplication has been enabled.
+    pub fn suppressed_duplicates(&self) -> u64 {
+        match *self.shared.dedup.lock().unwrap() {
+            Some(ref dedup) => dedup.suppressed(),
+            None => 0,
+        }
+    }
+
+    /// Enable or disable keeping the last `capacity` events received, with the time at which
+    /// they have been received. After a stall, the application can find out with
+    /// `event_history()` what happened while it wasn't polling, including the events that have
+    /// been dropped because too many were waiting to be returned by `poll()`. This is disabled by
+    /// default. Enabling it again forgets the events kept so far.
     ///
     /// # Example
     ///
-    /// On an arc, make a gradient on an encoder.
-    ///
     /// ```no_run
-    /// use monome::Monome;
+    /// # use std::time::Instant;
+    /// # use monome::Monome;
     /// let mut monome = Monome::new("/prefix").unwrap();
-    /// let mut v: [u8; 64] = [0; 64];
-    ///
-    /// for i in 0..64 {
-    ///     v[i] = (i / 4) as u8;
+    /// monome.set_event_history(Some(256));
+    /// let before = Instant::now();
+    /// // Something that blocks for a while.
+    /// for (at, event) in monome.event_history_since(before) {
+    ///     println!("{:?}: {:?}", at.duration_since(before), event);
     /// }
-    /// monome.ring_map(0, &v);
     /// ```
-    pub fn ring_map(&mut self, n: usize, values: &[u8; 64]) {
-        let mut args = Vec::with_capacity(65);
-        args.push(OscType::Int(n as i32));
-        for v in values.iter() {
-            args.push(OscType::Int(i32::from(*v)));
-        }
-
-        self.send("/ring/map", args);
+    pub fn set_event_history(&mut self, capacity: Option<usize>) {
+        *self.shared.history.lock().unwrap() = capacity.map(EventHistory::new);
     }
 
-    /// Enable or disable all tilt sensors (usually, there is only one), which allows receiving the
-    /// `/<prefix>/tilt/` events, with the n,x,y,z coordinates as parameters.
-    pub fn tilt_all(&mut self, on: bool) {
-        self.send(
-            "/tilt/set",
-            vec![OscType::Int(0), OscType::Int(if on { 1 } else { 0 })],
-        );
+    /// Get the events kept, oldest first, with the time at which they have been received. This is
+    /// empty if the history is disabled.
+    pub fn event_history(&self) -> Vec<(Instant, MonomeEvent)> {
+        match *self.shared.history.lock().unwrap() {
+            Some(ref history) => history.events(),
+            None => Vec::new(),
+        }
     }
 
-    /// Set the rotation for this device. This is either 0, 90, 180 or 270
-    pub fn set_rotation(&mut self, rotation: i32) {
-        self.send_no_prefix("/sys/rotation", vec![OscType::Int(rotation)]);
-        self.rotation = rotation;
+    /// Get the events kept that have been received at or after `start`, oldest first.
+    pub fn event_history_since(&self, start: Instant) -> Vec<(Instant, MonomeEvent)> {
+        match *self.shared.history.lock().unwrap() {
+            Some(ref history) => history.since(start),
+            None => Vec::new(),
+        }
     }
 
-    /// Set the prefix for this device.
-    pub fn set_prefix(&mut self, prefix: String) {
-        self.send_no_prefix("/sys/prefix", vec![OscType::String(prefix.clone())]);
-        self.prefix = prefix;
+    /// Enable or disable the measurement of the latency added by this library, between a call
+    /// that sends a message and the moment it is sent on the socket, and between the moment a
+    /// message is received on the socket and the moment it is returned by `poll()`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_latency_tracking(true);
+    /// for i in 0..1000 {
+    ///     monome.set(i % 16, 0, i % 16);
+    /// }
+    /// println!("{}", monome.latency_report());
+    /// ```
+    pub fn set_latency_tracking(&mut self, enabled: bool) {
+        self.shared.latency.set_enabled(enabled);
     }
 
-    /// Get the name of this device.
-    pub fn name(&self) -> String {
-        self.name.clone()
+    /// Get the latencies measured since latency tracking has been enabled, or since the last
+    /// call to `reset_latency_report`.
+    pub fn latency_report(&self) -> LatencyReport {
+        self.shared.latency.report()
     }
 
-    /// Get the type for this device (for example `"monome 128"`).
-    pub fn device_type(&self) -> MonomeDeviceType {
-        self.device_type.clone()
+    /// Clear the latencies measured so far.
+    pub fn reset_latency_report(&mut self) {
+        self.shared.latency.reset();
     }
 
-    /// Get the port for this device.
-    pub fn port(&self) -> i32 {
-        self.port
+    /// Receives a MonomeEvent, from a connected monome, which can be a grid key press, an event
+    /// from the tilt sensor, or a delta from an encoder, on an Arc. Only the events from the set
+    /// `prefix` will be received.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate monome;
+    ///
+    /// use monome::{Monome, MonomeEvent, KeyDirection};
+    /// let mut m = Monome::new("/prefix").unwrap();
+    ///
+    /// loop {
+    ///     match m.poll() {
+    ///         Some(MonomeEvent::GridKey{x, y, direction}) => {
+    ///             match direction {
+    ///                 KeyDirection::Down => {
+    ///                     println!("Key pressed: {}x{}", x, y);
+    ///                 }
+    ///                 KeyDirection::Up => {
+    ///                     println!("Key released: {}x{}", x, y);
+    ///                 }
+    ///             }
+    ///         }
+    ///         Some(MonomeEvent::Tilt{n: _n, x, y, z: _z}) => {
+    ///           println!("tilt update: pitch: {}, roll {}", x, y);
+    ///         }
+    ///         _ => {
+    ///           break;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn poll(&mut self) -> Option<MonomeEvent> {
+        self.switch_to_reconnected();
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        loop {
+            let (received_at, event) = match self.q.pop() {
+                Ok(received) => received,
+                Err(crossbeam::queue::PopError) => return None,
+            };
+            self.shared.latency.record_receive(received_at.elapsed());
+            let events = match self.dimmer {
+                // The key press that wakes the device up may be swallowed.
+                Some(ref mut dimmer) => dimmer
+                    .process(event)
+                    .into_iter()
+                    .map(|event| match event {
+                        IdleEvent::Event(event) => event,
+                        IdleEvent::Woke => MonomeEvent::Woke,
+                    })
+                    .collect(),
+                None => vec![event],
+            };
+            for event in events {
+                self.keys.process(&event);
+                let event = match self.actions.as_mut().and_then(|map| map.process(&event)) {
+                    Some(name) => MonomeEvent::Action {
+                        name: name.to_string(),
+                    },
+                    None => event,
+                };
+                self.pending.push_back(event);
+            }
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+        }
     }
 
-    /// Get the host for this device is at.
-    pub fn host(&self) -> String {
-        self.host.clone()
+    /// Receives the next event, waiting for up to `timeout` for one to arrive if none is
+    /// pending. Unlike calling `poll()` in a loop, this sleeps until an event is available.
+    /// Returns `None` if no event has arrived in time.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// use monome::Monome;
+    /// let mut m = Monome::new("/prefix").unwrap();
+    ///
+    /// loop {
+    ///     match m.poll_timeout(Duration::from_secs(1)) {
+    ///         Some(event) => println!("{:?}", event),
+    ///         None => println!("Nothing happened in the last second"),
+    ///     }
+    /// }
+    /// ```
+    pub fn poll_timeout(&mut self, timeout: Duration) -> Option<MonomeEvent> {
+        let deadline = Instant::now() + timeout;
+        let shared = self.shared.clone();
+        let (ref count, ref queued) = shared.queued;
+        loop {
+            let seen = *count.lock().unwrap();
+            if let Some(event) = self.poll() {
+                return Some(event);
+            }
+            let mut current = count.lock().unwrap();
+            while *current == seen {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::from_secs(0) {
+                    return None;
+                }
+                current = queued.wait_timeout(current, remaining).unwrap().0;
+            }
+        }
     }
 
-    /// Get the id of this device.
-    pub fn id(&self) -> String {
-        self.id.clone()
+    /// Receives all the events that are pending at the time of the call, and returns them in the
+    /// order in which they have been received. Events that arrive while this is running are left
+    /// for the next call, so the amount of work per call is bounded. This is useful for
+    /// applications that process their input once per frame.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::{thread, time};
+    /// use monome::{Monome, MonomeEvent, KeyDirection};
+    /// let mut m = Monome::new("/prefix").unwrap();
+    ///
+    /// loop {
+    ///     for event in m.poll_all() {
+    ///         if let MonomeEvent::GridKey { x, y, direction: KeyDirection::Down } = event {
+    ///             println!("Key pressed: {}x{}", x, y);
+    ///         }
+    ///     }
+    ///     thread::sleep(time::Duration::from_millis(16));
+    /// }
+    /// ```
+    pub fn poll_all(&mut self) -> Vec<MonomeEvent> {
+        let mut events = Vec::with_capacity(self.pending.len() + self.q.len());
+        self.poll_into(&mut events);
+        events
     }
 
-    /// Get the current prefix of this device.
-    pub fn prefix(&self) -> String {
-        self.prefix.clone()
+    /// Receives all the events that are pending at the time of the call, and appends them to
+    /// `events`, in the order in which they have been received. Returns the number of events
+    /// appended. This is the same as `poll_all`, but allows reusing a buffer.
+    pub fn poll_into(&mut self, events: &mut Vec<MonomeEvent>) -> usize {
+        let pending = self.pending.len() + self.q.len();
+        let mut count = 0;
+        while count < pending {
+            match self.poll() {
+                Some(event) => events.push(event),
+                None => break,
+            }
+            count += 1;
+        }
+        count
     }
 
-    /// Get the current rotation of this device.
-    pub fn rotation(&self) -> i32 {
-        self.rotation
+    /// Run a simple event loop: call `f` with each event received from the device, until the device
+    /// is disconnected, or forever if automatic reconnection is enabled. The events received during
+    /// a frame are handled together, at most about 60 times per second, so that the leds changed by
+    /// `f` are sent in batches. The thread sleeps while no event is received, unless the display
+    /// is dimming or brightening, see `set_idle_dimming()`.
+    ///
+    /// Each event is dispatched to the functions registered with `on_key()`, `on_tilt()`,
+    /// `on_encoder_delta()` and `on_encoder_key()`, if any, before `f` is called with it.
+    ///
+    /// This doesn't enable automatic reconnection, which is disabled by default: without it,
+    /// this returns when the device is unplugged. See `set_auto_reconnect()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - the function called for each event, with the device, to update its leds.
+    ///
+    /// # Example
+    ///
+    /// Light up the keys while they are held down:
+    ///
+    /// ```no_run
+    /// use monome::{KeyDirection, Monome, MonomeEvent};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.run(|monome, event| {
+    ///     if let MonomeEvent::GridKey { x, y, direction } = event {
+    ///         monome.set(x, y, direction == KeyDirection::Down);
+    ///     }
+    /// });
+    /// ```
+    pub fn run<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Monome, MonomeEvent),
+    {
+        let (wake, woken) = std::sync::mpsc::sync_channel(1);
+        let notifier = self.add_event_notifier(move || {
+            let _ = wake.try_send(());
+        });
+        let period = Duration::from_millis(RUN_FRAME_PERIOD_MS);
+        loop {
+            let frame_start = Instant::now();
+            for event in self.poll_all() {
+                self.dispatch(&event);
+                f(self, event);
+            }
+            self.update_idle_dimming();
+            if self.shared.stopped.load(Ordering::SeqCst) && self.reconnector.is_none() {
+                break;
+            }
+            let elapsed = frame_start.elapsed();
+            if elapsed < period {
+                thread::sleep(period - elapsed);
+            }
+            let settled = match self.dimmer {
+                Some(ref mut dimmer) => dimmer.is_settled(),
+                None => true,
+            };
+            if settled {
+                // Wake up regularly to notice when the device is disconnected.
+                let _ = woken.recv_timeout(Duration::from_secs(1));
+            } else {
+                // The display dims or brightens on each frame, which has already been waited for.
+                let _ = woken.try_recv();
+            }
+        }
+        self.remove_event_notifier(notifier);
     }
 
-    /// Get the size of this device, as a `(width, height)`.
-    pub fn size(&self) -> (i32, i32) {
-        self.size
-    }
-    /// Get the width of this device.
-    pub fn width(&self) -> usize {
-        self.size.0 as usize
+    /// Enable or disable automatic reconnection. When enabled, this object listens to the devices
+    /// added to serialosc, and when this device is plugged back after having been unplugged, sets
+    /// it up again, with the current prefix, and switches to it: this object keeps working, and
+    /// the device doesn't have to be enumerated again. This is disabled by default.
+    ///
+    /// When the device has been reconnected, the events describing its state are returned by
+    /// `poll()` again, as with `resync()`, so that the application knows it must redraw its
+    /// leds. The network input and output of a reconnected device run on threads, even if they
+    /// ran on a tokio runtime before.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use monome::{Monome, MonomeEvent};
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.set_auto_reconnect(true);
+    /// monome.run(|monome, event| {
+    ///     if let MonomeEvent::Size { .. } = event {
+    ///         // The device has been (re)connected, redraw everything.
+    ///         monome.all(false);
+    ///     }
+    /// });
+    /// ```
+    pub fn set_auto_reconnect(&mut self, enabled: bool) {
+        if enabled == self.reconnector.is_some() {
+            return;
+        }
+        if !enabled {
+            self.stop_reconnector();
+            return;
+        }
+        let socket = match new_listener_socket(self.serialosc.ip()) {
+            Ok(socket) => socket,
+            Err(e) => {
+                error!("Could not listen to device changes: {}", e);
+                return;
+            }
+        };
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        let serialosc = self.serialosc;
+        let id = self.id.clone();
+        let q = self.q.clone();
+        let shared = self.shared.clone();
+        let reconnector = thread::spawn(move || {
+            listen_device_changes(socket, serialosc, &stop, |event| match event {
+                DeviceChangeEvent::Added(ref added) if *added == id => {
+                    if let Err(e) = Monome::reconnect(serialosc, &id, &q, &shared) {
+                        error!("Could not reconnect to {}: {}", id, e);
+                    }
+                }
+                DeviceChangeEvent::Removed(ref removed) if *removed == id => {
+                    info!("Device {} removed, waiting for it to be plugged back", id);
+                }
+                _ => {}
+            });
+        });
+        self.reconnector = Some((stopped, reconnector));
     }
 
-    /// Get the height of this device.
-    pub fn height(&self) -> usize {
-        self.size.1 as usize
+    /// Returns true if automatic reconnection is enabled.
+    pub fn auto_reconnect(&self) -> bool {
+        self.reconnector.is_some()
     }
 
-    /// Adds the prefix, packs the OSC message into an u8 vector and sends it to the transport.
-    fn send(&mut self, addr: &str, args: Vec<OscType>) {
-        let with_prefix = format!("{}{}", self.prefix, addr);
-        self.send_no_prefix(&with_prefix, args);
-    }
+    /// Sets up the device `id` again, after it has been plugged back, and starts a new transport
+    /// for it, to which the client object switches.
+    fn reconnect(
+        serialosc: SocketAddr,
+        id: &str,
+        q: &Arc<ArrayQueue<(Instant, MonomeEvent)>>,
+        shared: &Arc<Shared>,
+    ) -> Result<(), MonomeError> {
+        let mut addr = None;
+        Monome::enumerate_devices_with(serialosc, |device| {
+            if device.name == id {
+                addr = Some(device.addr());
+            }
+        })?;
+        let addr = addr.ok_or(MonomeError::NoDevice)?;
+        let port = i32::from(addr.port());
+        let prefix = shared.prefix.lock().unwrap().clone();
+        let timeout = Duration::from_millis(SETUP_TIMEOUT_MS);
+        let (_, socket) = Monome::setup(&prefix, addr, timeout)?;
 
-    /// Packs the OSC message into an u8 vector and sends it to the transport.
-    fn send_no_prefix(&mut self, addr: &str, args: Vec<OscType>) {
-        let message = OscMessage {
-            addr: addr.to_owned(),
-            args: Some(args),
-        };
-        let packet = OscPacket::Message(message);
-        debug!("⇨ {:?}", packet);
-        let bytes: Vec<u8> = encode(&packet).unwrap();
-        match self.tx.try_send(bytes) {
-            Ok(()) => {}
-            Err(b) => {
-                let full = b.is_full();
-                let disconnected = b.is_disconnected();
-                error!("full: {:?}, disconnected: {:?}", full, disconnected);
+        // The transports of the previous connection must not mark this one as stopped.
+        shared.generation.fetch_add(1, Ordering::SeqCst);
+        shared.stopped.store(false, Ordering::SeqCst);
+        let (tx, priority_tx) = Transport::new(addr, q.clone(), shared.clone()).spawn(socket)?;
+        *shared.reconnected.lock().unwrap() = Some((port, tx, priority_tx));
+        info!("Device {} reconnected on port {}", id, port);
+        shared.notify_queued();
+        Ok(())
+    }
+
+    /// Switches to the transport of the device that has been reconnected, if any.
+    fn switch_to_reconnected(&mut self) {
+        let reconnected = self.shared.reconnected.lock().unwrap().take();
+        if let Some((port, tx, priority_tx)) = reconnected {
+            self.port = port;
+            self.tx = tx;
+            self.priority_tx = priority_tx;
+            self.leds_sent = None;
+            self.shared.shadow.lock().unwrap().forget();
+            self.resync();
+        }
+    }
+
+    /// Stops the thread that reconnects the device, if any, and waits for it to exit.
+    fn stop_reconnector(&mut self) {
+        if let Some((stopped, reconnector)) = self.reconnector.take() {
+            stopped.store(true, Ordering::SeqCst);
+            if reconnector.thread().id() != thread::current().id() && reconnector.join().is_err() {
+                error!("The reconnection thread panicked");
             }
         }
     }
 
-    /// Receives a MonomeEvent, from a connected monome, which can be a grid key press, an event
-    /// from the tilt sensor, or a delta from an encoder, on an Arc. Only the events from the set
-    /// `prefix` will be received.
+    /// Stop talking to the device: the messages waiting to be sent are sent, then the network
+    /// input and output stop, and the port used to talk to the device is released. This is the
+    /// same as dropping the object, but makes the intent explicit.
+    ///
+    /// When the network input and output run on threads, this waits for them to exit. When they
+    /// run on a tokio runtime, they exit shortly after this returns.
     ///
     /// # Example
     ///
     /// ```no_run
-    /// # extern crate monome;
-    ///
-    /// use monome::{Monome, MonomeEvent, KeyDirection};
-    /// let mut m = Monome::new("/prefix").unwrap();
-    ///
-    /// loop {
-    ///     match m.poll() {
-    ///         Some(MonomeEvent::GridKey{x, y, direction}) => {
-    ///             match direction {
-    ///                 KeyDirection::Down => {
-    ///                     println!("Key pressed: {}x{}", x, y);
-    ///                 }
-    ///                 KeyDirection::Up => {
-    ///                     println!("Key released: {}x{}", x, y);
-    ///                 }
-    ///             }
-    ///         }
-    ///         Some(MonomeEvent::Tilt{n: _n, x, y, z: _z}) => {
-    ///           println!("tilt update: pitch: {}, roll {}", x, y);
-    ///         }
-    ///         _ => {
-    ///           break;
-    ///         }
-    ///     }
-    /// }
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.all(false);
+    /// monome.close();
     /// ```
-    pub fn poll(&mut self) -> Option<MonomeEvent> {
-        match self.q.pop() {
-            Ok(buf) => self.parse(&buf),
-            Err(crossbeam::queue::PopError) => {
-                None
-            }
-        }
-    }
-
-    fn parse(&self, buf: &[u8]) -> Option<MonomeEvent> {
-        let packet = decode(buf).unwrap();
-        debug!("⇦ {:?}", packet);
-
-        match packet {
-            OscPacket::Message(message) => {
-                if message.addr.starts_with("/serialosc") {
-                    if message.addr == "/serialosc/device" {
-                        info!("/serialosc/device");
-                    } else if message.addr == "/serialosc/add" {
-                        if let Some(args) = message.args {
-                            if let OscType::String(ref device_name) = args[0] {
-                                info!("device added: {}", device_name);
-                            } else {
-                                warn!("unexpected message for prefix {}", message.addr);
-                            }
-                        } else if message.addr == "/serialosc/remove" {
-                            if let Some(args) = message.args {
-                                if let OscType::String(ref device_name) = args[0] {
-                                    info!("device removed: {}", device_name);
-                                } else {
-                                    warn!("unexpected message for prefix {}", message.addr);
-                                }
-                            }
-                        };
-                    }
-                } else if message.addr.starts_with("/sys") {
-                    // This should only be received during the setup phase
-                    debug!("/sys received: {:?}", message);
-                } else if message.addr.starts_with(&self.prefix) {
-                    if let Some(args) = &message.args {
-                        if message
-                            .addr
-                            .starts_with(&format!("{}/grid/key", self.prefix))
-                        {
-                            if let [OscType::Int(x), OscType::Int(y), OscType::Int(v)] =
-                                args.as_slice()
-                            {
-                                info!("Key: {}:{} {}", *x, *y, *v);
-                                let direction = if *v == 1 {
-                                    KeyDirection::Down
-                                } else {
-                                    KeyDirection::Up
-                                };
-                                return Some(MonomeEvent::GridKey {
-                                    x: *x,
-                                    y: *y,
-                                    direction,
-                                });
-                            }
-                            error!("Invalid /grid/key message received {:?}.", message);
-                        } else if message.addr.starts_with(&format!("{}/tilt", self.prefix)) {
-                            if let [OscType::Int(n), OscType::Int(x), OscType::Int(y), OscType::Int(z)] =
-                                args.as_slice()
-                            {
-                                info!("Tilt {} {},{},{}", *n, *x, *y, *z);
-                                return Some(MonomeEvent::Tilt {
-                                    n: *n,
-                                    x: *x,
-                                    y: *y,
-                                    z: *z,
-                                });
-                            }
-                            error!("Invalid /tilt message received {:?}.", message);
-                        } else if message
-                            .addr
-                            .starts_with(&format!("{}/enc/delta", self.prefix))
-                        {
-                            if let [OscType::Int(n), OscType::Int(delta)] = args.as_slice() {
-                                info!("Encoder delta {} {}", *n, *delta);
-                                return Some(MonomeEvent::EncoderDelta {
-                                    n: *n as usize,
-                                    delta: *delta,
-                                });
-                            }
-                            error!("Invalid /end/delta message received {:?}.", message);
-                        } else if message
-                            .addr
-                            .starts_with(&format!("{}/enc/key", self.prefix))
-                        {
-                            if let [OscType::Int(n), OscType::Int(direction)] = args.as_slice() {
-                                info!("Encoder key {} {}", *n, *direction);
-                                return Some(MonomeEvent::EncoderKey {
-                                    n: *n as usize,
-                                    direction: if *direction == 1 {
-                                        KeyDirection::Down
-                                    } else {
-                                        KeyDirection::Up
-                                    },
-                                });
-                            }
-                            error!("Invalid /end/key message received {:?}.", message);
-                        } else {
-                            error!("not handled: {:?}", message.addr);
-                        }
-                    }
-                }
-                None
-            }
-            OscPacket::Bundle(_bundle) => {
-                panic!("wtf.");
+    pub fn close(mut self) {
+        self.shutdown();
+    }
+
+    /// Disconnects the channels to the transport, so that it stops once it has sent the
+    /// messages waiting to be sent, and waits for its threads to exit, if any.
+    fn shutdown(&mut self) {
+        self.stop_reconnector();
+        drop(self.shared.reconnected.lock().unwrap().take());
+        self.tx = Outgoing::closed();
+        self.priority_tx = Outgoing::closed();
+        let threads: Vec<_> = self.shared.threads.lock().unwrap().drain(..).collect();
+        for thread in threads {
+            // This can be called from a notifier, on the thread receiving the events.
+            if thread.thread().id() != thread::current().id() && thread.join().is_err() {
+                error!("The transport thread panicked");
             }
         }
     }
 }
 
+impl Drop for Monome {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
 impl fmt::Debug for Monome {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let rv = write!(
             f,
             "Monome {}\n\ttype: {}\n\tport: {}\n\thost: {}\n\t\
             id: {}\n\tprefix: {}\n\trotation: {}",
-            self.name,
-            self.device_type,
-            self.port,
-            self.host,
-            self.id,
-            self.prefix,
-            self.rotation
+            self.name, self.device_type, self.port, self.host, self.id, self.prefix, self.rotation
         );
         if self.device_type == MonomeDeviceType::Grid {
             return write!(f, "\n\tsize: {}:{}", self.size.0, self.size.1);
@@ -1594,39 +4600,60 @@ impl fmt::Display for Monome {
 #[cfg(test)]
 mod tests {
     use crate::build_osc_message;
+    use crate::KeyDirection;
     use crate::Monome;
+    use crate::MonomeError;
+    use crate::MonomeEvent;
+    use crate::MonomeInfo;
     use crate::SERIALOSC_PORT;
+    use crate::{
+        apply_brightness_curve, bind_ip, local, parse_listed, reply_addr, ring_arc_messages,
+        ring_map_args, system_time_to_timetag, timetag_to_instant, BrightnessCurve,
+        DeviceChangeEvent, IdleDimmer, Listed, MonomeDeviceType, Outgoing, Shared, StartupOptions,
+        Transport,
+    };
+    use crossbeam::queue::ArrayQueue;
     use rosc::decoder::decode;
     use rosc::encoder::encode;
-    use rosc::{OscPacket, OscType};
+    use rosc::{OscBundle, OscPacket, OscType};
+    use std::io;
+    use std::net::{IpAddr, SocketAddr, UdpSocket};
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::{Arc, Condvar, Mutex};
     use std::thread;
-    use tokio::net::UdpSocket;
-    use tokio::prelude::*;
+    use std::time::{Duration, Instant, SystemTime};
+
+    /// Held by the tests that bind sockets from `START_PORT`, since `setup` expects to get the
+    /// first one.
+    pub(crate) static START_PORT_LOCK: Mutex<()> = Mutex::new(());
 
     #[test]
     fn setup() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
         let pair = Arc::new((Mutex::new(false), Condvar::new()));
         let pair2 = pair.clone();
 
         thread::spawn(move || {
             let fake_device_port = 1234;
-            let device_addr = format!("127.0.0.1:{}", fake_device_port).parse().unwrap();
-            let device_socket = UdpSocket::bind(&device_addr).unwrap();
+            let device_addr: SocketAddr =
+                format!("127.0.0.1:{}", fake_device_port).parse().unwrap();
+            let device_socket = UdpSocket::bind(device_addr).unwrap();
 
             // Avoid failing if serialocs is running on the default port.
-            let serialosc_addr = format!("127.0.0.1:{}", SERIALOSC_PORT + 1).parse().unwrap();
-            let serialosc_socket = UdpSocket::bind(&serialosc_addr).unwrap();
+            let serialosc_addr: SocketAddr =
+                format!("127.0.0.1:{}", SERIALOSC_PORT + 1).parse().unwrap();
+            let socket = UdpSocket::bind(serialosc_addr).unwrap();
 
             {
-                let &(ref lock, ref cvar) = &*pair2;
+                let (lock, cvar) = &*pair2;
                 let mut started = lock.lock().unwrap();
                 *started = true;
                 cvar.notify_all();
             }
 
-            let (socket, data, _, _) = serialosc_socket.recv_dgram(vec![0u8; 1024]).wait().unwrap();
-            let packet = decode(&data).unwrap();
+            let mut data = vec![0u8; 1024];
+            let (amt, _) = socket.recv_from(&mut data).unwrap();
+            let packet = decode(&data[..amt]).unwrap();
 
             let msg = match packet {
                 OscPacket::Message(m) => m,
@@ -1652,15 +4679,16 @@ mod tests {
 
             let bytes: Vec<u8> = encode(&packet).unwrap();
 
-            let app_addr = format!("127.0.0.1:{}", app_port).parse().unwrap();
-            let (mut socket, _) = socket.send_dgram(bytes, &app_addr).wait().unwrap();
+            let app_addr: SocketAddr = format!("127.0.0.1:{}", app_port).parse().unwrap();
+            socket.send_to(&bytes, app_addr).unwrap();
 
             fn receive_from_app_and_expect(
-                socket: UdpSocket,
+                socket: &UdpSocket,
                 expected_addr: String,
-            ) -> (UdpSocket, Option<Vec<OscType>>) {
-                let (socket, data, _, _) = socket.recv_dgram(vec![0u8; 1024]).wait().unwrap();
-                let packet = decode(&data).unwrap();
+            ) -> Option<Vec<OscType>> {
+                let mut data = vec![0u8; 1024];
+                let (amt, _) = socket.recv_from(&mut data).unwrap();
+                let packet = decode(&data[..amt]).unwrap();
 
                 let msg = match packet {
                     OscPacket::Message(m) => m,
@@ -1669,11 +4697,10 @@ mod tests {
 
                 assert!(msg.addr == expected_addr);
 
-                (socket, msg.args)
+                msg.args
             }
 
-            let (device_socket, args) =
-                receive_from_app_and_expect(device_socket, "/sys/port".into());
+            let args = receive_from_app_and_expect(&device_socket, "/sys/port".into());
             let port = if let OscType::Int(port) = args.unwrap()[0] {
                 assert!(port == 10000);
                 port
@@ -1681,8 +4708,7 @@ mod tests {
                 panic!("bad port");
             };
             assert!(port == 10000);
-            let (device_socket, args) =
-                receive_from_app_and_expect(device_socket, "/sys/host".into());
+            let args = receive_from_app_and_expect(&device_socket, "/sys/host".into());
             let argss = args.unwrap();
             let host = if let OscType::String(ref host) = argss[0] {
                 host
@@ -1690,8 +4716,7 @@ mod tests {
                 panic!("bad host");
             };
             assert!(host == "127.0.0.1");
-            let (device_socket, args) =
-                receive_from_app_and_expect(device_socket, "/sys/prefix".into());
+            let args = receive_from_app_and_expect(&device_socket, "/sys/prefix".into());
             let argss = args.unwrap();
             let prefix = if let OscType::String(ref prefix) = argss[0] {
                 prefix
@@ -1699,24 +4724,37 @@ mod tests {
                 panic!("bad prefix");
             };
             assert!(prefix == "/plop");
-            let (_device_socket, args) =
-                receive_from_app_and_expect(device_socket, "/sys/info".into());
+            let args = receive_from_app_and_expect(&device_socket, "/sys/info".into());
             assert!(args.is_none());
 
-            let message_addrs = vec![
+            // A chatty device: events and stale replies are interleaved with the informations.
+            let message_addrs = [
                 "/sys/port",
+                "/old/grid/key",
                 "/sys/host",
+                "/sys/prefix",
                 "/sys/id",
+                "/plop/tilt",
                 "/sys/prefix",
+                "/sys/connect",
                 "/sys/rotation",
                 "/sys/size",
             ];
 
-            let message_args = vec![
+            let message_args = [
                 vec![OscType::Int(fake_device_port)],
+                vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
                 vec![OscType::String("127.0.0.1".into())],
+                vec![OscType::String("/old".into())],
                 vec![OscType::String("monome blabla".into())],
+                vec![
+                    OscType::Int(0),
+                    OscType::Int(1),
+                    OscType::Int(2),
+                    OscType::Int(3),
+                ],
                 vec![OscType::String("/plop".into())],
+                vec![],
                 vec![OscType::Int(0)],
                 vec![OscType::Int(16), OscType::Int(8)],
             ];
@@ -1726,21 +4764,917 @@ mod tests {
             for i in 0..message_addrs.len() {
                 let packet = build_osc_message(message_addrs[i], message_args[i].clone());
                 let bytes: Vec<u8> = encode(&packet).unwrap();
-                socket = socket
-                    .send_dgram(bytes, &app_addr)
-                    .map(|(socket, _)| socket)
-                    .wait()
-                    .unwrap();
+                socket.send_to(&bytes, app_addr).unwrap();
             }
         });
 
-        let &(ref lock, ref cvar) = &*pair;
+        let (lock, cvar) = &*pair;
         let mut started = lock.lock().unwrap();
         while !*started {
             started = cvar.wait(started).unwrap();
         }
 
         // use another port in case serialosc is running on the local machine
-        let _m = Monome::new_with_port("/plop".to_string(), SERIALOSC_PORT + 1).unwrap();
+        let mut m = Monome::new_with_port("/plop".to_string(), SERIALOSC_PORT + 1).unwrap();
+
+        assert_eq!(
+            m.poll(),
+            Some(MonomeEvent::Size {
+                width: 16,
+                height: 8
+            })
+        );
+        assert_eq!(m.poll(), Some(MonomeEvent::Rotation { rotation: 0 }));
+        assert_eq!(
+            m.poll(),
+            Some(MonomeEvent::Prefix {
+                prefix: "/plop".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn enumeration_times_out() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
+        // Nothing is bound on this port anymore.
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        match Monome::enumerate_devices_at(local(i32::from(port))) {
+            Err(MonomeError::SerialoscUnreachable(ref e))
+                if e.kind() == io::ErrorKind::TimedOut => {}
+            other => panic!("unexpected enumeration result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn setup_times_out() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
+        // A device that never answers.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = i32::from(silent.local_addr().unwrap().port());
+        let timeout = Duration::from_millis(50);
+        match Monome::setup("/plop", local(port), timeout) {
+            Err(MonomeError::DeviceUnreachable(ref e)) if e.kind() == io::ErrorKind::TimedOut => {}
+            other => panic!("unexpected setup result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn bundle_timetags() {
+        let received_at = Instant::now();
+        let sent = SystemTime::now() - Duration::from_millis(100);
+        let sent_at = timetag_to_instant(&system_time_to_timetag(sent), received_at);
+        let early = received_at - sent_at;
+        assert!(early >= Duration::from_millis(99) && early < Duration::from_secs(1));
+        assert_eq!(
+            timetag_to_instant(&OscType::Time(0, 1), received_at),
+            received_at
+        );
+
+        // The events of a bundle are timestamped with its timetag.
+        let shared = Arc::new(Shared::new("/plop"));
+        let q = Arc::new(ArrayQueue::new(4));
+        let transport = Transport::new(local(1), q.clone(), shared);
+        let key = build_osc_message(
+            "/plop/grid/key",
+            vec![OscType::Int(0), OscType::Int(0), OscType::Int(1)],
+        );
+        let bundle = OscPacket::Bundle(OscBundle {
+            timetag: system_time_to_timetag(sent),
+            content: vec![key.clone(), key],
+        });
+        transport.dispatch(&encode(&bundle).unwrap());
+        for _ in 0..2 {
+            let (timestamp, _) = q.pop().unwrap();
+            assert!(timestamp < received_at);
+        }
+    }
+
+    #[test]
+    fn device_changes() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
+        // A fake serialosc, that notifies the application of a new device.
+        let serialosc = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let serialosc_port = i32::from(serialosc.local_addr().unwrap().port());
+        let changes = Monome::device_changes_with_port(serialosc_port).unwrap();
+        let mut buf = vec![0u8; 1024];
+        let (amt, app_addr) = serialosc.recv_from(&mut buf).unwrap();
+        match decode(&buf[..amt]).unwrap() {
+            OscPacket::Message(message) => assert_eq!(message.addr, "/serialosc/notify"),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        let add = build_osc_message("/serialosc/add", vec![OscType::String("m1".into())]);
+        serialosc.send_to(&encode(&add).unwrap(), app_addr).unwrap();
+        match changes.recv_timeout(Duration::from_secs(5)) {
+            Ok(DeviceChangeEvent::Added(id)) => assert_eq!(id, "m1"),
+            other => panic!("unexpected change {:?}", other),
+        }
+        Monome::stop_device_change_listeners();
+    }
+
+    #[test]
+    fn cancel_device_change_subscription() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
+        let serialosc = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let serialosc_port = i32::from(serialosc.local_addr().unwrap().port());
+        let called = Arc::new(AtomicUsize::new(0));
+        let counter = called.clone();
+        let cancelled =
+            Monome::register_device_change_callback_with_port(serialosc_port, move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        let kept =
+            Monome::register_device_change_callback_with_port(serialosc_port, |_| {}).unwrap();
+        assert!(cancelled.is_active());
+        cancelled.cancel();
+        assert!(kept.is_active());
+
+        // Only the remaining listener asks to be notified now.
+        serialosc
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut buf = vec![0u8; 1024];
+        let mut apps = Vec::new();
+        while let Ok((_, app_addr)) = serialosc.recv_from(&mut buf) {
+            if !apps.contains(&app_addr) {
+                apps.push(app_addr);
+            }
+        }
+        let add = build_osc_message("/serialosc/add", vec![OscType::String("m1".into())]);
+        for app in &apps {
+            serialosc.send_to(&encode(&add).unwrap(), app).unwrap();
+        }
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(called.load(Ordering::SeqCst), 0);
+        Monome::stop_device_change_listeners();
+        assert!(!kept.is_active());
+    }
+
+    #[test]
+    fn remote_host() {
+        let remote: SocketAddr = "192.168.1.20:12002".parse().unwrap();
+        assert!(bind_ip(local(1).ip()).is_loopback());
+        assert!(bind_ip(remote.ip()).is_unspecified());
+
+        // A socket bound to all the interfaces tells its peer the address it reaches it with.
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let reply = reply_addr(&socket, local(12002)).unwrap();
+        assert_eq!(reply, SocketAddr::new(local(1).ip(), port));
+
+        // The devices are on the host of the serialosc instance that reports them.
+        let listed = build_osc_message(
+            "/serialosc/device",
+            vec![
+                OscType::String("m1".into()),
+                OscType::String("monome 128".into()),
+                OscType::Int(15432),
+            ],
+        );
+        match parse_listed(&encode(&listed).unwrap(), remote).as_slice() {
+            [Listed::Device(device)] => {
+                assert_eq!(device.host(), remote.ip());
+                assert_eq!(device.serialosc_port(), 12002);
+                assert_eq!(device.addr(), "192.168.1.20:15432".parse().unwrap());
+            }
+            _ => panic!("the device was not listed"),
+        }
+
+        // Several devices, and the end of the list, in a bundle.
+        let bundle = OscPacket::Bundle(OscBundle {
+            timetag: OscType::Time(0, 1),
+            content: vec![
+                listed.clone(),
+                listed,
+                build_osc_message("/serialosc/device", vec![]),
+            ],
+        });
+        match parse_listed(&encode(&bundle).unwrap(), remote).as_slice() {
+            [Listed::Device(_), Listed::Device(_), Listed::End] => {}
+            _ => panic!("the devices were not listed"),
+        }
+    }
+
+    #[test]
+    fn ipv6_enumeration() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
+        let serialosc = UdpSocket::bind("[::1]:0").unwrap();
+        let serialosc_addr = serialosc.local_addr().unwrap();
+        let fake = thread::spawn(move || {
+            let mut buf = vec![0u8; 1024];
+            let (amt, app_addr) = serialosc.recv_from(&mut buf).unwrap();
+            let request = match decode(&buf[..amt]).unwrap() {
+                OscPacket::Message(message) => message,
+                packet => panic!("unexpected packet {:?}", packet),
+            };
+            // The application asks for the list on the same address family.
+            assert_eq!(request.addr, "/serialosc/list");
+            assert_eq!(
+                request.args.unwrap(),
+                vec![
+                    OscType::String("::1".into()),
+                    OscType::Int(i32::from(app_addr.port()))
+                ]
+            );
+            let listed = build_osc_message(
+                "/serialosc/device",
+                vec![
+                    OscType::String("m1".into()),
+                    OscType::String("monome 128".into()),
+                    OscType::Int(15432),
+                ],
+            );
+            serialosc
+                .send_to(&encode(&listed).unwrap(), app_addr)
+                .unwrap();
+        });
+        let devices = Monome::enumerate_devices_at(serialosc_addr).unwrap();
+        fake.join().unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].addr(), "[::1]:15432".parse().unwrap());
+        assert_eq!(
+            bind_ip("2001:db8::1".parse().unwrap()),
+            "::".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn new_with_id_and_name_on() {
+        let _lock = START_PORT_LOCK.lock().unwrap();
+        let serialosc = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let serialosc_addr = serialosc.local_addr().unwrap();
+        let fake = thread::spawn(move || {
+            let mut buf = vec![0u8; 1024];
+            for _ in 0..2 {
+                let (_, app_addr) = serialosc.recv_from(&mut buf).unwrap();
+                let listed = OscPacket::Bundle(OscBundle {
+                    timetag: OscType::Time(0, 1),
+                    content: vec![
+                        build_osc_message(
+                            "/serialosc/device",
+                            vec![
+                                OscType::String("m1".into()),
+                                OscType::String("monome 128".into()),
+                                OscType::Int(15432),
+                            ],
+                        ),
+                        build_osc_message("/serialosc/device", vec![]),
+                    ],
+                });
+                serialosc
+                    .send_to(&encode(&listed).unwrap(), app_addr)
+                    .unwrap();
+            }
+        });
+        let (host, port) = (serialosc_addr.ip(), i32::from(serialosc_addr.port()));
+        // Only the devices of this serialosc instance are considered.
+        assert!(matches!(
+            Monome::new_with_id_on("/plop", "m2", host, port),
+            Err(MonomeError::NoDevice)
+        ));
+        assert!(matches!(
+            Monome::new_with_name_on("/plop", "arc", host, port),
+            Err(MonomeError::NoDevice)
+        ));
+        fake.join().unwrap();
+    }
+
+    #[test]
+    fn adaptive_updates() {
+        let mut info = MonomeInfo::new("/plop");
+        info.host = Some("127.0.0.1".to_string());
+        info.id = Some("m1".to_string());
+        info.size = Some((16, 8));
+        info.rotation = Some(0);
+        let (sender, sent) = crossbeam::channel::unbounded();
+        let mut monome = Monome::start(
+            info,
+            "m1".to_string(),
+            MonomeDeviceType::Grid,
+            local(1),
+            "/plop".to_string(),
+            &StartupOptions::default(),
+            |_| Ok((Outgoing::Thread(sender), Outgoing::closed())),
+        )
+        .unwrap();
+        let addresses = || -> Vec<String> {
+            sent.try_iter()
+                .map(
+                    |(_, bytes): (Instant, Vec<u8>)| match decode(&bytes).unwrap() {
+                        OscPacket::Message(message) => message.addr,
+                        packet => panic!("unexpected packet {:?}", packet),
+                    },
+                )
+                .collect()
+        };
+
+        monome.set_adaptive_updates(true);
+        let mut leds = [0; 128];
+        monome.set_all_intensity(&leds);
+        assert_eq!(addresses(), vec!["/plop/grid/led/level/map"; 2]);
+        monome.set_all_intensity(&leds);
+        assert!(addresses().is_empty());
+        leds[20] = 15;
+        monome.set_all_intensity(&leds);
+        assert_eq!(addresses(), vec!["/plop/grid/led/level/set"]);
+        for x in 0..8 {
+            leds[16 * 7 + 8 + x] = 3;
+        }
+        monome.set_all_intensity(&leds);
+        assert_eq!(addresses(), vec!["/plop/grid/led/level/row"]);
+
+        // The leds changed by other means are not known anymore.
+        monome.set(0, 0, 15);
+        monome.set_all_intensity(&leds);
+        assert_eq!(
+            addresses(),
+            vec![
+                "/plop/grid/led/level/set",
+                "/plop/grid/led/level/map",
+                "/plop/grid/led/level/map"
+            ]
+        );
+    }
+
+    /// Returns a 16x8 grid, with the id "m1" and the prefix "/plop", that sends its messages to
+    /// the returned channel, once its setup messages have been sent.
+    pub(crate) fn test_grid() -> (Monome, crossbeam::channel::Receiver<(Instant, Vec<u8>)>) {
+        let (monome, sent, _) = test_grid_with_priority();
+        (monome, sent)
+    }
+
+    /// Returns an arc of `model`, e.g. "monome arc 4", which is also its id, with the prefix
+    /// "/plop", that sends its messages to the returned channel, once its setup messages have
+    /// been sent.
+    pub(crate) fn test_arc(
+        model: &str,
+    ) -> (Monome, crossbeam::channel::Receiver<(Instant, Vec<u8>)>) {
+        let mut info = MonomeInfo::new("/plop");
+        info.id = Some(model.to_string());
+        info.size = Some((0, 0));
+        info.model = Some(model.to_string());
+        let (monome, sent, _) = start_test_device(info, MonomeDeviceType::Arc);
+        (monome, sent)
+    }
+
+    /// A 16x8 grid whose messages are sent on the first channel returned, and whose messages
+    /// sent first on the second one.
+    #[allow(clippy::type_complexity)]
+    fn test_grid_with_priority() -> (
+        Monome,
+        crossbeam::channel::Receiver<(Instant, Vec<u8>)>,
+        crossbeam::channel::Receiver<(Instant, Vec<u8>)>,
+    ) {
+        let mut info = MonomeInfo::new("/plop");
+        info.id = Some("m1".to_string());
+        info.size = Some((16, 8));
+        start_test_device(info, MonomeDeviceType::Grid)
+    }
+
+    /// Starts the device described by `info`, whose id and size are set, whose messages are sent
+    /// on the first channel returned, once its setup messages have been sent, and whose messages
+    /// sent first on the second one.
+    #[allow(clippy::type_complexity)]
+    fn start_test_device(
+        mut info: MonomeInfo,
+        device_type: MonomeDeviceType,
+    ) -> (
+        Monome,
+        crossbeam::channel::Receiver<(Instant, Vec<u8>)>,
+        crossbeam::channel::Receiver<(Instant, Vec<u8>)>,
+    ) {
+        info.host = Some("127.0.0.1".to_string());
+        info.rotation = Some(0);
+        let id = info.id.clone().unwrap();
+        let (sender, sent) = crossbeam::channel::unbounded();
+        let (priority_sender, priority_sent) = crossbeam::channel::unbounded();
+        let monome = Monome::start(
+            info,
+            id,
+            device_type,
+            local(1),
+            "/plop".to_string(),
+            &StartupOptions::default(),
+            |_| Ok((Outgoing::Thread(sender), Outgoing::Thread(priority_sender))),
+        )
+        .unwrap();
+        while sent.try_recv().is_ok() {}
+        (monome, sent, priority_sent)
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_leds_are_converted() {
+        let (mut monome, sent) = test_grid();
+        monome.set_monochrome_threshold(Some(8));
+        monome.set_all_intensity(&[0; 128]);
+        while sent.try_recv().is_ok() {}
+
+        futures::executor::block_on(monome.send_async(
+            "/grid/led/level/set",
+            vec![OscType::Int(2), OscType::Int(3), OscType::Int(4)],
+        ))
+        .unwrap();
+        let (_, bytes) = sent.try_recv().unwrap();
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            build_osc_message(
+                "/plop/grid/led/set",
+                vec![OscType::Int(2), OscType::Int(3), OscType::Int(0)]
+            )
+        );
+        assert_eq!(monome.led(2, 3), 4);
+        // The leds are all sent again, as the ones displayed are not known anymore.
+        monome.set_all_intensity(&[0; 128]);
+        assert!(sent.try_recv().is_ok());
+    }
+
+    #[test]
+    fn urgent_leds_are_converted() {
+        let (mut monome, sent, priority_sent) = test_grid_with_priority();
+        monome.set_rotation(180);
+        monome.set_logical_coordinates(true);
+        let mut table = [0; 16];
+        table[7] = 3;
+        monome.set_brightness_curve(Some(BrightnessCurve::from_table(table)));
+        while sent.try_recv().is_ok() {}
+
+        monome.begin_batch();
+        monome.set_urgent(0, 1, 7);
+        assert!(sent.try_recv().is_err());
+        let (_, bytes) = priority_sent.try_recv().unwrap();
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            build_osc_message(
+                "/plop/grid/led/level/set",
+                vec![OscType::Int(15), OscType::Int(6), OscType::Int(3)]
+            )
+        );
+        // The led is known, in logical coordinates.
+        assert_eq!(monome.led(0, 1), 7);
+    }
+
+    #[test]
+    fn flush_on_drop() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        device
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut info = MonomeInfo::new("/plop");
+        info.host = Some("127.0.0.1".to_string());
+        info.id = Some("m1".to_string());
+        info.size = Some((16, 8));
+        info.rotation = Some(0);
+        let monome = Monome::start(
+            info,
+            "m1".to_string(),
+            MonomeDeviceType::Grid,
+            device.local_addr().unwrap(),
+            "/plop".to_string(),
+            &StartupOptions::default(),
+            |transport| transport.spawn(socket),
+        )
+        .unwrap();
+
+        let mut queued = 0;
+        for x in 0..64 {
+            let packet = build_osc_message(
+                "/plop/grid/led/set",
+                vec![OscType::Int(x), OscType::Int(0), OscType::Int(1)],
+            );
+            if monome.tx.try_send(encode(&packet).unwrap()) {
+                queued += 1;
+            }
+        }
+        drop(monome);
+
+        let mut received = 0;
+        let mut buf = vec![0; 1024];
+        while let Ok(amt) = device.recv(&mut buf) {
+            if let Ok(OscPacket::Message(message)) = decode(&buf[..amt]) {
+                if message.addr == "/plop/grid/led/set" {
+                    received += 1;
+                }
+            }
+        }
+        assert_eq!(received, queued);
+    }
+
+    #[test]
+    fn short_leds() {
+        let (mut monome, sent) = test_grid();
+
+        // Too few leds are not sent, instead of panicking.
+        monome.set_all(&[true; 64]);
+        monome.set_all_intensity(&[15; 127]);
+        assert!(sent.try_recv().is_err());
+        match monome.try_set_all_intensity(&[15; 64]) {
+            Err(MonomeError::InvalidLength { expected, actual }) => {
+                assert_eq!((expected, actual), (128, 64))
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(monome.try_set_all(&[true; 128]).is_ok());
+        assert_eq!(sent.try_iter().count(), 2);
+        assert!(monome.leds().iter().all(|&led| led == 15));
+        monome.set(2, 1, false);
+        assert_eq!(monome.led(2, 1), 0);
+    }
+
+    #[test]
+    fn held_keys() {
+        let (mut monome, _sent) = test_grid();
+        while monome.poll().is_some() {}
+        let key = |x, y, direction| MonomeEvent::GridKey { x, y, direction };
+        for event in [
+            key(2, 3, KeyDirection::Down),
+            key(4, 5, KeyDirection::Down),
+            key(2, 3, KeyDirection::Up),
+        ] {
+            monome.q.push((Instant::now(), event)).unwrap();
+        }
+        while monome.poll().is_some() {}
+        assert!(monome.is_down(4, 5));
+        assert!(!monome.is_down(2, 3));
+        assert_eq!(monome.held_keys().collect::<Vec<_>>(), vec![(4, 5)]);
+
+        // Resynchronizing releases the keys held.
+        monome.resync();
+        let events: Vec<MonomeEvent> = std::iter::from_fn(|| monome.poll()).collect();
+        assert_eq!(events.last(), Some(&key(4, 5, KeyDirection::Up)));
+        assert_eq!(monome.held_keys().count(), 0);
+    }
+
+    #[test]
+    fn handlers() {
+        let (mut monome, sent) = test_grid();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let keys = calls.clone();
+        monome.on_key(move |monome, x, y, direction| {
+            keys.lock()
+                .unwrap()
+                .push(format!("key {} {} {:?}", x, y, direction));
+            monome.set(x, y, direction == KeyDirection::Down);
+        });
+        let deltas = calls.clone();
+        monome.on_encoder_delta(move |monome, n, delta| {
+            deltas
+                .lock()
+                .unwrap()
+                .push(format!("delta {} {}", n, delta));
+            // Registered while dispatching, called from the next event on.
+            let late = deltas.clone();
+            monome.on_encoder_delta(move |_, _, _| late.lock().unwrap().push("late".to_string()));
+        });
+
+        let down = MonomeEvent::GridKey {
+            x: 1,
+            y: 2,
+            direction: KeyDirection::Down,
+        };
+        assert!(monome.dispatch(&down));
+        assert_eq!(sent.try_iter().count(), 1);
+        assert!(!monome.dispatch(&MonomeEvent::Tilt {
+            n: 0,
+            x: 1,
+            y: 2,
+            z: 3
+        }));
+        assert!(monome.dispatch(&MonomeEvent::EncoderDelta { n: 1, delta: -3 }));
+        assert!(monome.dispatch(&MonomeEvent::EncoderDelta { n: 0, delta: 2 }));
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["key 1 2 Down", "delta 1 -3", "delta 0 2", "late"]
+        );
+
+        monome.clear_handlers();
+        assert!(!monome.dispatch(&down));
+    }
+
+    #[test]
+    fn std_event_receiver() {
+        let (mut monome, _sent) = test_grid();
+        while monome.poll().is_some() {}
+        let key = MonomeEvent::GridKey {
+            x: 3,
+            y: 4,
+            direction: KeyDirection::Down,
+        };
+        monome.q.push((Instant::now(), key.clone())).unwrap();
+        // The events queued for `poll()` are sent on the channel first.
+        let events = monome.std_event_receiver();
+        assert_eq!(events.try_recv(), Ok(key));
+        assert!(events.try_recv().is_err());
+        assert_eq!(monome.poll(), None);
+
+        // Another receiver replaces it.
+        let _crossbeam = monome.event_receiver();
+        assert_eq!(
+            events.try_recv(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn poll_timeout() {
+        let (mut monome, _sent) = test_grid();
+        while monome.poll().is_some() {}
+        let start = Instant::now();
+        assert_eq!(monome.poll_timeout(Duration::from_millis(20)), None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let (q, shared) = (monome.q.clone(), monome.shared.clone());
+        let key = MonomeEvent::GridKey {
+            x: 1,
+            y: 2,
+            direction: KeyDirection::Down,
+        };
+        let sent = key.clone();
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            q.push((Instant::now(), sent)).unwrap();
+            shared.notify_queued();
+        });
+        assert_eq!(monome.poll_timeout(Duration::from_secs(10)), Some(key));
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn idle_dimming() {
+        let (mut monome, sent) = test_grid();
+        let mut dimmer = IdleDimmer::new(Duration::from_secs(0), 0.5);
+        dimmer.set_ramps(Duration::from_secs(0), Duration::from_secs(0));
+        monome.set_idle_dimming(Some(dimmer));
+        monome.set_all_intensity(&[8; 128]);
+        let frame = |sent: &crossbeam::channel::Receiver<(Instant, Vec<u8>)>| {
+            let (_, bytes) = sent.try_iter().next().unwrap();
+            match decode(&bytes).unwrap() {
+                OscPacket::Message(message) => message.args.unwrap()[2].clone(),
+                packet => panic!("unexpected packet {:?}", packet),
+            }
+        };
+        assert_eq!(frame(&sent), OscType::Int(4));
+
+        let press = MonomeEvent::GridKey {
+            x: 1,
+            y: 2,
+            direction: KeyDirection::Down,
+        };
+        while monome.poll().is_some() {}
+        monome.q.push((Instant::now(), press.clone())).unwrap();
+        assert_eq!(monome.poll(), Some(MonomeEvent::Woke));
+        assert_eq!(monome.poll(), Some(press));
+        assert!(monome.is_down(1, 2));
+        assert_eq!(monome.poll(), None);
+    }
+
+    #[test]
+    fn output_filter() {
+        let (mut monome, sent) = test_grid();
+        monome.set_skip_redundant_leds(true);
+        monome.set(0, 0, true);
+        monome.set(0, 0, true);
+        monome.set(0, 0, 15);
+        monome.set(0, 0, false);
+        assert_eq!(sent.try_iter().count(), 2);
+        // The leds set by an output are known, and not dropped when set back.
+        let output = monome.output();
+        output.set(0, 0, true);
+        monome.set(0, 0, false);
+        monome.set(0, 0, false);
+        assert_eq!(sent.try_iter().count(), 2);
+        // Outputs are filtered too.
+        output.set(0, 0, false);
+        assert_eq!(sent.try_iter().count(), 0);
+        // The leds of a message that couldn't be queued are not known, and are sent again.
+        let (full, _receiver) = crossbeam::channel::bounded(0);
+        let tx = std::mem::replace(&mut monome.tx, Outgoing::Thread(full));
+        monome.set(1, 0, true);
+        monome.tx = tx;
+        assert_eq!(monome.led(1, 0), 0);
+        monome.set(1, 0, true);
+        assert_eq!(sent.try_iter().count(), 1);
+
+        monome.set_max_message_rate(Some(10));
+        for x in 0..16 {
+            monome.set(x, 1, true);
+        }
+        assert_eq!(sent.try_iter().count(), 10);
+        assert_eq!(monome.rate_limited_messages(), 6);
+        // The leds dropped are not known to be on.
+        assert_eq!(monome.led(15, 1), 0);
+        output.set(0, 2, true);
+        assert_eq!(sent.try_iter().count(), 0);
+        assert_eq!(monome.rate_limited_messages(), 7);
+    }
+
+    #[test]
+    fn send_queue_full() {
+        let (mut monome, _sent) = test_grid();
+        let (full, _receiver) = crossbeam::channel::bounded(0);
+        monome.tx = Outgoing::Thread(full);
+        let dropped = |result| matches!(result, Err(MonomeError::SendQueueFull));
+        assert!(dropped(monome.map(0, 0, &[0u8; 8])));
+        assert!(dropped(monome.row(0, 0, &0u8)));
+        assert!(dropped(monome.col(0, 0, &0u8)));
+        // Batched messages are queued.
+        monome.begin_batch();
+        assert!(monome.row(0, 0, &0u8).is_ok());
+    }
+
+    #[test]
+    fn event_notifiers() {
+        let (mut monome, _sent) = test_grid();
+        let notifier = monome.add_event_notifier(|| {});
+        // The device is gone: run returns right away, and removes its own notifier.
+        monome.shared.stopped.store(true, Ordering::SeqCst);
+        let mut events = 0;
+        monome.run(|_, _| events += 1);
+        assert!(events > 0);
+        assert_eq!(monome.shared.notifiers.lock().unwrap().len(), 1);
+        assert!(monome.remove_event_notifier(notifier));
+        assert!(!monome.remove_event_notifier(notifier));
+        assert!(monome.shared.notifiers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wrong_device_type() {
+        let (mut monome, sent) = test_grid();
+        monome.device_type = MonomeDeviceType::Arc;
+        let wrong = |result| {
+            matches!(
+                result,
+                Err(MonomeError::WrongDeviceType {
+                    expected: MonomeDeviceType::Grid,
+                    actual: MonomeDeviceType::Arc,
+                })
+            )
+        };
+        assert!(wrong(monome.map(0, 0, &[0u8; 8])));
+        assert!(wrong(monome.row(0, 0, &0u8)));
+        assert!(wrong(monome.col(0, 0, &0u8)));
+        assert!(wrong(monome.try_set_all(&[false; 128])));
+        assert!(wrong(monome.try_set_all_intensity(&[0; 128])));
+        assert!(sent.try_recv().is_err());
+    }
+
+    #[test]
+    fn invalid_positions() {
+        let (mut monome, sent) = test_grid();
+        let invalid = |result| matches!(result, Err(MonomeError::InvalidPosition { .. }));
+        assert!(invalid(monome.map(3, 5, &[0u8; 8])));
+        assert!(invalid(monome.map(16, 0, &[0u8; 8])));
+        assert!(invalid(monome.row(0, 8, &0u8)));
+        assert!(invalid(monome.row(-8, 0, &0u8)));
+        assert!(invalid(monome.col(16, 0, &0u8)));
+        assert!(invalid(monome.col(3, 4, &0u8)));
+        assert!(sent.try_recv().is_err());
+
+        assert!(monome.map(8, 0, &[0u8; 8]).is_ok());
+        assert!(monome.row(8, 7, &0u8).is_ok());
+        assert!(monome.col(15, 0, &0u8).is_ok());
+        assert_eq!(sent.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn ring_arc() {
+        let int = |value| OscType::Int(value);
+        let messages = ring_arc_messages(1, &crate::ring_arc_leds(337.5, 45., 15));
+        assert_eq!(
+            messages,
+            vec![
+                ("/ring/range", vec![int(1), int(0), int(3), int(15)]),
+                ("/ring/range", vec![int(1), int(60), int(63), int(15)]),
+            ]
+        );
+        let messages = ring_arc_messages(0, &[(0, 7), (1, 15), (2, 15), (3, 7), (5, 7)]);
+        assert_eq!(
+            messages,
+            vec![
+                ("/ring/set", vec![int(0), int(0), int(7)]),
+                ("/ring/range", vec![int(0), int(1), int(2), int(15)]),
+                ("/ring/set", vec![int(0), int(3), int(7)]),
+                ("/ring/set", vec![int(0), int(5), int(7)]),
+            ]
+        );
+        assert!(ring_arc_messages(0, &[]).is_empty());
+    }
+
+    #[test]
+    fn ring_map_values() {
+        use crate::IntoAddrAndArgs;
+        let args = |values: Vec<OscType>| -> Vec<i32> {
+            values
+                .into_iter()
+                .map(|arg| match arg {
+                    OscType::Int(i) => i,
+                    _ => panic!("not an int"),
+                })
+                .collect()
+        };
+
+        let mut intensities = [0u8; 64];
+        intensities[3] = 7;
+        let (frag, values) = (&intensities).as_addr_frag_and_args();
+        let map = args(ring_map_args(2, &frag, values));
+        assert_eq!(map.len(), 65);
+        assert_eq!(map[..5], [2, 0, 0, 0, 7]);
+
+        let (frag, values) = (&[0b0000_0011u8, 0, 0, 0, 0, 0, 0, 0x80]).as_addr_frag_and_args();
+        let map = args(ring_map_args(0, &frag, values));
+        assert_eq!(map.len(), 65);
+        assert_eq!(map[1..4], [15, 15, 0]);
+        assert_eq!(map[64], 15);
+        assert_eq!(map.iter().skip(1).filter(|&&led| led == 15).count(), 3);
+
+        let mut on = [false; 64];
+        on[10] = true;
+        let (frag, values) = (&on).as_addr_frag_and_args();
+        let map = args(ring_map_args(1, &frag, values));
+        assert_eq!(map[1 + 10], 15);
+        assert_eq!(map.iter().skip(1).filter(|&&led| led == 15).count(), 1);
+    }
+
+    #[test]
+    fn brightness_curve() {
+        let curve =
+            BrightnessCurve::from_table([0, 1, 1, 2, 2, 3, 3, 4, 5, 6, 7, 8, 10, 11, 13, 15]);
+        let mut args = vec![OscType::Int(4), OscType::Int(2), OscType::Int(15)];
+        apply_brightness_curve(&curve, "/plop/grid/led/level/set", &mut args);
+        assert_eq!(args[..2], [OscType::Int(4), OscType::Int(2)]);
+        assert_eq!(args[2], OscType::Int(15));
+
+        let mut args = vec![OscType::Int(1), OscType::Int(7)];
+        apply_brightness_curve(&curve, "/plop/ring/all", &mut args);
+        assert_eq!(args, vec![OscType::Int(1), OscType::Int(4)]);
+
+        let mut args = vec![OscType::Int(0), OscType::Int(0), OscType::Int(7)];
+        apply_brightness_curve(&curve, "/plop/grid/led/row", &mut args);
+        assert_eq!(args[2], OscType::Int(7));
+
+        let mut args: Vec<OscType> = (0..66).map(|i| OscType::Int(i % 16)).collect();
+        apply_brightness_curve(&curve, "/plop/grid/led/level/map", &mut args);
+        assert_eq!(args[..2], [OscType::Int(0), OscType::Int(1)]);
+        assert_eq!(args[12], OscType::Int(10));
+    }
+
+    #[test]
+    fn stale_transport_stop() {
+        let shared = Arc::new(Shared::new("/plop"));
+        let q = Arc::new(ArrayQueue::new(1));
+        let stale = Transport::new(local(1), q.clone(), shared.clone());
+        // The device has been set up again.
+        shared.generation.fetch_add(1, Ordering::SeqCst);
+        let current = Transport::new(local(2), q, shared.clone());
+        stale.stop();
+        assert!(!shared.stopped.load(Ordering::SeqCst));
+        current.stop();
+        assert!(shared.stopped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn handshake_ignores_noise() {
+        let message = |addr: &str, args: Vec<OscType>| build_osc_message(addr, args);
+        let mut info = MonomeInfo::new("plop");
+        info.fill(message("/sys/id", vec![]));
+        info.fill(message("/sys/size", vec![OscType::Int(16)]));
+        info.fill(message("/sys/port", vec![OscType::String("1234".into())]));
+        info.fill(message("/sys/prefix", vec![OscType::String("/old".into())]));
+        info.fill(message("/sys/portal", vec![OscType::Int(1)]));
+        assert!(info.port.is_none());
+        assert!(info.prefix.is_none());
+        assert!(info.size.is_none());
+        assert!(info.rotation.is_none());
+        // The messages of a bundle are filled one by one.
+        info.fill(OscPacket::Bundle(OscBundle {
+            timetag: OscType::Time(0, 1),
+            content: vec![message("/sys/rotation", vec![OscType::Int(90)])],
+        }));
+        assert_eq!(info.rotation, Some(90));
+
+        info.fill(message("/sys/port", vec![OscType::Int(1234)]));
+        info.fill(message(
+            "/sys/host",
+            vec![OscType::String("127.0.0.1".into())],
+        ));
+        info.fill(message("/sys/id", vec![OscType::String("m1".into())]));
+        info.fill(message("/plop/grid/key", vec![OscType::Int(0); 3]));
+        info.fill(message(
+            "/sys/prefix",
+            vec![OscType::String("/plop".into())],
+        ));
+        info.fill(message("/sys/rotation", vec![OscType::Int(0)]));
+        assert!(!info.complete());
+        info.fill(message(
+            "/sys/size",
+            vec![OscType::Int(16), OscType::Int(8)],
+        ));
+        assert!(info.complete());
+        assert_eq!(info.size, Some((16, 8)));
     }
 }
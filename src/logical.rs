@@ -0,0 +1,244 @@
+//! Conversion of the coordinates of the leds set by an application that ignores the rotation of
+//! the device, to the coordinates of the rotated device.
+
+use rosc::OscType;
+
+use crate::rotate_coordinates;
+
+/// The conversion of the messages that set leds, addressed as if the device had no rotation, to
+/// messages addressed as the device expects them with its rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct LedOrientation {
+    /// The size of the device without rotation.
+    size: (i32, i32),
+    /// The rotation of the device, in degrees.
+    rotation: i32,
+}
+
+/// The leds set by a message, as `(x, y, value)`, a value being an intensity, or 0 or 1 for the
+/// messages that set leds on or off.
+type Leds = Vec<(i32, i32, i32)>;
+
+impl LedOrientation {
+    /// Create a conversion for a device of `size`, as `(width, height)` without rotation, that
+    /// has a rotation of `rotation` degrees.
+    pub(crate) fn new(size: (i32, i32), rotation: i32) -> LedOrientation {
+        LedOrientation { size, rotation }
+    }
+
+    /// Set the rotation of the device, in degrees.
+    pub(crate) fn set_rotation(&mut self, rotation: i32) {
+        self.rotation = rotation;
+    }
+
+    /// Converts a message sent to `addr`, without prefix, with `args`, to the messages to send
+    /// to the rotated device. Messages that don't set leds at coordinates are returned as they
+    /// are. The converted messages have the same kind as the message, e.g. a row becomes a
+    /// column on a device rotated by a quarter turn, unless the leds don't line up as the
+    /// device expects them, in which case they are set one by one.
+    pub(crate) fn convert(&self, addr: &str, args: Vec<OscType>) -> Vec<(String, Vec<OscType>)> {
+        let unchanged = |args| vec![(addr.to_string(), args)];
+        if (self.rotation / 90).rem_euclid(4) == 0 {
+            return unchanged(args);
+        }
+        let (level, kind) = match addr.strip_prefix("/grid/led/") {
+            Some(rest) => match rest.strip_prefix("level/") {
+                Some(kind) => (true, kind),
+                None => (false, rest),
+            },
+            None => return unchanged(args),
+        };
+        let mut ints = Vec::with_capacity(args.len());
+        for arg in &args {
+            match *arg {
+                OscType::Int(i) => ints.push(i),
+                _ => return unchanged(args),
+            }
+        }
+        let leds = match (kind, ints.as_slice()) {
+            ("set", &[x, y, value]) => vec![(x, y, value)],
+            ("map", &[x, y, ref values @ ..]) => map_leds(level, x, y, values),
+            ("row", &[x, y, ref values @ ..]) => line_leds(level, values)
+                .map(|(i, value)| (x + i, y, value))
+                .collect(),
+            ("col", &[x, y, ref values @ ..]) => line_leds(level, values)
+                .map(|(i, value)| (x, y + i, value))
+                .collect(),
+            _ => return unchanged(args),
+        };
+        let mut rotated: Leds = leds
+            .into_iter()
+            .filter(|&(x, y, _)| x >= 0 && y >= 0 && x < self.size.0 && y < self.size.1)
+            .map(|(x, y, value)| {
+                let (x, y) = rotate_coordinates(x, y, self.size.0, self.size.1, self.rotation);
+                (x, y, value)
+            })
+            .collect();
+        rotated.sort_by_key(|&(x, y, _)| (y, x));
+
+        let frag = if level { "level/" } else { "" };
+        let message = |kind: &str, x: i32, y: i32, values: Vec<i32>| {
+            let mut args = vec![OscType::Int(x), OscType::Int(y)];
+            args.extend(values.into_iter().map(OscType::Int));
+            (format!("/grid/led/{}{}", frag, kind), args)
+        };
+        let converted = match kind {
+            "set" => None,
+            "map" => as_map(level, &rotated).map(|(x, y, values)| message("map", x, y, values)),
+            _ => as_row(level, &rotated)
+                .map(|(x, y, values)| message("row", x, y, values))
+                .or_else(|| {
+                    // A column is a row of the transposed leds.
+                    let mut transposed: Leds =
+                        rotated.iter().map(|&(x, y, value)| (y, x, value)).collect();
+                    transposed.sort_by_key(|&(y, x, _)| (x, y));
+                    as_row(level, &transposed).map(|(y, x, values)| message("col", x, y, values))
+                }),
+        };
+        match converted {
+            Some(message) => vec![message],
+            None => rotated
+                .into_iter()
+                .map(|(x, y, value)| message("set", x, y, vec![value]))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the leds set by a `map` message for the quad at `x_offset`, `y_offset`.
+pub(crate) fn map_leds(level: bool, x_offset: i32, y_offset: i32, values: &[i32]) -> Leds {
+    let mut leds = Vec::with_capacity(64);
+    for y in 0..8 {
+        for x in 0..8 {
+            let value = if level {
+                values.get((y * 8 + x) as usize).cloned()
+            } else {
+                values.get(y as usize).map(|mask| (mask >> x) & 1)
+            };
+            if let Some(value) = value {
+                leds.push((x_offset + x, y_offset + y, value));
+            }
+        }
+    }
+    leds
+}
+
+/// Returns the leds set by a `row` or `col` message, as their index from its offset, with their
+/// value.
+pub(crate) fn line_leds(level: bool, values: &[i32]) -> impl Iterator<Item = (i32, i32)> + '_ {
+    let per_value = if level { 1 } else { 8 };
+    (0..values.len() * per_value).map(move |i| {
+        let value = if level {
+            values[i]
+        } else {
+            (values[i / 8] >> (i % 8)) & 1
+        };
+        (i as i32, value)
+    })
+}
+
+/// Returns the arguments of a `map` message setting `leds`, sorted by row, as the offset of the
+/// quad and its values, if they form a complete quad at offsets that are multiples of 8.
+fn as_map(level: bool, leds: &[(i32, i32, i32)]) -> Option<(i32, i32, Vec<i32>)> {
+    let &(x_offset, y_offset, _) = leds.first()?;
+    if leds.len() != 64 || x_offset % 8 != 0 || y_offset % 8 != 0 {
+        return None;
+    }
+    for (i, &(x, y, _)) in leds.iter().enumerate() {
+        if (x, y) != (x_offset + i as i32 % 8, y_offset + i as i32 / 8) {
+            return None;
+        }
+    }
+    Some((x_offset, y_offset, values(level, leds)))
+}
+
+/// Returns the arguments of a `row` message setting `leds`, sorted by row, as its offset and
+/// its values, if they are contiguous on a single row, from an offset that is a multiple of 8.
+fn as_row(level: bool, leds: &[(i32, i32, i32)]) -> Option<(i32, i32, Vec<i32>)> {
+    let &(x_offset, y, _) = leds.first()?;
+    if x_offset % 8 != 0 || (!level && !leds.len().is_multiple_of(8)) {
+        return None;
+    }
+    for (i, &(led_x, led_y, _)) in leds.iter().enumerate() {
+        if (led_x, led_y) != (x_offset + i as i32, y) {
+            return None;
+        }
+    }
+    Some((x_offset, y, values(level, leds)))
+}
+
+/// Returns the values of a message setting `leds`: their intensities, or masks of 8 leds that
+/// are on or off, the first led being the lowest bit.
+fn values(level: bool, leds: &[(i32, i32, i32)]) -> Vec<i32> {
+    if level {
+        leds.iter().map(|&(_, _, value)| value).collect()
+    } else {
+        leds.chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |mask, (i, &(_, _, on))| mask | ((on & 1) << i))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i32]) -> Vec<OscType> {
+        values.iter().map(|&i| OscType::Int(i)).collect()
+    }
+
+    #[test]
+    fn convert() {
+        // A 16x8 grid rotated by a quarter turn is 8 leds wide and 16 leds high.
+        let orientation = LedOrientation::new((16, 8), 90);
+        assert_eq!(
+            orientation.convert("/grid/led/level/set", ints(&[0, 0, 9])),
+            vec![("/grid/led/level/set".to_string(), ints(&[7, 0, 9]))]
+        );
+        assert_eq!(
+            orientation.convert("/grid/led/all", ints(&[1])),
+            vec![("/grid/led/all".to_string(), ints(&[1]))]
+        );
+        // A row becomes a column.
+        assert_eq!(
+            orientation.convert("/grid/led/row", ints(&[0, 7, 0b1000_0001, 0xff])),
+            vec![(
+                "/grid/led/col".to_string(),
+                ints(&[0, 0, 0b1000_0001, 0xff])
+            )]
+        );
+        let values: Vec<i32> = (0..64).collect();
+        let mut args = ints(&[8, 0]);
+        args.extend(ints(&values));
+        let converted = orientation.convert("/grid/led/level/map", args);
+        assert_eq!(converted.len(), 1);
+        let (ref addr, ref args) = converted[0];
+        assert_eq!(addr, "/grid/led/level/map");
+        assert_eq!(args[..2], ints(&[0, 8])[..]);
+        // The top left led of the quad is now at its top right.
+        assert_eq!(args[2 + 7], OscType::Int(0));
+        assert_eq!(args[2 + 15], OscType::Int(1));
+
+        let orientation = LedOrientation::new((12, 8), 180);
+        assert_eq!(
+            orientation.convert("/grid/led/level/row", ints(&[8, 0, 1, 2, 3, 4])),
+            vec![("/grid/led/level/row".to_string(), ints(&[0, 7, 4, 3, 2, 1]))]
+        );
+        // Leds that don't line up are set one by one.
+        let converted = orientation.convert("/grid/led/row", ints(&[0, 0, 0b1111_0000]));
+        assert_eq!(converted.len(), 8);
+        assert_eq!(
+            converted[0],
+            ("/grid/led/set".to_string(), ints(&[4, 7, 1]))
+        );
+        assert_eq!(
+            converted[7],
+            ("/grid/led/set".to_string(), ints(&[11, 7, 0]))
+        );
+    }
+}
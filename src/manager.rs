@@ -0,0 +1,529 @@
+//! Several devices sharing a single socket and a single pair of threads.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{bounded, Receiver, Sender, TrySendError};
+use log::*;
+use rosc::decoder::decode;
+use rosc::{OscMessage, OscPacket};
+
+use monome_core::parse;
+
+use crate::channels::CHANNEL_CAPACITY;
+use crate::{
+    new_bound_socket, Monome, MonomeDevice, MonomeError, MonomeEvent, Outgoing, StartupOptions,
+    Transport, OUTGOING_CAPACITY, SERIALOSC_PORT,
+};
+
+/// A message received from one of the devices of a `MonomeManager`, as seen by the monitors
+/// added with `MonomeManager::monitor()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitoredMessage {
+    /// The id of the device that sent the message.
+    pub id: String,
+    /// When the message has been received.
+    pub received_at: Instant,
+    /// The message, as sent by the device: events with the prefix of the device, and `/sys`
+    /// messages alike.
+    pub message: OscMessage,
+    /// The event of the message, if it is one, before the client object of the device processes
+    /// it, e.g. to change the orientation of the keys.
+    pub event: Option<MonomeEvent>,
+}
+
+/// The channels on which the client object of a device sends its messages, and where to send
+/// them.
+struct Route {
+    /// The address of the device.
+    addr: SocketAddr,
+    /// The transport of the device.
+    transport: Arc<Transport>,
+    /// The messages to send.
+    receiver: crossbeam::channel::Receiver<(Instant, Vec<u8>)>,
+    /// The messages to send first.
+    priority_receiver: crossbeam::channel::Receiver<(Instant, Vec<u8>)>,
+}
+
+/// Connects to several devices, for example a grid and an arc used together, and talks to all of
+/// them with a single socket, a single thread sending messages and a single thread receiving
+/// events, instead of a socket and two threads per device. Each device has its own `Monome`
+/// handle, to draw on it, and the events of all the devices are received together, tagged with
+/// the id of the device that sent them.
+///
+/// Each device has its own bounded queue of messages to send, and the sending thread takes one
+/// message of each device in turn, so that a flood of led messages to one device doesn't starve
+/// the others.
+///
+/// The devices are set up one after the other: events sent by a device while the next ones are
+/// being set up are lost.
+///
+/// # Example
+///
+/// ```no_run
+/// use monome::{MonomeEvent, MonomeManager};
+///
+/// let mut manager = MonomeManager::new("/prefix").unwrap();
+/// for monome in manager.devices_mut() {
+///     monome.all(false);
+/// }
+/// loop {
+///     for (id, event) in manager.poll_all() {
+///         println!("{}: {:?}", id, event);
+///     }
+/// }
+/// ```
+pub struct MonomeManager {
+    /// The client objects of the devices.
+    devices: Vec<Monome>,
+    /// The device to poll first, so that a busy device doesn't delay the others.
+    next: usize,
+    /// The threads sending and receiving the messages of all the devices.
+    threads: Vec<thread::JoinHandle<()>>,
+    /// The channels to which the messages received from the devices are copied.
+    monitors: Arc<Mutex<Vec<Sender<MonitoredMessage>>>>,
+}
+
+impl MonomeManager {
+    /// Enumerate the devices on the default serialosc port, and connect to all of them, with
+    /// `prefix`.
+    pub fn new<S>(prefix: S) -> Result<MonomeManager, MonomeError>
+    where
+        S: Into<String>,
+    {
+        let devices = Monome::enumerate_devices_with_port(SERIALOSC_PORT)?;
+        MonomeManager::from_devices(&devices, prefix, &StartupOptions::default())
+    }
+
+    /// Connect to `devices`, with `prefix`, applying `options` to each of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `devices`: the devices acquired through `enumerate_devices`.
+    /// * `prefix`: the prefix to use for these devices and this application
+    /// * `options` - what to do when setting up the devices. The device preferences are ignored.
+    pub fn from_devices<S>(
+        devices: &[MonomeDevice],
+        prefix: S,
+        options: &StartupOptions,
+    ) -> Result<MonomeManager, MonomeError>
+    where
+        S: Into<String>,
+    {
+        if devices.is_empty() {
+            return Err(MonomeError::NoDevice);
+        }
+        let prefix = prefix.into();
+        // A single socket talks to all the devices: it must be reachable from all their hosts.
+        let peer = devices
+            .iter()
+            .map(|device| device.host)
+            .find(|host| !host.is_loopback())
+            .unwrap_or(devices[0].host);
+        let socket = new_bound_socket(peer)?;
+        let mut routes = Vec::new();
+        let mut monomes = Vec::new();
+        for device in devices {
+            let mut info =
+                Monome::setup_on(&socket, &prefix, device.addr(), options.setup_timeout)?;
+            info.model = Some(device.model.clone());
+            info.serialosc = Some(device.serialosc_addr());
+            let monome = Monome::start(
+                info,
+                device.name.clone(),
+                device.device_type.clone(),
+                device.addr(),
+                prefix.clone(),
+                options,
+                |transport| Ok(route(transport, &mut routes)),
+            )?;
+            monomes.push(monome);
+        }
+
+        let send_socket = socket.try_clone().map_err(MonomeError::Bind)?;
+        // Wake up regularly to notice when all the devices are gone.
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .map_err(MonomeError::Bind)?;
+        // Devices of different hosts may be exposed on the same port.
+        let transports: HashMap<SocketAddr, (String, Arc<Transport>)> = routes
+            .iter()
+            .zip(&monomes)
+            .map(|(route, monome)| (route.addr, (monome.id.clone(), route.transport.clone())))
+            .collect();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let monitors = Arc::new(Mutex::new(Vec::new()));
+
+        let done = stopped.clone();
+        let sender_thread = thread::spawn(move || {
+            send_all(&send_socket, routes);
+            done.store(true, Ordering::SeqCst);
+        });
+        let monitored = monitors.clone();
+        let receiver_thread = thread::spawn(move || {
+            let mut buf = vec![0; 1024];
+            while !stopped.load(Ordering::SeqCst) {
+                match socket.recv_from(&mut buf) {
+                    Ok((amt, from)) => match transports.get(&from) {
+                        Some((id, transport)) => {
+                            let mut monitors = monitored.lock().unwrap();
+                            if !monitors.is_empty() {
+                                let prefix = transport.shared.prefix.lock().unwrap().clone();
+                                monitor(&mut monitors, id, &prefix, &buf[..amt]);
+                            }
+                            drop(monitors);
+                            transport.dispatch(&buf[..amt]);
+                        }
+                        None => debug!("⇦ Datagram from an unknown device {}", from),
+                    },
+                    Err(ref e)
+                        if e.kind() == io::ErrorKind::WouldBlock
+                            || e.kind() == io::ErrorKind::TimedOut => {}
+                    Err(e) => {
+                        error!("server error = {:?}", e);
+                        break;
+                    }
+                }
+            }
+            for (_, transport) in transports.values() {
+                transport.stop();
+            }
+        });
+
+        Ok(MonomeManager {
+            devices: monomes,
+            next: 0,
+            threads: vec![sender_thread, receiver_thread],
+            monitors,
+        })
+    }
+
+    /// Get the client objects of the devices, in the order in which they have been connected.
+    pub fn devices(&self) -> &[Monome] {
+        &self.devices
+    }
+
+    /// Get the client objects of the devices, to draw on them.
+    pub fn devices_mut(&mut self) -> &mut [Monome] {
+        &mut self.devices
+    }
+
+    /// Get the client object of the device with id `id`, if it is managed.
+    pub fn device(&mut self, id: &str) -> Option<&mut Monome> {
+        self.devices.iter_mut().find(|monome| monome.id == id)
+    }
+
+    /// Receive the next event of any device, with the id of the device that sent it. The
+    /// devices are polled in turn, so that a busy device doesn't delay the others.
+    pub fn poll(&mut self) -> Option<(String, MonomeEvent)> {
+        let count = self.devices.len();
+        for i in 0..count {
+            let index = (self.next + i) % count;
+            if let Some(event) = self.devices[index].poll() {
+                self.next = (index + 1) % count;
+                return Some((self.devices[index].id.clone(), event));
+            }
+        }
+        None
+    }
+
+    /// Receive all the events that are pending at the time of the call, from all the devices,
+    /// with the id of the device that sent them.
+    pub fn poll_all(&mut self) -> Vec<(String, MonomeEvent)> {
+        let mut events = Vec::new();
+        for monome in &mut self.devices {
+            let id = monome.id.clone();
+            events.extend(
+                monome
+                    .poll_all()
+                    .into_iter()
+                    .map(|event| (id.clone(), event)),
+            );
+        }
+        events
+    }
+
+    /// Monitor all the devices: returns a channel that receives a copy of every message received
+    /// from any device, the `/sys` messages included, with the id of the device that sent it and
+    /// the event it contains, if any. This is meant for dashboards and debugging: the events are
+    /// still returned by `poll()`. Monitors that don't consume their messages quickly enough miss
+    /// messages, and are removed once their receiver has been dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use monome::MonomeManager;
+    ///
+    /// let mut manager = MonomeManager::new("/prefix").unwrap();
+    /// let messages = manager.monitor();
+    /// thread::spawn(move || {
+    ///     for monitored in messages.iter() {
+    ///         println!("{}: {} {:?}", monitored.id, monitored.message.addr, monitored.event);
+    ///     }
+    /// });
+    /// ```
+    pub fn monitor(&mut self) -> Receiver<MonitoredMessage> {
+        let (sender, receiver) = bounded(CHANNEL_CAPACITY);
+        self.monitors.lock().unwrap().push(sender);
+        receiver
+    }
+}
+
+impl Drop for MonomeManager {
+    fn drop(&mut self) {
+        // Disconnect the channels of the devices, so that the threads exit.
+        self.devices.clear();
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                error!("The transport thread panicked");
+            }
+        }
+    }
+}
+
+/// Sends a copy of each message of a datagram received from the device with id `id` and prefix
+/// `prefix` to `monitors`. Monitors that have dropped their receiver are removed.
+fn monitor(monitors: &mut Vec<Sender<MonitoredMessage>>, id: &str, prefix: &str, buf: &[u8]) {
+    let received_at = Instant::now();
+    let mut packets = match decode(buf) {
+        Ok(packet) => vec![packet],
+        // The transport of the device reports it.
+        Err(_) => return,
+    };
+    while let Some(packet) = packets.pop() {
+        let message = match packet {
+            OscPacket::Message(message) => message,
+            OscPacket::Bundle(bundle) => {
+                packets.extend(bundle.content.into_iter().rev());
+                continue;
+            }
+        };
+        let monitored = MonitoredMessage {
+            id: id.to_string(),
+            received_at,
+            event: parse(prefix, OscPacket::Message(message.clone())),
+            message,
+        };
+        monitors.retain(|sender| match sender.try_send(monitored.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(monitored)) => {
+                error!("monitor channel full, dropping {:?}", monitored);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+/// Creates the channels of the client object of `transport`, and adds them to `routes`.
+fn route(transport: Transport, routes: &mut Vec<Route>) -> (Outgoing, Outgoing) {
+    let (sender, receiver) = crossbeam::channel::bounded(OUTGOING_CAPACITY);
+    let (priority_sender, priority_receiver) = crossbeam::channel::bounded(OUTGOING_CAPACITY);
+    routes.push(Route {
+        addr: transport.device_addr,
+        transport: Arc::new(transport),
+        receiver,
+        priority_receiver,
+    });
+    (Outgoing::Thread(sender), Outgoing::Thread(priority_sender))
+}
+
+/// Sends the messages of all the client objects with `socket`, until all of them are gone. The
+/// client objects take turns, one message each, so that a busy device doesn't delay the
+/// others: the priority messages of a client object are only sent before its other messages.
+fn send_all(socket: &std::net::UdpSocket, mut routes: Vec<Route>) {
+    let send = |route: &Route, (sent_at, bytes): (Instant, Vec<u8>)| {
+        route
+            .transport
+            .record_send(socket.send_to(&bytes, route.addr), sent_at);
+    };
+    while !routes.is_empty() {
+        // Wait until a client object has a message, or is gone.
+        let mut select = crossbeam::channel::Select::new();
+        for route in &routes {
+            select.recv(&route.priority_receiver);
+            select.recv(&route.receiver);
+        }
+        select.ready();
+        drop(select);
+
+        // The routes of the client objects that have been dropped.
+        let mut gone = Vec::new();
+        for (index, route) in routes.iter().enumerate() {
+            let message = match route.priority_receiver.try_recv() {
+                Err(crossbeam::channel::TryRecvError::Empty) => route.receiver.try_recv(),
+                message => message,
+            };
+            match message {
+                Ok(message) => send(route, message),
+                Err(crossbeam::channel::TryRecvError::Empty) => {}
+                // The client object has been dropped: send what it left, and forget it.
+                Err(crossbeam::channel::TryRecvError::Disconnected) => {
+                    for message in route.priority_receiver.try_iter() {
+                        send(route, message);
+                    }
+                    for message in route.receiver.try_iter() {
+                        send(route, message);
+                    }
+                    route.transport.stop();
+                    gone.push(index);
+                }
+            }
+        }
+        for index in gone.into_iter().rev() {
+            routes.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_osc_message;
+    use crate::{KeyDirection, Shared};
+    use crossbeam::queue::ArrayQueue;
+    use rosc::decoder::decode;
+    use rosc::encoder::encode;
+    use rosc::{OscPacket, OscType};
+    use std::net::UdpSocket;
+
+    /// Starts a device that answers the setup, and returns it once set up, with the address of
+    /// the application.
+    fn fake_device(
+        id: &'static str,
+    ) -> (MonomeDevice, thread::JoinHandle<(UdpSocket, SocketAddr)>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = i32::from(socket.local_addr().unwrap().port());
+        let device = MonomeDevice::new(id, "monome 128", port, SERIALOSC_PORT);
+        let setup = thread::spawn(move || {
+            let mut buf = vec![0u8; 1024];
+            let app_addr = loop {
+                let (amt, from) = socket.recv_from(&mut buf).unwrap();
+                if let Ok(OscPacket::Message(message)) = decode(&buf[..amt]) {
+                    if message.addr == "/sys/info" {
+                        break from;
+                    }
+                }
+            };
+            let replies = vec![
+                build_osc_message("/sys/port", vec![OscType::Int(i32::from(app_addr.port()))]),
+                build_osc_message("/sys/host", vec![OscType::String("127.0.0.1".into())]),
+                build_osc_message("/sys/id", vec![OscType::String(id.into())]),
+                build_osc_message("/sys/prefix", vec![OscType::String("/plop".into())]),
+                build_osc_message("/sys/size", vec![OscType::Int(16), OscType::Int(8)]),
+                build_osc_message("/sys/rotation", vec![OscType::Int(0)]),
+            ];
+            for reply in replies {
+                socket.send_to(&encode(&reply).unwrap(), app_addr).unwrap();
+            }
+            (socket, app_addr)
+        });
+        (device, setup)
+    }
+
+    #[test]
+    fn manager() {
+        let _lock = crate::tests::START_PORT_LOCK.lock().unwrap();
+        let (first, first_setup) = fake_device("m1");
+        let (second, second_setup) = fake_device("m2");
+        let mut manager =
+            MonomeManager::from_devices(&[first, second], "/plop", &StartupOptions::default())
+                .unwrap();
+        let (_, first_app) = first_setup.join().unwrap();
+        let (second_socket, second_app) = second_setup.join().unwrap();
+        // Both devices talk to the same socket.
+        assert_eq!(first_app, second_app);
+        assert_eq!(manager.devices().len(), 2);
+        let monitor = manager.monitor();
+
+        let key = build_osc_message(
+            "/plop/grid/key",
+            vec![OscType::Int(1), OscType::Int(2), OscType::Int(1)],
+        );
+        let rotation = build_osc_message("/sys/rotation", vec![OscType::Int(180)]);
+        for message in [key, rotation] {
+            second_socket
+                .send_to(&encode(&message).unwrap(), second_app)
+                .unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let received = loop {
+            assert!(Instant::now() < deadline, "no key received");
+            match manager.poll() {
+                Some((id, event @ MonomeEvent::GridKey { .. })) => break (id, event),
+                Some(_) => {}
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        };
+        assert_eq!(
+            received,
+            (
+                "m2".to_string(),
+                MonomeEvent::GridKey {
+                    x: 1,
+                    y: 2,
+                    direction: KeyDirection::Down
+                }
+            )
+        );
+        // Monitors receive the messages of all the devices, `/sys` messages included.
+        let monitored = monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(monitored.id, "m2");
+        assert_eq!(monitored.message.addr, "/plop/grid/key");
+        assert_eq!(monitored.event, Some(received.1));
+        let monitored = monitor.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(monitored.id, "m2");
+        assert_eq!(monitored.message.addr, "/sys/rotation");
+        assert_eq!(monitored.event, None);
+
+        // The messages for a device are sent to this device.
+        manager.device("m2").unwrap().set(3, 4, true);
+        let mut buf = vec![0u8; 1024];
+        let (amt, _) = second_socket.recv_from(&mut buf).unwrap();
+        match decode(&buf[..amt]).unwrap() {
+            OscPacket::Message(message) => assert_eq!(message.addr, "/plop/grid/led/set"),
+            packet => panic!("unexpected packet {:?}", packet),
+        }
+        assert!(manager.device("m3").is_none());
+    }
+
+    #[test]
+    fn routes_take_turns() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = device.local_addr().unwrap();
+        let mut routes = Vec::new();
+        let mut queue = |messages: &[u8], priority: &[u8]| {
+            let transport = Transport::new(
+                addr,
+                Arc::new(ArrayQueue::new(32)),
+                Arc::new(Shared::new("/plop")),
+            );
+            let (sender, priority_sender) = route(transport, &mut routes);
+            for (outgoing, bytes) in [(&sender, messages), (&priority_sender, priority)] {
+                for &byte in bytes {
+                    assert!(outgoing.try_send(vec![byte]));
+                }
+            }
+            (sender, priority_sender)
+        };
+        let senders = vec![queue(b"abc", b""), queue(b"d", b"E"), queue(b"", b"FG")];
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender_thread = thread::spawn(move || send_all(&socket, routes));
+        let mut buf = vec![0u8; 16];
+        let mut sent = Vec::new();
+        for _ in 0..7 {
+            let amt = device.recv(&mut buf).unwrap();
+            sent.extend_from_slice(&buf[..amt]);
+        }
+        assert_eq!(sent, b"aEFbdGc");
+        drop(senders);
+        sender_thread.join().unwrap();
+    }
+}
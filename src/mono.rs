@@ -0,0 +1,123 @@
+//! Conversion of the intensities sent to grids whose leds can only be on or off.
+
+use rosc::OscType;
+
+/// Converts a message sent to `addr`, without prefix, with `args`, that sets leds of a grid to
+/// intensities, to the messages that set them on or off: the leds are on when their intensity is
+/// `threshold` or more. Other messages are returned as they are.
+pub(crate) fn threshold(
+    addr: &str,
+    args: Vec<OscType>,
+    threshold: u8,
+) -> Vec<(String, Vec<OscType>)> {
+    let unchanged = |args| vec![(addr.to_string(), args)];
+    let kind = match addr.strip_prefix("/grid/led/level/") {
+        Some(kind) => kind,
+        None => return unchanged(args),
+    };
+    let mut ints = Vec::with_capacity(args.len());
+    for arg in &args {
+        match *arg {
+            OscType::Int(i) => ints.push(i),
+            _ => return unchanged(args),
+        }
+    }
+    let on = |intensity: i32| {
+        if intensity >= i32::from(threshold) {
+            1
+        } else {
+            0
+        }
+    };
+    let message = |kind: &str, values: Vec<i32>| {
+        (
+            format!("/grid/led/{}", kind),
+            values.into_iter().map(OscType::Int).collect(),
+        )
+    };
+    match (kind, ints.as_slice()) {
+        ("set", &[x, y, intensity]) => vec![message("set", vec![x, y, on(intensity)])],
+        ("all", &[intensity]) => vec![message("all", vec![on(intensity)])],
+        ("map", &[x, y, ref intensities @ ..]) if intensities.len() == 64 => {
+            let mut values = vec![x, y];
+            values.extend(intensities.chunks(8).map(|row| mask(row, on)));
+            vec![message("map", values)]
+        }
+        ("row", &[x, y, ref intensities @ ..]) | ("col", &[x, y, ref intensities @ ..]) => {
+            // Masks set 8 leds each: the leds past the last complete mask are set one by one.
+            let complete = intensities.len() / 8 * 8;
+            let mut messages = Vec::new();
+            if complete > 0 {
+                let mut values = vec![x, y];
+                values.extend(intensities[..complete].chunks(8).map(|leds| mask(leds, on)));
+                messages.push(message(kind, values));
+            }
+            for (i, &intensity) in intensities.iter().enumerate().skip(complete) {
+                let (x, y) = if kind == "row" {
+                    (x + i as i32, y)
+                } else {
+                    (x, y + i as i32)
+                };
+                messages.push(message("set", vec![x, y, on(intensity)]));
+            }
+            messages
+        }
+        _ => unchanged(args),
+    }
+}
+
+/// Returns the mask of up to 8 leds, the first one being the lowest bit, from their
+/// intensities.
+fn mask<F>(intensities: &[i32], on: F) -> i32
+where
+    F: Fn(i32) -> i32,
+{
+    intensities
+        .iter()
+        .enumerate()
+        .fold(0, |mask, (i, &intensity)| mask | (on(intensity) << i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i32]) -> Vec<OscType> {
+        values.iter().map(|&i| OscType::Int(i)).collect()
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(
+            threshold("/grid/led/level/set", ints(&[1, 2, 8]), 8),
+            vec![("/grid/led/set".to_string(), ints(&[1, 2, 1]))]
+        );
+        assert_eq!(
+            threshold("/grid/led/level/all", ints(&[7]), 8),
+            vec![("/grid/led/all".to_string(), ints(&[0]))]
+        );
+        assert_eq!(
+            threshold("/grid/led/set", ints(&[1, 2, 1]), 8),
+            vec![("/grid/led/set".to_string(), ints(&[1, 2, 1]))]
+        );
+        let mut args = ints(&[8, 0]);
+        args.extend(ints(&[15; 64]));
+        let converted = threshold("/grid/led/level/map", args, 8);
+        assert_eq!(converted[0].0, "/grid/led/map");
+        assert_eq!(converted[0].1[2..], ints(&[0xff; 8])[..]);
+
+        let converted = threshold(
+            "/grid/led/level/col",
+            ints(&[3, 0, 15, 0, 15, 0, 0, 0, 0, 0, 9, 1]),
+            8,
+        );
+        assert_eq!(
+            converted,
+            vec![
+                ("/grid/led/col".to_string(), ints(&[3, 0, 0b101])),
+                ("/grid/led/set".to_string(), ints(&[3, 8, 1])),
+                ("/grid/led/set".to_string(), ints(&[3, 9, 0])),
+            ]
+        );
+    }
+}
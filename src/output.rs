@@ -0,0 +1,468 @@
+//! A handle to send led messages to a device, that can be cloned and moved to other threads.
+
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use log::*;
+use rosc::encoder::encode;
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::{
+    apply_brightness_curve, ring_arc_leds, ring_arc_messages, ring_map_args, IntoAddrAndArgs,
+    Monome, MonomeDeviceType, Outgoing, Shared,
+};
+
+/// The sending side of a `Monome`, returned by `Monome::output()`. It only holds a channel to
+/// the transport and a few properties of the device, so it is cheap to clone: several parts of
+/// an application, e.g. a sequencer, its user interface and a level meter, can each hold one and
+/// update the leds concurrently, while the `Monome` itself receives the events.
+///
+/// The messages are sent with the prefix the device has when they are sent. An output stops
+/// working when the device is disconnected, and is not switched to the new connection when the
+/// device is set up again automatically: call `Monome::output()` again then, see
+/// `is_connected()`.
+///
+/// An output returned by `Monome::output_for()` belongs to an owner, and only sets the leds of
+/// the regions of the grid its owner has claimed with `Monome::claim_region()`, so that the
+/// components sharing a grid don't fight over the same leds.
+#[derive(Clone)]
+pub struct MonomeOutput {
+    /// The channel to the transport of the connection this output has been created for.
+    tx: Outgoing,
+    /// The state shared with the client object and the transport.
+    shared: Arc<Shared>,
+    /// The type of the device.
+    device_type: MonomeDeviceType,
+    /// The connection to the device this output is for, see `Shared::generation`.
+    generation: usize,
+    /// The owner of the regions of the grid this output is restricted to, if any.
+    owner: Option<String>,
+}
+
+impl fmt::Debug for MonomeOutput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MonomeOutput")
+            .field("device_type", &self.device_type)
+            .field("generation", &self.generation)
+            .field("owner", &self.owner)
+            .finish()
+    }
+}
+
+impl Monome {
+    /// Returns a handle that sends led messages to this device, and that can be cloned and moved
+    /// to other threads. See `MonomeOutput`.
+    ///
+    /// # Example
+    ///
+    /// Update the leds from another thread:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// let output = monome.output();
+    /// std::thread::spawn(move || {
+    ///     for i in 0..16 {
+    ///         output.set(i, 0, true);
+    ///     }
+    /// });
+    /// ```
+    pub fn output(&self) -> MonomeOutput {
+        MonomeOutput {
+            tx: self.tx.clone(),
+            shared: self.shared.clone(),
+            device_type: self.device_type.clone(),
+            generation: self.shared.generation.load(Ordering::SeqCst),
+            owner: None,
+        }
+    }
+
+    /// Returns a handle that sends led messages to this device, as `output()` does, but only
+    /// sets the leds of the regions claimed by `owner`. The leds it sets outside of them are
+    /// reported as conflicts in the log, and ignored. This is meant for components that share a
+    /// grid, each drawing in its own regions.
+    ///
+    /// # Example
+    ///
+    /// A sequencer owns the top half of a grid, and a mixer the bottom half:
+    ///
+    /// ```no_run
+    /// use monome::Monome;
+    /// let mut monome = Monome::new("/prefix").unwrap();
+    /// monome.claim_region("sequencer", (0, 0, 16, 4)).unwrap();
+    /// monome.claim_region("mixer", (0, 4, 16, 4)).unwrap();
+    /// let mixer = monome.output_for("mixer");
+    /// // Only the bottom half is lit.
+    /// mixer.all(true);
+    /// ```
+    pub fn output_for(&self, owner: &str) -> MonomeOutput {
+        MonomeOutput {
+            owner: Some(owner.to_string()),
+            ..self.output()
+        }
+    }
+
+    /// Claim `region` of a grid, as `(x, y, width, height)`, for `owner`, for the outputs
+    /// returned by `output_for()`. An owner can claim multiple regions. Returns the name of the
+    /// other owner if part of the region is already claimed. See `RegionLocks`.
+    pub fn claim_region(
+        &mut self,
+        owner: &str,
+        region: (usize, usize, usize, usize),
+    ) -> Result<(), String> {
+        self.shared.locks.lock().unwrap().claim(owner, region)
+    }
+
+    /// Release all the regions claimed by `owner`.
+    pub fn release_regions(&mut self, owner: &str) {
+        self.shared.locks.lock().unwrap().release(owner);
+    }
+}
+
+impl MonomeOutput {
+    /// Returns true if the messages sent with this output can still reach the device: it is
+    /// false once the device has been disconnected, and stays false after it has been set up
+    /// again.
+    pub fn is_connected(&self) -> bool {
+        !self.shared.stopped.load(Ordering::SeqCst)
+            && self.shared.generation.load(Ordering::SeqCst) == self.generation
+    }
+
+    /// Set a single led on a grid on or off, or to an intensity. See `Monome::set`.
+    pub fn set<'a, A>(&self, x: i32, y: i32, arg: A)
+    where
+        A: IntoAddrAndArgs<'a, OscType>,
+    {
+        if !self.check(MonomeDeviceType::Grid) {
+            return;
+        }
+        let (frag, arg) = arg.as_addr_frag_and_args();
+        self.send(
+            &format!("/grid/led/{}set", frag),
+            vec![OscType::Int(x), OscType::Int(y), arg],
+        );
+    }
+
+    /// Set all the leds of a grid on or off, or to an intensity. See `Monome::all`.
+    pub fn all<'a, A>(&self, arg: A)
+    where
+        A: IntoAddrAndArgs<'a, OscType>,
+    {
+        if !self.check(MonomeDeviceType::Grid) {
+            return;
+        }
+        let (frag, arg) = arg.as_addr_frag_and_args();
+        self.send(&format!("/grid/led/{}all", frag), vec![arg]);
+    }
+
+    /// Set the leds of an 8x8 quad of a grid. See `Monome::map`.
+    pub fn map<'a, A>(&self, x_offset: i32, y_offset: i32, masks: A)
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>>,
+    {
+        if !self.check(MonomeDeviceType::Grid) {
+            return;
+        }
+        let (frag, arg) = masks.as_addr_frag_and_args();
+        let mut args = vec![OscType::Int(x_offset), OscType::Int(y_offset)];
+        args.extend(arg);
+        self.send(&format!("/grid/led/{}map", frag), args);
+    }
+
+    /// Set a row of a grid. See `Monome::row`.
+    pub fn row<'a, A>(&self, x_offset: i32, y: i32, leds: &A)
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>> + ?Sized,
+    {
+        if !self.check(MonomeDeviceType::Grid) {
+            return;
+        }
+        let (frag, arg) = leds.as_addr_frag_and_args();
+        let mut args = vec![OscType::Int(x_offset), OscType::Int(y)];
+        args.extend(arg);
+        self.send(&format!("/grid/led/{}row", frag), args);
+    }
+
+    /// Set a column of a grid. See `Monome::col`.
+    pub fn col<'a, A>(&self, x: i32, y_offset: i32, leds: &A)
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>> + ?Sized,
+    {
+        if !self.check(MonomeDeviceType::Grid) {
+            return;
+        }
+        let (frag, arg) = leds.as_addr_frag_and_args();
+        let mut args = vec![OscType::Int(x), OscType::Int(y_offset)];
+        args.extend(arg);
+        self.send(&format!("/grid/led/{}col", frag), args);
+    }
+
+    /// Set a single led of an encoder of an arc. See `Monome::ring_set`.
+    pub fn ring_set(&self, n: usize, index: u32, intensity: u32) {
+        if !self.check(MonomeDeviceType::Arc) {
+            return;
+        }
+        let args = vec![
+            OscType::Int(n as i32),
+            OscType::Int(index as i32),
+            OscType::Int(intensity as i32),
+        ];
+        self.send("/ring/set", args);
+    }
+
+    /// Set all the leds of an encoder of an arc. See `Monome::ring_all`.
+    pub fn ring_all(&self, n: usize, intensity: u32) {
+        if !self.check(MonomeDeviceType::Arc) {
+            return;
+        }
+        let args = vec![OscType::Int(n as i32), OscType::Int(intensity as i32)];
+        self.send("/ring/all", args);
+    }
+
+    /// Set a range of leds of an encoder of an arc. See `Monome::ring_range`.
+    pub fn ring_range(&self, n: usize, start_offset: usize, end_offset: usize, intensity: u32) {
+        if !self.check(MonomeDeviceType::Arc) {
+            return;
+        }
+        let args = vec![
+            OscType::Int(n as i32),
+            OscType::Int(start_offset as i32),
+            OscType::Int(end_offset as i32),
+            OscType::Int(intensity as i32),
+        ];
+        self.send("/ring/range", args);
+    }
+
+    /// Light an arc of leds of an encoder of an arc, from an angle. See `Monome::ring_arc`.
+    pub fn ring_arc(&self, n: usize, start_angle: f32, sweep: f32, intensity: u32) {
+        if !self.check(MonomeDeviceType::Arc) {
+            return;
+        }
+        let leds = ring_arc_leds(start_angle, sweep, intensity.min(15) as u8);
+        for (addr, args) in ring_arc_messages(n, &leds) {
+            self.send(addr, args);
+        }
+    }
+
+    /// Set all the leds of an encoder of an arc to specific values. See `Monome::ring_map`.
+    pub fn ring_map<'a, A>(&self, n: usize, values: A)
+    where
+        A: IntoAddrAndArgs<'a, Vec<OscType>>,
+    {
+        if !self.check(MonomeDeviceType::Arc) {
+            return;
+        }
+        let (frag, values) = values.as_addr_frag_and_args();
+        self.send("/ring/map", ring_map_args(n, &frag, values));
+    }
+
+    /// Returns true if the device is of type `device_type`, logs an error otherwise.
+    fn check(&self, device_type: MonomeDeviceType) -> bool {
+        if self.device_type != device_type {
+            error!(
+                "Called {} method on something that is not an {}.",
+                device_type, device_type
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Restricts the message to the regions of the owner of this output, if any, then sends it
+    /// through the filters of the device, see `Shared::send_filtered`, converted as the device
+    /// expects it, see `Shared::convert`. Returns true if all the messages have been queued.
+    fn send(&self, addr: &str, args: Vec<OscType>) -> bool {
+        let messages = match self.owner {
+            Some(ref owner) => self.restrict(owner, addr, args),
+            None => vec![(addr.to_string(), args)],
+        };
+        let mut queued = true;
+        for (addr, args) in messages {
+            queued &= self.shared.send_filtered(&addr, args, |args| {
+                let mut queued = true;
+                for (addr, args) in self.shared.convert(&addr, args) {
+                    queued &= self.send_one(&addr, args);
+                }
+                queued
+            });
+        }
+        queued
+    }
+
+    /// Returns the messages to send instead of a message sent to `addr`, without prefix, with
+    /// `args`, so that only the leds of the regions of `owner` are set. A message that sets leds
+    /// outside of them is replaced by a map of each quad that has leds of the regions set, where
+    /// the other leds keep their last intensities, see `Monome::led()`.
+    fn restrict(&self, owner: &str, addr: &str, args: Vec<OscType>) -> Vec<(String, Vec<OscType>)> {
+        let shadow = self.shared.shadow.lock().unwrap();
+        let writes = match shadow.writes(addr, &args) {
+            Some(writes) => writes,
+            None => return vec![(addr.to_string(), args)],
+        };
+        let width = shadow.width() as usize;
+        let locks = self.shared.locks.lock().unwrap();
+        let (allowed, conflicts): (Vec<_>, Vec<_>) = writes
+            .into_iter()
+            .partition(|&(index, _)| locks.owner_at(index % width, index / width) == Some(owner));
+        if conflicts.is_empty() {
+            return vec![(addr.to_string(), args)];
+        }
+        warn!(
+            "{} set {} leds outside of its regions, ignored.",
+            owner,
+            conflicts.len()
+        );
+
+        // The quads, as their offsets and their intensities in row order.
+        let mut quads: Vec<((i32, i32), [u8; 64])> = Vec::new();
+        for (index, intensity) in allowed {
+            let (x, y) = ((index % width) as i32, (index / width) as i32);
+            let offsets = (x / 8 * 8, y / 8 * 8);
+            let position = match quads.iter().position(|&(quad, _)| quad == offsets) {
+                Some(position) => position,
+                None => {
+                    let mut leds = [0; 64];
+                    for (i, led) in leds.iter_mut().enumerate() {
+                        let (j, k) = ((i % 8) as i32, (i / 8) as i32);
+                        *led = shadow.get(offsets.0 + j, offsets.1 + k).unwrap_or(0);
+                    }
+                    quads.push((offsets, leds));
+                    quads.len() - 1
+                }
+            };
+            quads[position].1[((y - offsets.1) * 8 + x - offsets.0) as usize] = intensity;
+        }
+        quads
+            .into_iter()
+            .map(|((x_offset, y_offset), leds)| {
+                let mut args = vec![OscType::Int(x_offset), OscType::Int(y_offset)];
+                args.extend(leds.iter().map(|&led| OscType::Int(i32::from(led))));
+                ("/grid/led/level/map".to_string(), args)
+            })
+            .collect()
+    }
+
+    /// Adds the prefix, packs the OSC message and sends it to the transport. Returns true if the
+    /// message has been queued.
+    fn send_one(&self, addr: &str, mut args: Vec<OscType>) -> bool {
+        let addr = format!("{}{}", self.shared.prefix.lock().unwrap(), addr);
+        if let Some(ref curve) = *self.shared.curve.lock().unwrap() {
+            apply_brightness_curve(curve, &addr, &mut args);
+        }
+        // The leds displayed are not the ones the client object knows anymore.
+        if addr.contains("/grid/led/") {
+            self.shared.leds_changed.store(true, Ordering::SeqCst);
+        }
+        let packet = OscPacket::Message(OscMessage {
+            addr,
+            args: Some(args),
+        });
+        debug!("⇨ {:?}", packet);
+        match encode(&packet) {
+            Ok(bytes) => self.tx.try_send(bytes),
+            Err(e) => {
+                error!("Could not encode {:?}: {:?}", packet, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_grid;
+    use rosc::decoder::decode;
+
+    #[test]
+    fn output_clones_send_with_prefix() {
+        let (mut monome, receiver) = test_grid();
+
+        let output = monome.output();
+        let other = output.clone();
+        assert!(output.is_connected());
+        std::thread::spawn(move || other.set(1, 2, true))
+            .join()
+            .unwrap();
+        // The leds set by an output are known by the client object.
+        assert_eq!(monome.led(1, 2), 15);
+        monome.set_prefix("/other".to_string());
+        output.all(false);
+        output.ring_all(0, 15);
+
+        let addrs: Vec<String> = receiver
+            .try_iter()
+            .map(|(_, bytes)| match decode(&bytes).unwrap() {
+                OscPacket::Message(message) => message.addr,
+                packet => panic!("unexpected packet {:?}", packet),
+            })
+            .collect();
+        assert_eq!(
+            addrs,
+            vec!["/plop/grid/led/set", "/sys/prefix", "/other/grid/led/all"]
+        );
+
+        // The leds changed by an output are sent again when adaptive updates are enabled.
+        monome.set_adaptive_updates(true);
+        monome.set_all_intensity(&[0; 128]);
+        monome.set_all_intensity(&[0; 128]);
+        assert_eq!(receiver.try_iter().count(), 2);
+        output.set(0, 0, 15);
+        monome.set_all_intensity(&[0; 128]);
+        assert_eq!(receiver.try_iter().count(), 3);
+
+        monome.shared.generation.fetch_add(1, Ordering::SeqCst);
+        assert!(!output.is_connected());
+    }
+
+    #[test]
+    fn owned_regions() {
+        let (mut monome, receiver) = test_grid();
+        monome.claim_region("top", (0, 0, 16, 4)).unwrap();
+        monome.claim_region("bottom", (0, 4, 16, 4)).unwrap();
+        assert_eq!(
+            monome.claim_region("bottom", (0, 3, 1, 1)),
+            Err("top".to_string())
+        );
+        let top = monome.output_for("top");
+        let bottom = monome.output_for("bottom");
+        let addrs = || -> Vec<String> {
+            receiver
+                .try_iter()
+                .map(|(_, bytes)| match decode(&bytes).unwrap() {
+                    OscPacket::Message(message) => message.addr,
+                    packet => panic!("unexpected packet {:?}", packet),
+                })
+                .collect()
+        };
+
+        // Messages within the regions of the owner are sent as is.
+        top.set(3, 3, 9);
+        bottom.row(0, 7, &[true; 16][..]);
+        assert_eq!(
+            addrs(),
+            vec!["/plop/grid/led/level/set", "/plop/grid/led/row"]
+        );
+        // Leds outside of them are ignored.
+        bottom.set(3, 3, 15);
+        assert!(addrs().is_empty());
+        // Messages that cross them only set the leds of the regions, quad by quad.
+        bottom.all(0);
+        assert_eq!(addrs().len(), 2);
+        assert_eq!(monome.led(3, 3), 9);
+        assert_eq!(monome.led(5, 7), 0);
+        top.all(15);
+        assert_eq!(addrs().len(), 2);
+        assert_eq!(monome.led(5, 7), 0);
+        assert_eq!(monome.led(15, 0), 15);
+
+        monome.release_regions("top");
+        top.set(0, 0, 0);
+        assert!(addrs().is_empty());
+        assert_eq!(monome.led(0, 0), 15);
+        // Outputs without an owner are not restricted.
+        monome.output().set(0, 0, 0);
+        assert_eq!(monome.led(0, 0), 0);
+    }
+}
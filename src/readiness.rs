@@ -0,0 +1,58 @@
+//! A file descriptor that becomes readable when events are available, to integrate with external
+//! event loops on Unix.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+
+/// A file descriptor that becomes readable when events are available from a device. It can be
+/// registered with an external event loop (e.g. `epoll`, `mio`), so that a device can be one
+/// source of events among others, without a dedicated thread polling it.
+///
+/// When the file descriptor is readable, call `clear()`, then receive the events with `poll()`
+/// until it returns `None`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::os::unix::io::AsRawFd;
+/// use monome::Monome;
+/// let mut monome = Monome::new("/prefix").unwrap();
+/// let readiness = monome.event_readiness().unwrap();
+/// let fd = readiness.as_raw_fd();
+/// // Register `fd` for reading with an event loop. When it's readable:
+/// readiness.clear();
+/// while let Some(event) = monome.poll() {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub struct EventReadiness {
+    /// The end of the socket pair that receives a datagram each time an event is available.
+    reader: UnixDatagram,
+}
+
+impl EventReadiness {
+    /// Create a readiness file descriptor, and the function that makes it readable.
+    pub(crate) fn new() -> io::Result<(EventReadiness, impl Fn() + Send + Sync)> {
+        let (reader, writer) = UnixDatagram::pair()?;
+        reader.set_nonblocking(true)?;
+        writer.set_nonblocking(true)?;
+        let notify = move || {
+            // If the socket buffer is full, the descriptor is readable anyway.
+            let _ = writer.send(&[1]);
+        };
+        Ok((EventReadiness { reader }, notify))
+    }
+
+    /// Make the file descriptor non-readable again, until new events are available.
+    pub fn clear(&self) {
+        let mut buf = [0; 16];
+        while self.reader.recv(&mut buf).is_ok() {}
+    }
+}
+
+impl AsRawFd for EventReadiness {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+}
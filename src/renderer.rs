@@ -0,0 +1,190 @@
+//! Double buffered drawing, sent to the device at a fixed frame rate.
+
+use std::mem;
+use std::time::{Duration, Instant};
+
+use monome_core::GridFrame;
+
+use crate::{GridFrameBuffer, Monome};
+
+/// A double buffered renderer for a grid: the application draws in the back buffer, whenever and
+/// as often as it wants, and calls `swap()` when a frame is complete. `tick()`, called regularly,
+/// sends the last complete frame to the device, at most at the frame rate of the renderer, and
+/// only the quads that changed since the previous frame sent.
+///
+/// `swap()` exchanges the buffers: the back buffer then holds the frame completed before the one
+/// that has just been swapped, and is usually redrawn entirely.
+///
+/// # Example
+///
+/// ```no_run
+/// use monome::{GridRenderer, Monome};
+///
+/// let mut monome = Monome::new("/prefix").unwrap();
+/// let mut renderer = GridRenderer::for_device(&monome, 30.);
+/// let mut i = 0;
+/// loop {
+///     let back = renderer.back_mut();
+///     back.fill(0);
+///     back.set(i % back.width(), 0, 15);
+///     renderer.swap();
+///     renderer.tick(&mut monome);
+///     i += 1;
+///     std::thread::sleep(std::time::Duration::from_millis(1));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GridRenderer {
+    /// The frame being drawn.
+    back: GridFrame,
+    /// The last complete frame.
+    front: GridFrame,
+    /// Whether a frame has been completed since the last frame sent.
+    pending: bool,
+    /// The leds sent to the device.
+    output: GridFrameBuffer,
+    /// The minimum time between two frames sent.
+    interval: Duration,
+    /// When the last frame has been sent, if any.
+    last_frame: Option<Instant>,
+}
+
+impl GridRenderer {
+    /// Create a renderer for a grid of `width` by `height` leds, that sends at most
+    /// `frames_per_second` frames per second.
+    pub fn new(width: usize, height: usize, frames_per_second: f32) -> GridRenderer {
+        GridRenderer {
+            back: GridFrame::new(width, height),
+            front: GridFrame::new(width, height),
+            pending: false,
+            output: GridFrameBuffer::new(width, height),
+            interval: frame_interval(frames_per_second),
+            last_frame: None,
+        }
+    }
+
+    /// Create a renderer of the size of `monome`, that sends at most `frames_per_second` frames
+    /// per second.
+    pub fn for_device(monome: &Monome, frames_per_second: f32) -> GridRenderer {
+        GridRenderer::new(monome.width(), monome.height(), frames_per_second)
+    }
+
+    /// Get the maximum number of frames sent per second.
+    pub fn frame_rate(&self) -> f32 {
+        1. / self.interval.as_secs_f32()
+    }
+
+    /// Set the maximum number of frames sent per second.
+    pub fn set_frame_rate(&mut self, frames_per_second: f32) {
+        self.interval = frame_interval(frames_per_second);
+    }
+
+    /// Get the frame being drawn.
+    pub fn back(&self) -> &GridFrame {
+        &self.back
+    }
+
+    /// Get the frame being drawn, to draw on it.
+    pub fn back_mut(&mut self) -> &mut GridFrame {
+        &mut self.back
+    }
+
+    /// Get the last complete frame, that is sent on the next frame if it hasn't been sent yet.
+    pub fn front(&self) -> &GridFrame {
+        &self.front
+    }
+
+    /// Complete the frame being drawn: it becomes the frame sent on the next tick that is due,
+    /// replacing the previous complete frame if it hasn't been sent yet.
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.back, &mut self.front);
+        self.pending = true;
+    }
+
+    /// Returns how long to wait, from now, before the next frame can be sent.
+    pub fn time_until_next_frame(&self) -> Duration {
+        self.time_until_next_frame_at(Instant::now())
+    }
+
+    /// Returns how long to wait, from `now`, before the next frame can be sent.
+    pub fn time_until_next_frame_at(&self, now: Instant) -> Duration {
+        match self.last_frame {
+            Some(last) => (last + self.interval).saturating_duration_since(now),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// Send the last complete frame to `monome`, if it hasn't been sent yet and the previous
+    /// frame has been sent long enough ago. Returns true if a frame has been sent.
+    pub fn tick(&mut self, monome: &mut Monome) -> bool {
+        self.tick_at(monome, Instant::now())
+    }
+
+    /// Send the last complete frame to `monome`, as if it was `now`, if it hasn't been sent yet
+    /// and the previous frame has been sent long enough before `now`. Returns true if a frame
+    /// has been sent.
+    pub fn tick_at(&mut self, monome: &mut Monome, now: Instant) -> bool {
+        if !self.pending || self.time_until_next_frame_at(now) > Duration::from_secs(0) {
+            return false;
+        }
+        for y in 0..self.front.height() {
+            for x in 0..self.front.width() {
+                self.output.set(x, y, self.front.get(x, y).unwrap_or(0));
+            }
+        }
+        self.output.flush(monome);
+        self.pending = false;
+        self.last_frame = Some(now);
+        true
+    }
+
+    /// Send all the leds on the next frame, e.g. after the leds of the device have been changed
+    /// by other means.
+    pub fn invalidate(&mut self) {
+        self.output.invalidate();
+        self.pending = true;
+    }
+}
+
+/// Returns the time between two frames at `frames_per_second`, which is clamped to be at least
+/// one frame per minute.
+fn frame_interval(frames_per_second: f32) -> Duration {
+    Duration::from_secs_f64(1. / f64::from(frames_per_second.max(1. / 60.)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_grid;
+
+    #[test]
+    fn frame_rate() {
+        let (mut monome, sent) = test_grid();
+
+        let start = Instant::now();
+        let mut renderer = GridRenderer::for_device(&monome, 10.);
+        assert!(!renderer.tick_at(&mut monome, start));
+
+        renderer.back_mut().set(0, 0, 15);
+        renderer.swap();
+        assert_eq!(renderer.front().get(0, 0), Some(15));
+        assert_eq!(renderer.back().get(0, 0), Some(0));
+        assert!(renderer.tick_at(&mut monome, start));
+        assert_eq!(sent.try_iter().count(), 2);
+
+        // Only the last frame completed is sent, when the next frame is due.
+        renderer.back_mut().set(9, 0, 15);
+        renderer.swap();
+        renderer.back_mut().set(1, 0, 15);
+        renderer.swap();
+        let later = start + Duration::from_millis(50);
+        assert_eq!(
+            renderer.time_until_next_frame_at(later),
+            Duration::from_millis(50)
+        );
+        assert!(!renderer.tick_at(&mut monome, later));
+        assert!(renderer.tick_at(&mut monome, start + Duration::from_millis(100)));
+        assert_eq!(sent.try_iter().count(), 1);
+        assert!(!renderer.tick_at(&mut monome, start + Duration::from_millis(300)));
+    }
+}
@@ -0,0 +1,170 @@
+//! The network input and output of devices on a tokio runtime, for asynchronous applications.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use log::*;
+use tokio::net::UdpSocket;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    enumeration_request, enumeration_timed_out, parse_listed, reply_addr, setup_packets,
+    setup_timed_out, Listed, MonomeDevice, MonomeError, MonomeInfo, Outgoing, Transport,
+    DEVICE_ENUMERATION_TIMEOUT_MS, OUTGOING_CAPACITY,
+};
+
+/// Registers a socket with the runtime in which this is called. This must be called from within a
+/// runtime.
+pub(crate) fn into_async_socket(socket: std::net::UdpSocket) -> Result<UdpSocket, MonomeError> {
+    socket.set_nonblocking(true).map_err(MonomeError::Bind)?;
+    UdpSocket::from_std(socket).map_err(MonomeError::Bind)
+}
+
+/// Tell the device at `addr` to send its events to a new socket, with `prefix`, and wait for its
+/// informations, for at most `timeout`.
+pub(crate) async fn setup(
+    prefix: &str,
+    addr: SocketAddr,
+    timeout: Duration,
+) -> Result<(MonomeInfo, UdpSocket), MonomeError> {
+    let socket = crate::new_bound_socket(addr.ip())?;
+    let local_addr = reply_addr(&socket, addr)?;
+    let socket = into_async_socket(socket)?;
+    for bytes in setup_packets(local_addr, prefix)? {
+        socket
+            .send_to(&bytes, addr)
+            .await
+            .map_err(MonomeError::DeviceUnreachable)?;
+    }
+
+    let mut info = MonomeInfo::new(prefix);
+    let mut buf = vec![0u8; 1024];
+
+    // Loop until we've received all the /sys/info messages
+    let receiving = async {
+        while !info.complete() {
+            let (amt, _) = socket
+                .recv_from(&mut buf)
+                .await
+                .map_err(MonomeError::DeviceUnreachable)?;
+            info.fill_datagram(&buf[..amt]);
+        }
+        Ok(())
+    };
+    match tokio::time::timeout(timeout, receiving).await {
+        Ok(received) => received?,
+        Err(_) => return Err(setup_timed_out()),
+    }
+
+    Ok((info, socket))
+}
+
+/// Ask the serialosc instance at `serialosc` for the list of devices, and call `found` for each
+/// device reported, until the enumeration is complete.
+pub(crate) async fn enumerate_devices<F>(
+    serialosc: SocketAddr,
+    mut found: F,
+) -> Result<(), MonomeError>
+where
+    F: FnMut(MonomeDevice),
+{
+    let socket = crate::new_bound_socket(serialosc.ip())?;
+    let bytes = enumeration_request(reply_addr(&socket, serialosc)?)?;
+    let socket = into_async_socket(socket)?;
+    socket
+        .send_to(&bytes, serialosc)
+        .await
+        .map_err(MonomeError::SerialoscUnreachable)?;
+    let mut buf = vec![0u8; 1024];
+    // Whether serialosc has answered, to tell it from a serialosc that is not running.
+    let mut answered = false;
+    loop {
+        let received = tokio::time::timeout(
+            Duration::from_millis(DEVICE_ENUMERATION_TIMEOUT_MS),
+            socket.recv_from(&mut buf),
+        )
+        .await;
+        let amt = match received {
+            Ok(Ok((amt, _))) => amt,
+            Ok(Err(e)) => return Err(MonomeError::SerialoscUnreachable(e)),
+            Err(_) if !answered => return Err(enumeration_timed_out()),
+            Err(_) => return Ok(()),
+        };
+        answered = true;
+        for listed in parse_listed(&buf[..amt], serialosc) {
+            match listed {
+                Listed::Device(device) => found(device),
+                Listed::End => return Ok(()),
+                Listed::Ignored => {}
+            }
+        }
+    }
+}
+
+impl Transport {
+    /// Runs the transport on the current runtime, sending and receiving with `socket`, and
+    /// returns the channels on which the client object sends the messages to the device, the
+    /// second one being sent first.
+    pub(crate) fn spawn_task(self, socket: UdpSocket) -> (Outgoing, Outgoing) {
+        self.spawn_task_on(&Handle::current(), socket)
+    }
+
+    /// Runs the transport on the runtime of `handle`, sending and receiving with `socket`. This
+    /// can be called from outside of any runtime.
+    pub(crate) fn spawn_on(
+        self,
+        handle: &Handle,
+        socket: std::net::UdpSocket,
+    ) -> Result<(Outgoing, Outgoing), MonomeError> {
+        // The socket is registered with the runtime that is entered.
+        let _guard = handle.enter();
+        let socket = into_async_socket(socket)?;
+        Ok(self.spawn_task_on(handle, socket))
+    }
+
+    /// Runs the transport on the runtime of `handle`, and returns the channels on which the
+    /// client object sends the messages to the device.
+    fn spawn_task_on(self, handle: &Handle, socket: UdpSocket) -> (Outgoing, Outgoing) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(OUTGOING_CAPACITY);
+        let (priority_sender, priority_receiver) = tokio::sync::mpsc::channel(OUTGOING_CAPACITY);
+        handle.spawn(self.run(socket, receiver, priority_receiver));
+        (Outgoing::Task(sender), Outgoing::Task(priority_sender))
+    }
+
+    /// Sends the messages from the client object to the device, and dispatches the messages
+    /// received from the device, until the client object is dropped. Messages from the priority
+    /// channel are sent first.
+    async fn run(
+        self,
+        socket: UdpSocket,
+        mut rx: Receiver<(Instant, Vec<u8>)>,
+        mut priority_rx: Receiver<(Instant, Vec<u8>)>,
+    ) {
+        let addr = self.device_addr;
+        let mut buf = vec![0; 1024];
+        loop {
+            tokio::select! {
+                biased;
+                Some((sent_at, b)) = priority_rx.recv() => {
+                    self.record_send(socket.send_to(&b, addr).await, sent_at);
+                }
+                message = rx.recv() => match message {
+                    Some((sent_at, b)) => {
+                        self.record_send(socket.send_to(&b, addr).await, sent_at);
+                    }
+                    // The client object has been dropped.
+                    None => break,
+                },
+                received = socket.recv_from(&mut buf) => match received {
+                    Ok((amt, _)) => self.dispatch(&buf[..amt]),
+                    Err(e) => {
+                        error!("server error = {:?}", e);
+                        break;
+                    }
+                },
+            }
+        }
+        self.stop();
+    }
+}
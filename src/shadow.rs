@@ -0,0 +1,186 @@
+//! The intensities of the leds of a grid, as set by the messages sent to it.
+
+use rosc::OscType;
+
+use crate::logical::{line_leds, map_leds};
+
+/// The last intensities written to each led of a grid, kept up to date from the messages that
+/// set leds. Leds set on read as 15, and leds set off as 0.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LedShadow {
+    /// The width of the grid.
+    width: i32,
+    /// The height of the grid.
+    height: i32,
+    /// The intensities of the leds, packed in row order.
+    leds: Vec<u8>,
+    /// For each led, in row order, whether its intensity has been written since the shadow has
+    /// been created or forgotten.
+    known: Vec<bool>,
+}
+
+impl LedShadow {
+    /// Create the shadow of a grid of `size`, as `(width, height)`, whose leds are not known
+    /// yet, and read as off.
+    pub(crate) fn new(size: (i32, i32)) -> LedShadow {
+        let (width, height) = (size.0.max(0), size.1.max(0));
+        let count = (width * height) as usize;
+        LedShadow {
+            width,
+            height,
+            leds: vec![0; count],
+            known: vec![false; count],
+        }
+    }
+
+    /// Forget which leds are known, e.g. when the device has been set up again and may display
+    /// anything. The intensities are kept.
+    pub(crate) fn forget(&mut self) {
+        self.known.iter_mut().for_each(|known| *known = false);
+    }
+
+    /// Get the intensity of the led at `x`, `y`, or `None` if it is outside of the grid.
+    pub(crate) fn get(&self, x: i32, y: i32) -> Option<u8> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.leds[(y * self.width + x) as usize])
+    }
+
+    /// Get the width of the grid.
+    pub(crate) fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Get the intensities of all the leds, packed in row order.
+    pub(crate) fn leds(&self) -> &[u8] {
+        &self.leds
+    }
+
+    /// Update the leds set by a message sent to `addr`, without prefix, with `args`. Messages
+    /// that don't set leds are ignored.
+    pub(crate) fn update(&mut self, addr: &str, args: &[OscType]) {
+        if let Some(writes) = self.writes(addr, args) {
+            for (index, intensity) in writes {
+                self.leds[index] = intensity;
+                self.known[index] = true;
+            }
+        }
+    }
+
+    /// Forget the leds set by a message sent to `addr`, without prefix, with `args`, e.g. when
+    /// it may only have been partly sent.
+    pub(crate) fn forget_writes(&mut self, addr: &str, args: &[OscType]) {
+        if let Some(writes) = self.writes(addr, args) {
+            for (index, _) in writes {
+                self.known[index] = false;
+            }
+        }
+    }
+
+    /// Returns true if a message sent to `addr`, without prefix, with `args`, sets leds, and
+    /// only leds known to have the intensities they are set to.
+    pub(crate) fn is_redundant(&self, addr: &str, args: &[OscType]) -> bool {
+        match self.writes(addr, args) {
+            Some(writes) => writes
+                .into_iter()
+                .all(|(index, intensity)| self.known[index] && self.leds[index] == intensity),
+            None => false,
+        }
+    }
+
+    /// Returns the leds of the grid set by a message sent to `addr`, without prefix, with
+    /// `args`, as their index and the intensity they are set to, or `None` if the message
+    /// doesn't set leds.
+    pub(crate) fn writes(&self, addr: &str, args: &[OscType]) -> Option<Vec<(usize, u8)>> {
+        let (level, kind) = match addr.strip_prefix("/grid/led/") {
+            Some(rest) => match rest.strip_prefix("level/") {
+                Some(kind) => (true, kind),
+                None => (false, rest),
+            },
+            None => return None,
+        };
+        let mut ints = Vec::with_capacity(args.len());
+        for arg in args {
+            match *arg {
+                OscType::Int(i) => ints.push(i),
+                _ => return None,
+            }
+        }
+        let intensity = |value: i32| match (level, value) {
+            (true, value) => value.clamp(0, 15) as u8,
+            (false, 0) => 0,
+            (false, _) => 15,
+        };
+        let leds: Vec<(i32, i32, i32)> = match (kind, ints.as_slice()) {
+            ("all", &[value]) => {
+                let value = intensity(value);
+                return Some((0..self.leds.len()).map(|index| (index, value)).collect());
+            }
+            ("set", &[x, y, value]) => vec![(x, y, value)],
+            ("map", &[x, y, ref values @ ..]) => map_leds(level, x, y, values),
+            ("row", &[x, y, ref values @ ..]) => line_leds(level, values)
+                .map(|(i, value)| (x + i, y, value))
+                .collect(),
+            ("col", &[x, y, ref values @ ..]) => line_leds(level, values)
+                .map(|(i, value)| (x, y + i, value))
+                .collect(),
+            _ => return None,
+        };
+        let writes = leds
+            .into_iter()
+            .filter(|&(x, y, _)| x >= 0 && y >= 0 && x < self.width && y < self.height)
+            .map(|(x, y, value)| ((y * self.width + x) as usize, intensity(value)))
+            .collect();
+        Some(writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i32]) -> Vec<OscType> {
+        values.iter().map(|&i| OscType::Int(i)).collect()
+    }
+
+    #[test]
+    fn update() {
+        let mut shadow = LedShadow::new((16, 8));
+        shadow.update("/grid/led/all", &ints(&[1]));
+        assert!(shadow.leds().iter().all(|&led| led == 15));
+        shadow.update("/grid/led/level/set", &ints(&[3, 2, 7]));
+        assert_eq!(shadow.get(3, 2), Some(7));
+        shadow.update("/grid/led/row", &ints(&[8, 1, 0b0000_0010]));
+        assert_eq!(shadow.get(8, 1), Some(0));
+        assert_eq!(shadow.get(9, 1), Some(15));
+        let mut args = ints(&[8, 0]);
+        args.extend(ints(&[4; 64]));
+        shadow.update("/grid/led/level/map", &args);
+        assert_eq!(shadow.get(15, 7), Some(4));
+        assert_eq!(shadow.get(7, 7), Some(15));
+        shadow.update("/grid/led/level/col", &ints(&[0, 6, 1, 2, 3]));
+        assert_eq!(shadow.get(0, 7), Some(2));
+        assert_eq!(shadow.get(0, 8), None);
+        shadow.update("/grid/led/intensity", &ints(&[0]));
+        assert_eq!(shadow.get(0, 6), Some(1));
+    }
+
+    #[test]
+    fn redundant() {
+        let mut shadow = LedShadow::new((8, 8));
+        // The leds are not known yet.
+        assert!(!shadow.is_redundant("/grid/led/all", &ints(&[0])));
+        shadow.update("/grid/led/all", &ints(&[0]));
+        assert!(shadow.is_redundant("/grid/led/level/set", &ints(&[1, 1, 0])));
+        assert!(!shadow.is_redundant("/grid/led/set", &ints(&[1, 1, 1])));
+        shadow.update("/grid/led/row", &ints(&[0, 2, 0xff]));
+        assert!(shadow.is_redundant("/grid/led/level/set", &ints(&[3, 2, 15])));
+        assert!(!shadow.is_redundant("/grid/led/all", &ints(&[1])));
+        assert!(!shadow.is_redundant("/grid/led/intensity", &ints(&[15])));
+        assert!(!shadow.is_redundant("/ring/all", &ints(&[0, 0])));
+        shadow.forget();
+        assert!(!shadow.is_redundant("/grid/led/level/set", &ints(&[3, 2, 15])));
+        assert_eq!(shadow.get(3, 2), Some(15));
+    }
+}
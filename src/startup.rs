@@ -0,0 +1,151 @@
+//! Options applied when setting up a device, before it is handed to the application.
+
+use std::time::Duration;
+
+use crate::{MonomeDevice, MonomeDeviceType, SETUP_TIMEOUT_MS};
+
+/// Describes what to do when a device is set up, before it is returned to the application.
+///
+/// # Example
+///
+/// Turn off the leds left on by a previous application, and start at a low intensity:
+///
+/// ```no_run
+/// use monome::{Monome, StartupOptions};
+/// let mut options = StartupOptions::default();
+/// options.clear_leds = true;
+/// options.intensity = Some(4);
+/// let mut monome = Monome::new_with_options("/prefix", &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartupOptions {
+    /// Turn off all the leds of the device, so that nothing drawn by a previous application is
+    /// left.
+    pub clear_leds: bool,
+    /// If set, the global intensity of the leds of a grid, between 0 and 15, set before the
+    /// application draws anything. This is ignored for arcs.
+    pub intensity: Option<i32>,
+    /// When multiple devices are plugged in, which one to set up. The preferences are tried in
+    /// order, and the first one that matches a device decides. The "first" device is used if
+    /// none of them matches.
+    pub preferences: Vec<DevicePreference>,
+    /// How long to wait for the device to answer while setting it up, before giving up with
+    /// `MonomeError::DeviceUnreachable`. This is 2 seconds by default.
+    pub setup_timeout: Duration,
+}
+
+impl Default for StartupOptions {
+    fn default() -> StartupOptions {
+        StartupOptions {
+            clear_leds: false,
+            intensity: None,
+            preferences: Vec::new(),
+            setup_timeout: Duration::from_millis(SETUP_TIMEOUT_MS),
+        }
+    }
+}
+
+/// A criterion to choose a device among the ones that are plugged in.
+///
+/// # Example
+///
+/// Prefer a specific grid, or else any monome 128, or else the largest grid:
+///
+/// ```no_run
+/// use monome::{DevicePreference, Monome, StartupOptions};
+/// let mut options = StartupOptions::default();
+/// options.preferences = vec![
+///     DevicePreference::Id("m1000123".to_string()),
+///     DevicePreference::Model("monome 128".to_string()),
+///     DevicePreference::LargestGrid,
+/// ];
+/// let mut monome = Monome::new_with_options("/prefix", &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum DevicePreference {
+    /// The device with this serial number, e.g. "m1000123".
+    Id(String),
+    /// A device of this model, as reported by serialosc, e.g. "monome 128".
+    Model(String),
+    /// A device of this type.
+    Type(MonomeDeviceType),
+    /// The grid with the most leds, deduced from its model.
+    LargestGrid,
+}
+
+/// Returns the number of leds of a grid, deduced from its model, e.g. 128 for "monome 128", or 0
+/// if it is unknown.
+fn grid_led_count(device: &MonomeDevice) -> u32 {
+    device
+        .model
+        .split_whitespace()
+        .last()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Chooses a device among `devices` according to `preferences`. Returns the first device if no
+/// preference matches, and `None` if there is no device.
+pub(crate) fn choose_device<'a>(
+    devices: &'a [MonomeDevice],
+    preferences: &[DevicePreference],
+) -> Option<&'a MonomeDevice> {
+    for preference in preferences {
+        let chosen = match preference {
+            DevicePreference::Id(id) => devices.iter().find(|d| d.name == *id),
+            DevicePreference::Model(model) => devices.iter().find(|d| d.model == *model),
+            DevicePreference::Type(device_type) => {
+                devices.iter().find(|d| d.device_type == *device_type)
+            }
+            DevicePreference::LargestGrid => devices
+                .iter()
+                .filter(|d| d.device_type == MonomeDeviceType::Grid)
+                .max_by_key(|d| grid_led_count(d)),
+        };
+        if chosen.is_some() {
+            return chosen;
+        }
+    }
+    devices.first()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferences() {
+        let devices = vec![
+            MonomeDevice::new("m0000001", "monome arc 4", 1000, 12002),
+            MonomeDevice::new("m0000002", "monome 64", 1001, 12002),
+            MonomeDevice::new("m0000003", "monome 256", 1002, 12002),
+            MonomeDevice::new("m0000004", "monome 128", 1003, 12002),
+        ];
+        let chosen = |preferences: Vec<DevicePreference>| {
+            choose_device(&devices, &preferences).map(|d| d.port())
+        };
+
+        assert_eq!(chosen(vec![]), Some(1000));
+        assert_eq!(chosen(vec![DevicePreference::LargestGrid]), Some(1002));
+        assert_eq!(
+            chosen(vec![
+                DevicePreference::Id("m1000123".to_string()),
+                DevicePreference::Model("monome 128".to_string()),
+            ]),
+            Some(1003)
+        );
+        assert_eq!(
+            chosen(vec![DevicePreference::Id("m0000002".to_string())]),
+            Some(1001)
+        );
+        assert_eq!(
+            chosen(vec![DevicePreference::Type(MonomeDeviceType::Arc)]),
+            Some(1000)
+        );
+        assert_eq!(
+            chosen(vec![DevicePreference::Model("monome 512".to_string())]),
+            Some(1000)
+        );
+        assert!(choose_device(&[], &[DevicePreference::LargestGrid]).is_none());
+    }
+}
@@ -0,0 +1,47 @@
+//! Delivery of the events of a device as an asynchronous stream.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::mpsc::Receiver;
+
+use crate::MonomeEvent;
+
+/// A stream of the events of a device, for asynchronous applications. The stream ends when the
+/// device is dropped, or when `event_stream()` or `event_receiver()` is called again.
+///
+/// # Example
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use monome::Monome;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let mut monome = Monome::connect_async("/prefix").await.unwrap();
+///     let mut events = monome.event_stream();
+///     while let Some(event) = events.next().await {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+pub struct EventStream {
+    /// The receiving end of the channel on which the transport sends the events.
+    receiver: Receiver<MonomeEvent>,
+}
+
+impl EventStream {
+    /// Create a stream of the events received on `receiver`.
+    pub(crate) fn new(receiver: Receiver<MonomeEvent>) -> EventStream {
+        EventStream { receiver }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = MonomeEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<MonomeEvent>> {
+        self.receiver.poll_recv(cx)
+    }
+}